@@ -13,8 +13,13 @@ use tynm::type_name;
 
 pub fn bench_all(c: &mut Criterion) {
     bench_direct(c);
+    bench_direct_relaxed(c);
+    bench_direct_sharded(c);
     bench_keyed::<HashMapStateStore<u32>>(c);
     bench_keyed::<DashMapStateStore<u32>>(c);
+    bench_keyed_cold_start(c);
+    #[cfg(feature = "thread-cache")]
+    bench_keyed_thread_cached::<DashMapStateStore<u32>>(c);
 }
 
 const THREADS: u32 = 20;
@@ -50,6 +55,100 @@ fn bench_direct(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_direct_relaxed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_threaded");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("direct_relaxed", |b| {
+        let clock = clock::QuantaUpkeepClock::from_interval(Duration::from_micros(10))
+            .expect("Could not spawn upkeep thread");
+
+        b.iter_custom(|iters| {
+            let lim = Arc::new(RateLimiter::direct_relaxed_with_clock(
+                Quota::per_second(nonzero!(50u32)),
+                &clock,
+            ));
+            let mut children = vec![];
+            let start = Instant::now();
+            for _i in 0..THREADS {
+                let lim = lim.clone();
+                children.push(thread::spawn(move || {
+                    for _i in 0..iters {
+                        black_box(lim.check().is_ok());
+                    }
+                }));
+            }
+            for child in children {
+                child.join().unwrap()
+            }
+            start.elapsed()
+        })
+    });
+    group.finish();
+}
+
+fn bench_direct_sharded(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_threaded");
+    group.throughput(Throughput::Elements(1));
+    let quotas = [
+        ("mostly_deny", Quota::per_second(nonzero!(50u32))),
+        (
+            "mostly_allow",
+            Quota::with_period(Duration::from_nanos(1))
+                .unwrap()
+                .allow_burst(nonzero!(u32::MAX)),
+        ),
+    ];
+    for (name, quota) in quotas.iter() {
+        group.bench_with_input(
+            BenchmarkId::new("direct_unsharded", name),
+            quota,
+            |b, quota| {
+                let clock = clock::QuantaUpkeepClock::from_interval(Duration::from_micros(10))
+                    .expect("Could not spawn upkeep thread");
+                b.iter_custom(|iters| {
+                    let lim = Arc::new(RateLimiter::direct_with_clock(*quota, &clock));
+                    run_threads(iters, move || black_box(lim.check().is_ok()))
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("direct_sharded", name),
+            quota,
+            |b, quota| {
+                let clock = clock::QuantaUpkeepClock::from_interval(Duration::from_micros(10))
+                    .expect("Could not spawn upkeep thread");
+                b.iter_custom(|iters| {
+                    let lim = Arc::new(RateLimiter::direct_sharded_with_clock(
+                        *quota,
+                        nonzero!(THREADS),
+                        &clock,
+                    ));
+                    run_threads(iters, move || black_box(lim.check().is_ok()))
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Runs `check` `iters` times on each of `THREADS` threads, returning the time it took.
+fn run_threads(iters: u64, check: impl Fn() -> bool + Clone + Send + 'static) -> Duration {
+    let mut children = vec![];
+    let start = Instant::now();
+    for _i in 0..THREADS {
+        let check = check.clone();
+        children.push(thread::spawn(move || {
+            for _i in 0..iters {
+                check();
+            }
+        }));
+    }
+    for child in children {
+        child.join().unwrap()
+    }
+    start.elapsed()
+}
+
 fn bench_keyed<M: KeyedStateStore<u32> + Default + Send + Sync + 'static>(c: &mut Criterion) {
     let mut group = c.benchmark_group("multi_threaded");
 
@@ -88,3 +187,94 @@ fn bench_keyed<M: KeyedStateStore<u32> + Default + Send + Sync + 'static>(c: &mu
     });
     group.finish();
 }
+
+/// Measures a cold start: Each thread checks `iter` keys that the rate limiter hasn't seen yet,
+/// against a [`DashMapStateStore`] that starts out empty and one that has room for all the keys.
+fn bench_keyed_cold_start(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_threaded");
+    group.throughput(Throughput::Elements(1));
+    for preallocate in [false, true] {
+        let name = if preallocate { "preallocated" } else { "empty" };
+        group.bench_function(BenchmarkId::new("keyed_cold_start", name), |b| {
+            let clock = clock::QuantaUpkeepClock::from_interval(Duration::from_micros(10))
+                .expect("Could not spawn upkeep thread");
+
+            b.iter_custom(|iters| {
+                let quota = Quota::per_second(nonzero!(50u32));
+                let state: DashMapStateStore<u64> = if preallocate {
+                    DashMapStateStore::with_capacity(iters as usize * THREADS as usize)
+                } else {
+                    DashMapStateStore::default()
+                };
+                let lim = Arc::new(RateLimiter::new(quota, state, &clock));
+
+                let mut children = vec![];
+                let start = Instant::now();
+                for thread in 0..THREADS {
+                    let lim = lim.clone();
+                    children.push(thread::spawn(move || {
+                        for i in 0..iters {
+                            let key = i * u64::from(THREADS) + u64::from(thread);
+                            black_box(lim.check_key(&key).is_ok());
+                        }
+                    }));
+                }
+                for child in children {
+                    child.join().unwrap()
+                }
+                start.elapsed()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Compares checking keys that mostly get allowed through against a shared state store with
+/// checking them against thread-local chunks of cells. A chunk size of 1 checks every cell
+/// against the shared state store.
+#[cfg(feature = "thread-cache")]
+fn bench_keyed_thread_cached<M: KeyedStateStore<u32> + Default + Send + Sync + 'static>(
+    c: &mut Criterion,
+) {
+    let mut group = c.benchmark_group("multi_threaded");
+
+    // We perform 3 checks per thread per iter:
+    group.throughput(Throughput::Elements(3));
+    let quota = Quota::per_second(nonzero!(1_000_000_000u32));
+
+    for chunk_size in [None, Some(nonzero!(64u32))] {
+        let name = match chunk_size {
+            Some(_) => "keyed_thread_cached",
+            None => "keyed_mostly_allow",
+        };
+        group.bench_function(BenchmarkId::new(name, type_name::<M>()), |b| {
+            let clock = clock::QuantaUpkeepClock::from_interval(Duration::from_micros(10))
+                .expect("Could not spawn upkeep thread");
+
+            b.iter_custom(|iters| {
+                let lim = Arc::new(
+                    RateLimiter::new(quota, M::default(), &clock)
+                        .thread_cached(chunk_size.unwrap_or(nonzero!(1u32))),
+                );
+
+                let mut children = vec![];
+                let start = Instant::now();
+                for _i in 0..THREADS {
+                    let lim = lim.clone();
+                    children.push(thread::spawn(move || {
+                        for _i in 0..iters {
+                            black_box(lim.check_key(&1u32).is_ok());
+                            black_box(lim.check_key(&2u32).is_ok());
+                            black_box(lim.check_key(&3u32).is_ok());
+                        }
+                    }));
+                }
+                for child in children {
+                    child.join().unwrap()
+                }
+                start.elapsed()
+            })
+        });
+    }
+    group.finish();
+}
@@ -2,16 +2,17 @@
 //!
 //! The two functions in here measure the throughput against a rate-limiter that mostly allows
 //! (allowing max_value of `u32` per nanosecond), and one that mostly denies (allowing only one
-//! per hour).
+//! per hour). A third one measures the cost of reading each clock on its own.
 
 use criterion::{black_box, BenchmarkId, Criterion, Throughput};
-use governor::{clock, Quota, RateLimiter};
+use governor::{clock, clock::Clock, Quota, RateLimiter};
 use nonzero_ext::*;
 use std::time::Duration;
 
 pub fn bench_all(c: &mut Criterion) {
     bench_mostly_allow(c);
     bench_mostly_deny(c);
+    bench_now(c);
 }
 
 macro_rules! with_realtime_clocks {
@@ -29,6 +30,11 @@ macro_rules! with_realtime_clocks {
                 .expect("could not spawn upkeep thread");
             $group.bench_with_input(BenchmarkId::new($name, "QuantaUpkeepClock"), &clock, |$b, $clock| $closure);
         }
+        {
+            let clock = clock::CoarseMonotonicClock::with_interval(Duration::from_micros(40))
+                .expect("could not spawn refresh thread");
+            $group.bench_with_input(BenchmarkId::new($name, "CoarseMonotonicClock"), &clock, |$b, $clock| $closure);
+        }
     };
 }
 
@@ -58,3 +64,14 @@ fn bench_mostly_deny(c: &mut Criterion) {
     }};
     group.finish();
 }
+
+fn bench_now(c: &mut Criterion) {
+    let mut group = c.benchmark_group("realtime_clock");
+    group.throughput(Throughput::Elements(1));
+    with_realtime_clocks! {("now", group) |b, clock| {
+        b.iter(|| {
+            black_box(clock.now());
+        });
+    }};
+    group.finish();
+}
@@ -60,6 +60,16 @@
 //! RateLimiter::direct_with_clock(Quota::per_second(nonzero!(50u32)), &clock);
 //! ```
 //!
+//! To wait for a rate limiter with the `async` methods (like
+//! [`until_ready`][crate::RateLimiter::until_ready]) in `no_std` mode,
+//! implement the [`AsyncClock`][crate::clock::AsyncClock] trait for
+//! your clock, using the timer of your async executor. With `std`,
+//! the realtime clocks wait using the `futures-timer` crate. To wait
+//! using `async-std`'s `task::sleep`, enable the `async-std` feature
+//! and construct the rate limiter with the `AsyncStdClock`; to wait
+//! using the `smol` runtime's timer, enable the `smol` feature and
+//! use the `SmolClock`.
+//!
 //! # Data ownership and references to rate limiters
 //!
 //! `governor`'s rate limiter state is not hidden behind an [interior
@@ -6,15 +6,17 @@
 
 use std::prelude::v1::*;
 
-use std::convert::TryInto;
+use std::fmt;
 use std::fmt::Debug;
+use std::future::Future;
 use std::ops::Add;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Duration;
 
-use crate::nanos::Nanos;
+use crate::nanos::{AtomicNanos, Interval, Nanos};
+use crate::Quota;
 
 /// A measurement from a clock.
 pub trait Reference:
@@ -41,6 +43,84 @@ pub trait Clock: Clone {
     fn now(&self) -> Self::Instant;
 }
 
+/// A time source that can also wait asynchronously, used by rate limiters' `async` methods
+/// (like [`until_ready`][crate::RateLimiter::until_ready]).
+///
+/// With the `std` feature, all clocks implementing [`ReasonablyRealtime`] are `AsyncClock`s that
+/// wait using the [`futures-timer`](https://crates.io/crates/futures-timer) crate, which runs its
+/// own timer thread and works with any executor. To wait with
+/// [`async-std`](https://crates.io/crates/async-std)'s `task::sleep` instead, enable the
+/// `async-std` feature and use the `AsyncStdClock`; to wait with the
+/// [`async-io`](https://crates.io/crates/async-io) timer that
+/// [`smol`](https://crates.io/crates/smol) uses, enable the `smol` feature and use the
+/// `SmolClock`. Enabling these features doesn't change how the other clocks wait.
+///
+/// In `no_std` builds, the timer of the async executor can be plugged in by implementing this
+/// trait for a clock that reads the executor's time. For example, a clock that reads
+/// [`embassy`](https://embassy.dev)'s `embassy_time::Instant` would set `type Delay =
+/// embassy_time::Timer` and return `Timer::after(embassy_time::Duration::from_micros(..))` from
+/// `delay`.
+///
+/// # Example
+///
+/// A clock whose delay advances a fake time source instead of waiting:
+///
+/// ```rust
+/// # use std::future::{ready, Ready};
+/// # use std::time::Duration;
+/// # use governor::{clock::{AsyncClock, Clock, FakeRelativeClock}, Quota, RateLimiter};
+/// # use nonzero_ext::nonzero;
+/// #[derive(Clone, Default)]
+/// struct SkippingClock(FakeRelativeClock);
+///
+/// impl Clock for SkippingClock {
+///     type Instant = <FakeRelativeClock as Clock>::Instant;
+///
+///     fn now(&self) -> Self::Instant {
+///         self.0.now()
+///     }
+/// }
+///
+/// impl AsyncClock for SkippingClock {
+///     type Delay = Ready<()>;
+///
+///     fn delay(&self, duration: Duration) -> Self::Delay {
+///         self.0.advance(duration);
+///         ready(())
+///     }
+/// }
+///
+/// let clock = SkippingClock::default();
+/// let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+/// lim.check().unwrap();
+/// let _waiting = lim.until_ready(); // Resolves once polled, after advancing the clock.
+/// ```
+pub trait AsyncClock: Clock {
+    /// The future that [`delay`][AsyncClock::delay] returns.
+    ///
+    /// Delays are [`Unpin`], so that the futures, streams and sinks of rate limiters can poll them
+    /// without pinning them first. To wait on a future that isn't, box it with [`Box::pin`].
+    type Delay: Future<Output = ()> + Unpin;
+
+    /// Returns a future that resolves once `duration` has passed on the clock.
+    fn delay(&self, duration: Duration) -> Self::Delay;
+}
+
+/// A time source that can also block the calling thread while time passes, used by rate
+/// limiters' blocking methods (like
+/// [`until_ready_blocking`][crate::RateLimiter::until_ready_blocking]).
+///
+/// With the `std` feature, all clocks implementing [`ReasonablyRealtime`] are `BlockingClock`s
+/// that sleep with [`std::thread::sleep`]. The [`FakeRelativeClock`] advances itself instead of
+/// sleeping, so that tests using it don't block.
+pub trait BlockingClock: Clock {
+    /// Blocks the calling thread until `duration` has passed on the clock.
+    ///
+    /// Implementations may return early (e.g. on a spurious wakeup); callers check the time
+    /// again after sleeping.
+    fn sleep(&self, duration: Duration);
+}
+
 impl Reference for Duration {
     fn duration_since(&self, earlier: Self) -> Nanos {
         self.checked_sub(earlier)
@@ -67,33 +147,47 @@ impl Add<Nanos> for Duration {
 /// and returns that.
 ///
 /// # Thread safety
-/// The mock time is represented as an atomic u64 count of nanoseconds, behind an [`Arc`].
-/// Clones of this clock will all show the same time, even if the original advances.
-#[derive(Debug, Clone, Default)]
+/// The mock time is represented as an atomic count of nanoseconds (a u64, or a u128 with the
+/// `wide-nanos` feature), behind an [`Arc`]. Clones of this clock will all show the same time,
+/// even if the original advances.
+#[derive(Clone, Default)]
 pub struct FakeRelativeClock {
-    now: Arc<AtomicU64>,
+    now: Arc<AtomicNanos>,
 }
 
 impl FakeRelativeClock {
     /// Advances the fake clock by the given amount.
+    ///
+    /// # Panics
+    /// Panics if the clock would show a time past ~584 years (or, with the `wide-nanos` feature,
+    /// past ~2.5 trillion years).
     pub fn advance(&self, by: Duration) {
-        let by: u64 = by
-            .as_nanos()
-            .try_into()
-            .expect("Can not represent times past ~584 years");
+        let by = Nanos::from(by);
+        let advanced = |prev: Nanos| {
+            prev.checked_add(by)
+                .expect("Can not represent times past the longest representable duration")
+        };
 
         let mut prev = self.now.load(Ordering::Acquire);
-        let mut next = prev + by;
+        let mut next = advanced(prev);
         while let Err(next_prev) =
             self.now
                 .compare_exchange_weak(prev, next, Ordering::Release, Ordering::Relaxed)
         {
             prev = next_prev;
-            next = prev + by;
+            next = advanced(prev);
         }
     }
 }
 
+impl fmt::Debug for FakeRelativeClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("FakeRelativeClock")
+            .field("now", &self.now())
+            .finish()
+    }
+}
+
 impl PartialEq for FakeRelativeClock {
     fn eq(&self, other: &Self) -> bool {
         self.now.load(Ordering::Relaxed) == other.now.load(Ordering::Relaxed)
@@ -104,7 +198,76 @@ impl Clock for FakeRelativeClock {
     type Instant = Nanos;
 
     fn now(&self) -> Self::Instant {
-        self.now.load(Ordering::Relaxed).into()
+        self.now.load(Ordering::Relaxed)
+    }
+}
+
+/// Sleeping on a fake clock advances it by the duration, without blocking.
+impl BlockingClock for FakeRelativeClock {
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// A mock clock that advances in ticks, where one tick is the replenishment interval of a quota.
+///
+/// This makes tests that check how many cells replenish over time read naturally: Advancing the
+/// clock by `n` ticks replenishes exactly `n` cells of the quota's burst capacity on a rate
+/// limiter that uses the clock (as long as the burst capacity isn't full).
+///
+/// The clock's time is the quota's replenishment interval times the number of ticks since the
+/// clock was constructed, rounded up to whole nanoseconds, so rounding errors don't add up for
+/// quotas whose interval isn't a whole number of nanoseconds. Like with the
+/// [`FakeRelativeClock`], clones of this clock all show the same time.
+///
+/// # Example
+/// ```rust
+/// # use nonzero_ext::nonzero;
+/// # use governor::{clock::TickClock, Quota, RateLimiter};
+/// let quota = Quota::per_second(nonzero!(4u32));
+/// let clock = TickClock::new(quota);
+/// let lim = RateLimiter::direct_with_clock(quota, &clock);
+/// assert_eq!(Ok(()), lim.check_n(nonzero!(4u32)));
+/// clock.advance(2);
+/// assert_eq!(2, lim.available());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TickClock {
+    interval: Interval,
+    ticks: Arc<AtomicU64>,
+}
+
+impl TickClock {
+    /// Constructs a tick clock for the given quota, at tick zero.
+    pub fn new(quota: Quota) -> Self {
+        TickClock {
+            interval: quota.interval(),
+            ticks: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Advances the clock by the given number of ticks.
+    pub fn advance(&self, ticks: u64) {
+        self.ticks.fetch_add(ticks, Ordering::AcqRel);
+    }
+
+    /// Returns the number of ticks that the clock has advanced by since it was constructed.
+    pub fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::Acquire)
+    }
+}
+
+impl PartialEq for TickClock {
+    fn eq(&self, other: &Self) -> bool {
+        self.now() == other.now()
+    }
+}
+
+impl Clock for TickClock {
+    type Instant = Nanos;
+
+    fn now(&self) -> Self::Instant {
+        self.interval.times(self.ticks()).ceil()
     }
 }
 
@@ -113,11 +276,24 @@ mod with_std;
 #[cfg(feature = "std")]
 pub use with_std::*;
 
+#[cfg(feature = "std")]
+mod coarse;
+#[cfg(feature = "std")]
+pub use coarse::*;
+
 #[cfg(all(feature = "std", feature = "quanta"))]
 mod quanta;
 #[cfg(all(feature = "std", feature = "quanta"))]
 pub use self::quanta::*;
 
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use self::wasm::*;
+
+mod dynamic;
+pub use dynamic::*;
+
 mod default;
 
 pub use default::*;
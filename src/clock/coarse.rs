@@ -0,0 +1,82 @@
+use std::prelude::v1::*;
+
+use crate::clock::{Clock, ReasonablyRealtime, Reference};
+use crate::nanos::Nanos;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct CoarseTime {
+    origin: Instant,
+    elapsed: AtomicU64,
+}
+
+impl CoarseTime {
+    fn refresh(&self) {
+        let elapsed = Nanos::from(self.origin.elapsed());
+        self.elapsed.store(elapsed.into(), Ordering::Relaxed);
+    }
+}
+
+/// A low-overhead monotonic clock that only reads the system's monotonic clock once per
+/// configurable interval.
+///
+/// A background thread refreshes a cached reading of [`Instant::now`] once per interval; reading
+/// the clock is then a single atomic load. The background thread stops after the last clone of
+/// the clock is dropped (at the latest, one interval later).
+///
+/// This trades precision for throughput: readings of the clock lag behind the real time by up to
+/// one interval (plus however late the background thread gets scheduled), so rate limiters using
+/// it make decisions as of a slightly earlier time. Since cells replenish in steps of the
+/// interval, cells that replenish within one interval all become available at once when the time
+/// is refreshed, which admits a little extra burst right at interval boundaries. Intervals
+/// should therefore be much shorter than the replenishment interval of the quotas the clock is
+/// used with.
+///
+/// Whether this is faster than a [`QuantaClock`][crate::clock::QuantaClock] depends on how often
+/// the clock is read; you should measure and compare performance before picking one or the other.
+#[derive(Debug, Clone)]
+pub struct CoarseMonotonicClock {
+    time: Arc<CoarseTime>,
+}
+
+impl CoarseMonotonicClock {
+    /// Returns a new `CoarseMonotonicClock` whose readings get refreshed once per `interval`.
+    pub fn with_interval(interval: Duration) -> Result<CoarseMonotonicClock, std::io::Error> {
+        let time = Arc::new(CoarseTime {
+            origin: Instant::now(),
+            elapsed: AtomicU64::new(0),
+        });
+        let weak: Weak<CoarseTime> = Arc::downgrade(&time);
+        thread::Builder::new()
+            .name("governor-coarse-clock".to_string())
+            .spawn(move || {
+                while let Some(time) = weak.upgrade() {
+                    time.refresh();
+                    drop(time);
+                    thread::sleep(interval);
+                }
+            })?;
+        Ok(CoarseMonotonicClock { time })
+    }
+}
+
+impl Clock for CoarseMonotonicClock {
+    type Instant = Nanos;
+
+    fn now(&self) -> Self::Instant {
+        self.time.elapsed.load(Ordering::Relaxed).into()
+    }
+}
+
+impl ReasonablyRealtime for CoarseMonotonicClock {
+    fn convert_from_reference(
+        reference: (Self::Instant, Instant),
+        reading: Self::Instant,
+    ) -> Instant {
+        let diff: Duration = reading.duration_since(reference.0).into();
+        reference.1 + diff
+    }
+}
@@ -0,0 +1,88 @@
+use std::prelude::v1::*;
+
+use crate::clock::{Clock, Reference};
+use crate::nanos::Nanos;
+use std::fmt;
+use std::sync::Arc;
+
+/// The object-safe part of [`Clock`] that [`DynClock`] dispatches to.
+trait ErasedClock: Send + Sync {
+    fn elapsed(&self) -> Nanos;
+}
+
+/// A clock together with the reading that its elapsed time is measured from.
+struct Erased<C: Clock> {
+    clock: C,
+    origin: C::Instant,
+}
+
+impl<C> ErasedClock for Erased<C>
+where
+    C: Clock + Send + Sync,
+{
+    fn elapsed(&self) -> Nanos {
+        self.clock.now().duration_since(self.origin)
+    }
+}
+
+/// A clock that dispatches to any other clock at runtime.
+///
+/// Rate limiters are generic over their clock, which is usually what you want. If the clock is
+/// chosen at runtime, however (say, the [`DefaultClock`][crate::clock::DefaultClock] in
+/// production and a [`FakeRelativeClock`][crate::clock::FakeRelativeClock] in tests), this allows
+/// using the same rate limiter type with either, instead of making all code that uses the rate
+/// limiter generic over the clock.
+///
+/// Since clocks' `Instant` types differ, a `DynClock` measures time as the nanoseconds that
+/// passed on the wrapped clock since the `DynClock` was constructed. Clones of a `DynClock` share
+/// the wrapped clock and this starting point.
+///
+/// `DynClock`s don't implement [`AsyncClock`][crate::clock::AsyncClock], as delays of different
+/// clocks can't be told apart at runtime.
+///
+/// # Example
+/// ```rust
+/// # use governor::{clock::{DynClock, FakeRelativeClock}, Quota, RateLimiter};
+/// # use nonzero_ext::nonzero;
+/// # use std::time::Duration;
+/// let fake = FakeRelativeClock::default();
+/// let clock = DynClock::new(fake.clone());
+/// let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+/// assert_eq!(Ok(()), lim.check());
+/// assert!(lim.check().is_err());
+/// fake.advance(Duration::from_secs(1));
+/// assert_eq!(Ok(()), lim.check());
+/// ```
+#[derive(Clone)]
+pub struct DynClock {
+    clock: Arc<dyn ErasedClock>,
+}
+
+impl DynClock {
+    /// Wraps a clock, measuring time from its current reading.
+    pub fn new<C>(clock: C) -> DynClock
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        let origin = clock.now();
+        DynClock {
+            clock: Arc::new(Erased { clock, origin }),
+        }
+    }
+}
+
+impl fmt::Debug for DynClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynClock")
+            .field("elapsed", &self.clock.elapsed())
+            .finish()
+    }
+}
+
+impl Clock for DynClock {
+    type Instant = Nanos;
+
+    fn now(&self) -> Self::Instant {
+        self.clock.elapsed()
+    }
+}
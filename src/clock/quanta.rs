@@ -59,8 +59,19 @@ impl Reference for QuantaInstant {
 /// retrieve the current time and update an atomic U64; the clock then can retrieve that time
 /// (and is as behind as, at most, that interval).
 ///
-/// The background thread is stopped as soon as the last clone of the clock is
-/// dropped.
+/// Reading the clock is then a single atomic load, which is much cheaper than
+/// [`QuantaClock`]'s reading of the time stamp counter. In exchange, rate limiters using this
+/// clock make their decisions as of the time of the upkeep thread's last update: Cells that
+/// replenish within one interval all become available at once when the time is updated, so a
+/// rate limiter may admit up to one interval's worth of extra cells over any period. The upkeep
+/// interval should therefore be much shorter than the replenishment interval of the quotas the
+/// clock is used with.
+///
+/// The clock owns the upkeep thread's handle, and the background thread is stopped as soon as
+/// the last clone of the clock is dropped. Readings stop advancing once the thread is stopped,
+/// so keep the clock (or a rate limiter using it, which holds a clone) alive for as long as
+/// you read it. Quanta keeps the recent time in a global variable, so all upkeep clocks in a
+/// process share it, and it is updated by all of their upkeep threads.
 ///
 /// Whether this is faster than a [`QuantaClock`] depends on the utilization of the rate limiter
 /// and the upkeep interval that you pick; you should measure and compare performance before
@@ -103,3 +114,13 @@ impl ReasonablyRealtime for QuantaClock {
         reference.1 + diff
     }
 }
+
+impl ReasonablyRealtime for QuantaUpkeepClock {
+    fn convert_from_reference(
+        reference: (Self::Instant, Instant),
+        reading: Self::Instant,
+    ) -> Instant {
+        let diff: Duration = reading.duration_since(reference.0).into();
+        reference.1 + diff
+    }
+}
@@ -0,0 +1,65 @@
+use crate::clock::Clock;
+use crate::nanos::Nanos;
+use std::fmt;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// A clock for WebAssembly targets running in a JavaScript environment (e.g. browsers, web
+/// workers or node.js), using the
+/// [`performance.now()`](https://developer.mozilla.org/en-US/docs/Web/API/Performance/now)
+/// function.
+///
+/// On `wasm32-unknown-unknown`, the standard library's [`Instant`][std::time::Instant] is not
+/// available; this clock reads the monotonic high-resolution time that the JavaScript
+/// environment provides instead. It is only available with the `wasm` feature, which does not
+/// depend on the `std` feature, and can be used with `no_std` builds.
+///
+/// The clock's time source can be replaced with
+/// [`with_time_source`][WasmClock::with_time_source], e.g. to simulate the passage of time in
+/// tests.
+#[derive(Clone, Copy)]
+pub struct WasmClock {
+    millis: fn() -> f64,
+}
+
+impl WasmClock {
+    /// Returns a clock that reads the time in milliseconds from `millis` rather than from
+    /// `performance.now()`.
+    ///
+    /// The readings must be monotonic, and relative to some fixed point in the past.
+    pub fn with_time_source(millis: fn() -> f64) -> Self {
+        WasmClock { millis }
+    }
+}
+
+fn performance_now() -> f64 {
+    let performance = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("performance"))
+        .expect("the global object must have a `performance` property");
+    performance.unchecked_into::<web_sys::Performance>().now()
+}
+
+impl Default for WasmClock {
+    fn default() -> Self {
+        WasmClock::with_time_source(performance_now)
+    }
+}
+
+impl fmt::Debug for WasmClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("WasmClock").finish()
+    }
+}
+
+impl Clock for WasmClock {
+    type Instant = Nanos;
+
+    fn now(&self) -> Self::Instant {
+        let millis = (self.millis)();
+        // Negative (or NaN) readings can't be represented and clamp to zero:
+        let nanos = if millis > 0.0 {
+            (millis * 1_000_000.0) as u64
+        } else {
+            0
+        };
+        nanos.into()
+    }
+}
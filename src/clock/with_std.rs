@@ -1,10 +1,12 @@
-use super::{Clock, Reference};
+use super::{AsyncClock, BlockingClock, Clock, Reference};
 
 use std::prelude::v1::*;
 
 use crate::nanos::Nanos;
 use std::ops::Add;
 use std::time::{Duration, Instant, SystemTime};
+#[cfg(any(feature = "async-std", feature = "smol"))]
+use std::{future::Future, pin::Pin};
 
 /// The monotonic clock implemented by [`Instant`].
 #[derive(Clone, Debug, Default)]
@@ -81,7 +83,10 @@ impl Clock for SystemClock {
 /// Identifies clocks that run similarly to the monotonic realtime clock.
 ///
 /// Clocks implementing this trait can be used with rate-limiters functions that operate
-/// asynchronously.
+/// asynchronously: They implement [`AsyncClock`][crate::clock::AsyncClock], waiting with the
+/// `futures-timer` crate's timer, which runs on the system's monotonic clock. They also
+/// implement [`BlockingClock`][crate::clock::BlockingClock], sleeping with
+/// [`std::thread::sleep`].
 pub trait ReasonablyRealtime: Clock {
     /// Returns a reference point at the start of an operation.
     fn reference_point(&self) -> (Self::Instant, Instant) {
@@ -95,6 +100,90 @@ pub trait ReasonablyRealtime: Clock {
     ) -> Instant;
 }
 
+impl<C: ReasonablyRealtime> AsyncClock for C {
+    type Delay = futures_timer::Delay;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        futures_timer::Delay::new(duration)
+    }
+}
+
+/// A monotonic clock that waits using `async-std`'s `task::sleep`.
+///
+/// It reads the time like the [`MonotonicClock`], but its [`AsyncClock`] delays run on
+/// `async-std`'s timer instead of `futures-timer`'s timer thread. Its sleep future can't be
+/// named, so each delay is boxed.
+#[cfg(feature = "async-std")]
+#[derive(Clone, Debug, Default)]
+pub struct AsyncStdClock;
+
+#[cfg(feature = "async-std")]
+impl Clock for AsyncStdClock {
+    type Instant = Instant;
+
+    fn now(&self) -> Self::Instant {
+        Instant::now()
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl AsyncClock for AsyncStdClock {
+    type Delay = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
+
+#[cfg(feature = "async-std")]
+impl BlockingClock for AsyncStdClock {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A monotonic clock that waits using the `async-io` timer that `smol` runs on.
+///
+/// It reads the time like the [`MonotonicClock`], but its [`AsyncClock`] delays run on
+/// `async-io`'s timer instead of `futures-timer`'s timer thread. That timer completes with the
+/// time it fired at, so each delay is wrapped in a boxed future that discards it.
+#[cfg(feature = "smol")]
+#[derive(Clone, Debug, Default)]
+pub struct SmolClock;
+
+#[cfg(feature = "smol")]
+impl Clock for SmolClock {
+    type Instant = Instant;
+
+    fn now(&self) -> Self::Instant {
+        Instant::now()
+    }
+}
+
+#[cfg(feature = "smol")]
+impl AsyncClock for SmolClock {
+    type Delay = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        Box::pin(async move {
+            async_io::Timer::after(duration).await;
+        })
+    }
+}
+
+#[cfg(feature = "smol")]
+impl BlockingClock for SmolClock {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+impl<C: ReasonablyRealtime> BlockingClock for C {
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
 impl ReasonablyRealtime for MonotonicClock {
     fn convert_from_reference(
         _reference: (Self::Instant, Instant),
@@ -22,9 +22,15 @@ pub enum NegativeMultiDecision<E: fmt::Display> {
     /// simultaneous decisions).
     BatchNonConforming(u32, E),
 
-    /// The number of cells tested (the first argument) is larger than
-    /// the bucket's capacity, which means the decision can never have
-    /// a conforming result. The argument gives the maximum number of
-    /// cells that could ever have a conforming result.
-    InsufficientCapacity(u32),
+    /// The number of cells tested is larger than the bucket's
+    /// capacity, which means the decision can never have a conforming
+    /// result, no matter how long the caller waits.
+    InsufficientCapacity {
+        /// The number of cells tested.
+        requested: u32,
+
+        /// The maximum number of cells that could ever have a
+        /// conforming result (the quota's burst size).
+        max_burst: u32,
+    },
 }
@@ -1,35 +1,89 @@
 use std::prelude::v1::*;
 
-use crate::nanos::Nanos;
-use crate::state::StateStore;
+use crate::middleware::RateLimitingMiddleware;
+use crate::nanos::{AtomicNanos, Interval, Nanos};
+use crate::state::{StateLookup, StateStore};
 use crate::{clock, NegativeMultiDecision, Quota};
+use std::convert::{Infallible, TryFrom};
+use std::marker::PhantomData;
 use std::num::NonZeroU32;
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::time::Duration;
 use std::{cmp, fmt};
 
-#[cfg(feature = "std")]
-use crate::Jitter;
+#[cfg(feature = "serde")]
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 
 /// A negative rate-limiting outcome.
 ///
 /// `NotUntil`'s methods indicate when a caller can expect the next positive
 /// rate-limiting result.
+///
+/// # Serialization
+///
+/// With the `serde` feature enabled, `NotUntil` can be serialized (e.g. to tell a client when to
+/// retry in the body of a response). It is represented by a structure with the fields:
+///
+/// * `wait_time`: The time between the rate-limiting decision and the earliest time at which a
+///   decision could be conforming, in the same representation as a [`Duration`].
+/// * `retry_after_secs`: The wait time, rounded up to whole seconds (see
+///   [`retry_after_secs`][NotUntil::retry_after_secs]).
+/// * `quota`: The [`Quota`] that the cell did not conform to, in its serialized representation.
+///
+/// Since clock readings can't be serialized in general, all times are relative to the time the
+/// decision was made.
 #[derive(Debug, PartialEq)]
 pub struct NotUntil<'a, P: clock::Reference> {
-    limiter: &'a Gcra,
+    limiter: PhantomData<&'a Gcra>,
+    t: Interval,
+    tau: Nanos,
     tat: Nanos,
+    decided_at: Nanos,
     start: P,
 }
 
 impl<'a, P: clock::Reference> NotUntil<'a, P> {
+    /// Constructs a negative decision for a rate limiter with the parameters in `limiter`, which
+    /// could be conforming at `tat` and was made at `decided_at`, both relative to `start`.
+    pub(crate) fn new(limiter: &'a Gcra, tat: Nanos, decided_at: Nanos, start: P) -> Self {
+        let (t, tau) = limiter.params();
+        NotUntil {
+            limiter: PhantomData,
+            t,
+            tau,
+            tat,
+            decided_at,
+            start,
+        }
+    }
+
     /// Returns the earliest time at which a decision could be
     /// conforming (excluding conforming decisions made by the Decider
     /// that are made in the meantime).
+    ///
+    /// This is an absolute time on the rate limiter's clock, e.g. a [`std::time::Instant`] for
+    /// the [`MonotonicClock`][crate::clock::MonotonicClock], so it can be stored as a deadline
+    /// instead of recomputing the wait from the current time. It is the time at which a single
+    /// cell frees up, the same time that [`wait_time_from`](#method.wait_time_from) measures the
+    /// wait until: For any `now` before it, `earliest_possible()` is `wait_time_from(now)` after
+    /// `now`.
     pub fn earliest_possible(&self) -> P {
         let tat: Nanos = self.tat;
         self.start + tat
     }
 
+    /// Returns the quota that the rate-limiting decision was made against.
+    ///
+    /// For state stores that split the rate limiter's quota between several states (like
+    /// [`ShardedDirectStateStore`][crate::state::direct::ShardedDirectStateStore]), this is the
+    /// quota that each of the states enforces.
+    ///
+    /// The returned quota never [starts empty](Quota::start_empty): Only the rate and burst size
+    /// of the quota matter to decisions about existing states.
+    pub fn quota(&self) -> Quota {
+        Gcra::quota_for(self.t, self.tau)
+    }
+
     /// Returns the minimum amount of time from the time that the
     /// decision was made that must pass before a
     /// decision can be conforming.
@@ -41,10 +95,71 @@ impl<'a, P: clock::Reference> NotUntil<'a, P> {
         earliest.duration_since(earliest.min(from)).into()
     }
 
-    #[cfg(feature = "std")] // not used unless we use Instant-compatible clocks.
-    pub(crate) fn earliest_possible_with_offset(&self, jitter: Jitter) -> P {
-        let tat = jitter + self.tat;
-        self.start + tat
+    /// Returns the amount of time from `from` that must pass before a decision can be
+    /// conforming, rounded *up* to a multiple of `granularity`.
+    ///
+    /// Rounding up means that a client that waits for the returned time does not retry too early
+    /// (e.g. with a granularity of a millisecond, to avoid retrying in a tight loop just before
+    /// the rate limiter allows the cell). Wait times that already are a multiple of
+    /// `granularity` are returned as they are, and a zero `granularity` returns the exact wait
+    /// time, like [`wait_time_from`](#method.wait_time_from). Wait times that can't be rounded
+    /// up without overflowing a `Duration` saturate at [`Duration::MAX`].
+    pub fn wait_time_from_rounded(&self, from: P, granularity: Duration) -> Duration {
+        let wait = self.wait_time_from(from);
+        let step = granularity.as_nanos();
+        if step == 0 {
+            return wait;
+        }
+        let nanos = wait.as_nanos();
+        let rounded = match nanos % step {
+            0 => nanos,
+            rem => nanos + (step - rem),
+        };
+        u64::try_from(rounded / 1_000_000_000).map_or(Duration::MAX, |secs| {
+            Duration::new(secs, (rounded % 1_000_000_000) as u32)
+        })
+    }
+
+    /// Returns the number of whole seconds from `from` that must pass before a decision can be
+    /// conforming, suitable for an HTTP `Retry-After` header.
+    ///
+    /// The wait time is rounded *up* to the next whole second, so that a client that waits for
+    /// the returned number of seconds does not retry too early. If the time of the next expected
+    /// positive result is in the past, `retry_after_secs` returns 0.
+    pub fn retry_after_secs(&self, from: P) -> u64 {
+        let wait = self.wait_time_from(from);
+        if wait.subsec_nanos() > 0 {
+            wait.as_secs() + 1
+        } else {
+            wait.as_secs()
+        }
+    }
+
+    /// Returns the earliest time, formatted as an HTTP-date, at which a decision could be
+    /// conforming, suitable for an HTTP `Retry-After` header.
+    ///
+    /// The wall-clock time is computed by adding the wait time from `from` to the current system
+    /// time, and rounded *up* to the next whole second (the resolution of an HTTP-date), so that
+    /// a client that waits until the returned date does not retry too early.
+    #[cfg(all(feature = "std", feature = "httpdate"))]
+    pub fn retry_after_http_date(&self, from: P) -> String {
+        let earliest = std::time::SystemTime::now() + self.wait_time_from(from);
+        let subsec_nanos = earliest
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.subsec_nanos())
+            .unwrap_or(0);
+        let earliest = if subsec_nanos > 0 {
+            earliest + Duration::from_nanos(1_000_000_000 - u64::from(subsec_nanos))
+        } else {
+            earliest
+        };
+        httpdate::fmt_http_date(earliest)
+    }
+
+    /// Returns the minimum amount of time from the time that the decision was made that must
+    /// pass before a decision can be conforming.
+    pub(crate) fn wait_time(&self) -> Duration {
+        self.wait_time_from(self.start + self.decided_at)
     }
 }
 
@@ -54,89 +169,840 @@ impl<'a, P: clock::Reference> fmt::Display for NotUntil<'a, P> {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[cfg(feature = "serde")]
+impl<'a, P: clock::Reference> Serialize for NotUntil<'a, P> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let decided_at = self.start + self.decided_at;
+        let mut state = serializer.serialize_struct("NotUntil", 3)?;
+        state.serialize_field("wait_time", &self.wait_time_from(decided_at))?;
+        state.serialize_field("retry_after_secs", &self.retry_after_secs(decided_at))?;
+        state.serialize_field("quota", &self.quota())?;
+        state.end()
+    }
+}
+
+/// A positive rate-limiting outcome that may have borrowed capacity from the future.
+///
+/// This is returned by [`check_n_allow_borrow`][crate::RateLimiter::check_n_allow_borrow].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Borrowed {
+    cells: u32,
+    wait_time: Duration,
+}
+
+impl Borrowed {
+    /// Returns the number of cells that were allowed through beyond the capacity that was
+    /// available, i.e. that were borrowed from the future.
+    pub fn borrowed_cells(&self) -> u32 {
+        self.cells
+    }
+
+    /// Returns how long, from the time of the decision, the rate limiter will not allow any
+    /// cells through, while the borrowed capacity is repaid.
+    ///
+    /// If the decision did not borrow any cells, this can still be non-zero if the decision used
+    /// up all the available capacity.
+    pub fn wait_time(&self) -> Duration {
+        self.wait_time
+    }
+}
+
+/// Information about the rate-limiting state of a rate limiter (or of one of its keys).
+///
+/// The information is computed when it is requested, so constructing a `StateInformation` (e.g.
+/// for a [middleware][crate::middleware] that ignores it) is cheap.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct StateInformation {
+    t: Interval,
+    tau: Nanos,
+    tat: Nanos,
+    t0: Nanos,
+    cells: u32,
+}
+
+impl StateInformation {
+    /// Constructs the information for a state at time `t0` that has `remaining` cells of
+    /// capacity left, under the parameters `t` and `tau`, after a decision that allowed `cells`
+    /// cells through.
+    pub(crate) fn with_remaining(
+        t: Interval,
+        tau: Nanos,
+        t0: Nanos,
+        remaining: u64,
+        cells: u32,
+    ) -> Self {
+        let tat = match remaining {
+            0 => t0 + tau + Nanos::from(1),
+            remaining => (t0 + tau).saturating_sub(t.times(remaining - 1).ceil()),
+        };
+        StateInformation {
+            t,
+            tau,
+            tat,
+            t0,
+            cells,
+        }
+    }
+
+    /// Returns the number of cells that the decision this information was computed for allowed
+    /// through: `n` for a batch of `n` cells checked with
+    /// [`check_n`][crate::RateLimiter::check_n], and one for a single cell. Information that
+    /// wasn't computed for a decision, like that returned by
+    /// [`state_information`][crate::RateLimiter::state_information], reports zero cells.
+    pub fn allowed_cells(&self) -> u32 {
+        self.cells
+    }
+
+    /// Returns the time at which the information was computed, measured from the rate
+    /// limiter's start.
+    #[cfg(feature = "std")]
+    pub(crate) fn decided_at(&self) -> Nanos {
+        self.t0
+    }
+
+    /// Returns the number of cells that could be allowed through right away (ignoring
+    /// decisions made in the meantime), at most the quota's burst size.
+    pub fn remaining_burst_capacity(&self) -> u32 {
+        let StateInformation {
+            t, tau, tat, t0, ..
+        } = *self;
+        if t0 < tat.saturating_sub(tau) {
+            0
+        } else {
+            // The first cell can go through; count how many more fit into the bucket:
+            let base = cmp::max(tat, t0);
+            let more = t.count_within(base, (t0 + tau).saturating_sub(base));
+            u32::try_from(cmp::min(1 + more, t.count_in(tau))).unwrap_or(u32::MAX)
+        }
+    }
+
+    /// Returns the quota's burst size.
+    #[cfg(feature = "http")]
+    pub(crate) fn burst_size(&self) -> u64 {
+        self.t.count_in(self.tau)
+    }
+
+    /// Returns how long it takes, without any further decisions, until the full burst capacity
+    /// is available.
+    pub(crate) fn time_until_full(&self) -> Duration {
+        let StateInformation { t, tat, t0, .. } = *self;
+        // A state is at full capacity once it looks like a fresh one:
+        tat.saturating_sub(Gcra::full_state(t, t0)).into()
+    }
+
+    /// Returns the fraction of the quota's burst size that is available right now, as a number
+    /// between `0.0` (no cells can be allowed through) and `1.0` (the full burst capacity is
+    /// available).
+    ///
+    /// This is the [`remaining_burst_capacity`][StateInformation::remaining_burst_capacity]
+    /// divided by the burst size, e.g. for displaying how full the rate limiter's bucket is.
+    pub fn remaining_ratio(&self) -> f64 {
+        let burst_size = self.t.count_in(self.tau);
+        if burst_size == 0 {
+            return 0.0;
+        }
+        let ratio = f64::from(self.remaining_burst_capacity()) / burst_size as f64;
+        ratio.clamp(0.0, 1.0)
+    }
+}
+
+impl fmt::Debug for StateInformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("StateInformation")
+            .field("remaining_burst_capacity", &self.remaining_burst_capacity())
+            .field("remaining_ratio", &self.remaining_ratio())
+            .finish()
+    }
+}
+
+/// The parameters of the GCRA, which can be replaced while the rate limiter is in use.
+///
+/// Since the two parameters must be read consistently, they are guarded by a sequence lock: The
+/// sequence number is odd while the parameters are being replaced, and readers retry if it
+/// changed while they were reading.
 pub(crate) struct Gcra {
-    // The "weight" of a single packet in units of time.
-    t: Nanos,
+    seq: AtomicU64,
+
+    // The "weight" of a single packet in units of time: Whole nanoseconds, and the fraction of
+    // a nanosecond on top (see `Interval`).
+    t: AtomicU64,
+    t_frac: AtomicU32,
 
     // The "capacity" of the bucket.
-    tau: Nanos,
+    tau: AtomicNanos,
+
+    // Whether new states start out without any capacity.
+    start_empty: AtomicBool,
+
+    // Whether states that replenished completely don't carry any capacity over.
+    strict: AtomicBool,
+
+    // The latest time that decisions were made at (recorded at most once per
+    // `CLOCK_REGRESSION_TOLERANCE`), and how often the clock was seen going back before it.
+    latest: AtomicNanos,
+    clock_regressions: AtomicU64,
+}
+
+/// How far the clock may go back before a rate limiter treats it as a clock regression.
+///
+/// Readings taken concurrently on different threads can arrive slightly out of order; only
+/// readings that lie further in the past than this are clamped.
+const CLOCK_REGRESSION_TOLERANCE: Nanos = Nanos::new(1_000_000);
+
+impl fmt::Debug for Gcra {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let (t, tau) = self.params();
+        f.debug_struct("Gcra")
+            .field("t", &t)
+            .field("tau", &tau)
+            .finish()
+    }
+}
+
+impl PartialEq for Gcra {
+    fn eq(&self, other: &Self) -> bool {
+        self.params() == other.params()
+    }
 }
 
 impl Gcra {
     pub(crate) fn new(quota: Quota) -> Self {
-        let tau: Nanos = (quota.replenish_1_per * quota.max_burst.get()).into();
-        let t: Nanos = quota.replenish_1_per.into();
-        Gcra { tau, t }
+        let (t, tau) = Self::params_for(quota);
+        Gcra {
+            seq: AtomicU64::new(0),
+            t: AtomicU64::new(t.whole()),
+            t_frac: AtomicU32::new(t.frac()),
+            tau: AtomicNanos::new(tau),
+            start_empty: AtomicBool::new(quota.start_empty),
+            strict: AtomicBool::new(quota.strict),
+            latest: AtomicNanos::default(),
+            clock_regressions: AtomicU64::new(0),
+        }
+    }
+
+    /// Measures the time `t0` from the rate limiter's start, for making a decision.
+    ///
+    /// If the clock went back (by more than [`CLOCK_REGRESSION_TOLERANCE`]) since an earlier
+    /// decision, this counts a clock regression and returns the time of that decision instead:
+    /// Decisions at earlier times would be measured against states that account for later
+    /// cells, and could e.g. let a sliding window forget its cells.
+    pub(crate) fn elapsed<P: clock::Reference>(&self, start: P, t0: P) -> Nanos {
+        let t0 = t0.duration_since(start);
+        let latest = self.latest.load(Ordering::Relaxed);
+        if t0 + CLOCK_REGRESSION_TOLERANCE < latest {
+            self.clock_regressions.fetch_add(1, Ordering::Relaxed);
+            latest
+        } else {
+            if t0 > latest + CLOCK_REGRESSION_TOLERANCE {
+                self.latest.fetch_max(t0, Ordering::Relaxed);
+            }
+            t0
+        }
+    }
+
+    /// Returns how often decisions were made at times that the clock went back to.
+    pub(crate) fn clock_regressions(&self) -> u64 {
+        self.clock_regressions.load(Ordering::Relaxed)
+    }
+
+    /// Computes the parameters `(t, tau)` for a quota.
+    ///
+    /// Cells are spaced out by at least one nanosecond, since states are kept in whole
+    /// nanoseconds. `tau` is rounded up, so that it fits the entire burst size, and is at least
+    /// the quota's tolerance (saturating at ~584 years).
+    pub(crate) fn params_for(quota: Quota) -> (Interval, Nanos) {
+        let t = cmp::max(quota.interval(), Interval::ONE_NANO);
+        let tau = t.times(u64::from(quota.max_burst.get())).ceil();
+        let tolerance = Nanos::saturating_from_u128(quota.tolerance.as_nanos());
+        (t, cmp::max(tau, tolerance))
+    }
+
+    /// Returns a consistent reading of the parameters `(t, tau)`.
+    pub(crate) fn params(&self) -> (Interval, Nanos) {
+        loop {
+            let before = self.seq.load(Ordering::Acquire);
+            let t = self.t.load(Ordering::Relaxed);
+            let t_frac = self.t_frac.load(Ordering::Relaxed);
+            let tau = self.tau.load(Ordering::Relaxed);
+            fence(Ordering::Acquire);
+            let after = self.seq.load(Ordering::Relaxed);
+            if before == after && before & 1 == 0 {
+                return (Interval::new(t, t_frac), tau);
+            }
+            std::hint::spin_loop();
+        }
+    }
+
+    /// Replaces the parameters with those for a new quota.
+    pub(crate) fn set_quota(&self, quota: Quota) {
+        let (t, tau) = Self::params_for(quota);
+        let mut seq = self.seq.load(Ordering::Relaxed);
+        loop {
+            if seq & 1 == 1 {
+                // Another writer is replacing the parameters; wait for it to finish.
+                std::hint::spin_loop();
+                seq = self.seq.load(Ordering::Relaxed);
+                continue;
+            }
+            match self
+                .seq
+                .compare_exchange_weak(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(next_seq) => seq = next_seq,
+            }
+        }
+        fence(Ordering::Release);
+        self.t.store(t.whole(), Ordering::Relaxed);
+        self.t_frac.store(t.frac(), Ordering::Relaxed);
+        self.tau.store(tau, Ordering::Relaxed);
+        self.start_empty.store(quota.start_empty, Ordering::Relaxed);
+        self.strict.store(quota.strict, Ordering::Relaxed);
+        self.seq.store(seq + 2, Ordering::Release);
+    }
+
+    /// Returns the quota that the parameters `(t, tau)` enforce.
+    fn quota_for(t: Interval, tau: Nanos) -> Quota {
+        let max_burst = u32::try_from(t.count_in(tau)).unwrap_or(u32::MAX);
+        Quota::from_interval(
+            NonZeroU32::new(max_burst).unwrap_or(nonzero_ext::nonzero!(1u32)),
+            t,
+        )
+    }
+
+    /// Computes the state in which the entire burst capacity is available at `t0`.
+    fn full_state(t: Interval, t0: Nanos) -> Nanos {
+        t0 + Nanos::from(t.whole())
     }
 
     /// Computes and returns a new ratelimiter state if none exists yet.
-    fn starting_state(&self, t0: Nanos) -> Nanos {
-        t0 + self.t
+    ///
+    /// New states have their entire burst capacity available, unless the quota starts empty.
+    fn starting_state(&self, t: Interval, tau: Nanos, t0: Nanos) -> Nanos {
+        if self.start_empty.load(Ordering::Relaxed) {
+            Self::full_state(t, t0) + tau
+        } else {
+            Self::full_state(t, t0)
+        }
     }
 
-    /// Tests a single cell against the rate limiter state and updates it at the given key.
-    pub(crate) fn test_and_update<K, P: clock::Reference>(
+    /// Computes the time from which cells allowed through to the state `tat` at `t0` are
+    /// accounted for.
+    ///
+    /// For [strict](Quota::strict_interval) quotas, a state that has replenished completely is
+    /// treated like the [full state][Self::full_state] at `t0`, so that it doesn't let a cell
+    /// through early.
+    fn base(&self, t: Interval, tat: Nanos, t0: Nanos) -> Nanos {
+        if self.strict.load(Ordering::Relaxed) {
+            cmp::max(tat, Self::full_state(t, t0))
+        } else {
+            cmp::max(tat, t0)
+        }
+    }
+
+    /// Stores the starting state at the given key if the quota starts empty and there is no
+    /// state yet.
+    ///
+    /// Otherwise, a state that starts empty would be recomputed from scratch (and found empty)
+    /// on every check, since negative decisions don't update the state.
+    fn initialize<K: ?Sized>(
+        &self,
+        key: &K,
+        state: &impl StateLookup<K>,
+        t: Interval,
+        tau: Nanos,
+        t0: Nanos,
+    ) {
+        if self.start_empty.load(Ordering::Relaxed) {
+            let _ = state.measure_and_replace_at(key, |tat| match tat {
+                None => Ok(((), self.starting_state(t, tau, t0))),
+                Some(_) => Err(()),
+            });
+        }
+    }
+
+    /// Computes information about the state `tat` at time `t0`.
+    pub(crate) fn state_information(&self, tat: Option<Nanos>, t0: Nanos) -> StateInformation {
+        let (t, tau) = self.params();
+        let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+        StateInformation {
+            t,
+            tau,
+            tat,
+            t0,
+            cells: 0,
+        }
+    }
+
+    /// Computes how long before `t0` the state `tat` was last advanced to, if there is a state.
+    ///
+    /// A cell that is allowed through to a state that has replenished completely at time `a`
+    /// advances the state to `a` plus one interval, which is what the
+    /// [full state][Self::full_state] at `a` looks like, so the age is measured from there.
+    pub(crate) fn state_age(&self, tat: Option<Nanos>, t0: Nanos) -> Option<Duration> {
+        let (t, _) = self.params();
+        tat.map(|tat| Self::full_state(t, t0).saturating_sub(tat).into())
+    }
+
+    /// Computes the state after `n` cells are accounted for starting at `base`.
+    ///
+    /// If that state is too far in the future to be represented (e.g. because the clock has
+    /// been running for most of the ~584 years that [`Nanos`] can represent), the cells are
+    /// rejected: Saturating the state instead would let every following cell through. The
+    /// negative decision's earliest possible time is the latest representable time.
+    fn next_state<'n, P: clock::Reference>(
+        t: Interval,
+        tau: Nanos,
+        base: Nanos,
+        n: u64,
+        t0: Nanos,
+        start: P,
+    ) -> Result<Nanos, NotUntil<'n, P>> {
+        t.checked_after(base, n).ok_or(NotUntil {
+            limiter: PhantomData,
+            t,
+            tau,
+            tat: Nanos::MAX,
+            decided_at: t0,
+            start,
+        })
+    }
+
+    /// Tests a single cell against the rate limiter state and updates it at the given key,
+    /// informing the middleware of the outcome.
+    ///
+    /// Since negative outcomes carry a copy of the parameters, they may outlive `self`.
+    pub(crate) fn test_and_update<
+        'n,
+        K: ?Sized,
+        P: clock::Reference,
+        MW: RateLimitingMiddleware<K, P>,
+    >(
         &self,
         start: P,
         key: &K,
-        state: &impl StateStore<Key = K>,
+        state: &impl StateLookup<K>,
         t0: P,
-    ) -> Result<(), NotUntil<P>> {
-        let t0 = t0.duration_since(start);
-        let tau = self.tau;
-        let t = self.t;
-        state.measure_and_replace(key, |tat| {
-            let tat = tat.unwrap_or_else(|| self.starting_state(t0));
+        middleware: &MW,
+    ) -> Result<MW::PositiveOutcome, NotUntil<'n, P>> {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        self.initialize(key, state, t, tau, t0);
+        let result = state.measure_and_replace_at(key, |tat| {
+            let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
             let earliest_time = tat.saturating_sub(tau);
             if t0 < earliest_time {
                 Err(NotUntil {
-                    limiter: self,
+                    limiter: PhantomData,
+                    t,
+                    tau,
                     tat: earliest_time,
+                    decided_at: t0,
                     start,
                 })
             } else {
-                Ok(((), cmp::max(tat, t0) + t))
+                let next = Self::next_state(t, tau, self.base(t, tat, t0), 1, t0, start)?;
+                Ok((next, next))
             }
-        })
+        });
+        match result {
+            Ok(tat) => self.admit(
+                start,
+                key,
+                1,
+                state,
+                StateInformation {
+                    t,
+                    tau,
+                    tat,
+                    t0,
+                    cells: 1,
+                },
+                middleware,
+            ),
+            Err(negative) => {
+                middleware.disallow_cell(key, &negative);
+                Err(negative)
+            }
+        }
     }
 
-    /// Tests whether all `n` cells could be accommodated and updates the rate limiter state, if so.
-    pub(crate) fn test_n_all_and_update<K, P: clock::Reference>(
+    /// Offers `n` cells that the rate limiter state at the given key allowed through to the
+    /// middleware's [veto](RateLimitingMiddleware::veto_cell), and informs the middleware of the
+    /// outcome.
+    ///
+    /// If the middleware vetoes the cells, their capacity is returned to the state, and they are
+    /// rejected with the middleware's wait time.
+    fn admit<'n, K: ?Sized, P: clock::Reference, MW: RateLimitingMiddleware<K, P>>(
+        &self,
+        start: P,
+        key: &K,
+        n: u64,
+        state: &impl StateLookup<K>,
+        info: StateInformation,
+        middleware: &MW,
+    ) -> Result<MW::PositiveOutcome, NotUntil<'n, P>> {
+        let wait = match middleware.veto_cell(key, &info) {
+            None => return Ok(middleware.allow_cell(key, info)),
+            Some(wait) => wait,
+        };
+        let StateInformation { t, tau, t0, .. } = info;
+        self.refund(key, n, state, t0);
+        let negative = NotUntil {
+            limiter: PhantomData,
+            t,
+            tau,
+            tat: t0 + wait,
+            decided_at: t0,
+            start,
+        };
+        middleware.disallow_cell(key, &negative);
+        Err(negative)
+    }
+
+    /// Tests whether a single cell would conform to the rate limiter state at the given key,
+    /// without updating it.
+    pub(crate) fn test<K, P: clock::Reference>(
         &self,
         start: P,
         key: &K,
-        n: NonZeroU32,
         state: &impl StateStore<Key = K>,
         t0: P,
-    ) -> Result<(), NegativeMultiDecision<NotUntil<P>>> {
-        let t0 = t0.duration_since(start);
-        let tau = self.tau;
-        let t = self.t;
-        let additional_weight = t * (n.get() - 1) as u64;
+    ) -> Result<(), NotUntil<'_, P>> {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        let tat = state
+            .peek(key)
+            .unwrap_or_else(|| self.starting_state(t, tau, t0));
+        let earliest_time = tat.saturating_sub(tau);
+        if t0 < earliest_time {
+            Err(NotUntil {
+                limiter: PhantomData,
+                t,
+                tau,
+                tat: earliest_time,
+                decided_at: t0,
+                start,
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Tests whether all `n` cells could be accommodated and updates the rate limiter state, if so,
+    /// informing the middleware of the outcome.
+    pub(crate) fn test_n_all_and_update<
+        K: ?Sized,
+        P: clock::Reference,
+        MW: RateLimitingMiddleware<K, P>,
+    >(
+        &self,
+        start: P,
+        key: &K,
+        n: NonZeroU32,
+        state: &impl StateLookup<K>,
+        t0: P,
+        middleware: &MW,
+    ) -> Result<MW::PositiveOutcome, NegativeMultiDecision<NotUntil<P>>> {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        let n64 = u64::from(n.get());
 
-        // check that we can allow enough cells through. Note that `additional_weight` is the
-        // value of the cells *in addition* to the first cell - so add that first cell back.
-        if additional_weight + t > tau {
-            return Err(NegativeMultiDecision::InsufficientCapacity(
-                (tau.as_u64() / t.as_u64()) as u32,
-            ));
+        // check that we can allow enough cells through.
+        let burst_size = t.count_in(tau);
+        if n64 > burst_size {
+            return Err(NegativeMultiDecision::InsufficientCapacity {
+                requested: n.get(),
+                max_burst: u32::try_from(burst_size).unwrap_or(u32::MAX),
+            });
         }
-        state.measure_and_replace(key, |tat| {
-            let tat = tat.unwrap_or_else(|| self.starting_state(t0));
+        self.initialize(key, state, t, tau, t0);
+        let result = state.measure_and_replace_at(key, |tat| {
+            let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+            // The weight of the cells *in addition* to the first cell:
+            let additional_weight = t.weight(tat, n64 - 1);
             let earliest_time = (tat + additional_weight).saturating_sub(tau);
             if t0 < earliest_time {
                 Err(NegativeMultiDecision::BatchNonConforming(
                     n.get(),
                     NotUntil {
-                        limiter: self,
+                        limiter: PhantomData,
+                        t,
+                        tau,
                         tat: earliest_time,
+                        decided_at: t0,
                         start,
                     },
                 ))
             } else {
-                Ok(((), cmp::max(tat, t0) + t + additional_weight))
+                let next = Self::next_state(t, tau, self.base(t, tat, t0), n64, t0, start)
+                    .map_err(|negative| {
+                        NegativeMultiDecision::BatchNonConforming(n.get(), negative)
+                    })?;
+                Ok((next, next))
+            }
+        });
+        match result {
+            Ok(tat) => self
+                .admit(
+                    start,
+                    key,
+                    n64,
+                    state,
+                    StateInformation {
+                        t,
+                        tau,
+                        tat,
+                        t0,
+                        cells: n.get(),
+                    },
+                    middleware,
+                )
+                .map_err(|negative| NegativeMultiDecision::BatchNonConforming(n.get(), negative)),
+            Err(NegativeMultiDecision::BatchNonConforming(n, negative)) => {
+                middleware.disallow_cell(key, &negative);
+                Err(NegativeMultiDecision::BatchNonConforming(n, negative))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Tests `n` single cells in sequence against the rate limiter state and updates it at the
+    /// given key for the cells that conform, informing the middleware of each outcome.
+    ///
+    /// The cells that don't conform are rejected as if each of them waited for the ones before
+    /// it: The `j`-th rejected cell's earliest possible time is `j` cells' weight after the
+    /// first rejected cell's.
+    pub(crate) fn test_batch_and_update<
+        'n,
+        K,
+        P: clock::Reference,
+        MW: RateLimitingMiddleware<K, P>,
+    >(
+        &self,
+        start: P,
+        key: &K,
+        n: usize,
+        state: &impl StateLookup<K>,
+        t0: P,
+        middleware: &MW,
+    ) -> Vec<Result<MW::PositiveOutcome, NotUntil<'n, P>>> {
+        if n == 0 {
+            return Vec::new();
+        }
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        self.initialize(key, state, t, tau, t0);
+        let result = state.measure_and_replace_at(key, |tat| {
+            let mut tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+            // The states after each of the cells that conform:
+            let mut allowed = Vec::new();
+            while allowed.len() < n && t0 >= tat.saturating_sub(tau) {
+                match Self::next_state(t, tau, self.base(t, tat, t0), 1, t0, start) {
+                    Ok(next) => tat = next,
+                    Err(_) => break,
+                }
+                allowed.push(tat);
+            }
+            if allowed.is_empty() {
+                Err(tat)
+            } else {
+                Ok((allowed, tat))
+            }
+        });
+        let (allowed, tat) = match result {
+            Ok(allowed) => {
+                let tat = allowed[allowed.len() - 1];
+                (allowed, tat)
+            }
+            Err(tat) => (Vec::new(), tat),
+        };
+        let rejected = n - allowed.len();
+        let mut outcomes = Vec::with_capacity(n);
+        outcomes.extend(allowed.into_iter().map(|tat| {
+            self.admit(
+                start,
+                key,
+                1,
+                state,
+                StateInformation {
+                    t,
+                    tau,
+                    tat,
+                    t0,
+                    cells: 1,
+                },
+                middleware,
+            )
+        }));
+        outcomes.extend((0..rejected as u64).map(|j| {
+            let negative = NotUntil {
+                limiter: PhantomData,
+                t,
+                tau,
+                tat: (tat + t.weight(tat, j)).saturating_sub(tau),
+                decided_at: t0,
+                start,
+            };
+            middleware.disallow_cell(key, &negative);
+            Err(negative)
+        }));
+        outcomes
+    }
+
+    /// Updates the rate limiter state at the given key for `n` cells, if it conforms for at least
+    /// one cell, allowing the cells that don't fit into the burst capacity to use up capacity
+    /// from the future.
+    pub(crate) fn test_n_borrow_and_update<'n, K, P: clock::Reference>(
+        &self,
+        start: P,
+        key: &K,
+        n: NonZeroU32,
+        state: &impl StateStore<Key = K>,
+        t0: P,
+    ) -> Result<Borrowed, NotUntil<'n, P>> {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        self.initialize(key, state, t, tau, t0);
+        state.measure_and_replace(key, |tat| {
+            let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+            let earliest_time = tat.saturating_sub(tau);
+            if t0 < earliest_time {
+                return Err(NotUntil {
+                    limiter: PhantomData,
+                    t,
+                    tau,
+                    tat: earliest_time,
+                    decided_at: t0,
+                    start,
+                });
+            }
+            let info = StateInformation {
+                t,
+                tau,
+                tat,
+                t0,
+                cells: 0,
+            };
+            let available = info.remaining_burst_capacity();
+            let base = self.base(t, tat, t0);
+            let next = Self::next_state(t, tau, base, u64::from(n.get()), t0, start)?;
+            let borrowed = Borrowed {
+                cells: n.get().saturating_sub(available),
+                wait_time: next.saturating_sub(tau).saturating_sub(t0).into(),
+            };
+            Ok((borrowed, next))
+        })
+    }
+
+    /// Tests whether `reserve` more cells than a single one could be accommodated, and updates
+    /// the rate limiter state for only the single cell, if so.
+    ///
+    /// This keeps the capacity of `reserve` cells available for other decisions.
+    pub(crate) fn test_reserving_and_update<'n, K, P: clock::Reference>(
+        &self,
+        start: P,
+        key: &K,
+        reserve: u64,
+        state: &impl StateStore<Key = K>,
+        t0: P,
+    ) -> Result<(), NotUntil<'n, P>> {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        self.initialize(key, state, t, tau, t0);
+        state.measure_and_replace(key, |tat| {
+            let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+            let earliest_time = (tat + t.weight(tat, reserve)).saturating_sub(tau);
+            if t0 < earliest_time {
+                Err(NotUntil {
+                    limiter: PhantomData,
+                    t,
+                    tau,
+                    tat: earliest_time,
+                    decided_at: t0,
+                    start,
+                })
+            } else {
+                let next = Self::next_state(t, tau, self.base(t, tat, t0), 1, t0, start)?;
+                Ok(((), next))
             }
         })
     }
+
+    /// Counts a single cell against the rate limiter state at the given key right away, for
+    /// the earliest time at which the state allows it through, and returns how long after `t0`
+    /// that is.
+    pub(crate) fn schedule<K, P: clock::Reference>(
+        &self,
+        start: P,
+        key: &K,
+        state: &impl StateStore<Key = K>,
+        t0: P,
+    ) -> Nanos {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        self.initialize(key, state, t, tau, t0);
+        let scheduled: Result<Nanos, Infallible> = state.measure_and_replace(key, |tat| {
+            let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+            let slot = cmp::max(tat.saturating_sub(tau), t0);
+            let next = t
+                .checked_after(self.base(t, tat, t0), 1)
+                .unwrap_or(Nanos::MAX);
+            Ok((slot.saturating_sub(t0), next))
+        });
+        scheduled.unwrap_or_else(|never| match never {})
+    }
+
+    /// Returns `n` cells' worth of capacity to the rate limiter state at the given key.
+    ///
+    /// The theoretical arrival time is moved back by `n` replenishment intervals, but never
+    /// further than to the state of an empty bucket, so refunds can't make more than the burst
+    /// capacity available.
+    pub(crate) fn refund_n<K, P: clock::Reference>(
+        &self,
+        start: P,
+        key: &K,
+        n: NonZeroU32,
+        state: &impl StateStore<Key = K>,
+        t0: P,
+    ) {
+        let t0 = self.elapsed(start, t0);
+        self.refund(key, u64::from(n.get()), state, t0);
+    }
+
+    /// Returns `n` cells' worth of capacity to the rate limiter state at the given key, at time
+    /// `t0` (see [`refund_n`][Self::refund_n]).
+    fn refund<K: ?Sized>(&self, key: &K, n: u64, state: &impl StateLookup<K>, t0: Nanos) {
+        let (t, _) = self.params();
+        let refund = Nanos::from(t.times(n).whole());
+        let empty = Self::full_state(t, t0);
+        // If the bucket is already empty, there's nothing to refund and the state stays as is:
+        let _ = state.measure_and_replace_at(key, |tat| match tat {
+            Some(tat) if tat > empty => Ok(((), cmp::max(tat.saturating_sub(refund), empty))),
+            _ => Err(()),
+        });
+    }
+
+    /// Uses up the capacity of `n` cells at the given key, whether or not the rate limiter would
+    /// allow them through, but without using up more than its entire burst capacity.
+    pub(crate) fn charge_n<K, P: clock::Reference>(
+        &self,
+        start: P,
+        key: &K,
+        n: NonZeroU32,
+        state: &impl StateStore<Key = K>,
+        t0: P,
+    ) {
+        let t0 = self.elapsed(start, t0);
+        let (t, tau) = self.params();
+        let exhausted = Self::full_state(t, t0) + tau;
+        let _ = state.measure_and_replace(key, |tat| {
+            let tat = tat.unwrap_or_else(|| self.starting_state(t, tau, t0));
+            let charged = t
+                .checked_after(self.base(t, tat, t0), u64::from(n.get()))
+                .map_or(exhausted, |charged| cmp::min(charged, exhausted));
+            // A state that is already past exhausted (e.g. after borrowing) stays as is:
+            Ok::<_, ()>(((), cmp::max(tat, charged)))
+        });
+    }
 }
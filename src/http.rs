@@ -0,0 +1,60 @@
+//! Rate-limit headers for responses built with the [`http`](https://docs.rs/http) crate.
+//!
+//! [`insert_rate_limit_headers`] sets the `RateLimit-Limit`, `RateLimit-Remaining` and
+//! `RateLimit-Reset` headers described in the IETF draft [RateLimit header fields for
+//! HTTP](https://datatracker.ietf.org/doc/draft-ietf-httpapi-ratelimit-headers/) from the
+//! [`StateInformation`] that the
+//! [`StateInformationMiddleware`][crate::middleware::StateInformationMiddleware] returns for
+//! positive decisions.
+//!
+//! # Example
+//! ```rust
+//! # use governor::{http::insert_rate_limit_headers, middleware::StateInformationMiddleware};
+//! # use governor::{Quota, RateLimiter};
+//! # use nonzero_ext::nonzero;
+//! let lim = RateLimiter::direct(Quota::per_minute(nonzero!(60u32)))
+//!     .with_middleware(StateInformationMiddleware);
+//! let mut headers = http::HeaderMap::new();
+//! if let Ok(state) = lim.check() {
+//!     insert_rate_limit_headers(&state, &mut headers);
+//! }
+//! assert_eq!(headers["ratelimit-limit"], "60");
+//! assert_eq!(headers["ratelimit-remaining"], "59");
+//! ```
+
+use crate::StateInformation;
+use ::http::header::{HeaderMap, HeaderName, HeaderValue};
+
+/// The `RateLimit-Limit` header: The quota's burst size.
+pub const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+
+/// The `RateLimit-Remaining` header: The number of cells that could be allowed through right away.
+pub const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+
+/// The `RateLimit-Reset` header: The number of seconds until the full burst capacity is available.
+pub const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Inserts the rate-limit headers describing `state` into `headers`, replacing any values they
+/// had before.
+///
+/// * `RateLimit-Limit` is the quota's burst size.
+/// * `RateLimit-Remaining` is the [remaining burst
+///   capacity](StateInformation::remaining_burst_capacity).
+/// * `RateLimit-Reset` is the time (as delta-seconds, i.e. a whole number of seconds) until the
+///   rate limiter will have its entire burst capacity available again, if no more cells are
+///   allowed through in the meantime. It is rounded *up* to the next whole second, so that
+///   clients that wait for it don't find the quota still partially used up.
+pub fn insert_rate_limit_headers(state: &StateInformation, headers: &mut HeaderMap) {
+    let reset = state.time_until_full();
+    let reset = if reset.subsec_nanos() > 0 {
+        reset.as_secs() + 1
+    } else {
+        reset.as_secs()
+    };
+    headers.insert(RATELIMIT_LIMIT, HeaderValue::from(state.burst_size()));
+    headers.insert(
+        RATELIMIT_REMAINING,
+        HeaderValue::from(state.remaining_burst_capacity()),
+    );
+    headers.insert(RATELIMIT_RESET, HeaderValue::from(reset));
+}
@@ -2,9 +2,14 @@ use std::prelude::v1::*;
 
 use crate::nanos::Nanos;
 use rand::distributions::uniform::{SampleBorrow, SampleUniform, UniformInt, UniformSampler};
-use rand::distributions::{Distribution, Uniform};
-use rand::{thread_rng, Rng};
+use rand::distributions::Distribution;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::{Rng, RngCore};
+use std::cmp;
+use std::fmt;
 use std::ops::Add;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
 #[cfg(feature = "std")]
@@ -25,6 +30,7 @@ use std::time::Instant;
 /// Jitter can be added manually to a `Duration`:
 ///
 /// ```rust
+/// # #[cfg(feature = "std")] fn main() {
 /// # use governor::Jitter;
 /// # use std::time::Duration;
 /// let reference = Duration::from_secs(24);
@@ -32,6 +38,7 @@ use std::time::Instant;
 /// let result = jitter + reference;
 /// assert!(result >= reference + Duration::from_secs(1));
 /// assert!(result < reference + Duration::from_secs(2))
+/// # } #[cfg(not(feature = "std"))] fn main() {}
 /// ```
 ///
 /// In a `std` build (the default), Jitter can also be added to an `Instant`:
@@ -49,42 +56,483 @@ use std::time::Instant;
 /// # }
 /// # #[cfg(not(feature = "std"))] fn main() {}
 /// ```
-#[derive(Debug, PartialEq, Default, Clone, Copy)]
+///
+/// By default, jitter is drawn from the thread-local random number generator. To get a
+/// reproducible sequence of jitter values (e.g. in tests), supply a seeded source with
+/// [`with_rng`](#method.with_rng), or construct the jitter with one (like
+/// [`up_to_with_rng`](#method.up_to_with_rng) does):
+///
+/// ```rust
+/// # use governor::{Jitter, SeededJitterRng};
+/// # use std::time::Duration;
+/// static RNG_A: SeededJitterRng = SeededJitterRng::new(42);
+/// static RNG_B: SeededJitterRng = SeededJitterRng::new(42);
+/// let a = Jitter::up_to_with_rng(Duration::from_secs(1), &RNG_A);
+/// let b = Jitter::up_to_with_rng(Duration::from_secs(1), &RNG_B);
+/// for _ in 0..10 {
+///     assert_eq!(a + Duration::new(0, 0), b + Duration::new(0, 0));
+/// }
+/// ```
+///
+/// # Sources of randomness in `no_std` builds
+///
+/// Without the `std` feature, there is no thread-local random number generator to fall back on,
+/// so the constructors that would use it ([`up_to`](#method.up_to), [`new`](#method.new) and
+/// [`decorrelated`](#method.decorrelated)) don't exist. Jitter is then constructed with a
+/// [`JitterRng`] that the caller supplies, e.g. one backed by a hardware random number
+/// generator, or a [`SeededJitterRng`].
+///
+/// Jitter values are distributed uniformly over the interval, unless another
+/// [`JitterDistribution`] is given with [`with_distribution`](#method.with_distribution).
+#[derive(Default, Clone, Copy)]
 pub struct Jitter {
     min: Nanos,
     max: Nanos,
+    rng: Option<&'static dyn JitterRng>,
+    distribution: Option<&'static dyn JitterDistribution>,
 }
 
 impl Jitter {
-    #[cfg(feature = "std")]
     /// The "empty" jitter interval - no jitter at all.
     pub(crate) const NONE: Jitter = Jitter {
         min: Nanos::new(0),
         max: Nanos::new(0),
+        rng: None,
+        distribution: None,
     };
 
+    /// Constructs an empty Jitter interval, which adds no jitter at all.
+    ///
+    /// Adding it to a `Duration` (or `Instant`) returns that value unchanged, without drawing a
+    /// random number.
+    pub const fn none() -> Jitter {
+        Jitter::NONE
+    }
+
     /// Constructs a new Jitter interval, waiting at most a duration of `max`.
+    #[cfg(feature = "std")]
     pub fn up_to(max: Duration) -> Jitter {
         Jitter {
             min: Nanos::from(0),
             max: max.into(),
+            rng: None,
+            distribution: None,
         }
     }
 
     /// Constructs a new Jitter interval, waiting at least `min` and at most `min+interval`.
+    #[cfg(feature = "std")]
     pub fn new(min: Duration, interval: Duration) -> Jitter {
         let min: Nanos = min.into();
         let max: Nanos = min + Nanos::from(interval);
-        Jitter { min, max }
+        Jitter {
+            min,
+            max,
+            rng: None,
+            distribution: None,
+        }
+    }
+
+    /// Constructs a new Jitter interval, waiting at most a duration of `max`, that draws its
+    /// values from the given source of randomness.
+    ///
+    /// Unlike [`up_to`](#method.up_to), this is available in `no_std` builds.
+    pub fn up_to_with_rng(max: Duration, rng: &'static dyn JitterRng) -> Jitter {
+        Jitter {
+            min: Nanos::from(0),
+            max: max.into(),
+            rng: Some(rng),
+            distribution: None,
+        }
+    }
+
+    /// Constructs a new Jitter interval, waiting at least `min` and at most `min+interval`, that
+    /// draws its values from the given source of randomness.
+    ///
+    /// Unlike [`new`](#method.new), this is available in `no_std` builds.
+    pub fn new_with_rng(min: Duration, interval: Duration, rng: &'static dyn JitterRng) -> Jitter {
+        let min: Nanos = min.into();
+        let max: Nanos = min + Nanos::from(interval);
+        Jitter {
+            min,
+            max,
+            rng: Some(rng),
+            distribution: None,
+        }
+    }
+
+    /// The minimum amount of jitter that this interval adds to a wait period.
+    pub const fn min(&self) -> Duration {
+        self.min.as_duration()
+    }
+
+    /// The maximum amount of jitter that this interval adds to a wait period (`min + interval`).
+    ///
+    /// This can be used to compute an upper bound for the total time that waiting on a rate
+    /// limiter with this jitter can take.
+    pub const fn max(&self) -> Duration {
+        self.max.as_duration()
+    }
+
+    /// Draws the jitter values from the given source of randomness instead of the thread-local
+    /// random number generator.
+    pub fn with_rng(self, rng: &'static dyn JitterRng) -> Jitter {
+        Jitter {
+            rng: Some(rng),
+            ..self
+        }
+    }
+
+    /// Draws the jitter values from the given distribution over the interval, instead of the
+    /// default uniform distribution.
+    pub fn with_distribution(self, distribution: &'static dyn JitterDistribution) -> Jitter {
+        Jitter {
+            distribution: Some(distribution),
+            ..self
+        }
+    }
+
+    /// Constructs a new [`DecorrelatedJitter`], which grows each jitter value from the previous
+    /// one, staying between `base` and `cap` (which are swapped if `base` is longer).
+    #[cfg(feature = "std")]
+    pub fn decorrelated(base: Duration, cap: Duration) -> DecorrelatedJitter {
+        DecorrelatedJitter::new(base, cap)
     }
 
     /// Returns a random amount of jitter within the configured interval.
     pub(crate) fn get(&self) -> Nanos {
+        // Empty intervals (like `Jitter::none()`) have only one possible value, so don't bother
+        // the source of randomness:
         if self.min == self.max {
             return self.min;
         }
-        let uniform = Uniform::new(self.min, self.max);
-        uniform.sample(&mut thread_rng())
+        let distribution = self.distribution.unwrap_or(&Uniform);
+        let min: Duration = self.min.into();
+        let interval: Duration = self.max.saturating_sub(self.min).into();
+        let amount = match self.rng {
+            Some(rng) => distribution.sample(&mut JitterRngCore(rng), min, interval),
+            #[cfg(feature = "std")]
+            None => distribution.sample(&mut thread_rng(), min, interval),
+            // Without `std`, only empty intervals (like the default one) lack a source:
+            #[cfg(not(feature = "std"))]
+            None => min,
+        };
+        amount.into()
+    }
+}
+
+impl fmt::Debug for Jitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("Jitter")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .field("rng", &self.rng)
+            .field("distribution", &self.distribution)
+            .finish()
+    }
+}
+
+/// Two Jitter intervals are equal if they cover the same interval and draw from the same source
+/// of randomness and distribution.
+impl PartialEq for Jitter {
+    fn eq(&self, other: &Self) -> bool {
+        let same_rng = match (self.rng, other.rng) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a as *const dyn JitterRng as *const () == b as *const dyn JitterRng as *const ()
+            }
+            _ => false,
+        };
+        let same_distribution = match (self.distribution, other.distribution) {
+            (None, None) => true,
+            (Some(a), Some(b)) => {
+                a as *const dyn JitterDistribution as *const ()
+                    == b as *const dyn JitterDistribution as *const ()
+            }
+            _ => false,
+        };
+        self.min == other.min && self.max == other.max && same_rng && same_distribution
+    }
+}
+
+/// A "decorrelated" jitter, as described in the AWS Architecture Blog post on [Exponential
+/// Backoff and Jitter](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/).
+///
+/// Unlike [`Jitter`], which picks a value from a fixed interval every time it is used, a
+/// `DecorrelatedJitter` remembers the previous value it produced, and picks the next one
+/// uniformly from `[base, previous * 3)`, capped at `cap`. This spreads out retries of many
+/// clients better than a fixed interval does, while still growing roughly geometrically.
+///
+/// Since it needs to keep state, a `DecorrelatedJitter` is not `Copy`; the state is kept in an
+/// atomic integer, so a single `DecorrelatedJitter` can be shared between tasks.
+///
+/// # Relation to `Jitter`'s interval
+///
+/// A `DecorrelatedJitter` has no fixed `min`/`interval`: `base` acts as the lower bound for every
+/// value, and `cap` as the upper bound. To use it with methods that accept a [`Jitter`] (like
+/// [`until_ready_with_jitter`](struct.RateLimiter.html#method.until_ready_with_jitter)),
+/// use [`next_jitter`](#method.next_jitter), which draws the next value and returns it as a
+/// `Jitter` with `min` set to that value and an empty interval.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use governor::Jitter;
+/// # use std::time::Duration;
+/// let base = Duration::from_millis(10);
+/// let cap = Duration::from_secs(1);
+/// let jitter = Jitter::decorrelated(base, cap);
+/// let reference = Duration::from_secs(5);
+/// for _ in 0..10 {
+///     let result = &jitter + reference;
+///     assert!(result >= reference + base);
+///     assert!(result <= reference + cap);
+/// }
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct DecorrelatedJitter {
+    base: Nanos,
+    cap: Nanos,
+    previous: AtomicU64,
+    rng: Option<&'static dyn JitterRng>,
+}
+
+impl DecorrelatedJitter {
+    /// Constructs a new decorrelated jitter, whose values start at `base` and never exceed `cap`.
+    ///
+    /// Durations longer than ~584 years (like [`Duration::MAX`], for an unlimited cap) count as
+    /// ~584 years long. If `base` is longer than `cap`, the two are swapped, so that the values
+    /// always stay between the shorter and the longer duration.
+    #[cfg(feature = "std")]
+    pub fn new(base: Duration, cap: Duration) -> DecorrelatedJitter {
+        DecorrelatedJitter::with_bounds(base, cap, None)
+    }
+
+    /// Constructs a new decorrelated jitter, whose values start at `base` and never exceed `cap`,
+    /// and that draws its values from the given source of randomness.
+    ///
+    /// Unlike [`new`](#method.new), this is available in `no_std` builds. As with `new`, a
+    /// `base` longer than `cap` is swapped with it.
+    pub fn new_with_rng(
+        base: Duration,
+        cap: Duration,
+        rng: &'static dyn JitterRng,
+    ) -> DecorrelatedJitter {
+        DecorrelatedJitter::with_bounds(base, cap, Some(rng))
+    }
+
+    /// Constructs a decorrelated jitter between the shorter and the longer of `base` and `cap`.
+    fn with_bounds(
+        base: Duration,
+        cap: Duration,
+        rng: Option<&'static dyn JitterRng>,
+    ) -> DecorrelatedJitter {
+        let (base, cap) = if base <= cap {
+            (base, cap)
+        } else {
+            (cap, base)
+        };
+        let base = saturating_nanos(base);
+        let cap = saturating_nanos(cap);
+        DecorrelatedJitter {
+            base,
+            cap,
+            previous: AtomicU64::new(base.into()),
+            rng,
+        }
+    }
+
+    /// Draws the jitter values from the given source of randomness instead of the thread-local
+    /// random number generator.
+    pub fn with_rng(self, rng: &'static dyn JitterRng) -> DecorrelatedJitter {
+        DecorrelatedJitter {
+            rng: Some(rng),
+            ..self
+        }
+    }
+
+    /// Draws the next jitter value and returns it as a [`Jitter`] that always adds exactly that
+    /// value.
+    pub fn next_jitter(&self) -> Jitter {
+        let next = self.get();
+        Jitter {
+            min: next,
+            max: next,
+            rng: None,
+            distribution: None,
+        }
+    }
+
+    /// Returns the next jitter value, updating the state.
+    ///
+    /// If multiple tasks draw values concurrently, some of them may base their value on the same
+    /// previous one.
+    pub(crate) fn get(&self) -> Nanos {
+        let previous = self.previous.load(Ordering::Relaxed);
+        let high = cmp::max(self.base, Nanos::from(previous.saturating_mul(3)));
+        let interval = Jitter {
+            min: self.base,
+            max: high,
+            rng: self.rng,
+            distribution: None,
+        };
+        let next = cmp::min(self.cap, interval.get());
+        self.previous.store(next.into(), Ordering::Relaxed);
+        next
+    }
+}
+
+/// Converts a duration to nanoseconds, saturating at the longest representable [`Nanos`]
+/// (~584 years), so that "unlimited" caps like [`Duration::MAX`] work.
+fn saturating_nanos(duration: Duration) -> Nanos {
+    Nanos::saturating_from_u128(duration.as_nanos())
+}
+
+/// A distribution of jitter values over an interval.
+///
+/// Implementations pick a value in the interval `[min, min + interval)` using the given source of
+/// randomness. Since [`Jitter`] refers to its distribution with a `'static` reference, parametrized
+/// distributions should be kept in a `static`:
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use governor::{Jitter, distribution::Exponential};
+/// # use std::time::Duration;
+/// static MOSTLY_SHORT: Exponential = Exponential::new(4.0);
+/// let jitter = Jitter::up_to(Duration::from_secs(1)).with_distribution(&MOSTLY_SHORT);
+/// assert!(jitter + Duration::new(0, 0) < Duration::from_secs(1));
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+pub trait JitterDistribution: fmt::Debug + Sync {
+    /// Returns a value in `[min, min + interval)`.
+    fn sample(&self, rng: &mut dyn RngCore, min: Duration, interval: Duration) -> Duration;
+}
+
+/// The uniform distribution of jitter values, giving every value in the interval the same
+/// probability. This is the default distribution for [`Jitter`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Uniform;
+
+impl JitterDistribution for Uniform {
+    fn sample(&self, rng: &mut dyn RngCore, min: Duration, interval: Duration) -> Duration {
+        if interval == Duration::new(0, 0) {
+            return min;
+        }
+        let min: Nanos = min.into();
+        let uniform = rand::distributions::Uniform::new(min, min + interval);
+        uniform.sample(rng).into()
+    }
+}
+
+/// An exponential distribution of jitter values, truncated to the interval: Most values are
+/// close to the start of the interval, with few values spread out towards its end.
+///
+/// The distribution is parametrized by its rate, in multiples of one over the interval's length:
+/// A larger rate pushes more values towards the start of the interval. With a rate of `4.0`,
+/// around 75% of values fall into the first third of the interval.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exponential(f64);
+
+#[cfg(feature = "std")]
+impl Exponential {
+    /// Constructs a new exponential distribution with the given rate.
+    ///
+    /// # Panics
+    /// Panics if the rate is not a positive, finite number.
+    pub const fn new(rate: f64) -> Exponential {
+        assert!(
+            rate.is_finite() && rate > 0.0,
+            "exponential jitter distribution needs a positive rate"
+        );
+        Exponential(rate)
+    }
+}
+
+#[cfg(feature = "std")]
+impl JitterDistribution for Exponential {
+    fn sample(&self, rng: &mut dyn RngCore, min: Duration, interval: Duration) -> Duration {
+        let rate = self.0;
+        // Invert the CDF of the exponential distribution, truncated to [0, 1). Since `u` is in
+        // [0, 1), the logarithm's argument is in (0, 1], so the fraction is never negative.
+        let u: f64 = rng.gen();
+        let fraction = -(1.0 - u * (1.0 - (-rate).exp())).ln() / rate;
+        let nanos = interval.as_nanos() as f64 * fraction.min(1.0);
+        let offset = cmp::min(
+            Duration::from_nanos(nanos as u64),
+            interval
+                .checked_sub(Duration::from_nanos(1))
+                .unwrap_or(interval),
+        );
+        min + offset
+    }
+}
+
+/// A source of randomness for [`Jitter`].
+///
+/// Implementations must be usable from multiple threads at once, as a `Jitter` value (and the
+/// source it refers to) can be used by all the tasks waiting on a rate limiter.
+pub trait JitterRng: fmt::Debug + Sync {
+    /// Returns the next random number from the source.
+    fn next_u64(&self) -> u64;
+}
+
+/// A seedable, deterministic source of randomness for [`Jitter`].
+///
+/// Given the same seed, a `SeededJitterRng` always produces the same sequence of values, which
+/// makes code paths that apply jitter reproducible. It does not depend on `std`, and its
+/// constructor is a `const fn`, so it can live in a `static`.
+///
+/// This is not a cryptographically secure source of randomness.
+#[derive(Debug)]
+pub struct SeededJitterRng(AtomicU64);
+
+impl SeededJitterRng {
+    /// Constructs a new source of randomness from a seed.
+    pub const fn new(seed: u64) -> SeededJitterRng {
+        SeededJitterRng(AtomicU64::new(seed))
+    }
+}
+
+/// Uses the [SplitMix64](http://prng.di.unimi.it/splitmix64.c) generator, which only needs
+/// an atomic increment per value drawn.
+impl JitterRng for SeededJitterRng {
+    fn next_u64(&self) -> u64 {
+        const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut z = self
+            .0
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Adapts a [`JitterRng`] for use with `rand`'s distributions.
+struct JitterRngCore(&'static dyn JitterRng);
+
+impl RngCore for JitterRngCore {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.0.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
     }
 }
 
@@ -152,3 +600,22 @@ impl Add<Instant> for Jitter {
         rhs + amount
     }
 }
+
+impl Add<Duration> for &DecorrelatedJitter {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        let amount: Duration = self.get().into();
+        rhs + amount
+    }
+}
+
+#[cfg(feature = "std")]
+impl Add<Instant> for &DecorrelatedJitter {
+    type Output = Instant;
+
+    fn add(self, rhs: Instant) -> Instant {
+        let amount: Duration = self.get().into();
+        rhs + amount
+    }
+}
@@ -40,15 +40,23 @@ pub mod r#_guide;
 pub mod clock;
 mod errors;
 mod gcra;
+#[cfg(feature = "http")]
+pub mod http;
 mod jitter;
+pub mod middleware;
 mod nanos;
+#[cfg(feature = "proptest")]
+pub mod proptest;
 mod quota;
+mod sliding_window;
 pub mod state;
+#[cfg(feature = "tower")]
+pub mod tower;
 
 pub use errors::*;
-pub use gcra::NotUntil;
-pub use jitter::Jitter;
-pub use quota::Quota;
+pub use gcra::{Borrowed, NotUntil, StateInformation};
+pub use jitter::{DecorrelatedJitter, Jitter, JitterRng, SeededJitterRng};
+pub use quota::{GcraParams, ParseQuotaError, Quota};
 #[doc(inline)]
 pub use state::RateLimiter;
 
@@ -57,6 +65,13 @@ pub use state::direct::RatelimitedSink;
 #[cfg(feature = "std")]
 pub use state::direct::RatelimitedStream;
 
+/// Distributions that [`Jitter`] values can be drawn from.
+pub mod distribution {
+    #[cfg(feature = "std")]
+    pub use crate::jitter::Exponential;
+    pub use crate::jitter::{JitterDistribution, Uniform};
+}
+
 /// The collection of asynchronous traits exported from this crate.
 pub mod prelude {
     #[cfg(feature = "std")]
@@ -0,0 +1,626 @@
+//! Additional, customizable behavior for rate limiters.
+//!
+//! Rate-limiting middleware follows the principle that basic rate-limiting should be very cheap,
+//! and unless users desire more behavior, they should not pay any extra price.
+//!
+//! However, if you do desire more information about what the rate limiter does (or the ability to
+//! install hooks in the decision-making process), you can. The [`RateLimitingMiddleware`] trait
+//! in this module allows you to customize:
+//!
+//! * Any additional code that gets run when a rate-limiting decision is made.
+//! * What value is returned in the positive or negative case.
+//!
+//! Writing middleware does **not** let you override rate-limiting decisions: They remain the same
+//! (positive or negative) with or without middleware installed.
+//!
+//! # Example: Counting decisions
+//!
+//! The [`MetricsMiddleware`] calls a function for each rate-limiting decision, which can be used
+//! to update metrics counters:
+//!
+//! ```rust
+//! # #[cfg(feature = "std")] fn main() {
+//! # use std::num::NonZeroU32;
+//! # use std::sync::atomic::{AtomicU64, Ordering};
+//! # use std::sync::Arc;
+//! # use governor::{Quota, RateLimiter};
+//! # use governor::middleware::{MetricsMiddleware, RateLimitDecision};
+//! let allowed = Arc::new(AtomicU64::new(0));
+//! let throttled = Arc::new(AtomicU64::new(0));
+//! let (a, t) = (allowed.clone(), throttled.clone());
+//! let lim = RateLimiter::direct_with_middleware(
+//!     Quota::per_hour(NonZeroU32::new(2).unwrap()),
+//!     MetricsMiddleware::new(move |decision| {
+//!         let counter = match decision {
+//!             RateLimitDecision::Allowed => &a,
+//!             RateLimitDecision::Throttled => &t,
+//!         };
+//!         counter.fetch_add(1, Ordering::Relaxed);
+//!     }),
+//! );
+//! for _ in 0..3 {
+//!     let _ = lim.check();
+//! }
+//! assert_eq!(allowed.load(Ordering::Relaxed), 2);
+//! assert_eq!(throttled.load(Ordering::Relaxed), 1);
+//! # } #[cfg(not(feature = "std"))] fn main() {}
+//! ```
+//!
+//! # Example: Returning the rate-limiting state
+//!
+//! The [`StateInformationMiddleware`] makes positive decisions return information about the
+//! rate limiter's state:
+//!
+//! ```rust
+//! # #[cfg(feature = "std")] fn main() {
+//! # use std::num::NonZeroU32;
+//! # use governor::{Quota, RateLimiter};
+//! # use governor::middleware::StateInformationMiddleware;
+//! let lim = RateLimiter::direct(Quota::per_hour(NonZeroU32::new(2).unwrap()))
+//!     .with_middleware(StateInformationMiddleware);
+//! assert_eq!(lim.check().map(|s| s.remaining_burst_capacity()), Ok(1));
+//! # } #[cfg(not(feature = "std"))] fn main() {}
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::{clock, NotUntil, StateInformation};
+
+#[cfg(feature = "std")]
+use crate::nanos::Nanos;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Defines the behavior and return values of rate limiting decisions.
+///
+/// While the rate limiter defines whether a decision is positive, the middleware defines what
+/// additional values (other than `Ok` or `Err`) are returned from the [`RateLimiter`]'s check
+/// methods, and can run code (e.g. to update metrics) for each decision.
+///
+/// The default middleware in this crate is [`NoOpMiddleware`], which does nothing and returns
+/// `()` in the positive case.
+///
+/// Middleware is informed of the key that each decision was made for, of type `K`: The key type
+/// of keyed rate limiters, or [`NotKeyed`][crate::state::NotKeyed] for direct ones. Middleware
+/// that doesn't look at keys can be implemented for any `K`.
+///
+/// [`RateLimiter`]: crate::RateLimiter
+pub trait RateLimitingMiddleware<K: ?Sized, P: clock::Reference> {
+    /// The type that's returned by the rate limiter when a cell is allowed.
+    type PositiveOutcome: Sized;
+
+    /// Called when a positive rate-limiting decision is made.
+    ///
+    /// This function is able to affect the return type of [`check`][crate::RateLimiter::check]
+    /// (and others) in the Ok case: Whatever is returned here is the value of the Ok result
+    /// returned from the check functions.
+    fn allow_cell(&self, key: &K, state: StateInformation) -> Self::PositiveOutcome;
+
+    /// Called when a negative rate-limiting decision is made (the "not allowed but OK" case).
+    ///
+    /// The default implementation does nothing.
+    fn disallow_cell(&self, _key: &K, _not_until: &NotUntil<'_, P>) {}
+
+    /// Called when the rate limiter allows a cell (or a batch of cells, for
+    /// [`check_n`][crate::RateLimiter::check_n]) through, to let the middleware reject it
+    /// anyway, e.g. while an external kill switch or circuit breaker is on.
+    ///
+    /// The rate limiter makes its decision first: Cells that it rejects are rejected without
+    /// calling `veto_cell`. If `veto_cell` returns `None`, the cells are allowed through and
+    /// [`allow_cell`][RateLimitingMiddleware::allow_cell] is called. If it returns
+    /// `Some(wait)`, the cells' capacity is returned to the rate limiter, and they are rejected
+    /// with a [`NotUntil`] whose earliest possible time is `wait` after the decision; that
+    /// negative outcome is passed to [`disallow_cell`][RateLimitingMiddleware::disallow_cell].
+    /// Since the capacity is only returned after the decision, checks made on other threads in
+    /// the meantime may be rejected for lack of it.
+    ///
+    /// Decisions that the middleware isn't informed of (like those of
+    /// [`check_n_allow_borrow`][crate::RateLimiter::check_n_allow_borrow]) aren't offered to
+    /// `veto_cell` either. The default implementation never vetoes a cell.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::{self, FakeRelativeClock}, middleware::RateLimitingMiddleware};
+    /// # use governor::{Quota, RateLimiter};
+    /// # use governor::StateInformation;
+    /// # use nonzero_ext::nonzero;
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// # use std::time::Duration;
+    /// struct KillSwitch(AtomicBool);
+    ///
+    /// impl<K: ?Sized, P: clock::Reference> RateLimitingMiddleware<K, P> for KillSwitch {
+    ///     type PositiveOutcome = ();
+    ///
+    ///     fn allow_cell(&self, _key: &K, _state: StateInformation) {}
+    ///
+    ///     fn veto_cell(&self, _key: &K, _state: &StateInformation) -> Option<Duration> {
+    ///         self.0.load(Ordering::Relaxed).then(|| Duration::from_secs(1))
+    ///     }
+    /// }
+    ///
+    /// let clock = FakeRelativeClock::default();
+    /// let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock)
+    ///     .with_middleware(KillSwitch(AtomicBool::new(true)));
+    /// assert!(lim.check().is_err());
+    /// ```
+    fn veto_cell(&self, _key: &K, _state: &StateInformation) -> Option<Duration> {
+        None
+    }
+}
+
+/// A middleware that does nothing and returns `()` in the positive outcome.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoOpMiddleware;
+
+impl<K: ?Sized, P: clock::Reference> RateLimitingMiddleware<K, P> for NoOpMiddleware {
+    type PositiveOutcome = ();
+
+    #[inline]
+    fn allow_cell(&self, _key: &K, _state: StateInformation) -> Self::PositiveOutcome {}
+}
+
+/// Middleware that returns the state of the rate limiter if a positive decision is reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateInformationMiddleware;
+
+impl<K: ?Sized, P: clock::Reference> RateLimitingMiddleware<K, P> for StateInformationMiddleware {
+    type PositiveOutcome = StateInformation;
+
+    #[inline]
+    fn allow_cell(&self, _key: &K, state: StateInformation) -> Self::PositiveOutcome {
+        state
+    }
+}
+
+/// The kind of a rate-limiting decision, as reported by [`MetricsMiddleware`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitDecision {
+    /// The cell was allowed through.
+    Allowed,
+
+    /// The cell was not allowed through at this time.
+    Throttled,
+}
+
+/// Middleware that calls a function for every rate-limiting decision.
+///
+/// The function is called with the kind of the decision, which makes it easy to increment
+/// counters (e.g. atomic integers, or the counters of a metrics library like `prometheus`)
+/// for allowed and throttled cells. Other than calling the function, the middleware does no
+/// work, and it never allocates; positive outcomes are `()`, like with [`NoOpMiddleware`].
+///
+/// Batches of cells checked with [`check_n`][crate::RateLimiter::check_n] count as a single
+/// decision. Batches that can never be allowed through (because they exceed the quota's burst
+/// size) are not reported.
+pub struct MetricsMiddleware<F> {
+    callback: F,
+}
+
+impl<F> MetricsMiddleware<F>
+where
+    F: Fn(RateLimitDecision),
+{
+    /// Constructs a middleware that calls `callback` for each rate-limiting decision.
+    pub fn new(callback: F) -> Self {
+        MetricsMiddleware { callback }
+    }
+}
+
+impl<F> fmt::Debug for MetricsMiddleware<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsMiddleware").finish()
+    }
+}
+
+impl<F, K: ?Sized, P> RateLimitingMiddleware<K, P> for MetricsMiddleware<F>
+where
+    F: Fn(RateLimitDecision),
+    P: clock::Reference,
+{
+    type PositiveOutcome = ();
+
+    #[inline]
+    fn allow_cell(&self, _key: &K, _state: StateInformation) -> Self::PositiveOutcome {
+        (self.callback)(RateLimitDecision::Allowed)
+    }
+
+    #[inline]
+    fn disallow_cell(&self, _key: &K, _not_until: &NotUntil<'_, P>) {
+        (self.callback)(RateLimitDecision::Throttled)
+    }
+}
+
+/// A rate-limiting decision, as reported by [`ObserverMiddleware`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Decision<'a, K: ?Sized> {
+    /// The cell was allowed through.
+    Allowed {
+        /// The key that the decision was made for ([`NotKeyed`][crate::state::NotKeyed] for
+        /// direct rate limiters).
+        key: &'a K,
+    },
+
+    /// The cell was not allowed through at this time.
+    Throttled {
+        /// The key that the decision was made for ([`NotKeyed`][crate::state::NotKeyed] for
+        /// direct rate limiters).
+        key: &'a K,
+
+        /// The minimum amount of time from the decision that must pass before a cell could be
+        /// allowed through.
+        wait_time: Duration,
+    },
+}
+
+impl<'a, K: ?Sized> Decision<'a, K> {
+    /// Returns the key that the decision was made for.
+    pub fn key(&self) -> &'a K {
+        match self {
+            Decision::Allowed { key } | Decision::Throttled { key, .. } => key,
+        }
+    }
+
+    /// Returns whether the cell was allowed through.
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Decision::Allowed { .. })
+    }
+
+    /// Returns how long to wait before a cell could be allowed through, if the cell was
+    /// throttled.
+    pub fn wait_time(&self) -> Option<Duration> {
+        match self {
+            Decision::Allowed { .. } => None,
+            Decision::Throttled { wait_time, .. } => Some(*wait_time),
+        }
+    }
+}
+
+/// Middleware that calls an observer function with every rate-limiting decision, along with the
+/// key it was made for and, for throttled cells, how long to wait.
+///
+/// Unlike [`MetricsMiddleware`], the observer can tell keys apart, e.g. to log which clients get
+/// throttled. Positive outcomes are `()`, like with [`NoOpMiddleware`].
+///
+/// Batches of cells checked with [`check_n`][crate::RateLimiter::check_n] count as a single
+/// decision. Batches that can never be allowed through (because they exceed the quota's burst
+/// size) are not reported.
+///
+/// # Performance
+///
+/// The observer runs on the hot path: It is called synchronously, from the thread making the
+/// decision, before the check method returns, and it can be called from many threads at once.
+/// Any time it spends is added to the latency of every check, so it should do as little as
+/// possible (e.g. increment an atomic counter or push the decision onto a channel) and never
+/// block.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use std::num::NonZeroU32;
+/// # use std::sync::{Arc, Mutex};
+/// # use governor::{Quota, RateLimiter};
+/// # use governor::middleware::ObserverMiddleware;
+/// let throttled = Arc::new(Mutex::new(Vec::new()));
+/// let observed = throttled.clone();
+/// let lim = RateLimiter::keyed(Quota::per_hour(NonZeroU32::new(1).unwrap())).with_middleware(
+///     ObserverMiddleware::new(move |decision| {
+///         if !decision.is_allowed() {
+///             observed.lock().unwrap().push(*decision.key());
+///         }
+///     }),
+/// );
+/// for key in ["alice", "bob", "alice"] {
+///     let _ = lim.check_key(&key);
+/// }
+/// assert_eq!(*throttled.lock().unwrap(), vec!["alice"]);
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+pub struct ObserverMiddleware<F> {
+    observer: F,
+}
+
+impl<F> ObserverMiddleware<F> {
+    /// Constructs a middleware that calls `observer` with each rate-limiting decision.
+    pub fn new<K: ?Sized>(observer: F) -> Self
+    where
+        F: Fn(Decision<'_, K>) + Send + Sync,
+    {
+        ObserverMiddleware { observer }
+    }
+}
+
+impl<F> fmt::Debug for ObserverMiddleware<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObserverMiddleware").finish()
+    }
+}
+
+impl<F, K: ?Sized, P> RateLimitingMiddleware<K, P> for ObserverMiddleware<F>
+where
+    F: Fn(Decision<'_, K>) + Send + Sync,
+    P: clock::Reference,
+{
+    type PositiveOutcome = ();
+
+    #[inline]
+    fn allow_cell(&self, key: &K, _state: StateInformation) -> Self::PositiveOutcome {
+        (self.observer)(Decision::Allowed { key })
+    }
+
+    #[inline]
+    fn disallow_cell(&self, key: &K, not_until: &NotUntil<'_, P>) {
+        (self.observer)(Decision::Throttled {
+            key,
+            wait_time: not_until.wait_time(),
+        })
+    }
+}
+
+/// Middleware that calls a function with the wait time of every throttled cell, e.g. to record
+/// the distribution of wait times in a histogram.
+///
+/// The wait time is the one that the rate limiter reports in its negative decision (see
+/// [`NotUntil::wait_time_from`]), measured from the time the decision was made. It is computed
+/// once per decision, and the middleware does not allocate. Allowed cells are not reported, and
+/// positive outcomes are `()`, like with [`NoOpMiddleware`].
+///
+/// Since the function can be called from many threads at once, it only gets shared access to
+/// its environment: To record into a histogram that needs mutable access (like
+/// `hdrhistogram::Histogram`), wrap the histogram in a lock, or use a concurrent histogram. Like
+/// with the [`ObserverMiddleware`], the function runs on the hot path and should return quickly.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use std::num::NonZeroU32;
+/// # use std::sync::{Arc, Mutex};
+/// # use std::time::Duration;
+/// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+/// # use governor::middleware::WaitTimeMiddleware;
+/// let waits = Arc::new(Mutex::new(Vec::new()));
+/// let recorded = waits.clone();
+/// let clock = FakeRelativeClock::default();
+/// let lim = RateLimiter::direct_with_clock(Quota::per_second(NonZeroU32::new(1).unwrap()), &clock)
+///     .with_middleware(WaitTimeMiddleware::new(move |wait| {
+///         recorded.lock().unwrap().push(wait);
+///     }));
+/// lim.check().unwrap();
+/// assert!(lim.check().is_err());
+/// assert_eq!(*waits.lock().unwrap(), vec![Duration::from_secs(1)]);
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+pub struct WaitTimeMiddleware<F> {
+    record: F,
+}
+
+impl<F> WaitTimeMiddleware<F>
+where
+    F: Fn(Duration) + Send + Sync,
+{
+    /// Constructs a middleware that calls `record` with the wait time of each throttled cell.
+    pub fn new(record: F) -> Self {
+        WaitTimeMiddleware { record }
+    }
+}
+
+impl<F> fmt::Debug for WaitTimeMiddleware<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitTimeMiddleware").finish()
+    }
+}
+
+impl<F, K: ?Sized, P> RateLimitingMiddleware<K, P> for WaitTimeMiddleware<F>
+where
+    F: Fn(Duration) + Send + Sync,
+    P: clock::Reference,
+{
+    type PositiveOutcome = ();
+
+    #[inline]
+    fn allow_cell(&self, _key: &K, _state: StateInformation) -> Self::PositiveOutcome {}
+
+    #[inline]
+    fn disallow_cell(&self, _key: &K, not_until: &NotUntil<'_, P>) {
+        (self.record)(not_until.wait_time())
+    }
+}
+
+/// Middleware that measures the rate at which a rate limiter admits cells, as an exponentially
+/// weighted moving average (EWMA) of the allowed cells per second.
+///
+/// Read the measured rate with [`RateLimiter::admitted_rate`][crate::RateLimiter::admitted_rate].
+/// It is the rate that the rate limiter's users actually achieve, which is lower than the
+/// quota's rate when they check fewer cells than the quota allows.
+///
+/// # Smoothing
+///
+/// Each allowed cell adds `1 / window` to the measured rate, and the measured rate decays
+/// continuously by a factor of `e` over every `window` (i.e., after a time `dt` without allowed
+/// cells, it is multiplied by the smoothing factor `exp(-dt / window)`). Cells allowed through
+/// more than a few windows ago hardly contribute anymore: After a rate limiter's traffic
+/// changes, the measured rate moves about 63% of the way to the new rate within one window, and
+/// more than 99% of the way within five windows. A longer window gives a steadier measurement
+/// that follows changes more slowly. The [default][AdmittedRateMiddleware::default] window is
+/// 10 seconds.
+///
+/// Batches of cells checked with [`check_n`][crate::RateLimiter::check_n] count with all of their
+/// cells (see [`StateInformation::allowed_cells`]), and keyed rate limiters measure the rate
+/// they admit cells at over all keys.
+///
+/// # Performance
+///
+/// Every allowed cell updates the average in a compare-and-swap loop on a single atomic integer
+/// shared by all keys, so rate limiters that don't need the measurement should not use this
+/// middleware. Positive outcomes are `()`, like with [`NoOpMiddleware`]. This requires the `std`
+/// feature.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use std::num::NonZeroU32;
+/// # use std::time::Duration;
+/// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+/// # use governor::middleware::AdmittedRateMiddleware;
+/// let clock = FakeRelativeClock::default();
+/// let quota = Quota::per_second(NonZeroU32::new(10).unwrap());
+/// let lim = RateLimiter::direct_with_clock(quota, &clock)
+///     .with_middleware(AdmittedRateMiddleware::new(Duration::from_secs(1)));
+/// for _ in 0..100 {
+///     lim.check().unwrap();
+///     clock.advance(Duration::from_millis(500));
+/// }
+/// assert!((lim.admitted_rate() - 2.0).abs() < 0.5);
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[cfg(feature = "std")]
+pub struct AdmittedRateMiddleware {
+    window: Duration,
+    /// The bits of the `f64` `ln(Σ exp(tᵢ / window))`, for the times `tᵢ` (measured from the rate
+    /// limiter's start) at which cells were allowed through.
+    ///
+    /// The measured rate at time `t` is `exp(sum - t / window) / window`. Unlike the rate itself,
+    /// this sum doesn't change as time passes, so each allowed cell only needs to add its term,
+    /// in whatever order concurrent decisions arrive in.
+    log_sum: AtomicU64,
+}
+
+#[cfg(feature = "std")]
+impl AdmittedRateMiddleware {
+    /// Constructs a middleware that measures the admitted rate with the given smoothing window.
+    ///
+    /// # Panics
+    /// Panics if `window` is zero.
+    pub fn new(window: Duration) -> Self {
+        assert!(
+            window > Duration::ZERO,
+            "the smoothing window must not be zero"
+        );
+        AdmittedRateMiddleware {
+            window,
+            log_sum: AtomicU64::new(f64::NEG_INFINITY.to_bits()),
+        }
+    }
+
+    /// Returns the smoothing window.
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns the measured rate at time `now`, measured from the rate limiter's start.
+    pub(crate) fn rate_at(&self, now: Nanos) -> f64 {
+        let log_sum = f64::from_bits(self.log_sum.load(Ordering::Relaxed));
+        (log_sum - self.windows(now)).exp() / self.window.as_secs_f64()
+    }
+
+    /// Returns the number of windows from the rate limiter's start until `t`.
+    fn windows(&self, t: Nanos) -> f64 {
+        t.as_duration().as_secs_f64() / self.window.as_secs_f64()
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for AdmittedRateMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdmittedRateMiddleware")
+            .field("window", &self.window)
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for AdmittedRateMiddleware {
+    /// Constructs a middleware that measures the admitted rate with a 10-second window.
+    fn default() -> Self {
+        AdmittedRateMiddleware::new(Duration::from_secs(10))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: ?Sized, P: clock::Reference> RateLimitingMiddleware<K, P> for AdmittedRateMiddleware {
+    type PositiveOutcome = ();
+
+    #[inline]
+    fn allow_cell(&self, _key: &K, state: StateInformation) -> Self::PositiveOutcome {
+        let now = self.windows(state.decided_at());
+        let cells = f64::from(state.allowed_cells());
+        // ln(exp(sum) + cells × exp(now)), computed without overflowing:
+        let add = |log_sum: f64| now + ((log_sum - now).exp() + cells).ln();
+        let mut prev = self.log_sum.load(Ordering::Relaxed);
+        while let Err(next_prev) = self.log_sum.compare_exchange_weak(
+            prev,
+            add(f64::from_bits(prev)).to_bits(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            prev = next_prev;
+        }
+    }
+}
+
+/// Middleware that emits a [`tracing`](https://docs.rs/tracing) event for every rate-limiting
+/// decision.
+///
+/// Allowed cells are recorded at the `TRACE` level, and throttled cells at the `DEBUG` level,
+/// each with the key that the decision was made for (formatted with [`Debug`][fmt::Debug]) and
+/// an `allowed` field. Events for throttled cells also carry a `wait_time` field with the time
+/// until the cell could be allowed through, and events for allowed cells a `remaining` field
+/// with the remaining burst capacity. The events' target is `governor`.
+///
+/// The `tracing` macros check whether a subscriber is interested in an event before computing
+/// its fields, so this middleware costs little when nothing records these levels. Positive
+/// outcomes are `()`, like with [`NoOpMiddleware`]. This requires the `tracing` feature.
+///
+/// Batches of cells checked with [`check_n`][crate::RateLimiter::check_n] count as a single
+/// decision. Batches that can never be allowed through (because they exceed the quota's burst
+/// size) are not reported.
+///
+/// # Example
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use std::num::NonZeroU32;
+/// # use governor::{Quota, RateLimiter};
+/// # use governor::middleware::TracingMiddleware;
+/// let lim = RateLimiter::keyed(Quota::per_second(NonZeroU32::new(1).unwrap()))
+///     .with_middleware(TracingMiddleware);
+/// // Emits a `TRACE` event with `key = "alice"`, then a `DEBUG` event with a `wait_time`:
+/// assert!(lim.check_key(&"alice").is_ok());
+/// assert!(lim.check_key(&"alice").is_err());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TracingMiddleware;
+
+#[cfg(feature = "tracing")]
+impl<K: fmt::Debug + ?Sized, P: clock::Reference> RateLimitingMiddleware<K, P>
+    for TracingMiddleware
+{
+    type PositiveOutcome = ();
+
+    #[inline]
+    fn allow_cell(&self, key: &K, state: StateInformation) -> Self::PositiveOutcome {
+        tracing::trace!(
+            target: "governor",
+            key = ?key,
+            allowed = true,
+            remaining = state.remaining_burst_capacity(),
+            "cell allowed"
+        );
+    }
+
+    #[inline]
+    fn disallow_cell(&self, key: &K, not_until: &NotUntil<'_, P>) {
+        tracing::debug!(
+            target: "governor",
+            key = ?key,
+            allowed = false,
+            wait_time = ?not_until.wait_time(),
+            "cell throttled"
+        );
+    }
+}
@@ -2,33 +2,111 @@
 
 use crate::clock;
 
+use std::convert::TryFrom;
+#[cfg(not(feature = "wide-nanos"))]
 use std::convert::TryInto;
 use std::fmt;
 use std::ops::{Add, Div, Mul};
 use std::prelude::v1::*;
+#[cfg(not(feature = "wide-nanos"))]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::time::Duration;
 
+/// The integer type that [`Nanos`] counts nanoseconds in.
+#[cfg(not(feature = "wide-nanos"))]
+type Repr = u64;
+
+/// The integer type that [`Nanos`] counts nanoseconds in.
+#[cfg(feature = "wide-nanos")]
+type Repr = u128;
+
 /// A number of nanoseconds from a reference point.
 ///
 /// Nanos can not represent durations >584 years, but hopefully that
-/// should not be a problem in real-world applications.
+/// should not be a problem in real-world applications. Arithmetic on Nanos saturates at that
+/// limit instead of wrapping around, so that an overflowing computation can't turn a time far in
+/// the future into one in the past.
+///
+/// With the `wide-nanos` feature, Nanos are kept in a 128-bit integer instead, and can represent
+/// durations of up to 2<sup>96</sup> nanoseconds (~2.5 trillion years), which is as long as
+/// intervals with sub-nanosecond precision can be counted in.
 #[derive(PartialEq, Eq, Default, Clone, Copy, PartialOrd, Ord)]
-pub struct Nanos(u64);
+pub struct Nanos(Repr);
 
 impl Nanos {
-    pub(crate) fn as_u64(self) -> u64 {
+    /// The longest duration that can be represented.
+    #[cfg(not(feature = "wide-nanos"))]
+    pub(crate) const MAX: Nanos = Nanos(u64::MAX);
+
+    /// The longest duration that can be represented.
+    #[cfg(feature = "wide-nanos")]
+    pub(crate) const MAX: Nanos = Nanos(u128::MAX >> Interval::FRAC_BITS);
+
+    /// Returns the number of nanoseconds, saturating at `u64::MAX`.
+    #[cfg(not(feature = "wide-nanos"))]
+    pub(crate) const fn as_u64(self) -> u64 {
         self.0
     }
+
+    /// Returns the number of nanoseconds, saturating at `u64::MAX`.
+    #[cfg(feature = "wide-nanos")]
+    pub(crate) const fn as_u64(self) -> u64 {
+        if self.0 > u64::MAX as u128 {
+            u64::MAX
+        } else {
+            self.0 as u64
+        }
+    }
+
+    /// Returns the number of nanoseconds.
+    #[cfg(not(feature = "wide-nanos"))]
+    pub(crate) const fn as_u128(self) -> u128 {
+        self.0 as u128
+    }
+
+    /// Returns the number of nanoseconds.
+    #[cfg(feature = "wide-nanos")]
+    pub(crate) const fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// Returns the nanoseconds as a [`Duration`].
+    #[cfg(not(feature = "wide-nanos"))]
+    pub(crate) const fn as_duration(self) -> Duration {
+        Duration::from_nanos(self.0)
+    }
+
+    /// Returns the nanoseconds as a [`Duration`], saturating at [`Duration::MAX`].
+    #[cfg(feature = "wide-nanos")]
+    pub(crate) const fn as_duration(self) -> Duration {
+        const NANOS_PER_SEC: u128 = 1_000_000_000;
+        let secs = self.0 / NANOS_PER_SEC;
+        if secs > u64::MAX as u128 {
+            Duration::MAX
+        } else {
+            Duration::new(secs as u64, (self.0 % NANOS_PER_SEC) as u32)
+        }
+    }
+
+    /// Returns the nanoseconds in a `u128`, saturating at [`Nanos::MAX`].
+    pub(crate) const fn saturating_from_u128(n: u128) -> Nanos {
+        if n > Nanos::MAX.as_u128() {
+            Nanos::MAX
+        } else {
+            Nanos(n as Repr)
+        }
+    }
 }
 
-/// Nanos as used by Jitter and other std-only features.
-#[cfg(feature = "std")]
+/// Nanos as used by Jitter and other constant definitions.
 impl Nanos {
     pub(crate) const fn new(u: u64) -> Self {
-        Nanos(u)
+        Nanos(u as Repr)
     }
 }
 
+#[cfg(not(feature = "wide-nanos"))]
 impl From<Duration> for Nanos {
     fn from(d: Duration) -> Self {
         // This will panic:
@@ -40,10 +118,18 @@ impl From<Duration> for Nanos {
     }
 }
 
+/// Even [`Duration::MAX`] is shorter than [`Nanos::MAX`] with the `wide-nanos` feature, so this
+/// can't panic.
+#[cfg(feature = "wide-nanos")]
+impl From<Duration> for Nanos {
+    fn from(d: Duration) -> Self {
+        Nanos::saturating_from_u128(d.as_nanos())
+    }
+}
+
 impl fmt::Debug for Nanos {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        let d = Duration::from_nanos(self.0);
-        write!(f, "Nanos({:?})", d)
+        write!(f, "Nanos({:?})", self.as_duration())
     }
 }
 
@@ -51,7 +137,7 @@ impl Add<Nanos> for Nanos {
     type Output = Nanos;
 
     fn add(self, rhs: Nanos) -> Self::Output {
-        Nanos(self.0 + rhs.0)
+        Nanos(self.0.saturating_add(rhs.0)).min(Nanos::MAX)
     }
 }
 
@@ -59,7 +145,7 @@ impl Mul<u64> for Nanos {
     type Output = Nanos;
 
     fn mul(self, rhs: u64) -> Self::Output {
-        Nanos(self.0 * rhs)
+        Nanos(self.0.saturating_mul(Repr::from(rhs))).min(Nanos::MAX)
     }
 }
 
@@ -67,25 +153,27 @@ impl Div<Nanos> for Nanos {
     type Output = u64;
 
     fn div(self, rhs: Nanos) -> Self::Output {
-        self.0 / rhs.0
+        Nanos(self.0 / rhs.0).as_u64()
     }
 }
 
 impl From<u64> for Nanos {
     fn from(u: u64) -> Self {
-        Nanos(u)
+        Nanos::new(u)
     }
 }
 
+/// Saturates at `u64::MAX` with the `wide-nanos` feature.
 impl Into<u64> for Nanos {
     fn into(self) -> u64 {
-        self.0
+        self.as_u64()
     }
 }
 
+/// Saturates at [`Duration::MAX`] with the `wide-nanos` feature.
 impl Into<Duration> for Nanos {
     fn into(self) -> Duration {
-        Duration::from_nanos(self.0)
+        self.as_duration()
     }
 }
 
@@ -93,6 +181,19 @@ impl Nanos {
     pub(crate) fn saturating_sub(self, rhs: Nanos) -> Nanos {
         Nanos(self.0.saturating_sub(rhs.0))
     }
+
+    /// Returns the sum of two Nanos, or `None` if it is too long to be represented.
+    pub(crate) fn checked_add(self, rhs: Nanos) -> Option<Nanos> {
+        self.0
+            .checked_add(rhs.0)
+            .map(Nanos)
+            .filter(|sum| *sum <= Nanos::MAX)
+    }
+
+    /// Returns the remainder of dividing by `rhs`.
+    pub(crate) fn rem(self, rhs: Nanos) -> Nanos {
+        Nanos(self.0 % rhs.0)
+    }
 }
 
 impl clock::Reference for Nanos {
@@ -113,3 +214,258 @@ impl Add<Duration> for Nanos {
         self + other
     }
 }
+
+/// A [`Nanos`] that can be shared between threads: An [`AtomicU64`], or with the `wide-nanos`
+/// feature (since 128-bit atomics aren't available on stable Rust), a 128-bit integer behind a
+/// spinlock, which ignores the memory orderings it is given and always orders memory accesses
+/// like a lock does.
+#[cfg(not(feature = "wide-nanos"))]
+#[derive(Default)]
+pub(crate) struct AtomicNanos(AtomicU64);
+
+/// A [`Nanos`] that can be shared between threads: An [`AtomicU64`], or with the `wide-nanos`
+/// feature (since 128-bit atomics aren't available on stable Rust), a 128-bit integer behind a
+/// spinlock, which ignores the memory orderings it is given and always orders memory accesses
+/// like a lock does.
+#[cfg(feature = "wide-nanos")]
+#[derive(Default)]
+pub(crate) struct AtomicNanos(spin::Mutex<u128>);
+
+#[cfg(not(feature = "wide-nanos"))]
+impl AtomicNanos {
+    #[inline]
+    pub(crate) const fn new(n: Nanos) -> Self {
+        AtomicNanos(AtomicU64::new(n.0))
+    }
+
+    #[inline]
+    pub(crate) fn load(&self, order: Ordering) -> Nanos {
+        Nanos(self.0.load(order))
+    }
+
+    #[inline]
+    pub(crate) fn store(&self, n: Nanos, order: Ordering) {
+        self.0.store(n.0, order)
+    }
+
+    #[inline]
+    pub(crate) fn fetch_max(&self, n: Nanos, order: Ordering) -> Nanos {
+        Nanos(self.0.fetch_max(n.0, order))
+    }
+
+    #[inline]
+    pub(crate) fn compare_exchange_weak(
+        &self,
+        current: Nanos,
+        new: Nanos,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Nanos, Nanos> {
+        self.0
+            .compare_exchange_weak(current.0, new.0, success, failure)
+            .map(Nanos)
+            .map_err(Nanos)
+    }
+}
+
+#[cfg(feature = "wide-nanos")]
+impl AtomicNanos {
+    pub(crate) const fn new(n: Nanos) -> Self {
+        AtomicNanos(spin::Mutex::new(n.0))
+    }
+
+    pub(crate) fn load(&self, _order: Ordering) -> Nanos {
+        Nanos(*self.0.lock())
+    }
+
+    pub(crate) fn store(&self, n: Nanos, _order: Ordering) {
+        *self.0.lock() = n.0;
+    }
+
+    pub(crate) fn fetch_max(&self, n: Nanos, _order: Ordering) -> Nanos {
+        let mut value = self.0.lock();
+        let prev = *value;
+        *value = prev.max(n.0);
+        Nanos(prev)
+    }
+
+    pub(crate) fn compare_exchange_weak(
+        &self,
+        current: Nanos,
+        new: Nanos,
+        _success: Ordering,
+        _failure: Ordering,
+    ) -> Result<Nanos, Nanos> {
+        let mut value = self.0.lock();
+        if *value == current.0 {
+            *value = new.0;
+            Ok(current)
+        } else {
+            Err(Nanos(*value))
+        }
+    }
+}
+
+/// A replenishment interval: A number of nanoseconds that may have a fractional part, kept as a
+/// fixed-point number with 32 fractional bits.
+///
+/// At high rates, the interval between two cells is only a few nanoseconds long, and rounding
+/// it to whole nanoseconds changes the rate noticeably: 300,000,000 cells per second would
+/// become one cell every 3ns, or 333,333,333 cells per second. Rate-limiting states are kept
+/// in whole nanoseconds, so instead of storing the fraction, the GCRA spaces cells out on a
+/// grid of exact multiples of the interval (see [`Interval::weight`]).
+#[derive(PartialEq, Eq, Default, Clone, Copy, PartialOrd, Ord)]
+pub(crate) struct Interval(u128);
+
+impl Interval {
+    const FRAC_BITS: u32 = 32;
+
+    /// The shortest interval that the GCRA can space cells out by.
+    pub(crate) const ONE_NANO: Interval = Interval(1 << Self::FRAC_BITS);
+
+    /// Constructs an interval of `nanos` whole nanoseconds, plus `frac / 2^32` nanoseconds.
+    pub(crate) const fn new(nanos: u64, frac: u32) -> Self {
+        Interval(((nanos as u128) << Self::FRAC_BITS) | frac as u128)
+    }
+
+    /// Constructs the interval that divides `nanos` nanoseconds into `n` equal parts, rounded
+    /// down to the fixed-point precision.
+    pub(crate) const fn from_ratio(nanos: u128, n: u64) -> Self {
+        Interval((nanos << Self::FRAC_BITS) / n as u128)
+    }
+
+    /// Constructs an interval of `nanos` nanoseconds, rounded down to the fixed-point precision.
+    pub(crate) fn from_f64(nanos: f64) -> Self {
+        Interval((nanos * (1u64 << Self::FRAC_BITS) as f64) as u128)
+    }
+
+    /// Returns the interval in nanoseconds, including its fractional part.
+    pub(crate) fn as_f64(self) -> f64 {
+        self.0 as f64 / (1u64 << Self::FRAC_BITS) as f64
+    }
+
+    /// Returns the whole nanoseconds in the interval, i.e. the interval rounded down, saturating
+    /// at the longest representable [`Nanos`].
+    pub(crate) const fn whole(self) -> u64 {
+        let nanos = self.0 >> Self::FRAC_BITS;
+        if nanos > u64::MAX as u128 {
+            u64::MAX
+        } else {
+            nanos as u64
+        }
+    }
+
+    /// Returns the fractional part of the interval, in units of `2^-32` nanoseconds.
+    pub(crate) const fn frac(self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Returns `n` intervals back to back, saturating at the longest representable interval.
+    pub(crate) const fn times(self, n: u64) -> Self {
+        Interval(self.0.saturating_mul(n as u128))
+    }
+
+    /// Returns the interval, rounded up to whole nanoseconds, or `None` if that is too long to
+    /// be represented as [`Nanos`].
+    const fn checked_ceil(self) -> Option<Nanos> {
+        let mask = (1 << Self::FRAC_BITS) - 1;
+        let nanos = self.0.saturating_add(mask) >> Self::FRAC_BITS;
+        if nanos > Nanos::MAX.as_u128() {
+            None
+        } else {
+            Some(Nanos::saturating_from_u128(nanos))
+        }
+    }
+
+    /// Returns the interval, rounded up to whole nanoseconds, saturating at the longest
+    /// representable [`Nanos`].
+    pub(crate) const fn ceil(self) -> Nanos {
+        match self.checked_ceil() {
+            Some(nanos) => nanos,
+            None => Nanos::MAX,
+        }
+    }
+
+    /// Returns the number of whole intervals that fit into the longest representable [`Nanos`],
+    /// saturating at `u64::MAX`.
+    pub(crate) const fn count_in_longest(self) -> u64 {
+        let count = (Nanos::MAX.as_u128() << Self::FRAC_BITS) / self.0;
+        if count > u64::MAX as u128 {
+            u64::MAX
+        } else {
+            count as u64
+        }
+    }
+
+    /// Returns the number of whole intervals that fit into `duration`, saturating at `u64::MAX`.
+    pub(crate) fn count_in(self, duration: Nanos) -> u64 {
+        u64::try_from(self.index_of(duration)).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the index of the last point on the grid of intervals at or before `nanos`.
+    ///
+    /// With the `wide-nanos` feature, there can be more than `u64::MAX` points before the
+    /// longest representable [`Nanos`], so this doesn't saturate like
+    /// [`count_in`](Interval::count_in).
+    fn index_of(self, nanos: Nanos) -> u128 {
+        (nanos.as_u128() << Self::FRAC_BITS) / self.0
+    }
+
+    /// Returns the time that `n` cells take up when they're accounted for starting at `base`.
+    ///
+    /// The `k`th point on the grid is `k` intervals after the rate limiter's start, rounded up
+    /// to whole nanoseconds. Cells are charged the distance between consecutive grid points,
+    /// counted from the last grid point at or before `base`. For intervals with a fractional
+    /// part, that is sometimes one nanosecond more than the interval rounded down, and
+    /// sometimes not, so that cells that go through back to back, each starting where the
+    /// last one ended, follow the grid and average out to exactly the interval. For whole
+    /// intervals, this is always `n` intervals.
+    ///
+    /// The interval must be at least one nanosecond long.
+    pub(crate) fn weight(self, base: Nanos, n: u64) -> Nanos {
+        let k = self.index_of(base);
+        self.point(k.saturating_add(u128::from(n)))
+            .saturating_sub(self.point(k))
+    }
+
+    /// Returns the time that `n` cells accounted for starting at `base` end at, or `None` if
+    /// that time is too far in the future to be represented as [`Nanos`].
+    ///
+    /// Unlike `base + weight(base, n)`, this does not saturate: A state that saturated at the
+    /// limit would let every later cell through.
+    pub(crate) fn checked_after(self, base: Nanos, n: u64) -> Option<Nanos> {
+        let k = self.index_of(base);
+        let end = Interval(self.0.checked_mul(k.checked_add(u128::from(n))?)?).checked_ceil()?;
+        base.checked_add(end.saturating_sub(self.point(k)))
+    }
+
+    /// Returns the number of cells whose [`weight`](Interval::weight), when they're accounted
+    /// for starting at `base`, fits into `duration`.
+    pub(crate) fn count_within(self, base: Nanos, duration: Nanos) -> u64 {
+        let k = self.index_of(base);
+        u64::try_from(self.index_of(self.point(k) + duration) - k).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the `k`th point on the grid of intervals.
+    fn point(self, k: u128) -> Nanos {
+        Interval(self.0.saturating_mul(k)).ceil()
+    }
+}
+
+impl From<Nanos> for Interval {
+    fn from(n: Nanos) -> Self {
+        Interval(n.as_u128() << Self::FRAC_BITS)
+    }
+}
+
+impl fmt::Debug for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let d = Duration::from_nanos(self.whole());
+        if self.frac() == 0 {
+            write!(f, "Interval({:?})", d)
+        } else {
+            let frac = (u64::from(self.frac()) * 1_000_000_000) >> Self::FRAC_BITS;
+            write!(f, "Interval({:?} + 0.{:09}ns)", d, frac)
+        }
+    }
+}
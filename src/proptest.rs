@@ -0,0 +1,124 @@
+//! [Proptest](https://docs.rs/proptest) strategies for property-testing code that uses rate
+//! limiters.
+//!
+//! [`quotas`] generates valid quotas, and [`check_steps`] generates sequences of steps that
+//! advance a [`FakeRelativeClock`][crate::clock::FakeRelativeClock] and check a batch of cells,
+//! at a pace that suits a given quota. Together, they can drive a rate limiter through many
+//! different situations, including those where rounding and overflows matter. [`max_admitted`]
+//! returns the bound that the cells a rate limiter allows through must stay within.
+//!
+//! This requires the `proptest` feature.
+//!
+//! # Example
+//!
+//! Over any sequence of steps, a rate limiter never allows more cells through than its burst
+//! size plus the cells that replenish during the sequence:
+//!
+//! ```rust
+//! # use proptest::prelude::*;
+//! # use governor::clock::FakeRelativeClock;
+//! # use governor::proptest::{check_steps, max_admitted, quotas};
+//! # use governor::RateLimiter;
+//! # use std::time::Duration;
+//! proptest!(|((quota, steps) in quotas().prop_flat_map(|q| (Just(q), check_steps(q, 1..50))))| {
+//!     let clock = FakeRelativeClock::default();
+//!     let lim = RateLimiter::direct_with_clock(quota, &clock);
+//!     let mut admitted = 0u128;
+//!     let mut elapsed = Duration::ZERO;
+//!     for step in &steps {
+//!         clock.advance(step.advance);
+//!         elapsed += step.advance;
+//!         if lim.check_n(step.n).is_ok() {
+//!             admitted += u128::from(step.n.get());
+//!         }
+//!     }
+//!     prop_assert!(admitted <= max_admitted(quota, elapsed));
+//! });
+//! ```
+
+use std::prelude::v1::*;
+
+use crate::Quota;
+use ::proptest::collection::{vec, SizeRange};
+use ::proptest::prelude::*;
+use std::cmp;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// The longest time that a [`CheckStep`] advances the clock by.
+const MAX_ADVANCE: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// One step of a sequence that drives a rate limiter: Advance the clock, then check a batch of
+/// cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckStep {
+    /// How far to advance the clock before checking.
+    pub advance: Duration,
+
+    /// How many cells to check at once, e.g. with [`check_n`][crate::RateLimiter::check_n].
+    pub n: NonZeroU32,
+}
+
+/// Returns a strategy that generates burst sizes, mostly small ones but also some up to
+/// `u32::MAX`.
+fn burst_sizes() -> impl Strategy<Value = NonZeroU32> {
+    prop_oneof![
+        3 => 1..=100u32,
+        1 => 1..=u32::MAX,
+    ]
+    .prop_map(|n| NonZeroU32::new(n).unwrap())
+}
+
+/// Returns a strategy that generates valid quotas.
+///
+/// The quotas' replenishment intervals range from a nanosecond to a day, and their burst sizes
+/// from 1 to `u32::MAX` (mostly small ones). Some quotas replenish a number of cells per second,
+/// so that their replenishment intervals aren't whole nanoseconds.
+pub fn quotas() -> impl Strategy<Value = Quota> {
+    let periods = prop_oneof![
+        1..=1_000u64,
+        1_000..=1_000_000u64,
+        1_000_000..=1_000_000_000u64,
+        1_000_000_000..=86_400_000_000_000u64,
+    ]
+    .prop_map(|nanos| Quota::with_period(Duration::from_nanos(nanos)).unwrap());
+    let per_second = burst_sizes().prop_map(Quota::per_second);
+    (prop_oneof![periods, per_second], burst_sizes())
+        .prop_map(|(quota, max_burst)| quota.allow_burst(max_burst))
+}
+
+/// Returns a strategy that generates sequences of [`CheckStep`]s for a rate limiter with the
+/// given quota, with a number of steps in `len`.
+///
+/// The steps advance the clock by anywhere from nothing to twice the time the quota takes to
+/// replenish its entire burst (but at most a day, so that the clock doesn't overflow), and check
+/// batches of up to one cell more than the quota's burst size, so that the sequences contain
+/// allowed, throttled and impossible batches.
+pub fn check_steps(
+    quota: Quota,
+    len: impl Into<SizeRange>,
+) -> impl Strategy<Value = Vec<CheckStep>> {
+    let max_advance = quota
+        .burst_size_replenished_in()
+        .as_nanos()
+        .saturating_mul(2);
+    let max_advance = cmp::min(max_advance, MAX_ADVANCE.as_nanos()) as u64;
+    let max_n = quota.burst_size().get().saturating_add(1);
+    let step = (0..=max_advance, 1..=max_n).prop_map(|(advance, n)| CheckStep {
+        advance: Duration::from_nanos(advance),
+        n: NonZeroU32::new(n).unwrap(),
+    });
+    vec(step, len)
+}
+
+/// Returns the largest number of cells that a rate limiter with the given quota can allow
+/// through within `elapsed` of its construction: Its burst size, plus the cells that replenish
+/// in that time.
+pub fn max_admitted(quota: Quota, elapsed: Duration) -> u128 {
+    let params = quota.gcra_params();
+    // The emission interval, in units of 2^-32 nanoseconds:
+    let interval =
+        (params.emission_interval.as_nanos() << 32) + u128::from(params.emission_interval_frac);
+    let replenished = elapsed.as_nanos().saturating_mul(1 << 32) / interval;
+    u128::from(quota.burst_size().get()) + replenished
+}
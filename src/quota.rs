@@ -1,9 +1,18 @@
 use std::prelude::v1::*;
 
+use crate::gcra::Gcra;
+use crate::nanos::{Interval, Nanos};
 use nonzero_ext::nonzero;
+use std::fmt;
 use std::num::NonZeroU32;
+use std::str::FromStr;
 use std::time::Duration;
 
+use std::convert::TryFrom;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// A rate-limiting quota.
 ///
 /// Quotas are expressed in a positive number of "cells" (the maximum number of positive decisions /
@@ -26,6 +35,44 @@ use std::time::Duration;
 /// In other words, the burst size is the maximum number of cells that the rate limiter will ever
 /// allow through without replenishing them.
 ///
+/// # Long replenishment intervals
+/// The burst size and the replenishment interval together determine how long it takes to
+/// replenish the entire burst capacity, the
+/// [`burst_size_replenished_in`](#method.burst_size_replenished_in) duration: That is the window
+/// in which the rate limiter allows at most the burst size's number of cells through, plus one
+/// per interval. Adjusting the burst size of a quota with a long interval makes that window long,
+/// too: `Quota::with_period(Duration::from_secs(3600)).unwrap().allow_burst(nonzero!(10_000u32))`
+/// allows 10,000 cells through at once and then one cell per hour, and only replenishes the entire
+/// burst after 10,000 hours. To allow 10,000 cells per hour instead, use
+/// `Quota::per_hour(nonzero!(10_000u32))`, which replenishes one cell every 360 milliseconds.
+///
+/// Rate limiters keep the burst window in nanoseconds, like their states (see below), so it can be
+/// at most ~584 years long (without the `wide-nanos` feature).
+/// [`allow_burst`](#method.allow_burst) lowers burst sizes that don't fit into that window to the
+/// number of cells that do, so that [`burst_size`](#method.burst_size) always reports the burst
+/// size that rate limiters allow.
+///
+/// # Precision
+/// Quotas keep their replenishment interval with sub-nanosecond precision, so that quotas for
+/// millions of cells per second (whose intervals aren't a whole number of nanoseconds) are
+/// enforced at the rate they were constructed with.
+/// [`replenish_interval`](#method.replenish_interval) returns the interval rounded down to whole
+/// nanoseconds. Rate limiters space cells out by at least one nanosecond, so they allow at most
+/// 1,000,000,000 cells per second (per rate-limiting state) even if the quota allows more.
+///
+/// Rate limiters keep their states as the number of nanoseconds since the rate limiter was
+/// constructed, in a 64-bit integer, so that each state fits into an atomic integer and can be
+/// updated without taking a lock. That covers ~584 years since the rate limiter's construction,
+/// which leaves plenty of room for quotas with long intervals, like one cell per day, in
+/// long-running processes. Replenishment intervals longer than ~584 years are treated as
+/// ~584 years long.
+///
+/// With the `wide-nanos` feature, rate limiters keep their states (and burst windows) in 128-bit
+/// integers instead, which cover ~2.5 trillion years. Since there are no 128-bit atomic integers
+/// on stable Rust, each state is then updated under a spinlock, which makes checking cells
+/// slower, especially when many threads check the same state. Replenishment intervals are still
+/// at most ~584 years long.
+///
 /// # Examples
 ///
 /// Construct a quota that allows 50 cells per second (replenishing at a rate of one cell
@@ -56,10 +103,85 @@ use std::time::Duration;
 /// // The entire maximum burst size will be restored if no cells are let through for 45 hours:
 /// assert_eq!(q.burst_size_replenished_in(), Duration::from_secs(60 * 60 * (90 / 2)));
 /// ```
+///
+/// # Serialization
+///
+/// With the `serde` feature enabled, quotas can be serialized and deserialized. A quota is
+/// represented by its maximum burst size and the interval it takes to replenish a single cell,
+/// so the round trip is lossless. If the interval isn't a whole number of nanoseconds, its
+/// fractional part is carried in a `replenish_interval_frac` field, in units of `2^-32`
+/// nanoseconds. Quotas that [start empty](Quota::start_empty) also carry a `start_empty` flag,
+/// quotas [with a tolerance](Quota::with_tolerance) a `tolerance` duration, and
+/// [strict](Quota::strict_interval) quotas a `strict` flag.
+/// These fields default to zero (or `false`) when they are missing. Deserializing a quota with a
+/// zero burst size or a replenishment interval that is zero in both its whole and fractional
+/// parts fails, as neither can be constructed otherwise, and burst sizes too large for the
+/// interval are lowered like [`allow_burst`](Quota::allow_burst) lowers them.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawQuota"))]
 pub struct Quota {
     pub(crate) max_burst: NonZeroU32,
+    #[cfg_attr(feature = "serde", serde(rename = "replenish_interval"))]
     pub(crate) replenish_1_per: Duration,
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "replenish_interval_frac", skip_serializing_if = "is_zero")
+    )]
+    pub(crate) replenish_1_per_frac: u32,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "is_false"))]
+    pub(crate) start_empty: bool,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Duration::is_zero"))]
+    pub(crate) tolerance: Duration,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "is_false"))]
+    pub(crate) strict: bool,
+}
+
+#[cfg(feature = "serde")]
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+#[cfg(feature = "serde")]
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
+/// The unvalidated form of a [`Quota`], as it gets deserialized.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawQuota {
+    max_burst: NonZeroU32,
+    replenish_interval: Duration,
+    #[serde(default)]
+    replenish_interval_frac: u32,
+    #[serde(default)]
+    start_empty: bool,
+    #[serde(default)]
+    tolerance: Duration,
+    #[serde(default)]
+    strict: bool,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawQuota> for Quota {
+    type Error = &'static str;
+
+    fn try_from(raw: RawQuota) -> Result<Self, Self::Error> {
+        // Quotas replenishing faster than once a nanosecond only have a fractional interval:
+        if raw.replenish_interval.is_zero() && raw.replenish_interval_frac == 0 {
+            return Err("replenish_interval must not be zero");
+        }
+        let quota = Quota {
+            max_burst: NonZeroU32::MIN,
+            replenish_1_per: raw.replenish_interval,
+            replenish_1_per_frac: raw.replenish_interval_frac,
+            start_empty: raw.start_empty,
+            tolerance: raw.tolerance,
+            strict: raw.strict,
+        };
+        Ok(quota.allow_burst(raw.max_burst))
+    }
 }
 
 /// Constructors for Quotas
@@ -67,32 +189,42 @@ impl Quota {
     /// Construct a quota for a number of cells per second. The given number of cells is also
     /// assumed to be the maximum burst size.
     pub const fn per_second(max_burst: NonZeroU32) -> Quota {
-        let replenish_interval_ns = Duration::from_secs(1).as_nanos() / (max_burst.get() as u128);
-        Quota {
-            max_burst,
-            replenish_1_per: Duration::from_nanos(replenish_interval_ns as u64),
-        }
+        Quota::spread_over(max_burst, Duration::from_secs(1))
+    }
+
+    /// Construct a quota for limiting bandwidth to a number of bytes per second, treating each
+    /// byte as a cell. The burst size is one second's worth of bytes.
+    ///
+    /// Check payloads against a rate limiter for this quota with their size as the cost, e.g.
+    /// using [`check_cost`](crate::RateLimiter::check_cost). A payload can only be allowed
+    /// through if it fits into the burst size, so pick a burst large enough for the biggest
+    /// payload (with [`allow_burst`](#method.allow_burst)), or split bigger payloads into
+    /// chunks. Since rate limiters allow at most 1,000,000,000 cells per second (see
+    /// [Precision](#precision)), rates above that are enforced as 1,000,000,000 bytes per second.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use nonzero_ext::nonzero;
+    /// # use governor::Quota;
+    /// // 64 KiB/s, allowing payloads of up to 1 MiB at once:
+    /// let quota =
+    ///     Quota::bytes_per_second(nonzero!(64u32 * 1024)).allow_burst(nonzero!(1024u32 * 1024));
+    /// assert_eq!(quota.burst_size().get(), 1024 * 1024);
+    /// ```
+    pub const fn bytes_per_second(bytes: NonZeroU32) -> Quota {
+        Quota::per_second(bytes)
     }
 
     /// Construct a quota for a number of cells per 60-second period. The given number of cells is
     /// also assumed to be the maximum burst size.
     pub const fn per_minute(max_burst: NonZeroU32) -> Quota {
-        let replenish_interval_ns = Duration::from_secs(60).as_nanos() / (max_burst.get() as u128);
-        Quota {
-            max_burst,
-            replenish_1_per: Duration::from_nanos(replenish_interval_ns as u64),
-        }
+        Quota::spread_over(max_burst, Duration::from_secs(60))
     }
 
     /// Construct a quota for a number of cells per 60-minute (3600-second) period. The given number
     /// of cells is also assumed to be the maximum burst size.
     pub const fn per_hour(max_burst: NonZeroU32) -> Quota {
-        let replenish_interval_ns =
-            Duration::from_secs(60 * 60).as_nanos() / (max_burst.get() as u128);
-        Quota {
-            max_burst,
-            replenish_1_per: Duration::from_nanos(replenish_interval_ns as u64),
-        }
+        Quota::spread_over(max_burst, Duration::from_secs(60 * 60))
     }
 
     /// Construct a quota that replenishes one cell in a given
@@ -121,16 +253,319 @@ impl Quota {
             Some(Quota {
                 max_burst: nonzero!(1u32),
                 replenish_1_per,
+                replenish_1_per_frac: 0,
+                start_empty: false,
+                tolerance: Duration::ZERO,
+                strict: false,
             })
         }
     }
 
+    /// Construct a quota that allows exactly one cell per `period`, without any burst.
+    ///
+    /// The emission interval is `period` itself, exactly to the nanosecond, so this expresses
+    /// rates like "one cell every 90 seconds" that don't fit the per-second, per-minute or
+    /// per-hour constructors. It is the same quota as [`with_period`][Quota::with_period]
+    /// builds before a burst size is chosen.
+    ///
+    /// If the period is zero, returns `None`.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::Quota;
+    /// # use std::time::Duration;
+    /// let quota = Quota::one_per(Duration::from_secs(90)).unwrap();
+    /// assert_eq!(quota.replenish_interval(), Duration::from_secs(90));
+    /// assert_eq!(quota.burst_size().get(), 1);
+    /// assert_eq!(Quota::one_per(Duration::ZERO), None);
+    /// ```
+    pub fn one_per(period: Duration) -> Option<Quota> {
+        Quota::with_period(period)
+    }
+
+    /// Construct a quota from a frequency of cells per second, given in Hz.
+    ///
+    /// The frequency determines the replenishment interval: Each cell is replenished after
+    /// `1 / hz` seconds (see [Precision](#precision)). The maximum burst size is the number
+    /// of whole cells replenished per second (rounded down), but at least one cell; e.g., a
+    /// quota of 2.5Hz replenishes a cell every 400ms and allows a burst of 2 cells.
+    ///
+    /// Returns `None` if the frequency is not a positive, finite number, or if the resulting
+    /// replenishment interval can't be represented: That is the case for frequencies above
+    /// 1GHz (whose interval would be shorter than one nanosecond), and for frequencies so low
+    /// that the interval is longer than ~584 years.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::Quota;
+    /// # use std::time::Duration;
+    /// let quota = Quota::from_hz(2.5).unwrap();
+    /// assert_eq!(quota.replenish_interval(), Duration::from_millis(400));
+    /// assert_eq!(quota.burst_size().get(), 2);
+    /// ```
+    pub fn from_hz(hz: f64) -> Option<Quota> {
+        if !hz.is_finite() || hz <= 0.0 {
+            return None;
+        }
+        let interval_ns = 1_000_000_000.0 / hz;
+        if interval_ns < 1.0 || interval_ns >= u64::MAX as f64 {
+            return None;
+        }
+        let max_burst = NonZeroU32::new(hz as u32).unwrap_or(nonzero!(1u32));
+        Some(Quota::from_interval(
+            max_burst,
+            Interval::from_f64(interval_ns),
+        ))
+    }
+
     /// Adjusts the maximum burst size for a quota to construct a rate limiter with a capacity
     /// for at most the given number of cells.
+    ///
+    /// The burst size doesn't change the replenishment interval: A rate limiter with the
+    /// adjusted quota lets the whole burst through at once, and then one cell per interval (see
+    /// [Long replenishment intervals](#long-replenishment-intervals)). Burst sizes too large for
+    /// rate limiters to keep track of (taking close to ~584 years or more to replenish) are
+    /// lowered to the largest burst size that fits.
     pub const fn allow_burst(self, max_burst: NonZeroU32) -> Quota {
+        // A fresh state starts one interval in, so the burst and one more interval have to fit.
+        // Intervals are at most ~584 years long, so a burst of one cell always fits:
+        let fits = match self.interval().count_in_longest() {
+            0 | 1 => 1,
+            count => count - 1,
+        };
+        let max_burst = match NonZeroU32::new(fits as u32) {
+            Some(fitting) if fits < max_burst.get() as u64 => fitting,
+            _ => max_burst,
+        };
         Quota { max_burst, ..self }
     }
 
+    /// Adjusts a quota to shape traffic to its rate, by not allowing any bursts.
+    ///
+    /// This is the quota with a maximum burst size of 1, which keeps its replenishment interval:
+    /// A rate limiter with this quota allows cells through at most one replenishment interval
+    /// apart, and [`schedule`][crate::RateLimiter::schedule] paces cells exactly that far apart.
+    pub const fn shaping(self) -> Quota {
+        self.allow_burst(NonZeroU32::MIN)
+    }
+
+    /// Adjusts a quota to allow cells through strictly one replenishment interval apart, no
+    /// matter how long the rate limiter was idle.
+    ///
+    /// This differs from [`shaping`](#method.shaping) (or `allow_burst(1)`) only after a rate
+    /// limiter was idle: A state that has fully replenished lets its next cell through early, as
+    /// long as that cell arrives no more than one interval ahead of the rate, so a rate limiter
+    /// with a burst size of 1 allows two cells through back to back after an idle period. A
+    /// strict quota doesn't let any capacity carry over from idle periods: Its rate limiters treat
+    /// a state that has fully replenished as the full state at the time of the check (`t0 + T`,
+    /// in the terms of [`GcraParams`]), instead of as `t0`. So the second of the two cells is
+    /// rejected, and cells are never allowed through less than one interval apart. Use this for
+    /// downstream services that don't allow any clustering of their requests.
+    ///
+    /// The delay variation tolerance is unchanged: [`gcra_params`](#method.gcra_params) still
+    /// reports a `tau` of the replenishment interval times the burst size, and sets the `strict`
+    /// flag.
+    ///
+    /// A strict quota has a burst size of 1 and no [tolerance](#method.with_tolerance). If its
+    /// burst size is adjusted afterwards, the quota stays strict: Its rate limiters then allow
+    /// exactly the burst size through after an idle period.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+    /// # use nonzero_ext::nonzero;
+    /// # use std::time::Duration;
+    /// let clock = FakeRelativeClock::default();
+    /// let lim = RateLimiter::direct_with_clock(
+    ///     Quota::per_second(nonzero!(1u32)).strict_interval(),
+    ///     &clock,
+    /// );
+    /// assert!(lim.check().is_ok());
+    /// clock.advance(Duration::from_secs(10));
+    /// assert!(lim.check().is_ok());
+    /// assert!(lim.check().is_err());
+    /// ```
+    pub const fn strict_interval(self) -> Quota {
+        Quota {
+            max_burst: NonZeroU32::MIN,
+            tolerance: Duration::ZERO,
+            strict: true,
+            ..self
+        }
+    }
+
+    /// Makes rate limiters with this quota start out without any capacity, instead of allowing a
+    /// full burst right away.
+    ///
+    /// A rate-limiting state for a quota that starts empty allows its first cell through only
+    /// after one replenishment interval, and regains its full burst capacity only after
+    /// [`burst_size_replenished_in`](#method.burst_size_replenished_in). This is useful to warm
+    /// up a resource that can't take a full burst when cold, such as a cache that was just
+    /// started.
+    ///
+    /// The state starts out empty when it is first checked: That is, when a direct rate limiter
+    /// is checked for the first time, when a keyed rate limiter sees a key for the first time
+    /// (or after the key's state was removed, e.g. by
+    /// [`retain_recent`][crate::RateLimiter::retain_recent]), and after a rate limiter or key was
+    /// reset. Its capacity then replenishes from the time of that check.
+    ///
+    /// Right after construction, `check_n` rejects every batch: A batch of `n` cells is allowed
+    /// through only once `n` cells have been replenished.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+    /// # use nonzero_ext::nonzero;
+    /// # use std::time::Duration;
+    /// let clock = FakeRelativeClock::default();
+    /// let quota = Quota::per_second(nonzero!(4u32)).start_empty();
+    /// let lim = RateLimiter::direct_with_clock(quota, &clock);
+    /// assert!(lim.check().is_err());
+    /// clock.advance(Duration::from_millis(500));
+    /// assert!(lim.check_n(nonzero!(2u32)).is_ok());
+    /// ```
+    pub const fn start_empty(self) -> Quota {
+        Quota {
+            start_empty: true,
+            ..self
+        }
+    }
+
+    /// Sets the delay variation tolerance (`tau`, see [`GcraParams`]) to `tolerance`, instead of
+    /// deriving it from the burst size alone.
+    ///
+    /// The tolerance is how early a cell may arrive, compared to the rate, and still be allowed
+    /// through. By default, it is the time it takes to replenish the burst size. A longer
+    /// tolerance lets rate limiters absorb clustered arrivals, e.g. when an upstream load balancer
+    /// holds requests back and then forwards them together: Up to `tolerance /
+    /// replenish_interval` cells that arrive together are allowed through, while the rate that
+    /// cells are allowed through at in the long run stays the same.
+    ///
+    /// Since rate limiters can't tell whether cells cluster because of jitter or because they
+    /// come in bursts, a longer tolerance also lets that many cells through at once after a rate
+    /// limiter was idle, and lets [`check_n`][crate::RateLimiter::check_n] accept batches of that
+    /// size. [`burst_size`](#method.burst_size) keeps returning the burst size the quota was
+    /// constructed with, though. Tolerances shorter than the time it takes to replenish the burst
+    /// size have no effect, so the default (a zero tolerance) behaves as before. Tolerances
+    /// longer than ~584 years are treated as ~584 years long.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::Quota;
+    /// # use nonzero_ext::nonzero;
+    /// # use std::time::Duration;
+    /// let quota = Quota::per_second(nonzero!(10u32))
+    ///     .allow_burst(nonzero!(2u32))
+    ///     .with_tolerance(Duration::from_millis(500));
+    /// assert_eq!(quota.burst_size().get(), 2);
+    /// assert_eq!(quota.gcra_params().delay_variation_tolerance, Duration::from_millis(500));
+    /// ```
+    pub const fn with_tolerance(self, tolerance: Duration) -> Quota {
+        Quota { tolerance, ..self }
+    }
+
+    /// Scales a quota's rate by `factor`, e.g. to derive the quota of a premium tier from a base
+    /// quota.
+    ///
+    /// The scaled quota replenishes cells `factor` times as fast (its replenishment interval is
+    /// divided by `factor`, keeping a fraction of a nanosecond as described under
+    /// [Precision](#precision)), and its burst size is multiplied by `factor` as well, so the
+    /// scaled quota still replenishes its entire burst in the same time. The burst size is
+    /// rounded to the nearest whole number of cells, but is at least one cell. The scaled quota
+    /// [starts empty](#method.start_empty) if this quota does, and keeps its
+    /// [tolerance](#method.with_tolerance) and whether it is [strict](#method.strict_interval).
+    ///
+    /// Returns `None` if `factor` is not a positive, finite number, or if the scaled quota can't
+    /// be represented: That is the case if its burst size doesn't fit into a `u32`, or if its
+    /// replenishment interval would be too long (~584 years or more) or too short (less than
+    /// `2^-32` nanoseconds).
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::Quota;
+    /// # use nonzero_ext::nonzero;
+    /// let base = Quota::per_second(nonzero!(10u32));
+    /// assert_eq!(base.scale(10.0), Some(Quota::per_second(nonzero!(100u32))));
+    /// // 1.5 cells are rounded to 2:
+    /// assert_eq!(Quota::per_minute(nonzero!(3u32)).scale(0.5).unwrap().burst_size().get(), 2);
+    /// assert_eq!(base.scale(0.0), None);
+    /// ```
+    pub fn scale(self, factor: f64) -> Option<Quota> {
+        if !factor.is_finite() || factor <= 0.0 {
+            return None;
+        }
+        let interval_ns = self.interval().as_f64() / factor;
+        if interval_ns >= u64::MAX as f64 {
+            return None;
+        }
+        let interval = Interval::from_f64(interval_ns);
+        if interval == Interval::default() {
+            return None;
+        }
+        // Round to the nearest whole number of cells (`f64::round` needs `std`):
+        let burst = f64::from(self.max_burst.get()) * factor + 0.5;
+        if burst >= f64::from(u32::MAX) + 1.0 {
+            return None;
+        }
+        let max_burst = NonZeroU32::new(burst as u32).unwrap_or(nonzero!(1u32));
+        Some(Quota {
+            start_empty: self.start_empty,
+            tolerance: self.tolerance,
+            strict: self.strict,
+            ..Quota::from_interval(max_burst, interval)
+        })
+    }
+
+    /// Combines two quotas into the stricter of the two.
+    ///
+    /// The resulting quota replenishes cells at the slower rate of the two (using the longer
+    /// replenishment interval), and allows the smaller of the two burst sizes. That means the
+    /// result is at least as strict as each of the quotas - it can be stricter than either of
+    /// them, if one of them has the slower rate and the other the smaller burst size. If either
+    /// of the quotas [starts empty](#method.start_empty) or is [strict](#method.strict_interval),
+    /// so is the result, and it keeps the shorter of the two [tolerances](#method.with_tolerance).
+    ///
+    /// Using a combined quota keeps only one rate-limiting state, unlike checking two rate
+    /// limiters (one for each quota) in sequence.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::Quota;
+    /// # use nonzero_ext::nonzero;
+    /// let per_user = Quota::per_second(nonzero!(10u32));
+    /// let global = Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(20u32));
+    /// let combined = per_user.min(global);
+    /// assert_eq!(combined, Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(10u32)));
+    /// ```
+    pub const fn min(self, other: Quota) -> Quota {
+        let (replenish_1_per, replenish_1_per_frac) = if self.interval().whole()
+            > other.interval().whole()
+            || (self.interval().whole() == other.interval().whole()
+                && self.replenish_1_per_frac >= other.replenish_1_per_frac)
+        {
+            (self.replenish_1_per, self.replenish_1_per_frac)
+        } else {
+            (other.replenish_1_per, other.replenish_1_per_frac)
+        };
+        let max_burst = if self.max_burst.get() <= other.max_burst.get() {
+            self.max_burst
+        } else {
+            other.max_burst
+        };
+        Quota {
+            max_burst,
+            replenish_1_per,
+            replenish_1_per_frac,
+            start_empty: self.start_empty || other.start_empty,
+            tolerance: if self.tolerance.as_nanos() <= other.tolerance.as_nanos() {
+                self.tolerance
+            } else {
+                other.tolerance
+            },
+            strict: self.strict || other.strict,
+        }
+    }
+
     /// Construct a quota for a given burst size, replenishing the entire burst size in that
     /// given unit of time.
     ///
@@ -153,18 +588,37 @@ impl Quota {
         if replenish_all_per.as_nanos() == 0 {
             None
         } else {
-            Some(Quota {
-                max_burst,
-                replenish_1_per: replenish_all_per / max_burst.get(),
-            })
+            Some(Quota::spread_over(max_burst, replenish_all_per))
+        }
+    }
+
+    /// Constructs a quota that replenishes `max_burst` cells in `period`, which is also its
+    /// maximum burst size.
+    const fn spread_over(max_burst: NonZeroU32, period: Duration) -> Quota {
+        let interval = Interval::from_ratio(period.as_nanos(), max_burst.get() as u64);
+        Quota::from_interval(max_burst, interval)
+    }
+
+    /// Constructs a quota that replenishes one cell per `interval`, with the given maximum
+    /// burst size.
+    pub(crate) const fn from_interval(max_burst: NonZeroU32, interval: Interval) -> Quota {
+        Quota {
+            max_burst,
+            replenish_1_per: Duration::from_nanos(interval.whole()),
+            replenish_1_per_frac: interval.frac(),
+            start_empty: false,
+            tolerance: Duration::ZERO,
+            strict: false,
         }
     }
 }
 
 /// Retrieving information about a quota
+///
+/// These values are exactly the parameters that rate limiters constructed from the quota use.
 impl Quota {
     /// The time it takes for a rate limiter with an exhausted burst budget to replenish
-    /// a single element.
+    /// a single element, rounded down to whole nanoseconds.
     pub const fn replenish_interval(&self) -> Duration {
         self.replenish_1_per
     }
@@ -176,7 +630,222 @@ impl Quota {
 
     /// The time it takes to replenish the entire maximum burst size.
     pub const fn burst_size_replenished_in(&self) -> Duration {
-        let fill_in_ns = self.replenish_1_per.as_nanos() * self.max_burst.get() as u128;
-        Duration::from_nanos(fill_in_ns as u64)
+        let fill_in = self.interval().times(self.max_burst.get() as u64).ceil();
+        fill_in.as_duration()
+    }
+
+    /// The exact replenishment interval, including its fraction of a nanosecond.
+    ///
+    /// Intervals too long to be represented as [`Nanos`][crate::nanos::Nanos] (~584 years)
+    /// saturate at that limit.
+    pub(crate) const fn interval(&self) -> Interval {
+        let nanos = self.replenish_1_per.as_nanos();
+        if nanos > u64::MAX as u128 {
+            Interval::new(u64::MAX, 0)
+        } else {
+            Interval::new(nanos as u64, self.replenish_1_per_frac)
+        }
+    }
+}
+
+/// An error that occurs when parsing a [`Quota`] from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseQuotaError {
+    /// The string isn't of the form `<cells>/<unit>`, with an optional `;burst=<cells>`.
+    Malformed,
+
+    /// A number of cells isn't a positive integer that fits into a `u32`.
+    InvalidCount(String),
+
+    /// The unit of time isn't one of the supported units.
+    UnknownUnit(String),
+
+    /// An option after the rate isn't `burst=<cells>`.
+    UnknownOption(String),
+}
+
+impl fmt::Display for ParseQuotaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseQuotaError::Malformed => {
+                write!(
+                    f,
+                    "quotas must be given as `<cells>/<unit>[;burst=<cells>]`"
+                )
+            }
+            ParseQuotaError::InvalidCount(count) => write!(
+                f,
+                "invalid number of cells `{}`, expected a positive integer",
+                count
+            ),
+            ParseQuotaError::UnknownUnit(unit) => write!(
+                f,
+                "unknown unit of time `{}`, expected one of ms, s, min, h or d",
+                unit
+            ),
+            ParseQuotaError::UnknownOption(option) => {
+                write!(
+                    f,
+                    "unknown quota option `{}`, expected `burst=<cells>`",
+                    option
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseQuotaError {}
+
+fn parse_count(count: &str) -> Result<NonZeroU32, ParseQuotaError> {
+    let count = count.trim();
+    count
+        .parse()
+        .map_err(|_| ParseQuotaError::InvalidCount(count.to_string()))
+}
+
+/// Parses quotas from strings like `"100/s"`, `"60/min"` or `"1000/hour;burst=50"`, e.g. in
+/// configuration files or command-line arguments.
+///
+/// The grammar is `<cells>/<unit>[;burst=<cells>]`: The quota allows `<cells>` cells per
+/// `<unit>` of time, like [`per_second`](Quota::per_second) and its siblings do, with a burst
+/// size of `<cells>`, unless a different burst size is given (as with
+/// [`allow_burst`](Quota::allow_burst)). Numbers of cells are positive integers that fit into a
+/// `u32`, and the units are:
+///
+/// * `ms` (or `millisecond`),
+/// * `s` (or `sec`, `second`),
+/// * `min` (or `minute`),
+/// * `h` (or `hour`), and
+/// * `d` (or `day`).
+///
+/// Whitespace around the numbers, units and options is ignored.
+///
+/// # Example
+/// ```rust
+/// # use governor::{ParseQuotaError, Quota};
+/// # use nonzero_ext::nonzero;
+/// assert_eq!(Ok(Quota::per_minute(nonzero!(60u32))), "60/min".parse());
+/// assert_eq!(
+///     Ok(Quota::per_hour(nonzero!(1000u32)).allow_burst(nonzero!(50u32))),
+///     "1000/hour;burst=50".parse()
+/// );
+/// assert_eq!(
+///     Err(ParseQuotaError::InvalidCount("0".into())),
+///     "0/s".parse::<Quota>()
+/// );
+/// ```
+impl FromStr for Quota {
+    type Err = ParseQuotaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(';');
+        let rate = parts.next().unwrap_or_default();
+        let (cells, unit) = rate.split_once('/').ok_or(ParseQuotaError::Malformed)?;
+        let cells = parse_count(cells)?;
+        let period = match unit.trim() {
+            "ms" | "millisecond" => Duration::from_millis(1),
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(60 * 60),
+            "d" | "day" => Duration::from_secs(24 * 60 * 60),
+            unit => return Err(ParseQuotaError::UnknownUnit(unit.to_string())),
+        };
+        let mut burst = cells;
+        for option in parts {
+            match option.split_once('=') {
+                Some((name, count)) if name.trim() == "burst" => burst = parse_count(count)?,
+                _ => return Err(ParseQuotaError::UnknownOption(option.trim().to_string())),
+            }
+        }
+        Ok(Quota::spread_over(cells, period).allow_burst(burst))
+    }
+}
+
+/// The parameters of the generic cell rate algorithm (GCRA) that rate limiters use for a
+/// [`Quota`], for reimplementing the same rate-limiting decisions elsewhere (e.g. in another
+/// language, against a shared state store).
+///
+/// A rate-limiting state is a "theoretical arrival time" (TAT), in nanoseconds since the rate
+/// limiter was constructed. A cell arriving at time `t0` is allowed through if
+/// `t0 >= TAT - tau`, which moves the TAT to `max(TAT, t0) + T`; otherwise, the state stays as
+/// it is. A key without a state behaves as if its TAT was `t0 + T` (or `t0 + T + tau`, for
+/// quotas that [start empty](Quota::start_empty)), so that its first cell is the first of a
+/// full burst. For [strict](Quota::strict_interval) quotas, an allowed cell moves the TAT to
+/// `max(TAT, t0 + T) + T` instead.
+///
+/// If `T` has a fractional part, cells are charged the distance between consecutive multiples
+/// of `T` (counted from the rate limiter's start and rounded up to whole nanoseconds) instead,
+/// so that they average out to exactly `T`; the `T` that a key without a state starts from is
+/// rounded down to whole nanoseconds.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GcraParams {
+    /// The emission interval `T`, i.e. the time it takes to replenish a single cell, rounded down
+    /// to whole nanoseconds.
+    pub emission_interval: Duration,
+
+    /// The fractional part of the emission interval, in units of `2^-32` nanoseconds.
+    pub emission_interval_frac: u32,
+
+    /// The delay variation tolerance `tau`, i.e. how far the TAT may run ahead of the current
+    /// time: The emission interval times the burst size, rounded up to whole nanoseconds (or the
+    /// quota's [tolerance](Quota::with_tolerance), if that is longer).
+    pub delay_variation_tolerance: Duration,
+
+    /// Whether the quota is [strict](Quota::strict_interval), i.e. whether allowed cells move the
+    /// TAT to `max(TAT, t0 + T) + T` instead of `max(TAT, t0) + T`.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "is_false"))]
+    pub strict: bool,
+}
+
+/// Interoperating with other GCRA implementations
+impl Quota {
+    /// Returns the GCRA parameters that rate limiters use for the quota.
+    ///
+    /// These are exactly the parameters used in rate-limiting decisions: Emission intervals
+    /// shorter than a nanosecond are lengthened to one nanosecond.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::Quota;
+    /// # use nonzero_ext::nonzero;
+    /// # use std::time::Duration;
+    /// let params = Quota::per_second(nonzero!(4u32)).gcra_params();
+    /// assert_eq!(Duration::from_millis(250), params.emission_interval);
+    /// assert_eq!(0, params.emission_interval_frac);
+    /// assert_eq!(Duration::from_secs(1), params.delay_variation_tolerance);
+    /// ```
+    pub fn gcra_params(&self) -> GcraParams {
+        let (t, tau) = Gcra::params_for(*self);
+        GcraParams {
+            emission_interval: Duration::from_nanos(t.whole()),
+            emission_interval_frac: t.frac(),
+            delay_variation_tolerance: tau.into(),
+            strict: self.strict,
+        }
+    }
+
+    /// Constructs a quota from GCRA parameters, so that rate limiters using it make the same
+    /// decisions as another implementation using these parameters.
+    ///
+    /// The burst size is the number of whole emission intervals that fit into the delay
+    /// variation tolerance, so the parameters returned by [`gcra_params`][Quota::gcra_params]
+    /// construct the same quota again (except that quotas constructed this way don't start
+    /// empty, and that a [tolerance](Quota::with_tolerance) becomes part of their burst size).
+    /// Returns `None` if the emission interval is shorter than a nanosecond, or if the burst size
+    /// would be zero or not fit into a `u32`.
+    pub fn from_gcra_params(params: GcraParams) -> Option<Quota> {
+        let nanos = u64::try_from(params.emission_interval.as_nanos()).ok()?;
+        let t = Interval::new(nanos, params.emission_interval_frac);
+        if t < Interval::ONE_NANO {
+            return None;
+        }
+        let tau = u64::try_from(params.delay_variation_tolerance.as_nanos()).ok()?;
+        let max_burst = u32::try_from(t.count_in(Nanos::from(tau))).ok()?;
+        Some(Quota {
+            strict: params.strict,
+            ..Quota::from_interval(NonZeroU32::new(max_burst)?, t)
+        })
     }
 }
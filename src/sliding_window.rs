@@ -0,0 +1,166 @@
+use std::prelude::v1::*;
+
+use crate::gcra::{Gcra, NotUntil, StateInformation};
+use crate::middleware::RateLimitingMiddleware;
+use crate::nanos::Nanos;
+use crate::{clock, NegativeMultiDecision, Quota};
+use std::convert::TryFrom;
+use std::num::NonZeroU32;
+
+#[cfg(feature = "std")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "std"))]
+use spin::Mutex;
+
+/// The counts of cells allowed through in the current and the previous window.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct WindowCounts {
+    /// The index of the current window, counted from the rate limiter's start.
+    window: u64,
+    current: u64,
+    previous: u64,
+}
+
+impl WindowCounts {
+    /// Moves the counts forward to the given window; earlier windows (e.g. from clock readings
+    /// taken before another thread advanced the counts) count as the current one.
+    fn advance_to(&mut self, window: u64) {
+        if window <= self.window {
+            return;
+        }
+        self.previous = if window == self.window + 1 {
+            self.current
+        } else {
+            0
+        };
+        self.current = 0;
+        self.window = window;
+    }
+}
+
+/// Implements the sliding window counter algorithm.
+///
+/// Time is divided into fixed windows, each as long as it takes the quota to replenish its
+/// entire burst capacity, and each window allows the quota's burst size worth of cells. To avoid
+/// allowing twice the burst size at a window boundary, the count of the previous window is
+/// weighted by how much of it still overlaps with a window ending now: A cell is allowed through
+/// if the weighted count of the previous window plus the count of the current window stays
+/// within the burst size.
+///
+/// The quota's parameters are kept in a [`Gcra`], so that negative decisions can be reported as
+/// [`NotUntil`] values.
+#[derive(Debug)]
+pub(crate) struct SlidingWindow {
+    params: Gcra,
+    counts: Mutex<WindowCounts>,
+}
+
+impl SlidingWindow {
+    pub(crate) fn new(quota: Quota) -> Self {
+        // A quota that starts empty behaves as if a full window preceded the first one:
+        let previous = if quota.start_empty {
+            u64::from(quota.max_burst.get())
+        } else {
+            0
+        };
+        SlidingWindow {
+            params: Gcra::new(quota),
+            counts: Mutex::new(WindowCounts {
+                previous,
+                ..WindowCounts::default()
+            }),
+        }
+    }
+
+    /// The estimated number of cells allowed through in a window ending at `elapsed` in the
+    /// current window, rounded up.
+    fn estimate(counts: &WindowCounts, window: u64, elapsed: u64) -> u64 {
+        let overlap = u128::from(counts.previous) * u128::from(window - elapsed);
+        let previous = overlap.div_ceil(u128::from(window));
+        previous as u64 + counts.current
+    }
+
+    /// Returns how long after `elapsed` in the current window the estimate leaves room for `n`
+    /// more cells, given that the allowed window count is `limit`.
+    fn wait_time(counts: &WindowCounts, window: u64, elapsed: u64, n: u64, limit: u64) -> u64 {
+        let earliest_within = |previous: u64, room: u64| {
+            // The earliest point in a window at which `previous`, weighted by its overlap, fits
+            // into `room`:
+            let fits = u128::from(room) * u128::from(window) / u128::from(previous);
+            window - fits as u64
+        };
+        if counts.current + n > limit {
+            // The current window's count has to become the previous one first:
+            (window - elapsed) + earliest_within(counts.current, limit - n)
+        } else {
+            let room = limit - n - counts.current;
+            earliest_within(counts.previous, room).saturating_sub(elapsed)
+        }
+    }
+
+    /// Returns how often decisions were made at times that the clock went back to.
+    pub(crate) fn clock_regressions(&self) -> u64 {
+        self.params.clock_regressions()
+    }
+
+    /// Tests whether all `n` cells could be accommodated and counts them, if so, informing the
+    /// middleware of the outcome.
+    pub(crate) fn test_n_and_update<K, P: clock::Reference, MW: RateLimitingMiddleware<K, P>>(
+        &self,
+        start: P,
+        key: &K,
+        n: NonZeroU32,
+        t0: P,
+        middleware: &MW,
+    ) -> Result<MW::PositiveOutcome, NegativeMultiDecision<NotUntil<'_, P>>> {
+        let t0 = self.params.elapsed(start, t0);
+        let (t, tau) = self.params.params();
+        let limit = t.count_in(tau);
+        let n64 = u64::from(n.get());
+        if n64 > limit {
+            return Err(NegativeMultiDecision::InsufficientCapacity {
+                requested: n.get(),
+                max_burst: u32::try_from(limit).unwrap_or(u32::MAX),
+            });
+        }
+        let window = tau.as_u64();
+        let elapsed = t0.rem(tau).as_u64();
+
+        let mut counts = self.counts.lock();
+        counts.advance_to(t0 / tau);
+        let estimate = Self::estimate(&counts, window, elapsed);
+        if estimate + n64 > limit {
+            let wait = Self::wait_time(&counts, window, elapsed, n64, limit);
+            drop(counts);
+            let negative = NotUntil::new(&self.params, t0 + Nanos::from(wait), t0, start);
+            middleware.disallow_cell(key, &negative);
+            return Err(NegativeMultiDecision::BatchNonConforming(n.get(), negative));
+        }
+        counts.current += n64;
+        let counted_in = counts.window;
+        drop(counts);
+        let remaining = limit - (estimate + n64);
+        let info = StateInformation::with_remaining(t, tau, t0, remaining, n.get());
+        match middleware.veto_cell(key, &info) {
+            None => Ok(middleware.allow_cell(key, info)),
+            Some(wait) => {
+                self.uncount(counted_in, n64);
+                let negative = NotUntil::new(&self.params, t0 + wait, t0, start);
+                middleware.disallow_cell(key, &negative);
+                Err(NegativeMultiDecision::BatchNonConforming(n.get(), negative))
+            }
+        }
+    }
+
+    /// Takes back `n` cells that were counted in the given window, unless that window has
+    /// slid out of the counts already.
+    fn uncount(&self, window: u64, n: u64) {
+        let mut counts = self.counts.lock();
+        let count = match counts.window - window {
+            0 => &mut counts.current,
+            1 => &mut counts.previous,
+            _ => return,
+        };
+        *count = count.saturating_sub(n);
+    }
+}
@@ -2,21 +2,29 @@
 
 use std::prelude::v1::*;
 
+mod builder;
+mod chained;
 pub mod direct;
 mod in_memory;
 pub mod keyed;
 
-pub use self::in_memory::InMemoryState;
+pub use self::builder::RateLimiterBuilder;
+pub use self::chained::ChainedRateLimiter;
+pub use self::in_memory::{InMemoryState, RelaxedInMemoryState};
 
 use crate::gcra::Gcra;
+#[cfg(feature = "tower")]
+use crate::gcra::NotUntil;
+#[cfg(feature = "std")]
+use crate::middleware::AdmittedRateMiddleware;
+use crate::middleware::{NoOpMiddleware, RateLimitingMiddleware};
 use crate::nanos::Nanos;
-use crate::{clock, Quota};
+use crate::{clock, Jitter, Quota};
+use std::borrow::Borrow;
+use std::hash::Hash;
 
 pub use direct::*;
 
-#[cfg(feature = "std")]
-use std::time::Instant;
-
 /// A way for rate limiters to keep state.
 ///
 /// There are two important kinds of state stores: Direct and keyed. The direct kind have only
@@ -46,15 +54,113 @@ pub trait StateStore {
     fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
     where
         F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>;
+
+    /// Updates a state store's rate limiting state for a key given in a borrowed form (e.g. a
+    /// `&str` for `String` keys), like [`measure_and_replace`][StateStore::measure_and_replace].
+    ///
+    /// The default implementation converts the key to an owned key and calls
+    /// `measure_and_replace`; state stores that can look keys up by their borrowed form (like
+    /// [`HashMap::get`][std::collections::HashMap::get]) should override this method, so that
+    /// they only create an owned key when they make a new entry for it.
+    fn measure_and_replace_by<Q, T, F, E>(&self, key: &Q, f: F) -> Result<T, E>
+    where
+        Self::Key: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = Self::Key> + ?Sized,
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.measure_and_replace(&key.to_owned(), f)
+    }
+
+    /// Returns a state store's rate limiting state for a given key, without modifying it.
+    ///
+    /// Returns `None` if the state store has no state for the key (i.e., it is indistinguishable
+    /// from a fresh state). The default implementation calls
+    /// [`measure_and_replace`][StateStore::measure_and_replace] with a closure that never
+    /// replaces the value; state stores that create an entry for a key on every measurement
+    /// should override this method so that looking at the state does not allocate.
+    fn peek(&self, key: &Self::Key) -> Option<Nanos> {
+        self.measure_and_replace(key, Err::<((), Nanos), _>)
+            .err()
+            .flatten()
+    }
+
+    /// Resets a state store's rate limiting state for a given key to a fresh state.
+    ///
+    /// If the state store has no state for the key, this does nothing. The default
+    /// implementation calls [`measure_and_replace`][StateStore::measure_and_replace] with a
+    /// closure that replaces any existing state with the zero value, which stands for "no state"
+    /// (see [`InMemoryState`]); state stores that can remove a key's entry should override this
+    /// method and do that instead.
+    fn reset(&self, key: &Self::Key) {
+        let _ = self.measure_and_replace(key, |tat| match tat {
+            Some(_) => Ok(((), Nanos::default())),
+            None => Err(()),
+        });
+    }
+
+    /// Returns the quota that each of the state store's rate-limiting states for a key should
+    /// enforce, given the quota of the rate limiter.
+    ///
+    /// Rate limiters call this when they are constructed and when their quota is replaced. The
+    /// default implementation returns the quota unchanged; state stores that split a key's
+    /// capacity between several states (like [`ShardedDirectStateStore`]) override it to divide
+    /// the quota between them.
+    fn quota_per_state(&self, quota: Quota) -> Quota {
+        quota
+    }
+}
+
+/// Access to the rate limiting states of a state store by keys of type `Q`, which rate-limiting
+/// decisions are made through.
+///
+/// Every [`StateStore`] provides it for its own key type; [`ByBorrowedKey`] provides it for the
+/// borrowed forms of the key type.
+pub(crate) trait StateLookup<Q: ?Sized> {
+    /// Updates the rate limiting state for the given key (see
+    /// [`StateStore::measure_and_replace`]).
+    fn measure_and_replace_at<T, F, E>(&self, key: &Q, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>;
+}
+
+impl<S: StateStore> StateLookup<S::Key> for S {
+    fn measure_and_replace_at<T, F, E>(&self, key: &S::Key, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.measure_and_replace(key, f)
+    }
+}
+
+/// Looks up the rate limiting states of a state store by borrowed forms of its key type (see
+/// [`StateStore::measure_and_replace_by`]).
+pub(crate) struct ByBorrowedKey<'a, S>(pub(crate) &'a S);
+
+impl<'a, S, Q> StateLookup<Q> for ByBorrowedKey<'a, S>
+where
+    S: StateStore,
+    S::Key: Borrow<Q>,
+    Q: Hash + Eq + ToOwned<Owned = S::Key> + ?Sized,
+{
+    fn measure_and_replace_at<T, F, E>(&self, key: &Q, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.0.measure_and_replace_by(key, f)
+    }
 }
 
 /// A rate limiter.
 ///
 /// This is the structure that ties together the parameters (how many cells to allow in what time
 /// period) and the concrete state of rate limiting decisions. This crate ships in-memory state
-/// stores, but it's possible (by implementing the [`StateStore`] trait) to make others.  
+/// stores, but it's possible (by implementing the [`StateStore`] trait) to make others.
+///
+/// The behavior of rate-limiting decisions (e.g. what they return in the positive case) can be
+/// customized with [middleware][crate::middleware]; by default, rate limiters use the
+/// [`NoOpMiddleware`].
 #[derive(Debug)]
-pub struct RateLimiter<K, S, C>
+pub struct RateLimiter<K, S, C, MW = NoOpMiddleware>
 where
     S: StateStore<Key = K>,
     C: clock::Clock,
@@ -63,6 +169,8 @@ where
     gcra: Gcra,
     clock: C,
     start: C::Instant,
+    middleware: MW,
+    jitter: Jitter,
 }
 
 impl<K, S, C> RateLimiter<K, S, C>
@@ -75,16 +183,116 @@ where
     /// This is the most generic way to construct a rate-limiter; most users should prefer
     /// [`direct`] or other methods instead.
     pub fn new(quota: Quota, state: S, clock: &C) -> Self {
-        let gcra = Gcra::new(quota);
-        let start = clock.now();
+        Self::new_with_start(quota, state, clock, clock.now())
+    }
+
+    /// Creates a new rate limiter from components, with rate-limiting states measured from the
+    /// given point in time.
+    ///
+    /// Rate limiters that share a state store must measure from the same point in time.
+    pub(crate) fn new_with_start(quota: Quota, state: S, clock: &C, start: C::Instant) -> Self {
+        let gcra = Gcra::new(state.quota_per_state(quota));
         let clock = clock.clone();
         RateLimiter {
             state,
             clock,
             gcra,
             start,
+            middleware: NoOpMiddleware,
+            jitter: Jitter::NONE,
         }
     }
+}
+
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: StateStore<Key = K>,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Replaces the rate limiter's middleware, returning a rate limiter that uses the new one.
+    ///
+    /// The rate limiter's quota and state are kept.
+    pub fn with_middleware<Outer: RateLimitingMiddleware<K, C::Instant>>(
+        self,
+        middleware: Outer,
+    ) -> RateLimiter<K, S, C, Outer> {
+        RateLimiter {
+            state: self.state,
+            gcra: self.gcra,
+            clock: self.clock,
+            start: self.start,
+            middleware,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Sets the jitter that the rate limiter's waiting methods use when they aren't given one,
+    /// returning the rate limiter.
+    ///
+    /// Methods like [`until_ready`][RateLimiter::until_ready],
+    /// [`until_key_n_ready`][RateLimiter::until_key_n_ready] or
+    /// [`until_ready_blocking`][RateLimiter::until_ready_blocking] wait with this jitter, just
+    /// like their `_with_jitter` variants do with the jitter they're given, and so do streams
+    /// and sinks that are rate-limited without a jitter. By default, rate limiters have no
+    /// jitter.
+    ///
+    /// The default jitter never applies to the `_with_jitter` variants: The jitter passed to
+    /// them replaces the default one for that call, so passing [`Jitter::none()`][Jitter::none]
+    /// waits without any jitter.
+    pub fn with_default_jitter(self, jitter: Jitter) -> Self {
+        RateLimiter { jitter, ..self }
+    }
+
+    /// Replaces the quota that the rate limiter enforces, without resetting its state.
+    ///
+    /// This can be used to change rate limits at runtime (e.g. when reloading a configuration),
+    /// while keeping the rate limiter (and all references to it) alive. Rate-limiting decisions
+    /// made after this method returns use the new quota; for keyed rate limiters, all keys use
+    /// the new quota.
+    ///
+    /// The rate limiter's state is kept as it is, which means that cells that were allowed
+    /// through before the change keep the cost they had under the old quota:
+    /// * If the new quota's burst capacity spans a longer time (i.e., its maximum burst size
+    ///   multiplied by its replenishment interval is larger), more cells are available
+    ///   immediately. Any faster replenishment rate applies to cells that get replenished from
+    ///   then on.
+    /// * If the new quota is stricter, previously-allowed cells are not charged again: only cells
+    ///   allowed through after the change are subject to the new quota's replenishment interval.
+    pub fn set_quota(&self, quota: Quota) {
+        self.gcra.set_quota(self.state.quota_per_state(quota));
+    }
+
+    /// Returns how often the rate limiter's clock went back in time, as observed when making
+    /// decisions.
+    ///
+    /// Well-behaved clocks never go back, but custom clocks (or clocks across a system's suspend
+    /// and resume) might. If a decision is made at a time more than a millisecond before an
+    /// earlier decision, the rate limiter counts a clock regression, and makes the decision as if
+    /// it had been made at the earlier decision's time instead: Time seems to stand still until
+    /// the clock catches up again, so the rate limiter neither replenishes nor loses any capacity
+    /// in the meantime (and can't allow more cells through than it should).
+    pub fn clock_regressions(&self) -> u64 {
+        self.gcra.clock_regressions()
+    }
+
+    /// Allows a single cell through the rate limiter under a key of any kind (including
+    /// [`NotKeyed`]), returning the time at which the decision was made along with it.
+    ///
+    /// The middleware's positive outcome is discarded.
+    #[cfg(feature = "tower")]
+    pub(crate) fn check_any_key(
+        &self,
+        key: &K,
+    ) -> (C::Instant, Result<(), NotUntil<'_, C::Instant>>) {
+        let now = self.clock.now();
+        (
+            now,
+            self.gcra
+                .test_and_update(self.start, key, &self.state, now, &self.middleware)
+                .map(|_| ()),
+        )
+    }
 
     /// Consumes the `RateLimiter` and returns the state store.
     ///
@@ -94,21 +302,21 @@ where
     }
 }
 
+/// # Measuring the admitted rate
 #[cfg(feature = "std")]
-impl<K, S, C> RateLimiter<K, S, C>
+impl<K, S, C> RateLimiter<K, S, C, AdmittedRateMiddleware>
 where
     S: StateStore<Key = K>,
-    C: clock::ReasonablyRealtime,
+    C: clock::Clock,
 {
-    pub(crate) fn reference_reading(&self) -> (C::Instant, Instant) {
-        self.clock.reference_point()
-    }
-
-    pub(crate) fn instant_from_reference(
-        &self,
-        reference: (C::Instant, Instant),
-        reading: C::Instant,
-    ) -> Instant {
-        C::convert_from_reference(reference, reading)
+    /// Returns the rate at which the rate limiter has recently allowed cells through, in cells
+    /// per second.
+    ///
+    /// The rate is a moving average over the [`AdmittedRateMiddleware`]'s smoothing window,
+    /// evaluated at the current time, so it decays towards zero while no cells are allowed
+    /// through.
+    pub fn admitted_rate(&self) -> f64 {
+        let now = clock::Reference::duration_since(&self.clock.now(), self.start);
+        self.middleware.rate_at(now)
     }
 }
@@ -0,0 +1,138 @@
+use crate::middleware::{NoOpMiddleware, RateLimitingMiddleware};
+use crate::state::{InMemoryState, NotKeyed, StateStore};
+use crate::{clock, Jitter, Quota, RateLimiter};
+
+/// A builder for [`RateLimiter`]s, which collects the rate limiter's components one at a time.
+///
+/// A rate limiter's type depends on its key, state store, clock and middleware, so constructing
+/// one with several custom components takes several steps (like
+/// [`RateLimiter::new`] followed by [`with_middleware`][RateLimiter::with_middleware]). The
+/// builder takes the components in any order, and its [`build`][RateLimiterBuilder::build]
+/// method returns the rate limiter type that fits them.
+///
+/// Only the [quota](RateLimiterBuilder::quota) must be given: Without one, `build` isn't
+/// available. Components that aren't given are the defaults: A direct [`InMemoryState`], the
+/// [`DefaultClock`][clock::DefaultClock], the [`NoOpMiddleware`], and no
+/// [default jitter][RateLimiter::with_default_jitter].
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use std::time::Duration;
+/// # use governor::{clock::FakeRelativeClock, middleware::StateInformationMiddleware};
+/// # use governor::state::{keyed::DefaultKeyedStateStore, RateLimiterBuilder};
+/// # use governor::{Jitter, Quota, RateLimiter};
+/// let clock = FakeRelativeClock::default();
+/// let lim = RateLimiter::builder()
+///     .quota(Quota::per_second(nonzero!(5u32)))
+///     .store(DefaultKeyedStateStore::<&str>::default())
+///     .clock(&clock)
+///     .middleware(StateInformationMiddleware)
+///     .default_jitter(Jitter::up_to(Duration::from_millis(10)))
+///     .build();
+/// assert_eq!(lim.check_key(&"alice").map(|s| s.remaining_burst_capacity()), Ok(4));
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct RateLimiterBuilder<Q, S, C, MW> {
+    quota: Q,
+    state: S,
+    clock: C,
+    middleware: MW,
+    jitter: Jitter,
+}
+
+impl RateLimiterBuilder<(), InMemoryState, clock::DefaultClock, NoOpMiddleware> {
+    /// Constructs a builder that has no quota yet, and uses the default components.
+    pub fn new() -> Self {
+        RateLimiterBuilder {
+            quota: (),
+            state: InMemoryState::default(),
+            clock: clock::DefaultClock::default(),
+            middleware: NoOpMiddleware,
+            jitter: Jitter::NONE,
+        }
+    }
+}
+
+impl Default for RateLimiterBuilder<(), InMemoryState, clock::DefaultClock, NoOpMiddleware> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Q, S, C, MW> RateLimiterBuilder<Q, S, C, MW> {
+    /// Sets the quota that the rate limiter enforces.
+    pub fn quota(self, quota: Quota) -> RateLimiterBuilder<Quota, S, C, MW> {
+        RateLimiterBuilder {
+            quota,
+            state: self.state,
+            clock: self.clock,
+            middleware: self.middleware,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Sets the state store that the rate limiter keeps its states in, which also determines
+    /// its key type (e.g. [`NotKeyed`] for direct rate limiters).
+    pub fn store<S2: StateStore>(self, state: S2) -> RateLimiterBuilder<Q, S2, C, MW> {
+        RateLimiterBuilder {
+            quota: self.quota,
+            state,
+            clock: self.clock,
+            middleware: self.middleware,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Sets the clock that the rate limiter measures time with.
+    pub fn clock<C2: clock::Clock>(self, clock: &C2) -> RateLimiterBuilder<Q, S, C2, MW> {
+        RateLimiterBuilder {
+            quota: self.quota,
+            state: self.state,
+            clock: clock.clone(),
+            middleware: self.middleware,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Sets the [middleware][crate::middleware] that the rate limiter passes its decisions to.
+    pub fn middleware<MW2>(self, middleware: MW2) -> RateLimiterBuilder<Q, S, C, MW2> {
+        RateLimiterBuilder {
+            quota: self.quota,
+            state: self.state,
+            clock: self.clock,
+            middleware,
+            jitter: self.jitter,
+        }
+    }
+
+    /// Sets the jitter that the rate limiter waits with when it isn't given one (see
+    /// [`RateLimiter::with_default_jitter`]).
+    pub fn default_jitter(self, jitter: Jitter) -> Self {
+        RateLimiterBuilder { jitter, ..self }
+    }
+}
+
+impl<S, C, MW> RateLimiterBuilder<Quota, S, C, MW>
+where
+    S: StateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<S::Key, C::Instant>,
+{
+    /// Constructs the rate limiter.
+    pub fn build(self) -> RateLimiter<S::Key, S, C, MW> {
+        RateLimiter::new(self.quota, self.state, &self.clock)
+            .with_middleware(self.middleware)
+            .with_default_jitter(self.jitter)
+    }
+}
+
+/// # Constructing rate limiters with a builder
+impl RateLimiter<NotKeyed, InMemoryState, clock::DefaultClock> {
+    /// Returns a [`RateLimiterBuilder`] for constructing a rate limiter with custom components.
+    pub fn builder() -> RateLimiterBuilder<(), InMemoryState, clock::DefaultClock, NoOpMiddleware> {
+        RateLimiterBuilder::new()
+    }
+}
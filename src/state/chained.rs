@@ -0,0 +1,130 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::middleware::RateLimitingMiddleware;
+use crate::state::keyed::KeyedStateStore;
+use crate::state::{DirectStateStore, NotKeyed, StateStore};
+use crate::{clock, RateLimiter};
+use nonzero_ext::nonzero;
+use std::hash::Hash;
+
+/// Two rate limiters that a cell has to pass one after the other: A cheap primary rate limiter
+/// (e.g. one that keeps its state in memory), and a secondary one that is more expensive to
+/// consult (e.g. one that keeps its state in Redis).
+///
+/// A cell is only allowed through if both rate limiters allow it. The primary rate limiter is
+/// checked first, and the secondary one is only consulted if the primary one allows the cell, so
+/// cells that the primary rate limiter rejects don't cost a round trip. If the secondary rate
+/// limiter rejects the cell, the capacity that the primary one used up for it is refunded.
+///
+/// Both rate limiters' middlewares are informed of their decisions; if the secondary rate
+/// limiter rejects a cell, the primary one's middleware has seen it allowed through.
+///
+/// Construct one with [`RateLimiter::chained`].
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let local = RateLimiter::direct(Quota::per_second(nonzero!(10u32)));
+/// let global = RateLimiter::direct(Quota::per_second(nonzero!(1u32)));
+/// let lim = RateLimiter::chained(local, global);
+/// assert_eq!(Ok(()), lim.check());
+/// assert_ne!(Ok(()), lim.check());
+/// assert_eq!(9, lim.primary().available());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct ChainedRateLimiter<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: StateStore<Key = K>,
+    C: clock::Clock,
+{
+    /// Chains a primary rate limiter and a secondary one that is only consulted when the primary
+    /// one allows a cell through (see [`ChainedRateLimiter`]).
+    ///
+    /// Both rate limiters must use the same type of clock, so that their decisions can be
+    /// compared.
+    pub fn chained<S2, MW2>(
+        primary: Self,
+        secondary: RateLimiter<K, S2, C, MW2>,
+    ) -> ChainedRateLimiter<Self, RateLimiter<K, S2, C, MW2>>
+    where
+        S2: StateStore<Key = K>,
+    {
+        ChainedRateLimiter { primary, secondary }
+    }
+}
+
+impl<P, S> ChainedRateLimiter<P, S> {
+    /// Returns the primary rate limiter.
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+
+    /// Returns the secondary rate limiter.
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+
+    /// Consumes the chained rate limiter and returns the primary and secondary rate limiters.
+    pub fn into_inner(self) -> (P, S) {
+        (self.primary, self.secondary)
+    }
+}
+
+/// # Chained direct rate limiters
+impl<S1, MW1, S2, MW2, C>
+    ChainedRateLimiter<RateLimiter<NotKeyed, S1, C, MW1>, RateLimiter<NotKeyed, S2, C, MW2>>
+where
+    S1: DirectStateStore,
+    S2: DirectStateStore,
+    C: clock::Clock,
+    MW1: RateLimitingMiddleware<NotKeyed, C::Instant>,
+    MW2: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Allow a single cell through the rate limiters, if both of them allow it.
+    ///
+    /// If either rate limiter rejects the cell, `check` returns its negative decision. The
+    /// secondary rate limiter is not consulted if the primary one rejects the cell.
+    pub fn check(&self) -> Result<(), NotUntil<'_, C::Instant>> {
+        self.primary.check()?;
+        if let Err(negative) = self.secondary.check() {
+            self.primary.refund(nonzero!(1u32));
+            return Err(negative);
+        }
+        Ok(())
+    }
+}
+
+/// # Chained keyed rate limiters
+impl<K, S1, MW1, S2, MW2, C>
+    ChainedRateLimiter<RateLimiter<K, S1, C, MW1>, RateLimiter<K, S2, C, MW2>>
+where
+    K: Hash + Eq + Clone,
+    S1: KeyedStateStore<K>,
+    S2: KeyedStateStore<K>,
+    C: clock::Clock,
+    MW1: RateLimitingMiddleware<K, C::Instant>,
+    MW2: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Allow a single cell through the rate limiters for the given key, if both of them allow
+    /// it.
+    ///
+    /// If either rate limiter rejects the cell, `check_key` returns its negative decision. The
+    /// secondary rate limiter is not consulted if the primary one rejects the cell.
+    pub fn check_key(&self, key: &K) -> Result<(), NotUntil<'_, C::Instant>> {
+        self.primary.check_key(key)?;
+        if let Err(negative) = self.secondary.check_key(key) {
+            self.primary.refund_key(key, nonzero!(1u32));
+            return Err(negative);
+        }
+        Ok(())
+    }
+}
@@ -5,10 +5,21 @@
 
 use std::prelude::v1::*;
 
+#[cfg(feature = "std")]
+use std::convert::Infallible;
 use std::num::NonZeroU32;
+use std::time::Duration;
+#[cfg(feature = "std")]
+use std::time::{Instant, SystemTime};
 
-use crate::gcra::NotUntil;
-use crate::{clock, state::InMemoryState, NegativeMultiDecision, Quota};
+use crate::clock::Reference;
+use crate::gcra::{Borrowed, NotUntil, StateInformation};
+use crate::middleware::RateLimitingMiddleware;
+#[cfg(feature = "std")]
+use crate::state::keyed::StateSnapshot;
+use crate::state::{InMemoryState, RelaxedInMemoryState};
+use crate::{clock, NegativeMultiDecision, Quota};
+use nonzero_ext::nonzero;
 
 /// The "this state store does not use keys" key type.
 ///
@@ -41,6 +52,18 @@ impl RateLimiter<NotKeyed, InMemoryState, clock::DefaultClock> {
         let clock = clock::DefaultClock::default();
         Self::direct_with_clock(quota, &clock)
     }
+
+    /// Constructs a new in-memory direct rate limiter for a quota with the default real-time
+    /// clock, which passes its rate-limiting decisions to the given
+    /// [middleware][crate::middleware].
+    pub fn direct_with_middleware<
+        MW: RateLimitingMiddleware<NotKeyed, <clock::DefaultClock as clock::Clock>::Instant>,
+    >(
+        quota: Quota,
+        middleware: MW,
+    ) -> RateLimiter<NotKeyed, InMemoryState, clock::DefaultClock, MW> {
+        Self::direct(quota).with_middleware(middleware)
+    }
 }
 
 impl<C> RateLimiter<NotKeyed, InMemoryState, C>
@@ -54,19 +77,107 @@ where
     }
 }
 
+/// # Direct in-memory rate limiters - Restoring saved states
+#[cfg(feature = "std")]
+impl<C> RateLimiter<NotKeyed, InMemoryState, C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new direct rate limiter for a quota with a custom clock, whose state is
+    /// restored from a snapshot taken by [`export_state`][RateLimiter::export_state] (e.g.
+    /// before a restart).
+    ///
+    /// The time that passed between taking the snapshot and constructing the rate limiter counts
+    /// towards replenishing the restored state, as if the rate limiter had kept running. If the
+    /// state has replenished completely by then, the rate limiter starts out like one constructed
+    /// with [`direct_with_clock`][RateLimiter::direct_with_clock].
+    ///
+    /// ### Clock skew
+    /// The time that passed is determined using the system clock, which can jump or differ
+    /// between hosts. If the restoring host's system clock is ahead of the one that took the
+    /// snapshot, the restored state is more lenient than it should be (possibly fresh); if it is
+    /// behind (or the snapshot appears to be taken in the future), it is stricter, as if no time
+    /// had passed at all. After long downtimes, the rate limiter will usually have replenished
+    /// completely.
+    pub fn from_snapshot(quota: Quota, clock: &C, snapshot: StateSnapshot) -> Self {
+        let lim = RateLimiter::direct_with_clock(quota, clock);
+        let now = lim.clock.now().duration_since(lim.start);
+        let tat = snapshot.restore_at(now, SystemTime::now());
+        if tat > now {
+            let _ = lim
+                .state
+                .measure_and_replace(&NotKeyed::NonKey, |_| Ok::<_, Infallible>(((), tat)));
+        }
+        lim
+    }
+}
+
+/// A direct rate limiter whose state uses relaxed memory ordering (see
+/// [`RelaxedInMemoryState`]).
+pub type RelaxedDirectRateLimiter<C = clock::DefaultClock> =
+    RateLimiter<NotKeyed, RelaxedInMemoryState, C>;
+
+/// # Direct in-memory rate limiters - Relaxed memory ordering
+#[cfg(feature = "std")]
+impl RateLimiter<NotKeyed, RelaxedInMemoryState, clock::DefaultClock> {
+    /// Constructs a new in-memory direct rate limiter for a quota with the default real-time
+    /// clock, whose state uses relaxed memory ordering (see [`RelaxedInMemoryState`]).
+    pub fn direct_relaxed(quota: Quota) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::direct_relaxed_with_clock(quota, &clock)
+    }
+}
+
+impl<C> RateLimiter<NotKeyed, RelaxedInMemoryState, C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new direct rate limiter for a quota with a custom clock, whose state uses
+    /// relaxed memory ordering (see [`RelaxedInMemoryState`]).
+    pub fn direct_relaxed_with_clock(quota: Quota, clock: &C) -> Self {
+        RateLimiter::new(quota, RelaxedInMemoryState::default(), clock)
+    }
+}
+
 /// # Direct rate limiters - Manually checking cells
-impl<S, C> RateLimiter<NotKeyed, S, C>
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
 where
     S: DirectStateStore,
     C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
 {
     /// Allow a single cell through the rate limiter.
     ///
     /// If the rate limit is reached, `check` returns information about the earliest
     /// time that a cell might be allowed through again.
-    pub fn check(&self) -> Result<(), NotUntil<C::Instant>> {
-        self.gcra
-            .test_and_update(self.start, &NotKeyed::NonKey, &self.state, self.clock.now())
+    pub fn check(&self) -> Result<MW::PositiveOutcome, NotUntil<C::Instant>> {
+        self.check_at(self.clock.now())
+    }
+
+    /// Allow a single cell through the rate limiter, deciding as if the current time was `now`.
+    ///
+    /// This is like [`check`][RateLimiter::check], but uses the given time instead of reading
+    /// the rate limiter's clock, which makes it possible to drive a rate limiter through a
+    /// sequence of times (e.g. in property tests) without a shared fake clock. Times before the
+    /// rate limiter was constructed count as the time it was constructed at.
+    ///
+    /// Passing times out of order, or mixing `check_at` with methods that read the clock (like
+    /// `check`) while the times given to `check_at` run ahead of or behind the clock, is the
+    /// caller's responsibility. The state does not move backwards in time, and times more than a
+    /// millisecond before an earlier decision count as
+    /// [clock regressions][RateLimiter::clock_regressions]: Such cells are checked as if at the
+    /// time of the earlier decision.
+    pub fn check_at(
+        &self,
+        now: C::Instant,
+    ) -> Result<MW::PositiveOutcome, NotUntil<'_, C::Instant>> {
+        self.gcra.test_and_update(
+            self.start,
+            &NotKeyed::NonKey,
+            &self.state,
+            now,
+            &self.middleware,
+        )
     }
 
     /// Allow *only all* `n` cells through the rate limiter.
@@ -77,7 +188,9 @@ where
     ///   The result is `Err(NegativeMultiDecision::BatchNonConforming(NotUntil))`, which can
     ///   be interrogated about when the batch might next conform.
     /// * Failure (the batch can never go through): The rate limit quota's burst size is too low
-    ///   for the given number of cells to ever be allowed through.
+    ///   for the given number of cells to ever be allowed through. The result is
+    ///   `Err(NegativeMultiDecision::InsufficientCapacity { requested, max_burst })`; retrying
+    ///   the same batch is pointless.
     ///
     /// ### Performance
     /// This method diverges a little from the GCRA algorithm, using
@@ -86,8 +199,105 @@ where
     pub fn check_n(
         &self,
         n: NonZeroU32,
-    ) -> Result<(), NegativeMultiDecision<NotUntil<C::Instant>>> {
+    ) -> Result<MW::PositiveOutcome, NegativeMultiDecision<NotUntil<C::Instant>>> {
+        self.check_n_at(n, self.clock.now())
+    }
+
+    /// Allow *only all* `n` cells through the rate limiter, deciding as if the current time was
+    /// `now`.
+    ///
+    /// This is like [`check_n`][RateLimiter::check_n], but uses the given time instead of
+    /// reading the rate limiter's clock. See [`check_at`][RateLimiter::check_at] for the caveats
+    /// of passing explicit times.
+    pub fn check_n_at(
+        &self,
+        n: NonZeroU32,
+        now: C::Instant,
+    ) -> Result<MW::PositiveOutcome, NegativeMultiDecision<NotUntil<'_, C::Instant>>> {
         self.gcra.test_n_all_and_update(
+            self.start,
+            &NotKeyed::NonKey,
+            n,
+            &self.state,
+            now,
+            &self.middleware,
+        )
+    }
+
+    /// Allow *only all* of `cost` cells through the rate limiter, where `cost` is computed at
+    /// runtime (e.g. the size of a payload in bytes, see
+    /// [`Quota::bytes_per_second`]) and may be zero.
+    ///
+    /// A zero cost is a no-op: It does not consult the rate limiter or its middleware, and
+    /// returns `Ok(None)`. Any other cost is checked like [`check_n`][RateLimiter::check_n] would
+    /// check it, returning the middleware's positive outcome as `Ok(Some(_))`.
+    pub fn check_cost(
+        &self,
+        cost: u32,
+    ) -> Result<Option<MW::PositiveOutcome>, NegativeMultiDecision<NotUntil<'_, C::Instant>>> {
+        match NonZeroU32::new(cost) {
+            Some(n) => self.check_n(n).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Checks `n` single cells in sequence, e.g. for a batch of requests, and returns each
+    /// cell's outcome in order.
+    ///
+    /// The cells are checked as if by `n` calls to [`check`][RateLimiter::check], but in one
+    /// update of the rate limiter's state, so other threads' decisions can't interleave with the
+    /// batch's: The cells that the rate limiter's capacity allows through come first, and use up
+    /// that capacity; the remaining cells are rejected. Each rejected cell's negative outcome
+    /// accounts for the rejected cells before it, as if they were waiting to be let through one
+    /// at a time - the wait times of the rejected cells increase by one replenishment interval
+    /// from one to the next. The middleware is informed of every cell's outcome.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+    /// # use nonzero_ext::nonzero;
+    /// let clock = FakeRelativeClock::default();
+    /// let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    /// let outcomes = lim.check_batch(3);
+    /// assert_eq!(outcomes.iter().filter(|outcome| outcome.is_ok()).count(), 2);
+    /// assert!(outcomes[2].is_err());
+    /// ```
+    pub fn check_batch(
+        &self,
+        n: usize,
+    ) -> Vec<Result<MW::PositiveOutcome, NotUntil<'_, C::Instant>>> {
+        self.gcra.test_batch_and_update(
+            self.start,
+            &NotKeyed::NonKey,
+            n,
+            &self.state,
+            self.clock.now(),
+            &self.middleware,
+        )
+    }
+
+    /// Allow all `n` cells through the rate limiter, borrowing capacity from the future if
+    /// necessary.
+    ///
+    /// Unlike [`check_n`][RateLimiter::check_n], this allows the cells through as long as the
+    /// rate limiter would allow at least one cell through right now, no matter how many cells
+    /// exceed its available capacity (or even the quota's burst size). The cells that exceed it
+    /// are borrowed from the future: The rate limiter then does not allow any cells through until
+    /// the borrowed capacity is repaid, as if the cells had been allowed through one at a time at
+    /// the quota's rate. This means that over long periods of time, the rate limiter still allows
+    /// at most the quota's rate of cells through on average.
+    ///
+    /// The returned [`Borrowed`] value describes how many cells were borrowed, and how long the
+    /// rate limiter won't allow any more cells through. If the rate limiter is still repaying an
+    /// earlier debt, this returns the negative outcome of the earliest time that the rate
+    /// limiter might allow cells through again.
+    ///
+    /// Decisions made by this method are not passed to the rate limiter's middleware.
+    pub fn check_n_allow_borrow(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<Borrowed, NotUntil<'_, C::Instant>> {
+        self.gcra.test_n_borrow_and_update(
             self.start,
             &NotKeyed::NonKey,
             n,
@@ -95,13 +305,282 @@ where
             self.clock.now(),
         )
     }
+
+    /// Schedules a single cell for the earliest time that the rate limiter allows it through,
+    /// and returns how long to wait until then.
+    ///
+    /// Unlike [`check`][RateLimiter::check], this never rejects a cell: The cell is counted
+    /// against the quota right away, for the time it is scheduled at, so that each call gets the
+    /// next slot after the previous call's. Sleeping for the returned duration before sending the
+    /// cell shapes traffic to the quota's rate. With a quota that allows bursts, the cells of a
+    /// burst are scheduled right away; a [shaping](Quota::shaping) quota paces every cell exactly
+    /// one replenishment interval after the previous one.
+    ///
+    /// Cells scheduled in the future use up the rate limiter's capacity until their time comes,
+    /// so other methods (like `check`) don't allow cells through in their slots. Decisions made
+    /// by this method are not passed to the rate limiter's middleware.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+    /// # use nonzero_ext::nonzero;
+    /// # use std::time::Duration;
+    /// let clock = FakeRelativeClock::default();
+    /// let quota = Quota::per_second(nonzero!(4u32)).shaping();
+    /// let lim = RateLimiter::direct_with_clock(quota, &clock);
+    /// assert_eq!(Duration::ZERO, lim.schedule());
+    /// assert_eq!(Duration::from_millis(250), lim.schedule());
+    /// assert_eq!(Duration::from_millis(500), lim.schedule());
+    /// ```
+    pub fn schedule(&self) -> Duration {
+        self.gcra
+            .schedule(self.start, &NotKeyed::NonKey, &self.state, self.clock.now())
+            .into()
+    }
+
+    /// Tests whether a single cell would be allowed through the rate limiter right now, without
+    /// using up any of its capacity.
+    ///
+    /// This returns the same result that [`check`][RateLimiter::check] would return at this
+    /// point in time, but leaves the rate limiter's state unchanged; it is useful e.g. for
+    /// logging or metrics. Since other threads may check cells concurrently, a positive result
+    /// does not guarantee that a following `check` succeeds.
+    pub fn peek(&self) -> Result<(), NotUntil<'_, C::Instant>> {
+        self.gcra
+            .test(self.start, &NotKeyed::NonKey, &self.state, self.clock.now())
+    }
+
+    /// Returns information about the rate limiter's current state (e.g. how many cells could be
+    /// allowed through right now), without using up any of its capacity.
+    pub fn state_information(&self) -> StateInformation {
+        let now = self.clock.now().duration_since(self.start);
+        self.gcra
+            .state_information(self.state.peek(&NotKeyed::NonKey), now)
+    }
+
+    /// Returns the number of cells that the rate limiter would allow through right now, without
+    /// using up any of its capacity.
+    ///
+    /// This is at most the quota's burst size. Since other threads may check cells concurrently,
+    /// a following [`check_n`][RateLimiter::check_n] for that many cells is not guaranteed to
+    /// succeed.
+    pub fn available(&self) -> u32 {
+        self.state_information().remaining_burst_capacity()
+    }
+
+    /// Returns whether the rate limiter is cold, i.e. has never allowed a cell through (or
+    /// used up any capacity otherwise) since it was constructed or last
+    /// [`reset`][RateLimiter::reset].
+    ///
+    /// A cold rate limiter is in its pristine initial state, and the first cell it's asked about
+    /// sees the quota's entire burst capacity (or none, if the quota
+    /// [starts empty][crate::Quota::start_empty]). A rate limiter that has been used stops being
+    /// cold for good, even once its burst capacity has fully replenished.
+    ///
+    /// ```rust
+    /// # use nonzero_ext::*;
+    /// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+    /// # use std::time::Duration;
+    /// let clock = FakeRelativeClock::default();
+    /// let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    /// assert!(lim.is_cold());
+    /// lim.check().unwrap();
+    /// clock.advance(Duration::from_secs(10));
+    /// assert!(!lim.is_cold());
+    /// ```
+    pub fn is_cold(&self) -> bool {
+        self.state.peek(&NotKeyed::NonKey).is_none()
+    }
+
+    /// Returns how long after `now` the rate limiter will have its entire burst capacity available
+    /// again, if no more cells are allowed through in the meantime.
+    ///
+    /// If the full burst capacity is available at `now`, this returns a zero duration. Once the
+    /// time has passed, [`available`][RateLimiter::available] returns the quota's burst size.
+    pub fn full_at(&self, now: C::Instant) -> Duration {
+        let now = now.duration_since(self.start);
+        self.gcra
+            .state_information(self.state.peek(&NotKeyed::NonKey), now)
+            .time_until_full()
+    }
+
+    /// Returns how long ago the rate limiter's state was last advanced, e.g. to decide whether a
+    /// state that was saved a while ago is still worth trusting.
+    ///
+    /// This is the time since the last cell was allowed through, if the full burst
+    /// capacity was available to that cell. Cells that were allowed through in a burst advance
+    /// the state further, so after a burst, the age only starts growing once the burst capacity
+    /// it used up would have replenished again, and it is shorter than the time since the last
+    /// cell. The same goes for the very first cell, which advances the state by one
+    /// replenishment interval more than later cells do.
+    ///
+    /// A [restored][RateLimiter::from_snapshot] state reports the age it would have if the rate
+    /// limiter had kept running.
+    ///
+    /// If the rate limiter has no state (because it hasn't checked a cell yet, or it was reset
+    /// since), there's no age to report, and this returns `None`.
+    ///
+    /// ```rust
+    /// # use nonzero_ext::*;
+    /// # use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+    /// # use std::time::Duration;
+    /// let clock = FakeRelativeClock::default();
+    /// let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    /// assert_eq!(None, lim.state_age());
+    /// lim.check().unwrap();
+    /// clock.advance(Duration::from_secs(5));
+    /// lim.check().unwrap();
+    /// clock.advance(Duration::from_secs(5));
+    /// assert_eq!(Some(Duration::from_secs(5)), lim.state_age());
+    /// ```
+    pub fn state_age(&self) -> Option<Duration> {
+        let now = self.clock.now().duration_since(self.start);
+        self.gcra.state_age(self.state.peek(&NotKeyed::NonKey), now)
+    }
+
+    /// Returns the capacity of `n` cells that were previously allowed through to the rate
+    /// limiter.
+    ///
+    /// This is useful when a cell was allowed through but the work it stands for was never done
+    /// (e.g., because the request failed validation), so it should not count against the rate
+    /// limit. Refunds never make more than the quota's burst capacity available: Returning more
+    /// cells than were used up only restores the rate limiter to its initial state.
+    pub fn refund(&self, n: NonZeroU32) {
+        self.gcra.refund_n(
+            self.start,
+            &NotKeyed::NonKey,
+            n,
+            &self.state,
+            self.clock.now(),
+        )
+    }
+
+    /// Resets the rate limiter to its initial state, making the full burst capacity available
+    /// again.
+    ///
+    /// This is useful e.g. in tests or to lift a rate limit on administrative request.
+    pub fn reset(&self) {
+        self.state.reset(&NotKeyed::NonKey);
+    }
 }
 
+/// # Direct rate limiters - Saving state
 #[cfg(feature = "std")]
-mod future;
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Returns a snapshot of the rate limiter's state, which can be saved and passed to
+    /// [`from_snapshot`][RateLimiter::from_snapshot] to construct a rate limiter with the same
+    /// state later, e.g. after a restart.
+    ///
+    /// The snapshot doesn't depend on the rate limiter's clock or on when it was constructed; see
+    /// [`StateSnapshot`].
+    pub fn export_state(&self) -> StateSnapshot {
+        let now = self.clock.now().duration_since(self.start);
+        let tat = self.state.peek(&NotKeyed::NonKey).unwrap_or(now);
+        StateSnapshot::of(tat, now, SystemTime::now())
+    }
+}
+
+/// # Direct rate limiters - Absolute deadlines
 #[cfg(feature = "std")]
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Allow a single cell through the rate limiter, returning the absolute time at which a cell
+    /// might be allowed through again if the rate limit is reached.
+    ///
+    /// This is like [`check`][RateLimiter::check], for callers that schedule work by deadlines
+    /// rather than by durations: The deadline is the current [`Instant`] plus the time the rate
+    /// limiter's clock says is left to wait, so it's an `Instant` regardless of the clock's own
+    /// instant type.
+    ///
+    /// # Example
+    /// ```rust
+    /// # #[cfg(feature = "std")] fn main() {
+    /// # use nonzero_ext::*;
+    /// # use std::time::{Duration, Instant};
+    /// # use governor::{Quota, RateLimiter};
+    /// let lim = RateLimiter::direct(Quota::per_second(nonzero!(1u32)));
+    /// assert_eq!(Ok(()), lim.check_deadline());
+    /// let deadline = lim.check_deadline().unwrap_err();
+    /// assert!(deadline <= Instant::now() + Duration::from_secs(1));
+    /// # } #[cfg(not(feature = "std"))] fn main() {}
+    /// ```
+    pub fn check_deadline(&self) -> Result<MW::PositiveOutcome, Instant> {
+        let now = self.clock.now();
+        self.check_at(now)
+            .map_err(|negative| Instant::now() + negative.wait_time_from(now))
+    }
+}
+
+/// # Direct rate limiters - Charging only failures
+///
+/// Some operations should only count against the quota if they fail, e.g. to limit retries
+/// against a flaky dependency without throttling the calls that succeed. For these,
+/// [`try_begin`][RateLimiter::try_begin] tests whether an attempt may go ahead without using up
+/// any capacity, and [`charge_failure`][RateLimiter::charge_failure] uses up a cell once the
+/// attempt has failed.
+///
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::direct(Quota::per_minute(nonzero!(1u32)));
+/// for outcome in [Ok(()), Ok(()), Err("timeout")] {
+///     lim.try_begin().expect("only failures use up capacity");
+///     if outcome.is_err() {
+///         lim.charge_failure();
+///     }
+/// }
+/// assert!(lim.try_begin().is_err());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Tests whether an attempt whose failures are charged with
+    /// [`charge_failure`][RateLimiter::charge_failure] may go ahead right now, without using up
+    /// any of the rate limiter's capacity.
+    ///
+    /// This is the same test that [`peek`][RateLimiter::peek] makes: The attempt may go ahead
+    /// while the failures charged so far leave room for at least one more.
+    pub fn try_begin(&self) -> Result<(), NotUntil<'_, C::Instant>> {
+        self.peek()
+    }
+
+    /// Uses up a single cell of the rate limiter's capacity for an attempt that failed.
+    ///
+    /// The cell is charged even if the rate limiter has no capacity left (e.g. because several
+    /// attempts that began concurrently failed), but charges never use up more than the quota's
+    /// burst capacity: Once it is used up, another attempt may begin after one replenishment
+    /// interval. Charges are not passed to the rate limiter's middleware.
+    pub fn charge_failure(&self) {
+        self.gcra.charge_n(
+            self.start,
+            &NotKeyed::NonKey,
+            nonzero!(1u32),
+            &self.state,
+            self.clock.now(),
+        )
+    }
+}
+
+pub(crate) mod future;
 pub use future::*;
 
+#[cfg(feature = "std")]
+mod blocking;
+
 #[cfg(feature = "std")]
 mod sinks;
 #[cfg(feature = "std")]
@@ -110,6 +589,35 @@ pub use sinks::*;
 #[cfg(feature = "std")]
 mod streams;
 
+#[cfg(feature = "std")]
+mod sharded;
+#[cfg(feature = "std")]
+pub use sharded::*;
+
+mod tiered;
+pub use tiered::*;
+
+mod sliding_window;
+pub use sliding_window::*;
+
+mod reservation;
+pub use reservation::*;
+
+mod ramping;
+pub use ramping::*;
+
+mod adaptive;
+pub use adaptive::*;
+
+mod priority;
+pub use priority::*;
+
+mod grace;
+pub use grace::*;
+
+mod concurrency;
+pub use concurrency::*;
+
 use crate::state::{RateLimiter, StateStore};
 #[cfg(feature = "std")]
 pub use streams::*;
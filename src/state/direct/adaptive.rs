@@ -0,0 +1,225 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::nanos::Interval;
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, NegativeMultiDecision, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::num::NonZeroU32;
+
+#[cfg(feature = "std")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "std"))]
+use spin::Mutex;
+
+/// The fraction of the range between the minimum and the maximum rate that a success adds to
+/// the rate, unless configured otherwise.
+const DEFAULT_INCREASE_FRACTION: f64 = 0.01;
+
+/// The factor that an overload multiplies the rate by, unless configured otherwise.
+const DEFAULT_DECREASE_FACTOR: f64 = 0.5;
+
+/// A direct rate limiter whose quota adapts to feedback from the downstream resource it
+/// protects, with additive increase and multiplicative decrease (AIMD).
+///
+/// The rate limiter starts out at its maximum quota. The caller reports the outcome of the
+/// operations it lets through: Every [`report_success`][AdaptiveRateLimiter::report_success]
+/// adds a fixed number of cells per second to the rate (the *additive increase*, by default a
+/// hundredth of the range between the minimum and the maximum rate), and every
+/// [`report_overload`][AdaptiveRateLimiter::report_overload] (e.g. for a timeout, a rising
+/// latency, or an HTTP 503 response) multiplies the rate by a factor below one (the
+/// *multiplicative decrease*, by default one half). The rate never leaves the range between the
+/// minimum and the maximum quota's rates, and the burst size moves between theirs in proportion
+/// to the rate, rounded to the nearest whole cell (at least one).
+///
+/// Changing the quota keeps the cells that were already allowed through, as with
+/// [`RateLimiter::set_quota`].
+///
+/// Construct one with [`RateLimiter::adaptive`].
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::adaptive(
+///     Quota::per_second(nonzero!(10u32)),
+///     Quota::per_second(nonzero!(100u32)),
+/// );
+/// assert_eq!(100.0, lim.current_rate());
+/// lim.report_overload();
+/// assert_eq!(50.0, lim.current_rate());
+/// lim.report_success();
+/// assert_eq!(50.9, lim.current_rate());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct AdaptiveRateLimiter<C = clock::DefaultClock>
+where
+    C: clock::Clock,
+{
+    limiter: RateLimiter<NotKeyed, InMemoryState, C>,
+    min_quota: Quota,
+    max_quota: Quota,
+    increase: f64,
+    decrease: f64,
+    /// The current rate in cells per second; taking the lock serializes quota updates.
+    rate: Mutex<f64>,
+}
+
+/// # Direct in-memory rate limiters - Adaptive quotas
+#[cfg(feature = "std")]
+impl RateLimiter<NotKeyed, InMemoryState, clock::DefaultClock> {
+    /// Constructs a new in-memory direct rate limiter with the default real-time clock, whose
+    /// quota adapts between `min_quota` and `max_quota` (see [`AdaptiveRateLimiter`]).
+    ///
+    /// # Panics
+    /// Panics if `min_quota` replenishes cells faster than `max_quota`.
+    pub fn adaptive(min_quota: Quota, max_quota: Quota) -> AdaptiveRateLimiter {
+        let clock = clock::DefaultClock::default();
+        Self::adaptive_with_clock(min_quota, max_quota, &clock)
+    }
+}
+
+impl<C> RateLimiter<NotKeyed, InMemoryState, C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new in-memory direct rate limiter with a custom clock, whose quota adapts
+    /// between `min_quota` and `max_quota` (see [`AdaptiveRateLimiter`]).
+    ///
+    /// # Panics
+    /// Panics if `min_quota` replenishes cells faster than `max_quota`.
+    pub fn adaptive_with_clock(
+        min_quota: Quota,
+        max_quota: Quota,
+        clock: &C,
+    ) -> AdaptiveRateLimiter<C> {
+        let min_rate = rate_of(min_quota);
+        let max_rate = rate_of(max_quota);
+        assert!(
+            min_rate <= max_rate,
+            "the minimum quota must not be faster than the maximum quota"
+        );
+        AdaptiveRateLimiter {
+            limiter: RateLimiter::direct_with_clock(max_quota, clock),
+            min_quota,
+            max_quota,
+            increase: (max_rate - min_rate) * DEFAULT_INCREASE_FRACTION,
+            decrease: DEFAULT_DECREASE_FACTOR,
+            rate: Mutex::new(max_rate),
+        }
+    }
+}
+
+/// Returns the rate of a quota, in cells per second.
+fn rate_of(quota: Quota) -> f64 {
+    1e9 / quota.interval().as_f64()
+}
+
+impl<C> AdaptiveRateLimiter<C>
+where
+    C: clock::Clock,
+{
+    /// Sets the additive increase, i.e. the number of cells per second that every reported
+    /// success adds to the rate.
+    ///
+    /// # Panics
+    /// Panics if `cells_per_second` is negative or not finite.
+    pub fn with_additive_increase(self, cells_per_second: f64) -> Self {
+        assert!(
+            cells_per_second.is_finite() && cells_per_second >= 0.0,
+            "the additive increase must be a non-negative number"
+        );
+        AdaptiveRateLimiter {
+            increase: cells_per_second,
+            ..self
+        }
+    }
+
+    /// Sets the multiplicative decrease, i.e. the factor that every reported overload
+    /// multiplies the rate by.
+    ///
+    /// # Panics
+    /// Panics if `factor` isn't in the range `0.0..=1.0`.
+    pub fn with_multiplicative_decrease(self, factor: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&factor),
+            "the multiplicative decrease must be between 0 and 1"
+        );
+        AdaptiveRateLimiter {
+            decrease: factor,
+            ..self
+        }
+    }
+
+    /// Allow a single cell through the rate limiter, under its current quota.
+    ///
+    /// If the rate limit is reached, `check` returns information about the earliest time that a
+    /// cell might be allowed through again, under the current quota.
+    pub fn check(&self) -> Result<(), NotUntil<'_, C::Instant>> {
+        self.limiter.check()
+    }
+
+    /// Allow *only all* `n` cells through the rate limiter, under its current quota.
+    ///
+    /// See [`RateLimiter::check_n`] for the possible negative outcomes.
+    pub fn check_n(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<(), NegativeMultiDecision<NotUntil<'_, C::Instant>>> {
+        self.limiter.check_n(n)
+    }
+
+    /// Reports that an operation the rate limiter let through succeeded, which increases the
+    /// rate by the additive increase (up to the maximum quota's rate).
+    pub fn report_success(&self) {
+        self.adjust(|rate| rate + self.increase);
+    }
+
+    /// Reports that the downstream resource was overloaded, which multiplies the rate by the
+    /// multiplicative decrease (down to the minimum quota's rate).
+    pub fn report_overload(&self) {
+        self.adjust(|rate| rate * self.decrease);
+    }
+
+    /// Returns the rate that the rate limiter currently allows cells through at, in cells per
+    /// second.
+    pub fn current_rate(&self) -> f64 {
+        *self.rate.lock()
+    }
+
+    /// Returns the quota that the rate limiter currently enforces.
+    pub fn current_quota(&self) -> Quota {
+        self.quota_at(*self.rate.lock())
+    }
+
+    /// Returns the quota in effect at the given rate, in cells per second.
+    fn quota_at(&self, rate: f64) -> Quota {
+        let min_rate = rate_of(self.min_quota);
+        let max_rate = rate_of(self.max_quota);
+        if rate <= min_rate {
+            return self.min_quota;
+        }
+        if rate >= max_rate {
+            return self.max_quota;
+        }
+        let progress = (rate - min_rate) / (max_rate - min_rate);
+        let min_burst = f64::from(self.min_quota.burst_size().get());
+        let max_burst = f64::from(self.max_quota.burst_size().get());
+        let burst = min_burst + (max_burst - min_burst) * progress;
+        let max_burst = NonZeroU32::new((burst + 0.5) as u32).unwrap_or(nonzero!(1u32));
+        Quota::from_interval(max_burst, Interval::from_f64(1e9 / rate))
+    }
+
+    /// Moves the rate to the one that `next` computes from the current rate, kept between the
+    /// minimum and the maximum quota's rates, and applies its quota.
+    fn adjust(&self, next: impl FnOnce(f64) -> f64) {
+        let mut rate = self.rate.lock();
+        let adjusted = next(*rate).clamp(rate_of(self.min_quota), rate_of(self.max_quota));
+        if adjusted != *rate {
+            *rate = adjusted;
+            self.limiter.set_quota(self.quota_at(adjusted));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::num::NonZeroU32;
+
+use super::{InsufficientCapacity, RateLimiter};
+use crate::{
+    clock,
+    middleware::RateLimitingMiddleware,
+    state::{DirectStateStore, NotKeyed},
+    Jitter, NegativeMultiDecision,
+};
+
+/// # Direct rate limiters - blocking
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::BlockingClock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Blocks the calling thread until the rate limiter allows a cell through.
+    ///
+    /// While the rate limiter doesn't allow the cell through, this parks the calling thread for
+    /// the wait time the rate limiter returns (using the clock's
+    /// [`sleep`][clock::BlockingClock::sleep]), and then checks again; if the thread wakes up
+    /// too early, or other threads used up the capacity in the meantime, it goes back to sleep.
+    /// Don't call this from `async` code, where [`until_ready`](#method.until_ready) doesn't
+    /// block the executor.
+    ///
+    /// If multiple threads wait on the rate limiter, it is advisable to use
+    /// [`until_ready_blocking_with_jitter`](#method.until_ready_blocking_with_jitter), to avoid
+    /// thundering herds.
+    pub fn until_ready_blocking(&self) -> MW::PositiveOutcome {
+        self.until_ready_blocking_with_jitter(self.jitter)
+    }
+
+    /// Blocks the calling thread until the rate limiter allows a cell through, with a randomized
+    /// wait period.
+    ///
+    /// This is similar to [`until_ready_blocking`](#method.until_ready_blocking), but adds the
+    /// jitter to each wait, which can help reduce the likelihood of thundering herd effects if
+    /// multiple threads wait on the same rate limiter.
+    pub fn until_ready_blocking_with_jitter(&self, jitter: Jitter) -> MW::PositiveOutcome {
+        loop {
+            match self.check() {
+                Ok(x) => {
+                    return x;
+                }
+                Err(negative) => {
+                    self.clock
+                        .sleep(jitter + negative.wait_time_from(self.clock.now()));
+                }
+            }
+        }
+    }
+
+    /// Blocks the calling thread until the rate limiter allows all `n` cells through.
+    ///
+    /// This is similar to [`until_ready_blocking`](#method.until_ready_blocking), except it waits
+    /// for an arbitrary number of `n` cells to be available.
+    ///
+    /// Returns `InsufficientCapacity` if the `n` provided exceeds the maximum capacity of the
+    /// rate limiter, without blocking.
+    pub fn until_n_ready_blocking(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<MW::PositiveOutcome, InsufficientCapacity> {
+        self.until_n_ready_blocking_with_jitter(n, self.jitter)
+    }
+
+    /// Blocks the calling thread until the rate limiter allows all `n` cells through, with a
+    /// randomized wait period.
+    ///
+    /// This is similar to
+    /// [`until_ready_blocking_with_jitter`](#method.until_ready_blocking_with_jitter), except it
+    /// waits for an arbitrary number of `n` cells to be available.
+    ///
+    /// Returns `InsufficientCapacity` if the `n` provided exceeds the maximum capacity of the
+    /// rate limiter, without blocking.
+    pub fn until_n_ready_blocking_with_jitter(
+        &self,
+        n: NonZeroU32,
+        jitter: Jitter,
+    ) -> Result<MW::PositiveOutcome, InsufficientCapacity> {
+        loop {
+            match self.check_n(n) {
+                Ok(x) => {
+                    return Ok(x);
+                }
+                Err(NegativeMultiDecision::BatchNonConforming(_, negative)) => {
+                    self.clock
+                        .sleep(jitter + negative.wait_time_from(self.clock.now()));
+                }
+                Err(NegativeMultiDecision::InsufficientCapacity {
+                    requested,
+                    max_burst,
+                }) => {
+                    return Err(InsufficientCapacity {
+                        requested,
+                        max_burst,
+                    })
+                }
+            }
+        }
+    }
+}
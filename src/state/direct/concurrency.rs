@@ -0,0 +1,225 @@
+use std::prelude::v1::*;
+
+use std::fmt;
+use std::future::Future;
+use std::num::NonZeroU32;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::task::{Context, Poll, Waker};
+
+#[cfg(feature = "std")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "std"))]
+use spin::Mutex;
+
+use crate::gcra::NotUntil;
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, Quota, RateLimiter};
+
+/// The negative outcome of checking a [`ConcurrencyRateLimiter`].
+#[derive(Debug, PartialEq)]
+pub enum ConcurrencyRejection<'a, P: clock::Reference> {
+    /// As many operations as the rate limiter allows at once are in flight. No rate-limiting
+    /// decision was made; the operation can go ahead once a [`ConcurrencyGuard`] is dropped.
+    Saturated,
+
+    /// A concurrency slot was free, but the quota did not allow a cell through.
+    RateLimited(NotUntil<'a, P>),
+}
+
+impl<'a, P: clock::Reference> fmt::Display for ConcurrencyRejection<'a, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConcurrencyRejection::Saturated => write!(f, "too many operations in flight"),
+            ConcurrencyRejection::RateLimited(negative) => fmt::Display::fmt(negative, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, P: clock::Reference> std::error::Error for ConcurrencyRejection<'a, P> {}
+
+/// A direct rate limiter that also bounds the number of operations in flight at once.
+///
+/// Every operation needs both a cell from the quota and one of the `max_concurrent` concurrency
+/// slots. A successful [`check`][ConcurrencyRateLimiter::check] returns a [`ConcurrencyGuard`]
+/// that holds the operation's slot until it is dropped, while the cell is used up for good (it
+/// replenishes at the quota's rate, as it would for a [`RateLimiter`]).
+///
+/// The concurrency slot is taken first: If none is free, the check fails without using up any of
+/// the quota's capacity, and if the quota does not allow the cell through, the slot is given back
+/// right away.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{state::{ConcurrencyRateLimiter, ConcurrencyRejection}, Quota};
+/// let lim = ConcurrencyRateLimiter::new(Quota::per_second(nonzero!(100u32)), nonzero!(1u32));
+/// let guard = lim.check().unwrap();
+/// assert_eq!(Err(ConcurrencyRejection::Saturated), lim.check().map(|_| ()));
+/// drop(guard);
+/// assert!(lim.check().is_ok());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct ConcurrencyRateLimiter<C = clock::DefaultClock>
+where
+    C: clock::Clock,
+{
+    limiter: RateLimiter<NotKeyed, InMemoryState, C>,
+    max_concurrent: NonZeroU32,
+    in_flight: AtomicU32,
+    /// The tasks waiting for a concurrency slot to be released.
+    waiters: Mutex<Vec<Waker>>,
+}
+
+#[cfg(feature = "std")]
+impl ConcurrencyRateLimiter<clock::DefaultClock> {
+    /// Constructs a new in-memory concurrency rate limiter for a quota with the default real-time
+    /// clock, which allows at most `max_concurrent` operations in flight at once.
+    pub fn new(quota: Quota, max_concurrent: NonZeroU32) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::with_clock(quota, max_concurrent, &clock)
+    }
+}
+
+impl<C> ConcurrencyRateLimiter<C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new in-memory concurrency rate limiter for a quota with a custom clock, which
+    /// allows at most `max_concurrent` operations in flight at once.
+    pub fn with_clock(quota: Quota, max_concurrent: NonZeroU32, clock: &C) -> Self {
+        ConcurrencyRateLimiter {
+            limiter: RateLimiter::direct_with_clock(quota, clock),
+            max_concurrent,
+            in_flight: AtomicU32::new(0),
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Allow a single operation through the rate limiter, if both a concurrency slot and the
+    /// quota allow it.
+    ///
+    /// On success, the returned guard holds the operation's concurrency slot until it is
+    /// dropped. Otherwise, `check` returns which of the limits the operation ran into.
+    pub fn check(&self) -> Result<ConcurrencyGuard<'_, C>, ConcurrencyRejection<'_, C::Instant>> {
+        let max = self.max_concurrent.get();
+        self.in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                if n < max {
+                    Some(n + 1)
+                } else {
+                    None
+                }
+            })
+            .map_err(|_| ConcurrencyRejection::Saturated)?;
+        // The guard gives the slot back if the quota doesn't allow the cell through:
+        let guard = ConcurrencyGuard { limiter: self };
+        self.limiter
+            .check()
+            .map_err(ConcurrencyRejection::RateLimited)?;
+        Ok(guard)
+    }
+
+    /// Returns the number of operations in flight, i.e. the number of [`ConcurrencyGuard`]s that
+    /// haven't been dropped yet.
+    pub fn in_flight(&self) -> u32 {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Returns the number of operations that the rate limiter allows in flight at once.
+    pub fn max_concurrent(&self) -> NonZeroU32 {
+        self.max_concurrent
+    }
+
+    /// Returns the number of cells that the quota would allow through right now, regardless of
+    /// the concurrency slots, without using up any of its capacity.
+    pub fn available(&self) -> u32 {
+        self.limiter.available()
+    }
+
+    fn release(&self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+        for waker in self.waiters.lock().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl<C> ConcurrencyRateLimiter<C>
+where
+    C: clock::AsyncClock,
+{
+    /// Asynchronously resolves as soon as both a concurrency slot and the quota allow an
+    /// operation through.
+    ///
+    /// The future waits for whichever limit is binding: While all concurrency slots are taken,
+    /// it waits (without using up any of the quota's capacity) until a [`ConcurrencyGuard`] is
+    /// dropped, and while the quota doesn't allow the cell through, it waits on the clock's timer
+    /// until the quota might allow it. It then checks both limits again, so it might wait for
+    /// each of them several times, if other tasks take the capacity first.
+    pub async fn until_ready(&self) -> ConcurrencyGuard<'_, C> {
+        loop {
+            match self.check() {
+                Ok(guard) => return guard,
+                Err(ConcurrencyRejection::Saturated) => SlotReleased { limiter: self }.await,
+                Err(ConcurrencyRejection::RateLimited(negative)) => {
+                    let delay = self
+                        .limiter
+                        .clock
+                        .delay(negative.wait_time_from(self.limiter.clock.now()));
+                    delay.await;
+                }
+            }
+        }
+    }
+}
+
+/// Holds a concurrency slot of a [`ConcurrencyRateLimiter`], and releases it when dropped.
+#[must_use = "dropping the guard releases its concurrency slot right away"]
+#[derive(Debug)]
+pub struct ConcurrencyGuard<'a, C>
+where
+    C: clock::Clock,
+{
+    limiter: &'a ConcurrencyRateLimiter<C>,
+}
+
+impl<'a, C> Drop for ConcurrencyGuard<'a, C>
+where
+    C: clock::Clock,
+{
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/// Resolves once a concurrency slot might be free.
+struct SlotReleased<'a, C>
+where
+    C: clock::Clock,
+{
+    limiter: &'a ConcurrencyRateLimiter<C>,
+}
+
+impl<'a, C> Future for SlotReleased<'a, C>
+where
+    C: clock::Clock,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let limiter = self.limiter;
+        // Checking under the lock means that a slot released in the meantime wakes this task:
+        let mut waiters = limiter.waiters.lock();
+        if limiter.in_flight() < limiter.max_concurrent.get() {
+            return Poll::Ready(());
+        }
+        if !waiters.iter().any(|waker| waker.will_wake(cx.waker())) {
+            waiters.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
@@ -1,36 +1,112 @@
-use std::{error::Error, fmt, num::NonZeroU32};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::{fmt, num::NonZeroU32, time::Duration};
 
 use super::RateLimiter;
 use crate::{
-    clock,
+    clock::{self, Reference},
+    middleware::RateLimitingMiddleware,
     state::{DirectStateStore, NotKeyed},
     Jitter, NegativeMultiDecision,
 };
-use futures_timer::Delay;
 
 /// An error that occurs when the number of cells required in `check_n`
 /// exceeds the maximum capacity of the limiter.
-#[derive(Debug, Clone)]
-pub struct InsufficientCapacity(pub u32);
+///
+/// Unlike a [`NotUntil`][crate::NotUntil], this error means that the cells can never be allowed
+/// through, so waiting and retrying is pointless.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsufficientCapacity {
+    /// The number of cells requested.
+    pub requested: u32,
+
+    /// The maximum number of cells that the rate limiter could ever allow through at once (its
+    /// quota's burst size).
+    pub max_burst: u32,
+}
 
 impl fmt::Display for InsufficientCapacity {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "required number of cell {} exceeds bucket's capacity",
-            self.0
+            "required number of cells {} exceeds bucket's capacity of {}",
+            self.requested, self.max_burst
         )
     }
 }
 
-impl Error for InsufficientCapacity {}
+#[cfg(feature = "std")]
+impl std::error::Error for InsufficientCapacity {}
+
+/// An error that occurs when a rate limiter would not allow a cell through before a timeout
+/// expires.
+///
+/// It holds the time that the rate limiter still required the cell to wait when the timeout was
+/// given up on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedOut(pub Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "rate limiter requires waiting {:?}, which exceeds the timeout",
+            self.0
+        )
+    }
+}
 
 #[cfg(feature = "std")]
+impl std::error::Error for TimedOut {}
+
+/// Returns how long to wait before checking a rate limiter again, or an error if the cell
+/// requires waiting longer than the `remaining` time allows.
+///
+/// Jittered delays are cut short at the timeout, so that the cell gets a last chance to be
+/// checked before the timeout expires.
+pub(crate) fn delay_within(
+    wait: Duration,
+    jitter: Jitter,
+    remaining: Duration,
+) -> Result<Duration, TimedOut> {
+    if wait > remaining {
+        return Err(TimedOut(wait));
+    }
+    Ok(std::cmp::min(jitter + wait, remaining))
+}
+
+/// A cell that [`until_ready_fair`](RateLimiter::until_ready_fair) scheduled, which gets
+/// refunded if the waiting task gives up before its slot.
+struct FairTicket<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    limiter: &'a RateLimiter<NotKeyed, S, C, MW>,
+    waiting: bool,
+}
+
+impl<S, C, MW> Drop for FairTicket<'_, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    fn drop(&mut self) {
+        if self.waiting {
+            self.limiter.refund(NonZeroU32::MIN);
+        }
+    }
+}
+
 /// # Direct rate limiters - `async`/`await`
-impl<S, C> RateLimiter<NotKeyed, S, C>
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
 where
     S: DirectStateStore,
-    C: clock::ReasonablyRealtime,
+    C: clock::AsyncClock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
 {
     /// Asynchronously resolves as soon as the rate limiter allows it.
     ///
@@ -41,8 +117,10 @@ where
     ///
     /// If multiple futures are dispatched against the rate limiter, it is advisable to use
     /// [`until_ready_with_jitter`](#method.until_ready_with_jitter), to avoid thundering herds.
-    pub async fn until_ready(&self) {
-        self.until_ready_with_jitter(Jitter::NONE).await;
+    ///
+    /// The returned future is an [`UntilReady`], which can be named, e.g. to store it in a struct.
+    pub fn until_ready(&self) -> UntilReady<'_, S, C, MW> {
+        self.until_ready_with_jitter(self.jitter)
     }
 
     /// Asynchronously resolves as soon as the rate limiter allows it, with a randomized wait
@@ -56,22 +134,120 @@ where
     /// This method allows for a randomized additional delay between polls of the rate limiter,
     /// which can help reduce the likelihood of thundering herd effects if multiple tasks try to
     /// wait on the same rate limiter.
-    pub async fn until_ready_with_jitter(&self, jitter: Jitter) {
-        while let Err(negative) = self.check() {
-            let delay = Delay::new(jitter + negative.wait_time_from(self.clock.now()));
-            delay.await;
+    ///
+    /// The returned future is an [`UntilReady`], which can be named, e.g. to store it in a struct.
+    pub fn until_ready_with_jitter(&self, jitter: Jitter) -> UntilReady<'_, S, C, MW> {
+        UntilReady {
+            limiter: self,
+            jitter,
+            delay: None,
         }
     }
 
+    /// Asynchronously resolves as soon as the rate limiter allows it, unless that takes longer
+    /// than `timeout`.
+    ///
+    /// If the rate limiter requires waiting longer than the time that remains until the timeout
+    /// expires, the returned future resolves to a [`TimedOut`] error immediately, instead of
+    /// waiting in vain. Giving up does not use up any of the rate limiter's capacity: A cell is
+    /// only counted once the future resolves successfully.
+    pub async fn until_ready_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<MW::PositiveOutcome, TimedOut> {
+        self.until_ready_with_jitter_and_timeout(self.jitter, timeout)
+            .await
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows it, with a randomized wait
+    /// period, unless that takes longer than `timeout`.
+    ///
+    /// This is similar to [`until_ready_with_timeout`](#method.until_ready_with_timeout), and
+    /// adds jitter to the delays like
+    /// [`until_ready_with_jitter`](#method.until_ready_with_jitter) does. Delays that would
+    /// expire after the timeout are shortened to end at the timeout instead.
+    pub async fn until_ready_with_jitter_and_timeout(
+        &self,
+        jitter: Jitter,
+        timeout: Duration,
+    ) -> Result<MW::PositiveOutcome, TimedOut> {
+        let started = self.clock.now();
+        loop {
+            match self.check() {
+                Ok(x) => {
+                    return Ok(x);
+                }
+                Err(negative) => {
+                    let now = self.clock.now();
+                    let spent: Duration = now.duration_since(started).into();
+                    let wait = delay_within(
+                        negative.wait_time_from(now),
+                        jitter,
+                        timeout.saturating_sub(spent),
+                    )?;
+                    self.clock.delay(wait).await;
+                }
+            }
+        }
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows a cell through, handing out
+    /// cells to the tasks waiting on the rate limiter in the order that they started waiting.
+    ///
+    /// With [`until_ready`](#method.until_ready), every waiting task checks the rate limiter
+    /// again when its delay expires, and whichever task checks first gets the next cell, so a
+    /// task can lose out to later ones many times over. This method instead
+    /// [schedules](#method.schedule) the cell for the next free slot as soon as the task starts
+    /// waiting, and then waits until that slot: Tasks get their cells in the order they called
+    /// this method, and each task's wait is over after a single delay.
+    ///
+    /// Fairness has its costs: A waiting task's cell is counted against the quota from the time
+    /// it starts waiting, so other methods (like [`check`](#method.check)) don't allow cells
+    /// through while tasks are waiting on this method. If the returned future is dropped before
+    /// its slot, the cell is refunded; a task that starts waiting after that may get the same
+    /// slot as the last one already waiting. The rate limiter's middleware isn't consulted.
+    pub async fn until_ready_fair(&self) {
+        let wait = self.schedule();
+        if wait.is_zero() {
+            return;
+        }
+        let mut ticket = FairTicket {
+            limiter: self,
+            waiting: true,
+        };
+        self.clock.delay(wait).await;
+        ticket.waiting = false;
+    }
+
     /// Asynchronously resolves as soon as the rate limiter allows it.
     ///
     /// This is similar to `until_ready` except it waits for an abitrary number
     /// of `n` cells to be available.
     ///
-    /// Returns `InsufficientCapacity` if the `n` provided exceeds the maximum
-    /// capacity of the rate limiter.
-    pub async fn until_n_ready(&self, n: NonZeroU32) -> Result<(), InsufficientCapacity> {
-        self.until_n_ready_with_jitter(n, Jitter::NONE).await
+    /// Returns `InsufficientCapacity` right away if the `n` provided exceeds the maximum
+    /// capacity of the rate limiter, since waiting would never make that many cells available.
+    pub async fn until_n_ready(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<MW::PositiveOutcome, InsufficientCapacity> {
+        self.until_n_ready_with_jitter(n, self.jitter).await
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows `cost` cells through, where
+    /// `cost` is computed at runtime and may be zero (see
+    /// [`check_cost`][RateLimiter::check_cost]).
+    ///
+    /// A zero cost resolves right away without consulting the rate limiter or its middleware,
+    /// returning `Ok(None)`. Any other cost waits like [`until_n_ready`](#method.until_n_ready)
+    /// does, returning the middleware's positive outcome as `Ok(Some(_))`.
+    pub async fn until_cost_ready(
+        &self,
+        cost: u32,
+    ) -> Result<Option<MW::PositiveOutcome>, InsufficientCapacity> {
+        match NonZeroU32::new(cost) {
+            Some(n) => self.until_n_ready(n).await.map(Some),
+            None => Ok(None),
+        }
     }
 
     /// Asynchronously resolves as soon as the rate limiter allows it, with a
@@ -80,25 +256,93 @@ where
     /// This is similar to `until_ready_with_jitter` except it waits for an
     /// abitrary number of `n` cells to be available.
     ///
-    /// Returns `InsufficientCapacity` if the `n` provided exceeds the maximum
-    /// capacity of the rate limiter.
+    /// Returns `InsufficientCapacity` right away if the `n` provided exceeds the maximum
+    /// capacity of the rate limiter, since waiting would never make that many cells available.
     pub async fn until_n_ready_with_jitter(
         &self,
         n: NonZeroU32,
         jitter: Jitter,
-    ) -> Result<(), InsufficientCapacity> {
-        while let Err(err) = self.check_n(n) {
-            match err {
-                NegativeMultiDecision::BatchNonConforming(_, negative) => {
-                    let delay = Delay::new(jitter + negative.wait_time_from(self.clock.now()));
+    ) -> Result<MW::PositiveOutcome, InsufficientCapacity> {
+        loop {
+            match self.check_n(n) {
+                Ok(x) => {
+                    return Ok(x);
+                }
+                Err(NegativeMultiDecision::BatchNonConforming(_, negative)) => {
+                    let delay = self
+                        .clock
+                        .delay(jitter + negative.wait_time_from(self.clock.now()));
                     delay.await;
                 }
-                NegativeMultiDecision::InsufficientCapacity(cap) => {
-                    return Err(InsufficientCapacity(cap))
+                Err(NegativeMultiDecision::InsufficientCapacity {
+                    requested,
+                    max_burst,
+                }) => {
+                    return Err(InsufficientCapacity {
+                        requested,
+                        max_burst,
+                    })
                 }
             }
         }
+    }
+}
+
+/// The future that [`until_ready`](RateLimiter::until_ready) and
+/// [`until_ready_with_jitter`](RateLimiter::until_ready_with_jitter) return, which resolves to the
+/// middleware's positive outcome as soon as the rate limiter allows a cell through.
+///
+/// Unlike the futures of `async` methods, this type can be named, e.g. to keep futures waiting
+/// on the same rate limiter in a `Vec` or a struct field.
+#[must_use = "futures do nothing unless polled"]
+pub struct UntilReady<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::AsyncClock,
+{
+    limiter: &'a RateLimiter<NotKeyed, S, C, MW>,
+    jitter: Jitter,
+    /// The delay until the rate limiter might allow the cell through, if the future is waiting.
+    delay: Option<C::Delay>,
+}
+
+impl<'a, S, C, MW> fmt::Debug for UntilReady<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::AsyncClock,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UntilReady")
+            .field("jitter", &self.jitter)
+            .field("waiting", &self.delay.is_some())
+            .finish()
+    }
+}
+
+impl<'a, S, C, MW> Future for UntilReady<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::AsyncClock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    type Output = MW::PositiveOutcome;
 
-        Ok(())
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        loop {
+            if let Some(delay) = self.delay.as_mut() {
+                match Pin::new(delay).poll(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(()) => self.delay = None,
+                }
+            }
+            let limiter = self.limiter;
+            match limiter.check() {
+                Ok(x) => return Poll::Ready(x),
+                Err(negative) => {
+                    let wait = self.jitter + negative.wait_time_from(limiter.clock.now());
+                    self.delay = Some(limiter.clock.delay(wait));
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,136 @@
+use std::prelude::v1::*;
+
+use std::time::Duration;
+
+use crate::gcra::NotUntil;
+use crate::middleware::StateInformationMiddleware;
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, Quota, RateLimiter};
+
+/// The positive outcome of checking a cell against a [`GraceRateLimiter`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Admission {
+    /// The cell conforms to the quota.
+    Allowed,
+
+    /// The cell is beyond the quota's burst size, and was only allowed through as one of the
+    /// grace cells. The caller should delay the work it stands for by the `penalty`.
+    Throttled {
+        /// How long the caller should delay the cell's work.
+        penalty: Duration,
+    },
+}
+
+/// A direct rate limiter that allows a few cells beyond its quota's burst size through, but
+/// charges them an escalating penalty.
+///
+/// Once the quota's burst capacity is used up, the next `grace` cells are still allowed through,
+/// as [`Admission::Throttled`] with a penalty that the caller is expected to delay them by. The
+/// penalty doubles with every cell that the rate limiter is further over the limit: The first
+/// grace cell's penalty is the quota's [replenishment interval][Quota::replenish_interval], the
+/// second one's is twice that, and so on. Once the grace cells are used up too, cells are
+/// rejected with a [`NotUntil`] like a regular rate limiter's.
+///
+/// Grace cells use up capacity like any other cell does, so they replenish at the quota's rate:
+/// In the long run, the rate limiter allows cells through at no more than the quota's rate, and
+/// in any period of time it allows at most `grace` cells more through than a rate limiter for the
+/// quota would. The penalties are only advice to the caller, the rate limiter doesn't enforce
+/// them.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use std::time::Duration;
+/// # use governor::{state::{Admission, GraceRateLimiter}, Quota};
+/// let lim = GraceRateLimiter::new(Quota::per_minute(nonzero!(2u32)), 2).unwrap();
+/// assert_eq!(Ok(Admission::Allowed), lim.check());
+/// assert_eq!(Ok(Admission::Allowed), lim.check());
+/// assert_eq!(
+///     Ok(Admission::Throttled { penalty: Duration::from_secs(30) }),
+///     lim.check()
+/// );
+/// assert_eq!(
+///     Ok(Admission::Throttled { penalty: Duration::from_secs(60) }),
+///     lim.check()
+/// );
+/// assert!(lim.check().is_err());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct GraceRateLimiter<C = clock::DefaultClock>
+where
+    C: clock::Clock,
+{
+    limiter: RateLimiter<NotKeyed, InMemoryState, C, StateInformationMiddleware>,
+    grace: u32,
+    base_penalty: Duration,
+}
+
+#[cfg(feature = "std")]
+impl GraceRateLimiter<clock::DefaultClock> {
+    /// Constructs a new in-memory grace rate limiter for a quota with the default real-time
+    /// clock, which allows `grace` cells beyond the quota's burst size through (see
+    /// [`GraceRateLimiter`]).
+    ///
+    /// Returns `None` if the quota's burst size and the grace cells together exceed `u32::MAX`
+    /// cells.
+    pub fn new(quota: Quota, grace: u32) -> Option<Self> {
+        let clock = clock::DefaultClock::default();
+        Self::with_clock(quota, grace, &clock)
+    }
+}
+
+impl<C> GraceRateLimiter<C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new in-memory grace rate limiter for a quota with a custom clock, which
+    /// allows `grace` cells beyond the quota's burst size through (see [`GraceRateLimiter`]).
+    ///
+    /// Returns `None` if the quota's burst size and the grace cells together exceed `u32::MAX`
+    /// cells.
+    pub fn with_clock(quota: Quota, grace: u32, clock: &C) -> Option<Self> {
+        let max_burst = quota.burst_size().checked_add(grace)?;
+        Some(GraceRateLimiter {
+            limiter: RateLimiter::direct_with_clock(quota.allow_burst(max_burst), clock)
+                .with_middleware(StateInformationMiddleware),
+            grace,
+            base_penalty: quota.replenish_interval(),
+        })
+    }
+
+    /// Allow a single cell through the rate limiter, if the quota or the grace cells allow it.
+    ///
+    /// If the cell is within the quota's burst size, `check` returns [`Admission::Allowed`]; if
+    /// it is one of the grace cells, it returns [`Admission::Throttled`] with the penalty that the
+    /// caller should delay the cell by. If neither allows the cell through, `check` returns
+    /// information about the earliest time that a cell might be allowed through again.
+    pub fn check(&self) -> Result<Admission, NotUntil<'_, C::Instant>> {
+        let state = self.limiter.check()?;
+        // The number of grace cells that are used up, including this one:
+        let over = self.grace.saturating_sub(state.remaining_burst_capacity());
+        if over == 0 {
+            return Ok(Admission::Allowed);
+        }
+        let factor = 1u32.checked_shl(over - 1).unwrap_or(u32::MAX);
+        Ok(Admission::Throttled {
+            penalty: self
+                .base_penalty
+                .checked_mul(factor)
+                .unwrap_or(Duration::MAX),
+        })
+    }
+
+    /// Returns the number of cells beyond the quota's burst size that the rate limiter allows
+    /// through.
+    pub fn grace(&self) -> u32 {
+        self.grace
+    }
+
+    /// Returns the number of cells (including the grace cells) that the rate limiter would allow
+    /// through right now, without using up any of its capacity.
+    pub fn available(&self) -> u32 {
+        self.limiter.available()
+    }
+}
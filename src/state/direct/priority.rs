@@ -0,0 +1,125 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, Quota, RateLimiter};
+
+/// A direct rate limiter whose cells have priority levels, and that holds back some of its burst
+/// capacity for higher levels.
+///
+/// All levels share the quota, so the total rate at which cells are allowed through (at any
+/// levels) never exceeds it. Priority levels are numbered from `0` (the lowest) up to
+/// [`levels() - 1`][PriorityRateLimiter::levels] (the highest).
+///
+/// # Reservations
+///
+/// Each level except the highest one has a reserve: The number of cells of burst capacity that
+/// must still be available after one of its cells is allowed through. A cell at a level with a
+/// reserve of `r` cells is therefore only allowed through while at least `r + 1` cells are
+/// available, and the last `r` cells can only be used by higher levels. The highest level can use
+/// the entire burst capacity.
+///
+/// Reserves are given from the lowest level up, and can't grow as levels go up. When lower
+/// levels saturate the rate limiter, they use up the capacity down to their reserve; as it
+/// replenishes, the cells that higher levels check get the capacity first, and lower levels only
+/// get the capacity that higher levels leave over.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{state::PriorityRateLimiter, Quota};
+/// // Level 0 leaves 3 of the 10 cells for level 1:
+/// let lim = PriorityRateLimiter::new(Quota::per_minute(nonzero!(10u32)), [3]);
+/// for _ in 0..7 {
+///     assert_eq!(Ok(()), lim.check_with_priority(0));
+/// }
+/// assert_ne!(Ok(()), lim.check_with_priority(0));
+/// assert_eq!(Ok(()), lim.check_with_priority(1));
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct PriorityRateLimiter<C = clock::DefaultClock>
+where
+    C: clock::Clock,
+{
+    limiter: RateLimiter<NotKeyed, InMemoryState, C>,
+    /// The reserve of each level below the highest one, from the lowest level up.
+    reserves: Box<[u32]>,
+}
+
+#[cfg(feature = "std")]
+impl PriorityRateLimiter<clock::DefaultClock> {
+    /// Constructs a new in-memory priority rate limiter for a quota with the default real-time
+    /// clock, where the levels below the highest one hold back the given `reserves` (see
+    /// [`PriorityRateLimiter`]).
+    ///
+    /// # Panics
+    /// Panics if the reserves grow from one level to the next, or if a reserve is not smaller
+    /// than the quota's burst size.
+    pub fn new(quota: Quota, reserves: impl IntoIterator<Item = u32>) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::with_clock(quota, reserves, &clock)
+    }
+}
+
+impl<C> PriorityRateLimiter<C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new in-memory priority rate limiter for a quota with a custom clock, where the
+    /// levels below the highest one hold back the given `reserves` (see
+    /// [`PriorityRateLimiter`]).
+    ///
+    /// A priority rate limiter without any reserves has a single level.
+    ///
+    /// # Panics
+    /// Panics if the reserves grow from one level to the next, or if a reserve is not smaller
+    /// than the quota's burst size.
+    pub fn with_clock(quota: Quota, reserves: impl IntoIterator<Item = u32>, clock: &C) -> Self {
+        let reserves: Box<[u32]> = reserves.into_iter().collect();
+        assert!(
+            reserves.windows(2).all(|pair| pair[0] >= pair[1]),
+            "reserves must not grow from one level to the next"
+        );
+        assert!(
+            reserves
+                .iter()
+                .all(|reserve| *reserve < quota.burst_size().get()),
+            "reserves must be smaller than the quota's burst size"
+        );
+        PriorityRateLimiter {
+            limiter: RateLimiter::direct_with_clock(quota, clock),
+            reserves,
+        }
+    }
+
+    /// Allow a single cell at the given priority level through the rate limiter, if the
+    /// capacity that's available beyond the level's reserve allows it.
+    ///
+    /// Levels above the highest one are treated as the highest level.
+    ///
+    /// If the cell is not allowed through, `check_with_priority` returns information about the
+    /// earliest time that a cell at the level might be allowed through again.
+    pub fn check_with_priority(&self, level: usize) -> Result<(), NotUntil<'_, C::Instant>> {
+        let reserve = self.reserves.get(level).copied().unwrap_or(0);
+        self.limiter.gcra.test_reserving_and_update(
+            self.limiter.start,
+            &NotKeyed::NonKey,
+            u64::from(reserve),
+            &self.limiter.state,
+            self.limiter.clock.now(),
+        )
+    }
+
+    /// Returns the number of priority levels.
+    pub fn levels(&self) -> usize {
+        self.reserves.len() + 1
+    }
+
+    /// Returns the number of cells that the rate limiter would allow through right now at any
+    /// level, without using up any of its capacity.
+    pub fn available(&self) -> u32 {
+        self.limiter.available()
+    }
+}
@@ -0,0 +1,187 @@
+use std::prelude::v1::*;
+
+use crate::clock::Reference;
+use crate::gcra::NotUntil;
+use crate::nanos::{Interval, Nanos};
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, NegativeMultiDecision, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[cfg(feature = "std")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "std"))]
+use spin::Mutex;
+
+/// The number of steps in which a ramp moves from its start quota to its end quota.
+const RAMP_STEPS: u64 = 1000;
+
+/// A direct rate limiter whose quota moves gradually from a start quota to an end quota (e.g. to
+/// warm up a backend after a deploy, or to let a new client's traffic in slowly).
+///
+/// The quota is interpolated linearly over the ramp's duration, which starts when the rate
+/// limiter is constructed: Both the rate (cells per second) and the burst size move in a straight
+/// line from the start quota's to the end quota's, with the burst size rounded to the nearest
+/// whole cell (at least one). The quota is updated in 1000 equal steps, as cells are checked.
+/// Once the ramp is complete, the rate limiter stays at the end quota. Every step after the first
+/// uses the end quota's other settings: whether it [starts empty](Quota::start_empty), is
+/// [strict](Quota::strict_interval), and its [tolerance](Quota::with_tolerance).
+///
+/// Changing the quota keeps the cells that were already allowed through, as with
+/// [`RateLimiter::set_quota`].
+///
+/// Construct one with [`RateLimiter::ramp`].
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use std::time::Duration;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::ramp(
+///     Quota::per_second(nonzero!(10u32)),
+///     Quota::per_second(nonzero!(100u32)),
+///     Duration::from_secs(60),
+/// );
+/// assert_eq!(Ok(()), lim.check());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct RampingRateLimiter<C = clock::DefaultClock>
+where
+    C: clock::Clock,
+{
+    limiter: RateLimiter<NotKeyed, InMemoryState, C>,
+    start_quota: Quota,
+    end_quota: Quota,
+    over: Nanos,
+    /// The step whose quota the rate limiter uses, readable without taking the lock.
+    applied_step: AtomicU64,
+    /// Serializes quota updates, so that a later step's quota is never replaced by an earlier
+    /// one's.
+    updating: Mutex<u64>,
+}
+
+/// # Direct in-memory rate limiters - Ramping quotas
+#[cfg(feature = "std")]
+impl RateLimiter<NotKeyed, InMemoryState, clock::DefaultClock> {
+    /// Constructs a new in-memory direct rate limiter with the default real-time clock, whose
+    /// quota moves linearly from `start_quota` to `end_quota` over the duration `over` (see
+    /// [`RampingRateLimiter`]).
+    pub fn ramp(start_quota: Quota, end_quota: Quota, over: Duration) -> RampingRateLimiter {
+        let clock = clock::DefaultClock::default();
+        Self::ramp_with_clock(start_quota, end_quota, over, &clock)
+    }
+}
+
+impl<C> RateLimiter<NotKeyed, InMemoryState, C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new in-memory direct rate limiter with a custom clock, whose quota moves
+    /// linearly from `start_quota` to `end_quota` over the duration `over` (see
+    /// [`RampingRateLimiter`]).
+    pub fn ramp_with_clock(
+        start_quota: Quota,
+        end_quota: Quota,
+        over: Duration,
+        clock: &C,
+    ) -> RampingRateLimiter<C> {
+        RampingRateLimiter {
+            limiter: RateLimiter::direct_with_clock(start_quota, clock),
+            start_quota,
+            end_quota,
+            over: over.into(),
+            applied_step: AtomicU64::new(0),
+            updating: Mutex::new(0),
+        }
+    }
+}
+
+impl<C> RampingRateLimiter<C>
+where
+    C: clock::Clock,
+{
+    /// Allow a single cell through the rate limiter, under the quota in effect at the current
+    /// point of the ramp.
+    ///
+    /// If the rate limit is reached, `check` returns information about the earliest time that a
+    /// cell might be allowed through again, under the current quota.
+    pub fn check(&self) -> Result<(), NotUntil<'_, C::Instant>> {
+        let now = self.limiter.clock.now();
+        self.update_quota(now);
+        self.limiter.check_at(now)
+    }
+
+    /// Allow *only all* `n` cells through the rate limiter, under the quota in effect at the
+    /// current point of the ramp.
+    ///
+    /// See [`RateLimiter::check_n`] for the possible negative outcomes.
+    pub fn check_n(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<(), NegativeMultiDecision<NotUntil<'_, C::Instant>>> {
+        let now = self.limiter.clock.now();
+        self.update_quota(now);
+        self.limiter.check_n_at(n, now)
+    }
+
+    /// Returns the quota that the rate limiter used for its most recent decision.
+    pub fn current_quota(&self) -> Quota {
+        self.quota_at(self.applied_step.load(Ordering::Acquire))
+    }
+
+    /// Returns the step of the ramp that `now` falls into.
+    fn step_at(&self, now: C::Instant) -> u64 {
+        let elapsed = now.duration_since(self.limiter.start);
+        if elapsed >= self.over {
+            return RAMP_STEPS;
+        }
+        let step = elapsed.as_u128() * u128::from(RAMP_STEPS) / self.over.as_u128();
+        step as u64
+    }
+
+    /// Returns the quota in effect during the given step of the ramp.
+    fn quota_at(&self, step: u64) -> Quota {
+        if step == 0 {
+            return self.start_quota;
+        }
+        if step >= RAMP_STEPS {
+            return self.end_quota;
+        }
+        let progress = step as f64 / RAMP_STEPS as f64;
+        let interpolate = |from: f64, to: f64| from + (to - from) * progress;
+        let rate = interpolate(
+            1.0 / self.start_quota.interval().as_f64(),
+            1.0 / self.end_quota.interval().as_f64(),
+        );
+        let burst = interpolate(
+            f64::from(self.start_quota.burst_size().get()),
+            f64::from(self.end_quota.burst_size().get()),
+        );
+        let max_burst = NonZeroU32::new((burst + 0.5) as u32).unwrap_or(nonzero!(1u32));
+        Quota {
+            start_empty: self.end_quota.start_empty,
+            tolerance: self.end_quota.tolerance,
+            strict: self.end_quota.strict,
+            ..Quota::from_interval(max_burst, Interval::from_f64(1.0 / rate))
+        }
+    }
+
+    /// Moves the rate limiter's quota to the step of the ramp that `now` falls into, unless it
+    /// is already there (or past it, if another thread read the clock later).
+    fn update_quota(&self, now: C::Instant) {
+        let step = self.step_at(now);
+        if step <= self.applied_step.load(Ordering::Acquire) {
+            return;
+        }
+        let mut applied = self.updating.lock();
+        if step > *applied {
+            self.limiter.set_quota(self.quota_at(step));
+            *applied = step;
+            self.applied_step.store(step, Ordering::Release);
+        }
+    }
+}
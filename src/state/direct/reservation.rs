@@ -0,0 +1,123 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::middleware::RateLimitingMiddleware;
+use crate::state::{DirectStateStore, NotKeyed};
+use crate::{clock, RateLimiter};
+use nonzero_ext::nonzero;
+use std::fmt;
+
+/// A cell that was reserved on a direct rate limiter with [`RateLimiter::reserve`], to be used
+/// later.
+///
+/// The cell is counted against the rate limiter's quota as soon as it is reserved, exactly as if
+/// it had been allowed through with [`check`][RateLimiter::check], so outstanding reservations
+/// and cells allowed through together never exceed the quota. The reservation then either gets
+/// [redeemed](Reservation::redeem) when the cell is used, which keeps it counted, or
+/// [cancelled](Reservation::cancel), which refunds it to the rate limiter. Dropping a
+/// reservation without redeeming it cancels it.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::direct(Quota::per_minute(nonzero!(2u32)));
+/// let first = lim.reserve().unwrap();
+/// let second = lim.reserve().unwrap();
+/// assert!(lim.check().is_err());
+///
+/// first.redeem();
+/// second.cancel();
+/// assert_eq!(Ok(()), lim.check());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[must_use = "dropping a reservation without redeeming it refunds the reserved cell"]
+pub struct Reservation<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    limiter: &'a RateLimiter<NotKeyed, S, C, MW>,
+    outcome: Option<MW::PositiveOutcome>,
+    reserved_at: C::Instant,
+}
+
+/// # Direct rate limiters - Reserving cells
+impl<S, C, MW> RateLimiter<NotKeyed, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Reserves a single cell, to be used later.
+    ///
+    /// If the rate limiter allows a cell through right now, it is counted against the quota and
+    /// held by the returned [`Reservation`] until it is redeemed or cancelled. Otherwise,
+    /// `reserve` returns information about the earliest time that a cell might be reserved,
+    /// like [`check`][RateLimiter::check] does.
+    pub fn reserve(&self) -> Result<Reservation<'_, S, C, MW>, NotUntil<'_, C::Instant>> {
+        let now = self.clock.now();
+        let outcome = self.check_at(now)?;
+        Ok(Reservation {
+            limiter: self,
+            outcome: Some(outcome),
+            reserved_at: now,
+        })
+    }
+}
+
+impl<'a, S, C, MW> Reservation<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Returns the time from which on the reserved cell can be used: The time at which it was
+    /// reserved.
+    pub fn valid_from(&self) -> C::Instant {
+        self.reserved_at
+    }
+
+    /// Uses the reserved cell, keeping it counted against the rate limiter's quota, and returns
+    /// the middleware's positive outcome of the decision that reserved it.
+    pub fn redeem(mut self) -> MW::PositiveOutcome {
+        self.outcome
+            .take()
+            .expect("reservations are only redeemed once")
+    }
+
+    /// Gives up on the reserved cell, refunding it to the rate limiter (see
+    /// [`refund`][RateLimiter::refund]).
+    pub fn cancel(self) {
+        // Dropping the reservation refunds it.
+    }
+}
+
+impl<'a, S, C, MW> Drop for Reservation<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    fn drop(&mut self) {
+        if self.outcome.is_some() {
+            self.limiter.refund(nonzero!(1u32));
+        }
+    }
+}
+
+impl<'a, S, C, MW> fmt::Debug for Reservation<'a, S, C, MW>
+where
+    S: DirectStateStore,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reservation")
+            .field("reserved_at", &self.reserved_at)
+            .field("redeemed", &self.outcome.is_none())
+            .finish()
+    }
+}
@@ -0,0 +1,142 @@
+use std::prelude::v1::*;
+
+use crate::nanos::Nanos;
+use crate::state::{InMemoryState, NotKeyed, StateStore};
+use crate::{clock, Quota, RateLimiter};
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_HOME_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The shard that the current thread tries first; threads get assigned shards round-robin.
+    static HOME_SHARD: usize = NEXT_HOME_SHARD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// An [`InMemoryState`] on its own cache line, so that threads updating different shards don't
+/// contend for the same cache line.
+#[derive(Debug, Default)]
+#[repr(align(128))]
+struct Shard(InMemoryState);
+
+/// A direct state store that splits its rate-limiting state between several shards, to reduce
+/// contention when many threads check cells on the same rate limiter.
+///
+/// Each of `n` shards enforces `1/n`th of the quota: The replenishment interval is multiplied by
+/// `n`, and the burst size is divided by `n` (rounding up). Threads first check cells against
+/// their "own" shard, and only try the other shards if that one is exhausted, so they rarely
+/// update the same atomic value.
+///
+/// This trades precision for scalability:
+/// * Since burst sizes are rounded up, the rate limiter may allow up to `n - 1` cells more than
+///   the quota's burst size through at once.
+/// * Batches of cells checked with [`check_n`][RateLimiter::check_n] must fit into a single
+///   shard, so the largest possible batch is the quota's burst size divided by `n`.
+/// * The wait times returned in negative decisions are those of the checking thread's own shard;
+///   other shards may allow a cell through earlier.
+/// * Checks that get denied look at every shard before giving up, so they are more expensive
+///   than with the unsharded store. This state store pays off when many threads check cells
+///   that mostly get allowed, and the unsharded store's single atomic value becomes contended.
+///
+/// For exact rate limiting, use the default [`InMemoryState`].
+#[derive(Debug)]
+pub struct ShardedDirectStateStore {
+    shards: Box<[Shard]>,
+}
+
+impl ShardedDirectStateStore {
+    /// Constructs a state store with `shards` shards.
+    pub fn new(shards: NonZeroU32) -> Self {
+        let shards = (0..shards.get()).map(|_| Shard::default()).collect();
+        ShardedDirectStateStore { shards }
+    }
+
+    /// Returns the number of shards in the state store.
+    pub fn shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns all shards, starting with the current thread's own.
+    fn in_order(&self) -> impl Iterator<Item = &InMemoryState> {
+        let n = self.shards.len();
+        let home = HOME_SHARD.with(|home| *home);
+        (0..n).map(move |i| &self.shards[(home + i) % n].0)
+    }
+}
+
+impl StateStore for ShardedDirectStateStore {
+    type Key = NotKeyed;
+
+    fn measure_and_replace<T, F, E>(&self, _key: &Self::Key, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        let mut shards = self.in_order();
+        let home = shards.next().expect("at least one shard");
+        match home.measure_and_replace_one(&f) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                for shard in shards {
+                    if let Ok(result) = shard.measure_and_replace_one(&f) {
+                        return Ok(result);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn peek(&self, _key: &Self::Key) -> Option<Nanos> {
+        // The most permissive shard's state tells whether a cell could go through:
+        let mut earliest = None;
+        for shard in self.in_order() {
+            let tat = shard.peek_one()?;
+            earliest = Some(earliest.map_or(tat, |earliest: Nanos| earliest.min(tat)));
+        }
+        earliest
+    }
+
+    fn reset(&self, key: &Self::Key) {
+        for shard in self.shards.iter() {
+            shard.0.reset(key);
+        }
+    }
+
+    fn quota_per_state(&self, quota: Quota) -> Quota {
+        let n = self.shards.len() as u32;
+        // Round the burst size up; it is at least 1, so this can't overflow:
+        let burst = (quota.max_burst.get() - 1) / n + 1;
+        Quota {
+            start_empty: quota.start_empty,
+            tolerance: quota.tolerance,
+            strict: quota.strict,
+            ..Quota::from_interval(
+                NonZeroU32::new(burst).unwrap_or(quota.max_burst),
+                quota.interval().times(u64::from(n)),
+            )
+        }
+    }
+}
+
+/// # Direct sharded rate limiters - Constructors
+impl RateLimiter<NotKeyed, ShardedDirectStateStore, clock::DefaultClock> {
+    /// Constructs a new direct rate limiter with the default real-time clock, whose state is
+    /// split between `shards` shards.
+    ///
+    /// See [`ShardedDirectStateStore`] for the precision that this trades for scalability.
+    pub fn direct_sharded(quota: Quota, shards: NonZeroU32) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::direct_sharded_with_clock(quota, shards, &clock)
+    }
+}
+
+impl<C> RateLimiter<NotKeyed, ShardedDirectStateStore, C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new direct rate limiter with a custom clock, whose state is split between
+    /// `shards` shards.
+    pub fn direct_sharded_with_clock(quota: Quota, shards: NonZeroU32, clock: &C) -> Self {
+        RateLimiter::new(quota, ShardedDirectStateStore::new(shards), clock)
+    }
+}
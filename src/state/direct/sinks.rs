@@ -7,7 +7,6 @@ use crate::{
 };
 use futures::task::{Context, Poll};
 use futures::{Future, Sink, Stream};
-use futures_timer::Delay;
 use std::marker::PhantomData;
 use std::pin::Pin;
 
@@ -17,7 +16,7 @@ where
     S: Sink<Item>,
 {
     /// Limits the rate at which items can be put into the current sink.
-    fn ratelimit_sink<'a, D: DirectStateStore, C: clock::ReasonablyRealtime>(
+    fn ratelimit_sink<'a, D: DirectStateStore, C: clock::AsyncClock>(
         self,
         limiter: &'a RateLimiter<NotKeyed, D, C>,
     ) -> RatelimitedSink<'a, Item, S, D, C>
@@ -26,7 +25,7 @@ where
 
     /// Limits the rate at which items can be put into the current sink, with a randomized wait
     /// period.
-    fn ratelimit_sink_with_jitter<'a, D: DirectStateStore, C: clock::ReasonablyRealtime>(
+    fn ratelimit_sink_with_jitter<'a, D: DirectStateStore, C: clock::AsyncClock>(
         self,
         limiter: &'a RateLimiter<NotKeyed, D, C>,
         jitter: Jitter,
@@ -36,17 +35,17 @@ where
 }
 
 impl<Item, S: Sink<Item>> SinkRateLimitExt<Item, S> for S {
-    fn ratelimit_sink<D: DirectStateStore, C: clock::ReasonablyRealtime>(
+    fn ratelimit_sink<D: DirectStateStore, C: clock::AsyncClock>(
         self,
         limiter: &RateLimiter<NotKeyed, D, C>,
     ) -> RatelimitedSink<Item, S, D, C>
     where
         Self: Sized,
     {
-        RatelimitedSink::new(self, limiter, Jitter::NONE)
+        RatelimitedSink::new(self, limiter, limiter.jitter)
     }
 
-    fn ratelimit_sink_with_jitter<D: DirectStateStore, C: clock::ReasonablyRealtime>(
+    fn ratelimit_sink_with_jitter<D: DirectStateStore, C: clock::AsyncClock>(
         self,
         limiter: &RateLimiter<NotKeyed, D, C>,
         jitter: Jitter,
@@ -67,30 +66,24 @@ enum State {
 
 /// A [`Sink`][futures::Sink] combinator that only allows sending elements when the rate-limiter
 /// allows it.
-pub struct RatelimitedSink<
-    'a,
-    Item,
-    S: Sink<Item>,
-    D: DirectStateStore,
-    C: clock::ReasonablyRealtime,
-> {
+pub struct RatelimitedSink<'a, Item, S: Sink<Item>, D: DirectStateStore, C: clock::AsyncClock> {
     inner: S,
     state: State,
     limiter: &'a RateLimiter<NotKeyed, D, C>,
-    delay: Delay,
+    delay: Option<C::Delay>,
     jitter: Jitter,
     phantom: PhantomData<Item>,
 }
 
 /// Conversion methods for the sink combinator.
-impl<'a, Item, S: Sink<Item>, D: DirectStateStore, C: clock::ReasonablyRealtime>
+impl<'a, Item, S: Sink<Item>, D: DirectStateStore, C: clock::AsyncClock>
     RatelimitedSink<'a, Item, S, D, C>
 {
     fn new(inner: S, limiter: &'a RateLimiter<NotKeyed, D, C>, jitter: Jitter) -> Self {
         RatelimitedSink {
             inner,
             limiter,
-            delay: Delay::new(Default::default()),
+            delay: None,
             state: State::NotReady,
             jitter,
             phantom: PhantomData,
@@ -113,7 +106,7 @@ impl<'a, Item, S: Sink<Item>, D: DirectStateStore, C: clock::ReasonablyRealtime>
     }
 }
 
-impl<'a, Item, S: Sink<Item>, D: DirectStateStore, C: clock::ReasonablyRealtime> Sink<Item>
+impl<'a, Item, S: Sink<Item>, D: DirectStateStore, C: clock::AsyncClock> Sink<Item>
     for RatelimitedSink<'a, Item, S, D, C>
 where
     S: Unpin,
@@ -125,30 +118,23 @@ where
         loop {
             match self.state {
                 State::NotReady => {
-                    let reference = self.limiter.reference_reading();
                     if let Err(negative) = self.limiter.check() {
-                        let earliest = negative.earliest_possible_with_offset(self.jitter);
-                        let earliest = self.limiter.instant_from_reference(reference, earliest);
-                        self.delay.reset(earliest);
-                        let future = Pin::new(&mut self.delay);
-                        match future.poll(cx) {
-                            Poll::Pending => {
-                                self.state = State::Wait;
-                                return Poll::Pending;
-                            }
-                            Poll::Ready(_) => {}
-                        }
+                        let clock = &self.limiter.clock;
+                        let wait = self.jitter + negative.wait_time_from(clock.now());
+                        self.delay = Some(clock.delay(wait));
+                        self.state = State::Wait;
                     } else {
                         self.state = State::Ready;
                     }
                 }
                 State::Wait => {
-                    let future = Pin::new(&mut self.delay);
-                    match future.poll(cx) {
+                    let delay = self.delay.as_mut().expect("waiting without a delay");
+                    match Pin::new(delay).poll(cx) {
                         Poll::Pending => {
                             return Poll::Pending;
                         }
                         Poll::Ready(_) => {
+                            self.delay = None;
                             self.state = State::NotReady;
                         }
                     }
@@ -186,7 +172,7 @@ where
 }
 
 /// Pass-through implementation for [`futures::Stream`] if the Sink also implements it.
-impl<'a, Item, S: Stream + Sink<Item>, D: DirectStateStore, C: clock::ReasonablyRealtime> Stream
+impl<'a, Item, S: Stream + Sink<Item>, D: DirectStateStore, C: clock::AsyncClock> Stream
     for RatelimitedSink<'a, Item, S, D, C>
 where
     S::Item: Unpin,
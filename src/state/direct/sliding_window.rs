@@ -0,0 +1,135 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::middleware::{NoOpMiddleware, RateLimitingMiddleware};
+use crate::sliding_window::SlidingWindow;
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, NegativeMultiDecision, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::num::NonZeroU32;
+
+/// A direct rate limiter that uses the sliding window counter algorithm instead of GCRA.
+///
+/// The sliding window counter divides time into fixed windows, each as long as it takes the
+/// quota to replenish its entire burst capacity (e.g. one second for
+/// `Quota::per_second(nonzero!(10u32))`), and allows the quota's burst size worth of cells in
+/// each window. To avoid allowing up to twice the burst size around a window boundary, the count
+/// of the previous window is weighted by how much of it overlaps with the window that ends now.
+///
+/// Compared to GCRA, which replenishes cells one by one, this can be less smooth: After a burst,
+/// capacity returns only when the window the burst happened in starts to slide out of view.
+/// Many API gateways implement these semantics, however, so they may be what clients expect.
+///
+/// Construct one with [`RateLimiter::direct_sliding_window`]. Its state is kept in memory,
+/// behind a lock.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::direct_sliding_window(Quota::per_second(nonzero!(2u32)));
+/// assert_eq!(Ok(()), lim.check());
+/// assert_eq!(Ok(()), lim.check());
+/// assert_ne!(Ok(()), lim.check());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct SlidingWindowRateLimiter<C = clock::DefaultClock, MW = NoOpMiddleware>
+where
+    C: clock::Clock,
+{
+    algorithm: SlidingWindow,
+    clock: C,
+    start: C::Instant,
+    middleware: MW,
+}
+
+/// # Sliding window rate limiters - Constructors
+#[cfg(feature = "std")]
+impl RateLimiter<NotKeyed, InMemoryState, clock::DefaultClock> {
+    /// Constructs a new direct rate limiter for a quota that uses the sliding window counter
+    /// algorithm, with the default real-time clock.
+    pub fn direct_sliding_window(quota: Quota) -> SlidingWindowRateLimiter<clock::DefaultClock> {
+        let clock = clock::DefaultClock::default();
+        Self::direct_sliding_window_with_clock(quota, &clock)
+    }
+}
+
+impl<C> RateLimiter<NotKeyed, InMemoryState, C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new direct rate limiter for a quota that uses the sliding window counter
+    /// algorithm, with a custom clock.
+    pub fn direct_sliding_window_with_clock(
+        quota: Quota,
+        clock: &C,
+    ) -> SlidingWindowRateLimiter<C> {
+        SlidingWindowRateLimiter {
+            algorithm: SlidingWindow::new(quota),
+            clock: clock.clone(),
+            start: clock.now(),
+            middleware: NoOpMiddleware,
+        }
+    }
+}
+
+impl<C, MW> SlidingWindowRateLimiter<C, MW>
+where
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<NotKeyed, C::Instant>,
+{
+    /// Replaces the rate limiter's middleware, returning a rate limiter that uses the new one.
+    ///
+    /// The rate limiter's quota and state are kept.
+    pub fn with_middleware<Outer: RateLimitingMiddleware<NotKeyed, C::Instant>>(
+        self,
+        middleware: Outer,
+    ) -> SlidingWindowRateLimiter<C, Outer> {
+        SlidingWindowRateLimiter {
+            algorithm: self.algorithm,
+            clock: self.clock,
+            start: self.start,
+            middleware,
+        }
+    }
+
+    /// Allow a single cell through the rate limiter.
+    ///
+    /// If the rate limit is reached, `check` returns information about the earliest
+    /// time that a cell might be allowed through again.
+    pub fn check(&self) -> Result<MW::PositiveOutcome, NotUntil<'_, C::Instant>> {
+        self.check_n(nonzero!(1u32)).map_err(|e| match e {
+            NegativeMultiDecision::BatchNonConforming(_, negative) => negative,
+            NegativeMultiDecision::InsufficientCapacity { .. } => {
+                unreachable!("Quotas allow at least one cell per window")
+            }
+        })
+    }
+
+    /// Allow *only all* `n` cells through the rate limiter.
+    ///
+    /// Like [`RateLimiter::check_n`], this fails with
+    /// `NegativeMultiDecision::BatchNonConforming` if not all cells can be allowed through at the
+    /// current time, and with `NegativeMultiDecision::InsufficientCapacity` if the quota's burst
+    /// size is too low for the given number of cells to ever be allowed through.
+    pub fn check_n(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<MW::PositiveOutcome, NegativeMultiDecision<NotUntil<'_, C::Instant>>> {
+        self.algorithm.test_n_and_update(
+            self.start,
+            &NotKeyed::NonKey,
+            n,
+            self.clock.now(),
+            &self.middleware,
+        )
+    }
+
+    /// Returns how often the rate limiter's clock went back in time, as observed when making
+    /// decisions (see [`RateLimiter::clock_regressions`]).
+    pub fn clock_regressions(&self) -> u64 {
+        self.algorithm.clock_regressions()
+    }
+}
@@ -6,34 +6,40 @@ use crate::state::{DirectStateStore, NotKeyed};
 use crate::{clock, Jitter, RateLimiter};
 use futures::task::{Context, Poll};
 use futures::{Future, Sink, Stream};
-use futures_timer::Delay;
+use nonzero_ext::nonzero;
 use std::pin::Pin;
-use std::time::Duration;
 
 /// Allows converting a [`futures::Stream`] combinator into a rate-limited stream.
 pub trait StreamRateLimitExt<'a>: Stream {
     /// Limits the rate at which the stream produces items.
     ///
-    /// Note that this combinator limits the rate at which it yields
-    /// items, not necessarily the rate at which the underlying stream is polled.
-    /// The combinator will buffer at most one item in order to adhere to the
-    /// given limiter. I.e. if it already has an item buffered and needs to wait
-    /// it will not `poll` the underlying stream.
+    /// The combinator only polls the underlying stream once the limiter allows a cell through,
+    /// so it never buffers items, and a stream that produces items faster than the limiter
+    /// allows is slowed down. If the underlying stream ends, the cell that was allowed through
+    /// for its next item is returned to the limiter. (Since the underlying stream is only polled
+    /// once a cell is allowed through, the combinator can only find out that it ended after
+    /// waiting for that cell.) The cell is also returned if the combinator is dropped (or
+    /// converted [into the underlying stream](RatelimitedStream::into_inner)) while it waits for
+    /// the underlying stream's next item.
+    ///
+    /// The combinator waits using the limiter's [clock][clock::AsyncClock].
     fn ratelimit_stream<D: DirectStateStore, C: clock::Clock>(
         self,
         limiter: &'a RateLimiter<NotKeyed, D, C>,
     ) -> RatelimitedStream<'a, Self, D, C>
     where
         Self: Sized,
-        C: clock::ReasonablyRealtime;
+        C: clock::AsyncClock;
 
     /// Limits the rate at which the stream produces items, with a randomized wait period.
     ///
-    /// Note that this combinator limits the rate at which it yields
-    /// items, not necessarily the rate at which the underlying stream is polled.
-    /// The combinator will buffer at most one item in order to adhere to the
-    /// given limiter. I.e. if it already has an item buffered and needs to wait
-    /// it will not `poll` the underlying stream.
+    /// The combinator only polls the underlying stream once the limiter allows a cell through,
+    /// so it never buffers items, and a stream that produces items faster than the limiter
+    /// allows is slowed down. If the underlying stream ends, or the combinator is dropped while
+    /// it waits for the underlying stream's next item, the cell that was allowed through for
+    /// that item is returned to the limiter.
+    ///
+    /// The combinator waits using the limiter's [clock][clock::AsyncClock].
     fn ratelimit_stream_with_jitter<D: DirectStateStore, C: clock::Clock>(
         self,
         limiter: &'a RateLimiter<NotKeyed, D, C>,
@@ -41,7 +47,7 @@ pub trait StreamRateLimitExt<'a>: Stream {
     ) -> RatelimitedStream<'a, Self, D, C>
     where
         Self: Sized,
-        C: clock::ReasonablyRealtime;
+        C: clock::AsyncClock;
 }
 
 impl<'a, S: Stream> StreamRateLimitExt<'a> for S {
@@ -51,9 +57,9 @@ impl<'a, S: Stream> StreamRateLimitExt<'a> for S {
     ) -> RatelimitedStream<'a, Self, D, C>
     where
         Self: Sized,
-        C: clock::ReasonablyRealtime,
+        C: clock::AsyncClock,
     {
-        self.ratelimit_stream_with_jitter(limiter, Jitter::NONE)
+        self.ratelimit_stream_with_jitter(limiter, limiter.jitter)
     }
 
     fn ratelimit_stream_with_jitter<D: DirectStateStore, C: clock::Clock>(
@@ -63,41 +69,65 @@ impl<'a, S: Stream> StreamRateLimitExt<'a> for S {
     ) -> RatelimitedStream<'a, Self, D, C>
     where
         Self: Sized,
-        C: clock::ReasonablyRealtime,
+        C: clock::AsyncClock,
     {
         RatelimitedStream {
             inner: self,
             limiter,
-            buf: None,
-            delay: Delay::new(Duration::new(0, 0)),
+            delay: None,
+            held: None,
             jitter,
-            state: State::ReadInner,
+            state: State::Check,
         }
     }
 }
 
 #[derive(PartialEq, Debug)]
 enum State {
-    ReadInner,
-    NotReady,
+    /// Waiting for the limiter to allow a cell through.
+    Check,
+    /// Waiting for the delay until the limiter might allow a cell through.
     Wait,
+    /// A cell was allowed through; waiting for the inner stream's next item.
+    ReadInner,
+    /// The inner stream has ended.
+    Done,
+}
+
+/// A cell that was allowed through for the inner stream's next item, which gets refunded if the
+/// stream doesn't produce that item.
+struct HeldCell<'a, D: DirectStateStore, C: clock::Clock> {
+    limiter: &'a RateLimiter<NotKeyed, D, C>,
+}
+
+impl<D: DirectStateStore, C: clock::Clock> HeldCell<'_, D, C> {
+    /// Uses up the cell for an item that the inner stream produced.
+    fn use_up(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl<D: DirectStateStore, C: clock::Clock> Drop for HeldCell<'_, D, C> {
+    fn drop(&mut self) {
+        self.limiter.refund(nonzero!(1u32));
+    }
 }
 
 /// A [`Stream`][futures::Stream] combinator which will limit the rate of items being received.
 ///
 /// This is produced by the [`StreamRateLimitExt::ratelimit_stream`] and
 /// [`StreamRateLimitExt::ratelimit_stream_with_jitter`] methods.
-pub struct RatelimitedStream<'a, S: Stream, D: DirectStateStore, C: clock::Clock> {
+pub struct RatelimitedStream<'a, S: Stream, D: DirectStateStore, C: clock::AsyncClock> {
     inner: S,
     limiter: &'a RateLimiter<NotKeyed, D, C>,
-    delay: Delay,
-    buf: Option<S::Item>,
+    delay: Option<C::Delay>,
+    held: Option<HeldCell<'a, D, C>>,
     jitter: Jitter,
     state: State,
 }
 
 /// Conversion methods for the stream combinator.
-impl<'a, S: Stream, D: DirectStateStore, C: clock::Clock> RatelimitedStream<'a, S, D, C> {
+impl<'a, S: Stream, D: DirectStateStore, C: clock::AsyncClock> RatelimitedStream<'a, S, D, C> {
     /// Acquires a reference to the underlying stream that this combinator is pulling from.
     pub fn get_ref(&self) -> &S {
         &self.inner
@@ -108,86 +138,83 @@ impl<'a, S: Stream, D: DirectStateStore, C: clock::Clock> RatelimitedStream<'a,
         &mut self.inner
     }
 
-    /// Consumes this combinator, returning the underlying stream and any item
-    /// which it has already produced but which is still being held back
-    /// in order to abide by the limiter.
-    pub fn into_inner(self) -> (S, Option<S::Item>) {
-        (self.inner, self.buf)
+    /// Consumes this combinator, returning the underlying stream.
+    ///
+    /// If a cell was allowed through for the underlying stream's next item, it is returned to
+    /// the limiter.
+    pub fn into_inner(self) -> S {
+        self.inner
     }
 }
 
 /// Implements the [`futures::Stream`] combinator.
-impl<'a, S: Stream, D: DirectStateStore, C: clock::Clock> Stream for RatelimitedStream<'a, S, D, C>
+impl<'a, S: Stream, D: DirectStateStore, C: clock::AsyncClock> Stream
+    for RatelimitedStream<'a, S, D, C>
 where
     S: Unpin,
-    S::Item: Unpin,
-    Self: Unpin,
-    C: clock::ReasonablyRealtime,
 {
     type Item = S::Item;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
             match self.state {
-                State::ReadInner => {
-                    let inner = Pin::new(&mut self.inner);
-                    match inner.poll_next(cx) {
+                State::Check => match self.limiter.check() {
+                    Ok(()) => {
+                        self.held = Some(HeldCell {
+                            limiter: self.limiter,
+                        });
+                        self.state = State::ReadInner;
+                    }
+                    Err(negative) => {
+                        let clock = &self.limiter.clock;
+                        let wait = self.jitter + negative.wait_time_from(clock.now());
+                        self.delay = Some(clock.delay(wait));
+                        self.state = State::Wait;
+                    }
+                },
+                State::Wait => {
+                    let delay = self.delay.as_mut().expect("waiting without a delay");
+                    match Pin::new(delay).poll(cx) {
                         Poll::Pending => return Poll::Pending,
-                        Poll::Ready(None) => {
-                            // never talk tome or my inner again
-                            return Poll::Ready(None);
-                        }
-                        Poll::Ready(Some(x)) => {
-                            self.buf.replace(x);
-                            self.state = State::NotReady;
+                        Poll::Ready(()) => {
+                            self.delay = None;
+                            self.state = State::Check;
                         }
                     }
                 }
-                State::NotReady => {
-                    let reference = self.limiter.reference_reading();
-                    if let Err(negative) = self.limiter.check() {
-                        let earliest = negative.earliest_possible_with_offset(self.jitter);
-                        let earliest = self.limiter.instant_from_reference(reference, earliest);
-                        self.delay.reset(earliest);
-                        let future = Pin::new(&mut self.delay);
-                        match future.poll(cx) {
-                            Poll::Pending => {
-                                self.state = State::Wait;
-                                return Poll::Pending;
-                            }
-                            Poll::Ready(_) => {}
-                        }
-                    } else {
-                        self.state = State::ReadInner;
-                        return Poll::Ready(self.buf.take());
+                State::ReadInner => match Pin::new(&mut self.inner).poll_next(cx) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(None) => {
+                        self.held = None;
+                        self.state = State::Done;
                     }
-                }
-                State::Wait => {
-                    let future = Pin::new(&mut self.delay);
-                    match future.poll(cx) {
-                        Poll::Pending => {
-                            return Poll::Pending;
-                        }
-                        Poll::Ready(_) => {
-                            self.state = State::NotReady;
+                    Poll::Ready(Some(x)) => {
+                        if let Some(cell) = self.held.take() {
+                            cell.use_up();
                         }
+                        self.state = State::Check;
+                        return Poll::Ready(Some(x));
                     }
-                }
+                },
+                State::Done => return Poll::Ready(None),
             }
         }
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        self.inner.size_hint()
+        if self.state == State::Done {
+            (0, Some(0))
+        } else {
+            self.inner.size_hint()
+        }
     }
 }
 
 /// Pass-through implementation for [`futures::Sink`] if the Stream also implements it.
-impl<'a, Item, S: Stream + Sink<Item>, D: DirectStateStore, C: clock::Clock> Sink<Item>
+impl<'a, Item, S: Stream + Sink<Item>, D: DirectStateStore, C: clock::AsyncClock> Sink<Item>
     for RatelimitedStream<'a, S, D, C>
 where
     S: Unpin,
-    S::Item: Unpin,
 {
     type Error = <S as Sink<Item>>::Error;
 
@@ -0,0 +1,113 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::middleware::NoOpMiddleware;
+use crate::state::{InMemoryState, NotKeyed};
+use crate::{clock, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+
+/// A direct rate limiter that enforces several quotas at once (e.g. 10 cells per second *and*
+/// 100 cells per minute).
+///
+/// A cell is only allowed through if all of the quotas allow it, and cells are only counted
+/// against the quotas if they are allowed through: If a quota rejects a cell, the capacity that
+/// other quotas used up for it is returned to them.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{state::TieredRateLimiter, Quota};
+/// let lim = TieredRateLimiter::new(vec![
+///     Quota::per_second(nonzero!(10u32)),
+///     Quota::per_minute(nonzero!(100u32)),
+/// ]);
+/// assert_eq!(Ok(()), lim.check());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct TieredRateLimiter<C = clock::DefaultClock>
+where
+    C: clock::Clock,
+{
+    tiers: Box<[RateLimiter<NotKeyed, InMemoryState, C>]>,
+    clock: C,
+}
+
+#[cfg(feature = "std")]
+impl TieredRateLimiter<clock::DefaultClock> {
+    /// Constructs a new in-memory tiered rate limiter for the given quotas, with the default
+    /// real-time clock.
+    pub fn new(quotas: impl IntoIterator<Item = Quota>) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::with_clock(quotas, &clock)
+    }
+}
+
+impl<C> TieredRateLimiter<C>
+where
+    C: clock::Clock,
+{
+    /// Constructs a new in-memory tiered rate limiter for the given quotas, with a custom clock.
+    ///
+    /// A tiered rate limiter without any quotas allows every cell through.
+    pub fn with_clock(quotas: impl IntoIterator<Item = Quota>, clock: &C) -> Self {
+        let start = clock.now();
+        let tiers = quotas
+            .into_iter()
+            .map(|quota| RateLimiter::new_with_start(quota, InMemoryState::default(), clock, start))
+            .collect();
+        TieredRateLimiter {
+            tiers,
+            clock: clock.clone(),
+        }
+    }
+
+    /// Allow a single cell through the rate limiter, if all of its quotas allow it.
+    ///
+    /// If any of the quotas reject the cell, `check` returns the negative decision with the
+    /// longest wait, i.e. the earliest time at which all quotas might allow a cell through again.
+    /// A rejected cell does not use up any capacity.
+    pub fn check(&self) -> Result<(), NotUntil<'_, C::Instant>> {
+        let now = self.clock.now();
+        for (allowed, tier) in self.tiers.iter().enumerate() {
+            if let Err(negative) = tier.gcra.test_and_update(
+                tier.start,
+                &NotKeyed::NonKey,
+                &tier.state,
+                now,
+                &NoOpMiddleware,
+            ) {
+                for tier in &self.tiers[..allowed] {
+                    tier.gcra.refund_n(
+                        tier.start,
+                        &NotKeyed::NonKey,
+                        nonzero!(1u32),
+                        &tier.state,
+                        now,
+                    );
+                }
+                return Err(self.tiers[allowed + 1..]
+                    .iter()
+                    .filter_map(|tier| {
+                        tier.gcra
+                            .test(tier.start, &NotKeyed::NonKey, &tier.state, now)
+                            .err()
+                    })
+                    .fold(negative, |longest, negative| {
+                        if negative.earliest_possible() > longest.earliest_possible() {
+                            negative
+                        } else {
+                            longest
+                        }
+                    }));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the number of quotas that the rate limiter enforces.
+    pub fn tiers(&self) -> usize {
+        self.tiers.len()
+    }
+}
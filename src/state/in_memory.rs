@@ -1,51 +1,64 @@
 use std::prelude::v1::*;
 
-use crate::nanos::Nanos;
+use crate::nanos::{AtomicNanos, Nanos};
 use crate::state::{NotKeyed, StateStore};
 use std::fmt;
 use std::fmt::Debug;
-use std::num::NonZeroU64;
-use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
-use std::time::Duration;
 
 /// An in-memory representation of a GCRA's rate-limiting state.
 ///
-/// Implemented using [`AtomicU64`] operations, this state representation can be used to
+/// Implemented using [`AtomicU64`][std::sync::atomic::AtomicU64] operations (with the
+/// `wide-nanos` feature, a spinlock instead), this state representation can be used to
 /// construct rate limiting states for other in-memory states: e.g., this crate uses
 /// `InMemoryState` as the states it tracks in the keyed rate limiters it implements.
 ///
 /// Internally, the number tracked here is the theoretical arrival time (a GCRA term) in number of
 /// nanoseconds since the rate limiter was created.
 #[derive(Default)]
-pub struct InMemoryState(AtomicU64);
+pub struct InMemoryState(AtomicNanos);
+
+/// Returns the state that was stored as `tat`, with zero meaning that there is no state yet.
+fn state_from(tat: Nanos) -> Option<Nanos> {
+    if tat == Nanos::default() {
+        None
+    } else {
+        Some(tat)
+    }
+}
 
 impl InMemoryState {
+    pub(crate) fn new(tat: Nanos) -> Self {
+        InMemoryState(AtomicNanos::new(tat))
+    }
+
     pub(crate) fn measure_and_replace_one<T, F, E>(&self, f: F) -> Result<T, E>
     where
         F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
     {
         let mut prev = self.0.load(Ordering::Acquire);
-        let mut decision = f(NonZeroU64::new(prev).map(|n| n.get().into()));
+        let mut decision = f(state_from(prev));
         while let Ok((result, new_data)) = decision {
-            match self.0.compare_exchange_weak(
-                prev,
-                new_data.into(),
-                Ordering::Release,
-                Ordering::Relaxed,
-            ) {
+            match self
+                .0
+                .compare_exchange_weak(prev, new_data, Ordering::Release, Ordering::Relaxed)
+            {
                 Ok(_) => return Ok(result),
                 Err(next_prev) => prev = next_prev,
             }
-            decision = f(NonZeroU64::new(prev).map(|n| n.get().into()));
+            decision = f(state_from(prev));
         }
         // This map shouldn't be needed, as we only get here in the error case, but the compiler
         // can't see it.
         decision.map(|(result, _)| result)
     }
 
+    pub(crate) fn peek_one(&self) -> Option<Nanos> {
+        state_from(self.0.load(Ordering::Acquire))
+    }
+
     pub(crate) fn is_older_than(&self, nanos: Nanos) -> bool {
-        self.0.load(Ordering::Relaxed) <= nanos.into()
+        self.0.load(Ordering::Relaxed) <= nanos
     }
 }
 
@@ -59,11 +72,78 @@ impl StateStore for InMemoryState {
     {
         self.measure_and_replace_one(f)
     }
+
+    fn peek(&self, _key: &Self::Key) -> Option<Nanos> {
+        self.peek_one()
+    }
+
+    fn reset(&self, _key: &Self::Key) {
+        self.0.store(Nanos::default(), Ordering::Release);
+    }
 }
 
 impl Debug for InMemoryState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        let d = Duration::from_nanos(self.0.load(Ordering::Relaxed));
+        let d = self.0.load(Ordering::Relaxed).as_duration();
         write!(f, "InMemoryState({:?})", d)
     }
 }
+
+/// An in-memory rate-limiting state like [`InMemoryState`], whose atomic operations don't order
+/// any other memory accesses.
+///
+/// [`InMemoryState`] reads its state with `Acquire` and replaces it with `Release` ordering, so
+/// that everything a thread did before its cell was allowed through is visible to the thread
+/// whose cell is allowed through next. This state uses `Relaxed` ordering throughout, which
+/// saves memory barriers on weakly-ordered architectures like aarch64 if rate limiting is the
+/// only thing that the threads share.
+///
+/// Rate-limiting decisions themselves do not get any looser: The state is a single atomic value
+/// that is only ever replaced with a compare-and-swap, and those are totally ordered no matter
+/// the memory ordering, so this never allows more cells through than [`InMemoryState`] would.
+/// The guarantee that weakens is the one about other memory: A thread whose cell is allowed
+/// through may not yet see writes (e.g. to a shared cache) that a thread made before its own
+/// cell was allowed through earlier. Don't use the rate limiter to synchronize such accesses.
+///
+/// Construct a direct rate limiter using this state with
+/// [`RateLimiter::direct_relaxed`][crate::RateLimiter::direct_relaxed].
+#[derive(Default)]
+pub struct RelaxedInMemoryState(AtomicNanos);
+
+impl StateStore for RelaxedInMemoryState {
+    type Key = NotKeyed;
+
+    fn measure_and_replace<T, F, E>(&self, _key: &Self::Key, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        let mut prev = self.0.load(Ordering::Relaxed);
+        let mut decision = f(state_from(prev));
+        while let Ok((result, new_data)) = decision {
+            match self
+                .0
+                .compare_exchange_weak(prev, new_data, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return Ok(result),
+                Err(next_prev) => prev = next_prev,
+            }
+            decision = f(state_from(prev));
+        }
+        decision.map(|(result, _)| result)
+    }
+
+    fn peek(&self, _key: &Self::Key) -> Option<Nanos> {
+        state_from(self.0.load(Ordering::Relaxed))
+    }
+
+    fn reset(&self, _key: &Self::Key) {
+        self.0.store(Nanos::default(), Ordering::Relaxed);
+    }
+}
+
+impl Debug for RelaxedInMemoryState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let d = self.0.load(Ordering::Relaxed).as_duration();
+        write!(f, "RelaxedInMemoryState({:?})", d)
+    }
+}
@@ -6,17 +6,30 @@
 //!
 //! Rate limiters based on these types are constructed with
 //! [the `RateLimiter` constructors](../struct.RateLimiter.html#keyed-rate-limiters---default-constructors)
+//!
+//! Any `Hash + Eq + Clone` type can be a key, including tuples of such types: A rate limiter keyed
+//! on `(user_id, endpoint)` keeps a separate state for each pair. [`CompositeKey`] combines a
+//! varying number of components into one key.
 
+use std::borrow::Borrow;
 use std::hash::Hash;
 use std::num::NonZeroU32;
 use std::prelude::v1::*;
 
-use crate::state::StateStore;
+use crate::gcra::Gcra;
+use crate::middleware::{NoOpMiddleware, RateLimitingMiddleware};
+use crate::state::{ByBorrowedKey, StateStore};
 use crate::{
     clock::{self, Reference},
     nanos::Nanos,
-    NegativeMultiDecision, NotUntil, Quota, RateLimiter,
+    Jitter, NegativeMultiDecision, NotUntil, Quota, RateLimiter, StateInformation,
 };
+use nonzero_ext::nonzero;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+#[cfg(feature = "std")]
+use std::time::{Instant, SystemTime};
 
 /// A trait for state stores with one rate limiting state per key.
 ///
@@ -44,6 +57,13 @@ where
         RateLimiter::new(quota, state, &clock)
     }
 
+    /// Constructs a new keyed rate limiter backed by the [`DefaultKeyedStateStore`], whose
+    /// waiting methods use `jitter` when they aren't given one (see
+    /// [`with_default_jitter`][RateLimiter::with_default_jitter]).
+    pub fn keyed_with_jitter(quota: Quota, jitter: Jitter) -> Self {
+        Self::keyed(quota).with_default_jitter(jitter)
+    }
+
     #[cfg(all(feature = "std", feature = "dashmap"))]
     /// Constructs a new keyed rate limiter explicitly backed by a [`DashMap`][dashmap::DashMap].
     pub fn dashmap(quota: Quota) -> Self {
@@ -77,19 +97,92 @@ where
 }
 
 /// # Keyed rate limiters - Manually checking cells
-impl<K, S, C> RateLimiter<K, S, C>
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
 where
     S: KeyedStateStore<K>,
-    K: Hash,
+    K: Hash + Eq + Clone,
     C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
 {
     /// Allow a single cell through the rate limiter for the given key.
     ///
     /// If the rate limit is reached, `check_key` returns information about the earliest
     /// time that a cell might be allowed through again under that key.
-    pub fn check_key(&self, key: &K) -> Result<(), NotUntil<C::Instant>> {
-        self.gcra
-            .test_and_update(self.start, key, &self.state, self.clock.now())
+    ///
+    /// Like [`HashMap::get`][std::collections::HashMap::get], this takes the key in any borrowed
+    /// form of the key type (e.g. a `&str` for `String` keys), and the state stores in this
+    /// crate only create an owned key when they see the key for the first time. The middleware
+    /// is informed of the key in the form it was given in, so it has to handle that form, too
+    /// (as all the middleware in this crate does).
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::FakeRelativeClock, state::keyed::HashMapStateStore, Quota};
+    /// # use governor::RateLimiter;
+    /// # use nonzero_ext::nonzero;
+    /// let clock = FakeRelativeClock::default();
+    /// let lim: RateLimiter<String, HashMapStateStore<String>, _> =
+    ///     RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    /// assert_eq!(Ok(()), lim.check_key("alice"));
+    /// assert!(lim.check_key(&"alice".to_string()).is_err());
+    /// ```
+    pub fn check_key<Q>(
+        &self,
+        key: &Q,
+    ) -> Result<
+        <MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome,
+        NotUntil<'_, C::Instant>,
+    >
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        self.gcra.test_and_update(
+            self.start,
+            key,
+            &ByBorrowedKey(&self.state),
+            self.clock.now(),
+            &self.middleware,
+        )
+    }
+
+    /// Allow a single cell through the rate limiter for the given key, under a different quota
+    /// than the rate limiter's.
+    ///
+    /// This allows giving some keys (e.g. those of premium users) a different rate limit than
+    /// others, while tracking all keys in the same rate limiter.
+    ///
+    /// A key has the same rate-limiting state no matter which quota it is checked with: Every
+    /// cell that is allowed through adds the replenishment interval of the quota it was checked
+    /// with to the state, and a cell is only allowed through if the state fits into the burst
+    /// capacity of the quota it is checked with. Switching quotas therefore never resets a key's
+    /// state or grants it a fresh burst; the capacity that cells used up under one quota
+    /// (measured in the time it takes to replenish them) counts against all others. To limit a
+    /// key exactly as a quota describes, check it with the same quota every time.
+    ///
+    /// Like [`check_key`][RateLimiter::check_key], this takes the key in any borrowed form of the
+    /// key type.
+    pub fn check_key_with_quota<Q>(
+        &self,
+        key: &Q,
+        quota: Quota,
+    ) -> Result<
+        <MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome,
+        NotUntil<'_, C::Instant>,
+    >
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        Gcra::new(self.state.quota_per_state(quota)).test_and_update(
+            self.start,
+            key,
+            &ByBorrowedKey(&self.state),
+            self.clock.now(),
+            &self.middleware,
+        )
     }
 
     /// Allow *only all* `n` cells through the rate limiter for the given key.
@@ -102,17 +195,226 @@ where
     /// * Failure (the batch can never go through): The rate limit is too low for the given number
     ///   of cells.
     ///
+    /// Like [`check_key`][RateLimiter::check_key], this takes the key in any borrowed form of the
+    /// key type.
+    ///
     /// ### Performance
     /// This method diverges a little from the GCRA algorithm, using
     /// multiplication to determine the next theoretical arrival time, and so
     /// is not as fast as checking a single cell.
-    pub fn check_key_n(
+    #[allow(clippy::type_complexity)]
+    pub fn check_key_n<Q>(
         &self,
-        key: &K,
+        key: &Q,
         n: NonZeroU32,
-    ) -> Result<(), NegativeMultiDecision<NotUntil<C::Instant>>> {
+    ) -> Result<
+        <MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome,
+        NegativeMultiDecision<NotUntil<'_, C::Instant>>,
+    >
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        self.gcra.test_n_all_and_update(
+            self.start,
+            key,
+            n,
+            &ByBorrowedKey(&self.state),
+            self.clock.now(),
+            &self.middleware,
+        )
+    }
+
+    /// Allow *only all* of `cost` cells through the rate limiter for the given key, where `cost`
+    /// is computed at runtime (e.g. the size of a payload in bytes, see
+    /// [`Quota::bytes_per_second`]) and may be zero.
+    ///
+    /// A zero cost is a no-op: It does not consult the rate limiter or its middleware, doesn't
+    /// create a state for the key, and returns `Ok(None)`. Any other cost is checked like
+    /// [`check_key_n`][RateLimiter::check_key_n] would check it, returning the middleware's
+    /// positive outcome as `Ok(Some(_))`.
+    #[allow(clippy::type_complexity)]
+    pub fn check_key_cost<Q>(
+        &self,
+        key: &Q,
+        cost: u32,
+    ) -> Result<
+        Option<<MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome>,
+        NegativeMultiDecision<NotUntil<'_, C::Instant>>,
+    >
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        match NonZeroU32::new(cost) {
+            Some(n) => self.check_key_n(key, n).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Allow a single cell through the rate limiter for each of the given keys, only if all of
+    /// the keys allow it.
+    ///
+    /// This is useful for operations that count against several keys at once (e.g. a user, their
+    /// team and their organization): If any of the keys would reject the cell, none of them are
+    /// charged for it, and `check_keys` returns the negative decision with the longest wait, i.e.
+    /// the earliest time at which all keys might allow a cell through again.
+    ///
+    /// The keys are first checked without using up any capacity (as with
+    /// [`peek_key`][RateLimiter::peek_key]), so a rejection doesn't touch any state. Only if all
+    /// keys allow the cell, it is counted against each of them in turn. Should a concurrent check
+    /// use up a key's capacity in the meantime, the cell is refunded to the keys that were
+    /// already charged, and that key's negative decision is returned.
+    ///
+    /// A key that is given more than once is charged once for each time it is given. Decisions
+    /// made by this method are not passed to the rate limiter's middleware.
+    ///
+    /// Unlike [`check_key`][RateLimiter::check_key], this takes the keys in their owned form.
+    pub fn check_keys(&self, keys: &[K]) -> Result<(), NotUntil<'_, C::Instant>> {
+        let now = self.clock.now();
+        let longest = keys
+            .iter()
+            .filter_map(|key| self.gcra.test(self.start, key, &self.state, now).err())
+            .max_by_key(|negative| negative.earliest_possible());
+        if let Some(negative) = longest {
+            return Err(negative);
+        }
+        for (charged, key) in keys.iter().enumerate() {
+            if let Err(negative) =
+                self.gcra
+                    .test_and_update(self.start, key, &self.state, now, &NoOpMiddleware)
+            {
+                for key in &keys[..charged] {
+                    self.gcra
+                        .refund_n(self.start, key, nonzero!(1u32), &self.state, now);
+                }
+                return Err(negative);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tests whether a single cell would be allowed through the rate limiter for the given key
+    /// right now, without using up any of its capacity.
+    ///
+    /// This returns the same result that [`check_key`][RateLimiter::check_key] would return at
+    /// this point in time, but leaves the rate limiter's state unchanged (the state stores in
+    /// this crate don't allocate an entry for keys they haven't seen yet). Since other threads
+    /// may check cells concurrently, a positive result does not guarantee that a following
+    /// `check_key` succeeds.
+    pub fn peek_key(&self, key: &K) -> Result<(), NotUntil<'_, C::Instant>> {
         self.gcra
-            .test_n_all_and_update(self.start, key, n, &self.state, self.clock.now())
+            .test(self.start, key, &self.state, self.clock.now())
+    }
+
+    /// Returns information about the current rate limiting state for the given key (e.g. how
+    /// many cells could be allowed through right now), without using up any of its capacity.
+    ///
+    /// Keys that the rate limiter has not seen yet have the full burst capacity available.
+    pub fn key_state_information(&self, key: &K) -> StateInformation {
+        let now = self.clock.now().duration_since(self.start);
+        self.gcra.state_information(self.state.peek(key), now)
+    }
+
+    /// Returns the number of cells that the rate limiter would allow through for the given key
+    /// right now, without using up any of its capacity.
+    ///
+    /// This is at most the quota's burst size. Since other threads may check cells concurrently,
+    /// a following [`check_key_n`][RateLimiter::check_key_n] for that many cells is not
+    /// guaranteed to succeed.
+    pub fn available_for_key(&self, key: &K) -> u32 {
+        self.key_state_information(key).remaining_burst_capacity()
+    }
+
+    /// Returns how long after `now` the given key will have its entire burst capacity available
+    /// again, if no more cells are allowed through for it in the meantime.
+    ///
+    /// If the full burst capacity is available to the key at `now` (e.g. because the rate
+    /// limiter has not seen the key yet), this returns a zero duration.
+    pub fn full_at_for_key(&self, key: &K, now: C::Instant) -> Duration {
+        let now = now.duration_since(self.start);
+        self.gcra
+            .state_information(self.state.peek(key), now)
+            .time_until_full()
+    }
+
+    /// Returns how long ago the given key's state was last advanced, e.g. to decide whether a
+    /// state that was saved a while ago is still worth trusting.
+    ///
+    /// This is the time since the last cell was allowed through for the key, if the full burst
+    /// capacity was available to that cell. Cells that were allowed through in a burst advance
+    /// the state further, so after a burst, the age only starts growing once the burst capacity
+    /// it used up would have replenished again, and it is shorter than the time since the last
+    /// cell. The same goes for the very first cell for the key, which advances the state by one
+    /// replenishment interval more than later cells do.
+    ///
+    /// A [restored][RateLimiter::restore] state reports the age it would have if the rate limiter
+    /// had kept running.
+    ///
+    /// If the rate limiter has no state for the key (because it hasn't checked a cell for the key
+    /// yet, or the key's state was reset or removed since), there's no age to report, and this
+    /// returns `None`.
+    pub fn state_age_for_key(&self, key: &K) -> Option<Duration> {
+        let now = self.clock.now().duration_since(self.start);
+        self.gcra.state_age(self.state.peek(key), now)
+    }
+
+    /// Returns the capacity of `n` cells that were previously allowed through for the given key
+    /// to the rate limiter.
+    ///
+    /// Like [`refund`][RateLimiter::refund] does for direct rate limiters, this never makes more
+    /// than the quota's burst capacity available to the key. Refunding a key that the rate
+    /// limiter has no state for does nothing.
+    pub fn refund_key(&self, key: &K, n: NonZeroU32) {
+        self.gcra
+            .refund_n(self.start, key, n, &self.state, self.clock.now())
+    }
+
+    /// Tests whether an attempt for the given key, whose failures are charged with
+    /// [`charge_failure_key`][RateLimiter::charge_failure_key], may go ahead right now, without
+    /// using up any of the key's capacity.
+    ///
+    /// This is the keyed version of [`try_begin`][RateLimiter::try_begin].
+    pub fn try_begin_key(&self, key: &K) -> Result<(), NotUntil<'_, C::Instant>> {
+        self.peek_key(key)
+    }
+
+    /// Uses up a single cell of the given key's capacity for an attempt that failed.
+    ///
+    /// Like [`charge_failure`][RateLimiter::charge_failure] does for direct rate limiters, this
+    /// charges the cell even if the key has no capacity left, but never uses up more than the
+    /// quota's burst capacity.
+    pub fn charge_failure_key(&self, key: &K) {
+        self.gcra.charge_n(
+            self.start,
+            key,
+            nonzero!(1u32),
+            &self.state,
+            self.clock.now(),
+        )
+    }
+
+    /// Resets the rate limiting state for the given key to its initial state, making the full
+    /// burst capacity available to that key again.
+    ///
+    /// Resetting a key that the rate limiter has no state for does nothing.
+    pub fn reset_key(&self, key: &K) {
+        self.state.reset(key);
+    }
+}
+
+/// Keyed state stores that can tell how many keys they hold states for.
+///
+/// All the keyed state stores in this crate implement this trait.
+pub trait CountableKeyedStateStore<K: Hash>: KeyedStateStore<K> {
+    /// Returns the number of keys in the state store.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the state store holds no keys.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
 
@@ -133,6 +435,198 @@ pub trait ShrinkableKeyedStateStore<K: Hash>: KeyedStateStore<K> {
     ///
     /// If the state store does not support shrinking, this method is a no-op.   
     fn shrink_to_fit(&self) {}
+
+    /// Removes the states of all keys.
+    ///
+    /// The default implementation removes every key with
+    /// [`retain_recent`][ShrinkableKeyedStateStore::retain_recent]; state stores that can remove
+    /// all keys more efficiently should override it.
+    fn clear(&self) {
+        self.retain_recent(Nanos::MAX);
+    }
+}
+
+/// Keyed state stores whose states can be saved and restored.
+///
+/// This allows keeping rate-limiting states across process restarts, by saving a snapshot of
+/// all states before shutting down and restoring it when starting up again (see
+/// [`RateLimiter::snapshot`] and [`RateLimiter::restore`]). All the in-memory keyed state stores
+/// in this crate implement this trait.
+pub trait SnapshottableKeyedStateStore<K: Hash>: KeyedStateStore<K> {
+    /// Returns all keys in the state store, along with their states.
+    fn snapshot(&self) -> Vec<(K, Nanos)>;
+
+    /// Sets the state of a key, replacing any existing state.
+    fn restore(&self, key: K, state: Nanos);
+}
+
+/// A saved rate-limiting state for a single key, as returned by [`RateLimiter::snapshot`], or for
+/// a direct rate limiter, as returned by [`RateLimiter::export_state`].
+///
+/// Rather than the rate limiter's internal representation, which is only meaningful to the rate
+/// limiter that created it, a snapshot records how long it would take for the key's state to
+/// become indistinguishable from a fresh state, and the system time when the snapshot was taken.
+/// With the `serde` feature, snapshots can be serialized and deserialized.
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StateSnapshot {
+    remaining: Duration,
+    taken_at: SystemTime,
+}
+
+#[cfg(feature = "std")]
+impl StateSnapshot {
+    /// Returns the time that, as of the time the snapshot was taken, needed to pass until the
+    /// key's state would be indistinguishable from a fresh state.
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    /// Returns the system time at which the snapshot was taken.
+    pub fn taken_at(&self) -> SystemTime {
+        self.taken_at
+    }
+
+    /// Takes a snapshot of the state `tat` at time `now`, at the system time `taken_at`.
+    pub(crate) fn of(tat: Nanos, now: Nanos, taken_at: SystemTime) -> Self {
+        StateSnapshot {
+            remaining: tat.saturating_sub(now).into(),
+            taken_at,
+        }
+    }
+
+    /// Returns the state that restores the snapshot at time `now`, counting the system time that
+    /// passed since it was taken (as of `wall_clock_now`) towards replenishing it.
+    pub(crate) fn restore_at(&self, now: Nanos, wall_clock_now: SystemTime) -> Nanos {
+        let elapsed = wall_clock_now
+            .duration_since(self.taken_at)
+            .unwrap_or_default();
+        now + self.remaining.checked_sub(elapsed).unwrap_or_default()
+    }
+}
+
+/// # Keyed rate limiters - Absolute deadlines
+#[cfg(feature = "std")]
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: KeyedStateStore<K>,
+    K: Hash + Eq + Clone,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Allow a single cell through the rate limiter for the given key, returning the absolute
+    /// time at which a cell might be allowed through again under that key if the rate limit is
+    /// reached.
+    ///
+    /// This is the keyed counterpart of [`check_deadline`][RateLimiter::check_deadline], and
+    /// takes the key in any borrowed form of the key type, like
+    /// [`check_key`][RateLimiter::check_key].
+    pub fn check_key_deadline<Q>(
+        &self,
+        key: &Q,
+    ) -> Result<<MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome, Instant>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        let now = self.clock.now();
+        self.gcra
+            .test_and_update(
+                self.start,
+                key,
+                &ByBorrowedKey(&self.state),
+                now,
+                &self.middleware,
+            )
+            .map_err(|negative| Instant::now() + negative.wait_time_from(now))
+    }
+}
+
+/// # Keyed rate limiters - Inspecting states
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: SnapshottableKeyedStateStore<K>,
+    K: Hash,
+    C: clock::Clock,
+{
+    /// Returns an iterator over all keys that the rate limiter keeps state for, along with
+    /// information about their states.
+    ///
+    /// The iterator works on a copy of the keys and states taken when `iter` is called: It is
+    /// weakly consistent, meaning that rate-limiting decisions made while iterating are not
+    /// reflected, and that decisions made concurrently with the call may or may not be.
+    pub fn iter(&self) -> impl Iterator<Item = (K, StateInformation)> + '_ {
+        let now = self.clock.now().duration_since(self.start);
+        self.state
+            .snapshot()
+            .into_iter()
+            .map(move |(key, tat)| (key, self.gcra.state_information(Some(tat), now)))
+    }
+
+    /// Returns the number of keys that are throttled right now, i.e. whose burst capacity is
+    /// exhausted, so that checking a single cell for them would fail.
+    ///
+    /// This is meant as a gauge for monitoring, sampled periodically rather than in the hot
+    /// path: It goes through all keys that the rate limiter keeps state for (see
+    /// [`iter`][RateLimiter::iter]), so it takes time proportional to their number, and is weakly
+    /// consistent in the same way.
+    pub fn throttled_count(&self) -> usize {
+        self.iter()
+            .filter(|(_, info)| info.remaining_burst_capacity() == 0)
+            .count()
+    }
+}
+
+/// # Keyed rate limiters - Saving and restoring states
+#[cfg(feature = "std")]
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: SnapshottableKeyedStateStore<K>,
+    K: Hash,
+    C: clock::Clock,
+{
+    /// Returns snapshots of the rate-limiting states of all keys that are currently being
+    /// limited.
+    ///
+    /// Keys whose state is indistinguishable from a fresh state are left out. The snapshots can be
+    /// saved and passed to [`restore`][RateLimiter::restore] on a rate limiter that is
+    /// constructed later, e.g. after a restart.
+    pub fn snapshot(&self) -> Vec<(K, StateSnapshot)> {
+        let now = self.clock.now().duration_since(self.start);
+        let taken_at = SystemTime::now();
+        self.state
+            .snapshot()
+            .into_iter()
+            .filter(|(_, tat)| *tat > now)
+            .map(|(key, tat)| (key, StateSnapshot::of(tat, now, taken_at)))
+            .collect()
+    }
+
+    /// Restores the rate-limiting states of keys from snapshots taken by
+    /// [`snapshot`][RateLimiter::snapshot], replacing the keys' existing states.
+    ///
+    /// The time that passed between taking and restoring a snapshot counts towards replenishing
+    /// the restored states, as if the rate limiter had kept running.
+    ///
+    /// ### Clock skew
+    /// The time that passed is determined using the system clock, which can jump or differ
+    /// between hosts. If the restoring host's system clock is ahead of the one that took the
+    /// snapshot, the restored states are more lenient than they should be (possibly fresh); if it
+    /// is behind (or the snapshot appears to be taken in the future), they are stricter, as if no
+    /// time had passed at all. After long downtimes, most states will have replenished completely,
+    /// and are not restored at all.
+    pub fn restore(&self, snapshots: impl IntoIterator<Item = (K, StateSnapshot)>) {
+        let now = self.clock.now().duration_since(self.start);
+        let wall_clock_now = SystemTime::now();
+        for (key, snapshot) in snapshots {
+            let tat = snapshot.restore_at(now, wall_clock_now);
+            if tat > now {
+                self.state.restore(key, tat);
+            }
+        }
+    }
 }
 
 /// # Keyed rate limiters - Housekeeping
@@ -141,7 +635,7 @@ pub trait ShrinkableKeyedStateStore<K: Hash>: KeyedStateStore<K> {
 /// grows, while the number of active keys may stay smaller. To save on space, a keyed rate-limiter
 /// allows removing those keys that are "stale", i.e., whose values are no different from keys' that
 /// aren't present in the rate limiter state store.
-impl<K, S, C> RateLimiter<K, S, C>
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
 where
     S: ShrinkableKeyedStateStore<K>,
     K: Hash,
@@ -151,6 +645,9 @@ where
     ///
     /// Any key whose rate limiting state is indistinguishable from a "fresh" state (i.e., the
     /// theoretical arrival time lies in the past).
+    ///
+    /// This is safe to call while other threads check cells: keys that were used to make a
+    /// rate-limiting decision that isn't fully replenished yet are kept.
     pub fn retain_recent(&self) {
         // calculate the minimum retention parameter: Any key whose state store's theoretical
         // arrival time is larger than a starting state for the bucket gets to stay, everything
@@ -164,21 +661,83 @@ where
     pub fn shrink_to_fit(&self) {
         self.state.shrink_to_fit();
     }
+
+    /// Resets the rate limiting states of all keys, making the full burst capacity available to
+    /// every key again.
+    ///
+    /// This is like constructing a new rate limiter, but keeps the same rate limiter, so that
+    /// references to it (e.g. in an [`Arc`][std::sync::Arc]) stay valid. It is safe to call while
+    /// other threads check cells: Each check sees a key's state either as it was before the reset,
+    /// or as a fresh state.
+    pub fn reset_all(&self) {
+        self.state.clear();
+    }
+}
+
+/// # Keyed rate limiters - Counting keys
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: CountableKeyedStateStore<K>,
+    K: Hash,
+    C: clock::Clock,
+{
+    /// Returns the number of keys that the rate limiter keeps state for.
+    ///
+    /// This includes keys whose state is indistinguishable from a fresh state, until they are
+    /// removed with [`retain_recent`][RateLimiter::retain_recent].
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    /// Returns `true` if the rate limiter keeps no state for any key.
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
 }
 
 mod hashmap;
 
 pub use hashmap::HashMapStateStore;
 
+mod lru;
+
+pub use lru::LruStateStore;
+
 #[cfg(all(feature = "std", feature = "dashmap"))]
 mod dashmap;
 
 #[cfg(all(feature = "std", feature = "dashmap"))]
 pub use self::dashmap::DashMapStateStore;
 
-#[cfg(feature = "std")]
+mod composite;
+
+pub use composite::CompositeKey;
+
+mod extractor;
+
+pub use extractor::{ExtractingRateLimiter, KeyExtractor, RequestError};
+
+mod view;
+
+pub use view::SingleKeyRateLimiter;
+
 mod future;
 
+#[cfg(feature = "std")]
+mod blocking;
+
+#[cfg(feature = "thread-cache")]
+mod thread_cached;
+
+#[cfg(feature = "thread-cache")]
+pub use self::thread_cached::ThreadCachedRateLimiter;
+
+#[cfg(feature = "redis-store")]
+mod redis;
+
+#[cfg(feature = "redis-store")]
+pub use self::redis::{RedisFailurePolicy, RedisStateStore, RedisStoreError};
+
 #[cfg(any(all(feature = "std", not(feature = "dashmap")), not(feature = "std")))]
 /// The default keyed rate limiter type: a mutex-wrapped [`HashMap`][std::collections::HashMap].
 pub type DefaultKeyedStateStore<K> = HashMapStateStore<K>;
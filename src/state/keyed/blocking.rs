@@ -0,0 +1,70 @@
+use crate::{
+    clock, middleware::RateLimitingMiddleware, state::keyed::KeyedStateStore, Jitter, RateLimiter,
+};
+use std::borrow::Borrow;
+use std::hash::Hash;
+
+/// # Keyed rate limiters - blocking
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    K: Hash + Eq + Clone,
+    S: KeyedStateStore<K>,
+    C: clock::BlockingClock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Blocks the calling thread until the rate limiter allows a cell through for the given key.
+    ///
+    /// While the rate limiter doesn't allow the cell through, this parks the calling thread for
+    /// the wait time the rate limiter returns (using the clock's
+    /// [`sleep`][clock::BlockingClock::sleep]), and then checks again; if the thread wakes up
+    /// too early, or other threads used up the key's capacity in the meantime, it goes back to
+    /// sleep. Don't call this from `async` code, where
+    /// [`until_key_ready`](#method.until_key_ready) doesn't block the executor.
+    ///
+    /// If multiple threads wait on the same key, it is advisable to use
+    /// [`until_key_ready_blocking_with_jitter`](#method.until_key_ready_blocking_with_jitter), to
+    /// avoid thundering herds.
+    ///
+    /// Like [`check_key`][RateLimiter::check_key], this takes the key in any borrowed form of the
+    /// key type.
+    pub fn until_key_ready_blocking<Q>(
+        &self,
+        key: &Q,
+    ) -> <MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        self.until_key_ready_blocking_with_jitter(key, self.jitter)
+    }
+
+    /// Blocks the calling thread until the rate limiter allows a cell through for the given key,
+    /// with a randomized wait period.
+    ///
+    /// This is similar to [`until_key_ready_blocking`](#method.until_key_ready_blocking), but
+    /// adds the jitter to each wait, which can help reduce the likelihood of thundering herd
+    /// effects if multiple threads wait on the same key.
+    pub fn until_key_ready_blocking_with_jitter<Q>(
+        &self,
+        key: &Q,
+        jitter: Jitter,
+    ) -> <MW as RateLimitingMiddleware<Q, C::Instant>>::PositiveOutcome
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        loop {
+            match self.check_key(key) {
+                Ok(x) => {
+                    return x;
+                }
+                Err(negative) => {
+                    self.clock
+                        .sleep(jitter + negative.wait_time_from(self.clock.now()));
+                }
+            }
+        }
+    }
+}
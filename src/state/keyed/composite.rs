@@ -0,0 +1,93 @@
+use std::prelude::v1::*;
+
+use std::fmt;
+
+/// A key made up of several components, e.g. a user ID and the API endpoint that they call.
+///
+/// Keyed rate limiters accept any `Hash + Eq + Clone` type as their key, so tuples like
+/// `(user_id, endpoint)` work as keys as they are; `CompositeKey` is for the cases where they
+/// don't, e.g. when the number of components varies, or when a state store (like the Redis one)
+/// needs to turn keys into strings.
+///
+/// The components are kept as their [`Display`][fmt::Display] representations. Two composite
+/// keys are the same key (and share a rate-limiting state) if and only if they have the same
+/// components in the same order. Components are compared by their representations, so `1u32` and
+/// `"1"` are the same component.
+///
+/// A composite key is displayed as its components separated by `:`, with any `:` and `\` in
+/// a component escaped by a `\`, so that keys with different components are always displayed
+/// differently.
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{state::keyed::CompositeKey, Quota, RateLimiter};
+/// let lim = RateLimiter::hashmap(Quota::per_second(nonzero!(1u32)));
+/// let key = CompositeKey::new().with(1).with("/search");
+/// assert_eq!("1:/search", key.to_string());
+/// assert_eq!(Ok(()), lim.check_key(&key));
+/// assert_ne!(Ok(()), lim.check_key(&key));
+/// assert_eq!(Ok(()), lim.check_key(&CompositeKey::new().with(1).with("/login")));
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CompositeKey {
+    components: Vec<String>,
+}
+
+impl CompositeKey {
+    /// Constructs a composite key without any components.
+    pub fn new() -> Self {
+        CompositeKey::default()
+    }
+
+    /// Appends a component to the key.
+    pub fn with(mut self, component: impl fmt::Display) -> Self {
+        self.push(component);
+        self
+    }
+
+    /// Appends a component to the key in place.
+    pub fn push(&mut self, component: impl fmt::Display) {
+        self.components.push(component.to_string());
+    }
+
+    /// Returns the key's components, in order.
+    pub fn components(&self) -> &[String] {
+        &self.components
+    }
+}
+
+impl fmt::Display for CompositeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, component) in self.components.iter().enumerate() {
+            if i > 0 {
+                f.write_str(":")?;
+            }
+            for c in component.chars() {
+                if c == ':' || c == '\\' {
+                    f.write_str("\\")?;
+                }
+                fmt::Write::write_char(f, c)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+macro_rules! composite_key_from_tuple {
+    ($($component:ident),+) => {
+        impl<$($component: fmt::Display),+> From<($($component,)+)> for CompositeKey {
+            #[allow(non_snake_case)]
+            fn from(($($component,)+): ($($component,)+)) -> Self {
+                CompositeKey::new()$(.with($component))+
+            }
+        }
+    };
+}
+
+composite_key_from_tuple!(A);
+composite_key_from_tuple!(A, B);
+composite_key_from_tuple!(A, B, C);
+composite_key_from_tuple!(A, B, C, D);
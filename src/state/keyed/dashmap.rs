@@ -2,31 +2,82 @@
 
 use std::prelude::v1::*;
 
+use crate::clock::{self, Reference};
 use crate::nanos::Nanos;
-use crate::state::keyed::ShrinkableKeyedStateStore;
+use crate::state::keyed::{
+    CountableKeyedStateStore, ShrinkableKeyedStateStore, SnapshottableKeyedStateStore,
+};
 use crate::state::{InMemoryState, StateStore};
-use crate::{clock, Quota, RateLimiter};
+use crate::{Quota, RateLimiter, StateInformation};
+use ahash::RandomState;
 use dashmap::DashMap;
-use std::hash::Hash;
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
 
 /// A concurrent, thread-safe and fairly performant hashmap based on [`DashMap`].
-pub type DashMapStateStore<K> = DashMap<K, InMemoryState>;
+///
+/// Since this is a [`DashMap`], its methods for managing the map's memory can be used directly:
+/// [`DashMapStateStore::with_capacity`][DashMap::with_capacity] preallocates room for a number
+/// of keys (see also [`RateLimiter::dashmap_with_capacity`]), and
+/// [`shrink_to_fit`][DashMap::shrink_to_fit] releases the memory of removed keys.
+///
+/// # Thread safety
+///
+/// Removing keys and shrinking the map are safe to do while other threads check cells: The map
+/// is divided into shards, and these operations lock each shard in turn. Checks of keys in the
+/// shard that is being shrunk (or filtered) wait for the lock, so they see a short latency
+/// spike, but no rate-limiting state is lost.
+///
+/// # Cold starts
+///
+/// A map that starts out empty grows (locking each shard while it does) as lots of new keys
+/// arrive at once, e.g. when clients reconnect after a restart. Constructing the rate limiter with
+/// [`dashmap_with_capacity`][RateLimiter::dashmap_with_capacity] (or passing a store made with
+/// [`DashMap::with_capacity_and_hasher`] to [`RateLimiter::new`]) preallocates room for the keys
+/// that are expected, spread evenly over the shards. The `keyed_cold_start` benchmark compares
+/// the two.
+///
+/// # Hashers
+///
+/// Like [`DashMap`], the state store hashes keys with [`ahash`](https://docs.rs/ahash) by default,
+/// seeded randomly. To use a different hasher, e.g. a keyed SipHash
+/// ([`std::collections::hash_map::RandomState`]) for keys that are under an attacker's control,
+/// give its [`BuildHasher`] as the second type parameter and construct the rate limiter with
+/// [`dashmap_with_hasher`][RateLimiter::dashmap_with_hasher].
+pub type DashMapStateStore<K, S = RandomState> = DashMap<K, InMemoryState, S>;
 
-impl<K: Hash + Eq + Clone> StateStore for DashMapStateStore<K> {
+impl<K: Hash + Eq + Clone, S: BuildHasher + Clone> StateStore for DashMapStateStore<K, S> {
     type Key = K;
 
     fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
     where
         F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.measure_and_replace_by(key, f)
+    }
+
+    fn measure_and_replace_by<Q, T, F, E>(&self, key: &Q, f: F) -> Result<T, E>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
     {
         if let Some(v) = self.get(key) {
             // fast path: measure existing entry
             return v.measure_and_replace_one(f);
         }
         // make an entry and measure that:
-        let entry = self.entry(key.clone()).or_default();
+        let entry = self.entry(key.to_owned()).or_default();
         (*entry).measure_and_replace_one(f)
     }
+
+    fn peek(&self, key: &Self::Key) -> Option<Nanos> {
+        self.get(key).and_then(|v| v.peek_one())
+    }
+
+    fn reset(&self, key: &Self::Key) {
+        self.remove(key);
+    }
 }
 
 /// # Keyed rate limiters - [`DashMap`]-backed
@@ -43,7 +94,131 @@ where
     }
 }
 
-impl<K: Hash + Eq + Clone> ShrinkableKeyedStateStore<K> for DashMapStateStore<K> {
+impl<K, H> RateLimiter<K, DashMapStateStore<K, H>, clock::DefaultClock>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher + Clone,
+{
+    /// Constructs a new keyed rate limiter backed by a [`DashMap`][dashmap::DashMap] that hashes
+    /// keys with the given `hasher` (see [`DashMapStateStore`]).
+    pub fn dashmap_with_hasher(quota: Quota, hasher: H) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::dashmap_with_hasher_and_clock(quota, hasher, &clock)
+    }
+}
+
+impl<K, H, C> RateLimiter<K, DashMapStateStore<K, H>, C>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher + Clone,
+    C: clock::Clock,
+{
+    /// Constructs a new keyed rate limiter with a custom clock, backed by a
+    /// [`DashMap`][dashmap::DashMap] that hashes keys with the given `hasher` (see
+    /// [`DashMapStateStore`]).
+    pub fn dashmap_with_hasher_and_clock(quota: Quota, hasher: H, clock: &C) -> Self {
+        RateLimiter::new(quota, DashMap::with_hasher(hasher), clock)
+    }
+}
+
+impl<K> RateLimiter<K, DashMapStateStore<K>, clock::DefaultClock>
+where
+    K: Hash + Eq + Clone,
+{
+    /// Constructs a new keyed rate limiter backed by a [`DashMap`][dashmap::DashMap] that has
+    /// room for at least `capacity` keys before it needs to grow.
+    pub fn dashmap_with_capacity(quota: Quota, capacity: usize) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::dashmap_with_capacity_and_clock(quota, capacity, &clock)
+    }
+}
+
+impl<K, C> RateLimiter<K, DashMapStateStore<K>, C>
+where
+    K: Hash + Eq + Clone,
+    C: clock::Clock,
+{
+    /// Constructs a new rate limiter with a custom clock, backed by a
+    /// [`DashMap`][dashmap::DashMap] that has room for at least `capacity` keys before it needs
+    /// to grow.
+    pub fn dashmap_with_capacity_and_clock(quota: Quota, capacity: usize, clock: &C) -> Self {
+        RateLimiter::new(quota, DashMap::with_capacity(capacity), clock)
+    }
+}
+
+impl<K, H, C, MW> RateLimiter<K, DashMapStateStore<K, H>, C, MW>
+where
+    K: Hash + Eq + Clone,
+    H: BuildHasher + Clone,
+    C: clock::Clock,
+{
+    /// Retains only the keys for which `keep` returns `true`, given the key and information
+    /// about its rate-limiting state; the states of all other keys are removed, as if they had
+    /// never been checked.
+    ///
+    /// This is a more general version of [`retain_recent`][RateLimiter::retain_recent], which
+    /// removes the keys whose state is indistinguishable from a fresh one. See
+    /// [`DashMapStateStore`] for how this interacts with concurrent checks. `keep` is called
+    /// while the shard holding the key is locked, so it must not use the rate limiter.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{Quota, RateLimiter};
+    /// # use nonzero_ext::nonzero;
+    /// let lim = RateLimiter::dashmap(Quota::per_minute(nonzero!(10u32)));
+    /// for key in 0..10u32 {
+    ///     lim.check_key(&key).unwrap();
+    /// }
+    /// // Forget the keys that still have at least half of their burst capacity left:
+    /// lim.retain_keys(|_key, state| state.remaining_ratio() < 0.5);
+    /// assert!(lim.is_empty());
+    /// ```
+    pub fn retain_keys<F>(&self, mut keep: F)
+    where
+        F: FnMut(&K, StateInformation) -> bool,
+    {
+        let now = self.clock.now().duration_since(self.start);
+        self.state.retain(|key, state| {
+            let info = self.gcra.state_information(state.peek_one(), now);
+            keep(key, info)
+        });
+    }
+}
+
+impl<K: Hash + Eq + Clone, S: BuildHasher + Clone> SnapshottableKeyedStateStore<K>
+    for DashMapStateStore<K, S>
+{
+    fn snapshot(&self) -> Vec<(K, Nanos)> {
+        self.iter()
+            .filter_map(|entry| {
+                entry
+                    .value()
+                    .peek_one()
+                    .map(|tat| (entry.key().clone(), tat))
+            })
+            .collect()
+    }
+
+    fn restore(&self, key: K, state: Nanos) {
+        self.insert(key, InMemoryState::new(state));
+    }
+}
+
+impl<K: Hash + Eq + Clone, S: BuildHasher + Clone> CountableKeyedStateStore<K>
+    for DashMapStateStore<K, S>
+{
+    fn len(&self) -> usize {
+        DashMap::len(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        DashMap::is_empty(self)
+    }
+}
+
+impl<K: Hash + Eq + Clone, S: BuildHasher + Clone> ShrinkableKeyedStateStore<K>
+    for DashMapStateStore<K, S>
+{
     fn retain_recent(&self, drop_below: Nanos) {
         self.retain(|_, v| !v.is_older_than(drop_below));
     }
@@ -51,4 +226,8 @@ impl<K: Hash + Eq + Clone> ShrinkableKeyedStateStore<K> for DashMapStateStore<K>
     fn shrink_to_fit(&self) {
         self.shrink_to_fit();
     }
+
+    fn clear(&self) {
+        DashMap::clear(self);
+    }
 }
@@ -0,0 +1,162 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::middleware::RateLimitingMiddleware;
+use crate::state::keyed::KeyedStateStore;
+use crate::state::StateStore;
+use crate::{clock, RateLimiter};
+use std::fmt;
+use std::hash::Hash;
+
+/// A way to determine the key that a request is rate-limited under, e.g. a client's API key
+/// from a request header.
+///
+/// Extracting a key can fail (e.g. when the header is missing), with an error that the caller
+/// handles. Closures taking a reference to a request and returning a `Result` are key
+/// extractors.
+pub trait KeyExtractor<Req> {
+    /// The key that requests are rate-limited under.
+    type Key;
+
+    /// The error that extracting a key fails with.
+    type Error;
+
+    /// Returns the key for a request.
+    fn extract(&self, req: &Req) -> Result<Self::Key, Self::Error>;
+}
+
+impl<Req, K, E, F> KeyExtractor<Req> for F
+where
+    F: Fn(&Req) -> Result<K, E>,
+{
+    type Key = K;
+    type Error = E;
+
+    fn extract(&self, req: &Req) -> Result<K, E> {
+        self(req)
+    }
+}
+
+/// The negative outcome of checking a request against an [`ExtractingRateLimiter`].
+#[derive(Debug, PartialEq)]
+pub enum RequestError<'a, E, P: clock::Reference> {
+    /// The request's key could not be extracted. No rate-limiting decision was made.
+    KeyExtraction(E),
+
+    /// The request's key was extracted, and the rate limiter did not allow a cell through for
+    /// it.
+    RateLimited(NotUntil<'a, P>),
+}
+
+impl<'a, E: fmt::Display, P: clock::Reference> fmt::Display for RequestError<'a, E, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::KeyExtraction(e) => write!(f, "could not extract the key: {}", e),
+            RequestError::RateLimited(negative) => fmt::Display::fmt(negative, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, E, P> std::error::Error for RequestError<'a, E, P>
+where
+    E: std::error::Error + 'static,
+    P: clock::Reference,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RequestError::KeyExtraction(e) => Some(e),
+            RequestError::RateLimited(_) => None,
+        }
+    }
+}
+
+/// A keyed rate limiter that checks requests under the keys that a [`KeyExtractor`] extracts
+/// from them.
+///
+/// Construct one with [`RateLimiter::with_key_extractor`].
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{state::keyed::RequestError, Quota, RateLimiter};
+/// struct Request {
+///     api_key: Option<&'static str>,
+/// }
+///
+/// let lim = RateLimiter::keyed(Quota::per_second(nonzero!(1u32)))
+///     .with_key_extractor(|req: &Request| req.api_key.ok_or("missing API key"));
+/// let req = Request { api_key: Some("abc") };
+/// assert_eq!(Ok(()), lim.check(&req));
+/// assert!(matches!(lim.check(&req), Err(RequestError::RateLimited(_))));
+/// let anonymous = Request { api_key: None };
+/// assert_eq!(Err(RequestError::KeyExtraction("missing API key")), lim.check(&anonymous));
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct ExtractingRateLimiter<X, K, S, C, MW>
+where
+    S: StateStore<Key = K>,
+    C: clock::Clock,
+{
+    limiter: RateLimiter<K, S, C, MW>,
+    extractor: X,
+}
+
+/// # Keyed rate limiters - Extracting keys from requests
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    K: Hash + Eq + Clone,
+    S: KeyedStateStore<K>,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Wraps the rate limiter so that it checks requests under the keys that `extractor`
+    /// extracts from them (see [`ExtractingRateLimiter`]).
+    pub fn with_key_extractor<X>(self, extractor: X) -> ExtractingRateLimiter<X, K, S, C, MW> {
+        ExtractingRateLimiter {
+            limiter: self,
+            extractor,
+        }
+    }
+}
+
+impl<X, K, S, C, MW> ExtractingRateLimiter<X, K, S, C, MW>
+where
+    K: Hash + Eq + Clone,
+    S: KeyedStateStore<K>,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Extracts the request's key and allows a single cell through the rate limiter for it.
+    ///
+    /// If the key can't be extracted, `check` returns the extractor's error, without making a
+    /// rate-limiting decision. If the rate limit is reached for the key, it returns information
+    /// about the earliest time that a cell might be allowed through again.
+    pub fn check<Req>(
+        &self,
+        req: &Req,
+    ) -> Result<MW::PositiveOutcome, RequestError<'_, X::Error, C::Instant>>
+    where
+        X: KeyExtractor<Req, Key = K>,
+    {
+        let key = self
+            .extractor
+            .extract(req)
+            .map_err(RequestError::KeyExtraction)?;
+        self.limiter
+            .check_key(&key)
+            .map_err(RequestError::RateLimited)
+    }
+
+    /// Returns the rate limiter that requests are checked against.
+    pub fn limiter(&self) -> &RateLimiter<K, S, C, MW> {
+        &self.limiter
+    }
+
+    /// Returns the key extractor.
+    pub fn extractor(&self) -> &X {
+        &self.extractor
+    }
+}
@@ -1,20 +1,27 @@
 use std::prelude::v1::*;
 
 use crate::{
-    clock::{self},
-    state::keyed::KeyedStateStore,
-    Jitter, RateLimiter,
+    clock::{self, Reference},
+    middleware::RateLimitingMiddleware,
+    state::direct::future::delay_within,
+    state::{keyed::KeyedStateStore, InsufficientCapacity, TimedOut},
+    Jitter, NegativeMultiDecision, RateLimiter,
 };
-use futures_timer::Delay;
+use std::borrow::Borrow;
 use std::hash::Hash;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+/// The positive outcome that the middleware `MW` reports for keys given in the form `Q`.
+type PositiveOutcome<MW, Q, P> = <MW as RateLimitingMiddleware<Q, P>>::PositiveOutcome;
 
-#[cfg(feature = "std")]
 /// # Keyed rate limiters - `async`/`await`
-impl<K, S, C> RateLimiter<K, S, C>
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
 where
     K: Hash + Eq + Clone,
     S: KeyedStateStore<K>,
-    C: clock::ReasonablyRealtime,
+    C: clock::AsyncClock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
 {
     /// Asynchronously resolves as soon as the rate limiter allows it.
     ///
@@ -25,8 +32,18 @@ where
     ///
     /// If multiple futures are dispatched against the rate limiter, it is advisable to use
     /// [`until_ready_with_jitter`](#method.until_ready_with_jitter), to avoid thundering herds.
-    pub async fn until_key_ready(&self, key: &K) {
-        self.until_key_ready_with_jitter(key, Jitter::NONE).await;
+    /// The future waits with the rate limiter's [default jitter][RateLimiter::with_default_jitter]
+    /// (if it has one, e.g. through [`keyed_with_jitter`][RateLimiter::keyed_with_jitter]).
+    ///
+    /// Like [`check_key`][RateLimiter::check_key], this takes the key in any borrowed form of the
+    /// key type.
+    pub async fn until_key_ready<Q>(&self, key: &Q) -> PositiveOutcome<MW, Q, C::Instant>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        self.until_key_ready_with_jitter(key, self.jitter).await
     }
 
     /// Asynchronously resolves as soon as the rate limiter allows it, with a randomized wait
@@ -39,11 +56,203 @@ where
     ///
     /// This method allows for a randomized additional delay between polls of the rate limiter,
     /// which can help reduce the likelihood of thundering herd effects if multiple tasks try to
-    /// wait on the same rate limiter.
-    pub async fn until_key_ready_with_jitter(&self, key: &K, jitter: Jitter) {
-        while let Err(negative) = self.check_key(key) {
-            let delay = Delay::new(jitter + negative.wait_time_from(self.clock.now()));
-            delay.await;
+    /// wait on the same rate limiter. The given jitter is used instead of the rate limiter's
+    /// [default jitter][RateLimiter::with_default_jitter], not in addition to it.
+    pub async fn until_key_ready_with_jitter<Q>(
+        &self,
+        key: &Q,
+        jitter: Jitter,
+    ) -> PositiveOutcome<MW, Q, C::Instant>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        loop {
+            match self.check_key(key) {
+                Ok(x) => {
+                    return x;
+                }
+                Err(negative) => {
+                    let delay = self
+                        .clock
+                        .delay(jitter + negative.wait_time_from(self.clock.now()));
+                    delay.await;
+                }
+            }
+        }
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows `n` cells through for the given
+    /// key.
+    ///
+    /// This is similar to [`until_key_ready`](#method.until_key_ready) except it waits for an
+    /// arbitrary number of `n` cells to be available.
+    ///
+    /// Returns `InsufficientCapacity` right away if `n` exceeds the maximum capacity of the rate
+    /// limiter, since waiting would never make that many cells available.
+    pub async fn until_key_n_ready<Q>(
+        &self,
+        key: &Q,
+        n: NonZeroU32,
+    ) -> Result<PositiveOutcome<MW, Q, C::Instant>, InsufficientCapacity>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        self.until_key_n_ready_with_jitter(key, n, self.jitter)
+            .await
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows `cost` cells through for the
+    /// given key, where `cost` is computed at runtime and may be zero (see
+    /// [`check_key_cost`](#method.check_key_cost)).
+    ///
+    /// A zero cost resolves right away without consulting the rate limiter or its middleware,
+    /// returning `Ok(None)`. Any other cost waits like
+    /// [`until_key_n_ready`](#method.until_key_n_ready) does, returning the middleware's positive
+    /// outcome as `Ok(Some(_))`.
+    pub async fn until_key_cost_ready<Q>(
+        &self,
+        key: &Q,
+        cost: u32,
+    ) -> Result<Option<PositiveOutcome<MW, Q, C::Instant>>, InsufficientCapacity>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        match NonZeroU32::new(cost) {
+            Some(n) => self.until_key_n_ready(key, n).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows `n` cells through for the given
+    /// key, with a randomized wait period.
+    ///
+    /// This is similar to [`until_key_ready_with_jitter`](#method.until_key_ready_with_jitter)
+    /// except it waits for an arbitrary number of `n` cells to be available: Each delay lasts
+    /// until the key has replenished enough capacity for all `n` cells, plus the jitter.
+    ///
+    /// Returns `InsufficientCapacity` right away if `n` exceeds the maximum capacity of the rate
+    /// limiter, since waiting would never make that many cells available.
+    pub async fn until_key_n_ready_with_jitter<Q>(
+        &self,
+        key: &Q,
+        n: NonZeroU32,
+        jitter: Jitter,
+    ) -> Result<PositiveOutcome<MW, Q, C::Instant>, InsufficientCapacity>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        loop {
+            match self.check_key_n(key, n) {
+                Ok(x) => {
+                    return Ok(x);
+                }
+                Err(NegativeMultiDecision::BatchNonConforming(_, negative)) => {
+                    let delay = self
+                        .clock
+                        .delay(jitter + negative.wait_time_from(self.clock.now()));
+                    delay.await;
+                }
+                Err(NegativeMultiDecision::InsufficientCapacity {
+                    requested,
+                    max_burst,
+                }) => {
+                    return Err(InsufficientCapacity {
+                        requested,
+                        max_burst,
+                    })
+                }
+            }
+        }
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows a cell through for all of the
+    /// given keys, charging each of them for it.
+    ///
+    /// The keys are checked together, like [`check_keys`](#method.check_keys) does: No key is
+    /// charged until all of them allow the cell through, so waiting on one key doesn't hold on
+    /// to the capacity of the others. While any key rejects the cell, the future waits until the
+    /// earliest time at which all keys might allow it, so it takes as long as the slowest key's
+    /// wait rather than the sum of all of them.
+    ///
+    /// Like [`check_keys`](#method.check_keys), this takes the keys in their owned form.
+    pub async fn until_keys_ready(&self, keys: &[K]) {
+        loop {
+            match self.check_keys(keys) {
+                Ok(()) => {
+                    return;
+                }
+                Err(negative) => {
+                    let delay = self.clock.delay(negative.wait_time_from(self.clock.now()));
+                    delay.await;
+                }
+            }
+        }
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows a key through, unless that takes
+    /// longer than `timeout`.
+    ///
+    /// If the rate limiter requires waiting longer than the time that remains until the timeout
+    /// expires, the returned future resolves to a [`TimedOut`] error immediately, instead of
+    /// waiting in vain. Giving up does not use up any of the key's capacity: A cell is only
+    /// counted once the future resolves successfully.
+    pub async fn until_key_ready_with_timeout<Q>(
+        &self,
+        key: &Q,
+        timeout: Duration,
+    ) -> Result<PositiveOutcome<MW, Q, C::Instant>, TimedOut>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        self.until_key_ready_with_jitter_and_timeout(key, self.jitter, timeout)
+            .await
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows a key through, with a
+    /// randomized wait period, unless that takes longer than `timeout`.
+    ///
+    /// This is similar to [`until_key_ready_with_timeout`](#method.until_key_ready_with_timeout),
+    /// and adds jitter to the delays like
+    /// [`until_key_ready_with_jitter`](#method.until_key_ready_with_jitter) does. Delays that
+    /// would expire after the timeout are shortened to end at the timeout instead.
+    pub async fn until_key_ready_with_jitter_and_timeout<Q>(
+        &self,
+        key: &Q,
+        jitter: Jitter,
+        timeout: Duration,
+    ) -> Result<PositiveOutcome<MW, Q, C::Instant>, TimedOut>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        MW: RateLimitingMiddleware<Q, C::Instant>,
+    {
+        let started = self.clock.now();
+        loop {
+            match self.check_key(key) {
+                Ok(x) => {
+                    return Ok(x);
+                }
+                Err(negative) => {
+                    let now = self.clock.now();
+                    let spent: Duration = now.duration_since(started).into();
+                    let wait = delay_within(
+                        negative.wait_time_from(now),
+                        jitter,
+                        timeout.saturating_sub(spent),
+                    )?;
+                    self.clock.delay(wait).await;
+                }
+            }
         }
     }
 }
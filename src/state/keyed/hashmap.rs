@@ -3,10 +3,13 @@ use std::prelude::v1::*;
 use crate::nanos::Nanos;
 use crate::state::{InMemoryState, StateStore};
 use crate::{clock, Quota, RateLimiter};
+use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::hash::Hash;
 
-use crate::state::keyed::ShrinkableKeyedStateStore;
+use crate::state::keyed::{
+    CountableKeyedStateStore, ShrinkableKeyedStateStore, SnapshottableKeyedStateStore,
+};
 #[cfg(feature = "std")]
 use parking_lot::Mutex;
 #[cfg(not(feature = "std"))]
@@ -25,6 +28,15 @@ impl<K: Hash + Eq + Clone> StateStore for HashMapStateStore<K> {
     fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
     where
         F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.measure_and_replace_by(key, f)
+    }
+
+    fn measure_and_replace_by<Q, T, F, E>(&self, key: &Q, f: F) -> Result<T, E>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
     {
         let mut map = self.lock();
         if let Some(v) = (*map).get(key) {
@@ -32,11 +44,40 @@ impl<K: Hash + Eq + Clone> StateStore for HashMapStateStore<K> {
             return v.measure_and_replace_one(f);
         }
         // not-so-fast path: make a new entry and measure it.
-        let entry = (*map)
-            .entry(key.clone())
-            .or_insert_with(InMemoryState::default);
+        let entry = (*map).entry(key.to_owned()).or_default();
         entry.measure_and_replace_one(f)
     }
+
+    fn peek(&self, key: &Self::Key) -> Option<Nanos> {
+        self.lock().get(key).and_then(InMemoryState::peek_one)
+    }
+
+    fn reset(&self, key: &Self::Key) {
+        self.lock().remove(key);
+    }
+}
+
+impl<K: Hash + Eq + Clone> SnapshottableKeyedStateStore<K> for HashMapStateStore<K> {
+    fn snapshot(&self) -> Vec<(K, Nanos)> {
+        let map = self.lock();
+        map.iter()
+            .filter_map(|(key, state)| state.peek_one().map(|tat| (key.clone(), tat)))
+            .collect()
+    }
+
+    fn restore(&self, key: K, state: Nanos) {
+        self.lock().insert(key, InMemoryState::new(state));
+    }
+}
+
+impl<K: Hash + Eq + Clone> CountableKeyedStateStore<K> for HashMapStateStore<K> {
+    fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
 }
 
 impl<K: Hash + Eq + Clone> ShrinkableKeyedStateStore<K> for HashMapStateStore<K> {
@@ -49,6 +90,10 @@ impl<K: Hash + Eq + Clone> ShrinkableKeyedStateStore<K> for HashMapStateStore<K>
         let mut map = self.lock();
         map.shrink_to_fit();
     }
+
+    fn clear(&self) {
+        self.lock().clear();
+    }
 }
 
 /// # Keyed rate limiters - [`HashMap`]-backed
@@ -0,0 +1,349 @@
+use std::prelude::v1::*;
+
+use crate::nanos::Nanos;
+use crate::state::keyed::{
+    CountableKeyedStateStore, ShrinkableKeyedStateStore, SnapshottableKeyedStateStore,
+};
+use crate::state::{InMemoryState, StateStore};
+use crate::{clock, Quota, RateLimiter};
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+
+#[cfg(feature = "std")]
+use parking_lot::Mutex;
+#[cfg(not(feature = "std"))]
+use spin::Mutex;
+
+/// A key's state, along with the tick at which the key was last checked.
+#[derive(Debug)]
+struct Entry {
+    state: InMemoryState,
+    used: u64,
+}
+
+/// The keys' states, along with the order in which the keys were last checked.
+#[derive(Debug)]
+struct LruMap<K> {
+    states: HashMap<K, Entry>,
+    recency: BTreeMap<u64, K>,
+    tick: u64,
+}
+
+impl<K: Hash + Eq + Clone> LruMap<K> {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Removes the least recently checked keys until there is room for one more key, and
+    /// returns the keys that were removed.
+    fn make_room(&mut self, capacity: NonZeroUsize) -> Vec<K> {
+        let mut evicted = Vec::new();
+        while self.states.len() >= capacity.get() {
+            match self.recency.pop_first() {
+                Some((_, oldest)) => {
+                    self.states.remove(&oldest);
+                    evicted.push(oldest);
+                }
+                None => break,
+            }
+        }
+        evicted
+    }
+
+    /// Makes a key that has a state the most recently checked key.
+    fn touch<Q>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let used = self.next_tick();
+        if let Some(entry) = self.states.get_mut(key) {
+            if let Some(key) = self.recency.remove(&entry.used) {
+                self.recency.insert(used, key);
+            }
+            entry.used = used;
+        }
+    }
+
+    /// Inserts a state for a key that has none, making it the most recently checked key, and
+    /// returns the keys that were evicted to make room for it.
+    fn insert(&mut self, key: K, state: InMemoryState, capacity: NonZeroUsize) -> Vec<K> {
+        let evicted = self.make_room(capacity);
+        let used = self.next_tick();
+        self.recency.insert(used, key.clone());
+        self.states.insert(key, Entry { state, used });
+        evicted
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.states.remove(key) {
+            self.recency.remove(&entry.used);
+        }
+    }
+}
+
+/// A hook that is called with the keys that an [`LruStateStore`] evicts.
+type EvictionHook<K> = Box<dyn Fn(&K) + Send + Sync>;
+
+/// A thread-safe keyed state store that holds the states of at most a fixed number of keys,
+/// backed by a [`HashMap`].
+///
+/// When a key that the store has no state for is checked while the store is full, the state of
+/// the least recently checked key is evicted to make room. This bounds the memory that a keyed
+/// rate limiter uses, even if the keys it sees are under an attacker's control (e.g. spoofed IP
+/// addresses).
+///
+/// # Evicted keys start fresh
+///
+/// A key whose state was evicted is treated like a key the rate limiter has never seen: The next
+/// cell for it is checked against a fresh state, with its full burst capacity available. A client
+/// that can make the store evict its key (by sending cells under enough other keys) can
+/// therefore reset its own rate limit. The capacity should be large enough that the keys of
+/// well-behaved clients don't get evicted while their state still matters, i.e. within the
+/// quota's [`burst_size_replenished_in`][Quota::burst_size_replenished_in] of their last check.
+///
+/// Checking a key that has a state (whether the cell is allowed through or not) makes it the most
+/// recently checked key, so that clients over their limit can't get their state evicted by
+/// checking it more; reading its state, e.g. with
+/// [`available_for_key`][RateLimiter::available_for_key], does not. A key that has no state only
+/// gets one (possibly evicting another key's) if its cell is allowed through: Cells that are
+/// rejected under new keys can't evict the states of other keys.
+///
+/// All operations take a lock on the entire store, so this performs like a
+/// [`HashMapStateStore`][crate::state::keyed::HashMapStateStore].
+///
+/// # Observing evictions
+///
+/// A hook set with [`on_evict`][LruStateStore::on_evict] is called with every key whose state
+/// the store drops on its own: Keys evicted to make room for another key, and keys removed by
+/// [`retain_recent`][RateLimiter::retain_recent]. Keys whose states are removed explicitly (by
+/// [`reset_key`][RateLimiter::reset_key] or [`reset_all`][RateLimiter::reset_all]) aren't
+/// reported.
+pub struct LruStateStore<K> {
+    capacity: NonZeroUsize,
+    map: Mutex<LruMap<K>>,
+    on_evict: Option<EvictionHook<K>>,
+}
+
+impl<K> LruStateStore<K> {
+    /// Constructs an empty state store that holds the states of at most `capacity` keys.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        LruStateStore {
+            capacity,
+            map: Mutex::new(LruMap {
+                states: HashMap::new(),
+                recency: BTreeMap::new(),
+                tick: 0,
+            }),
+            on_evict: None,
+        }
+    }
+
+    /// Sets a hook that the store calls with every key whose state it evicts (see
+    /// [Observing evictions](#observing-evictions)), replacing any previous hook.
+    ///
+    /// The hook is called on the thread whose check (or `retain_recent` call) evicted the key,
+    /// after the store's lock is released, so it may use the rate limiter again (e.g. to check
+    /// the evicted key). A concurrent check may therefore have given the key a fresh state by
+    /// the time the hook is called.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use governor::{clock::FakeRelativeClock, state::keyed::LruStateStore, Quota, RateLimiter};
+    /// # use nonzero_ext::nonzero;
+    /// # use std::num::NonZeroUsize;
+    /// let store = LruStateStore::new(NonZeroUsize::new(1).unwrap())
+    ///     .on_evict(|key: &&str| println!("forgot about {}", key));
+    /// let lim = RateLimiter::new(
+    ///     Quota::per_second(nonzero!(1u32)),
+    ///     store,
+    ///     &FakeRelativeClock::default(),
+    /// );
+    /// lim.check_key(&"alice").unwrap();
+    /// lim.check_key(&"bob").unwrap(); // prints "forgot about alice"
+    /// ```
+    pub fn on_evict<F>(self, hook: F) -> Self
+    where
+        F: Fn(&K) + Send + Sync + 'static,
+    {
+        LruStateStore {
+            on_evict: Some(Box::new(hook)),
+            ..self
+        }
+    }
+
+    /// Returns the maximum number of keys that the state store holds states for.
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    /// Reports the evicted keys to the hook, if there is one.
+    fn report(&self, evicted: Vec<K>) {
+        if let Some(hook) = &self.on_evict {
+            for key in &evicted {
+                hook(key);
+            }
+        }
+    }
+}
+
+impl<K> fmt::Debug for LruStateStore<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("LruStateStore")
+            .field("capacity", &self.capacity)
+            .field("len", &self.map.lock().states.len())
+            .field("on_evict", &self.on_evict.is_some())
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone> StateStore for LruStateStore<K> {
+    type Key = K;
+
+    fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.measure_and_replace_by(key, f)
+    }
+
+    fn measure_and_replace_by<Q, T, F, E>(&self, key: &Q, f: F) -> Result<T, E>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        let (result, evicted) = {
+            let mut map = self.map.lock();
+            let existing = map
+                .states
+                .get(key)
+                .map(|entry| entry.state.measure_and_replace_one(&f));
+            match existing {
+                Some(result) => {
+                    map.touch(key);
+                    (result, Vec::new())
+                }
+                // Only keys whose cells are allowed get a state, so that rejected cells can't
+                // evict other keys' states:
+                None => match f(None) {
+                    Ok((result, tat)) => {
+                        let state = InMemoryState::new(tat);
+                        (Ok(result), map.insert(key.to_owned(), state, self.capacity))
+                    }
+                    Err(e) => (Err(e), Vec::new()),
+                },
+            }
+        };
+        self.report(evicted);
+        result
+    }
+
+    fn peek(&self, key: &Self::Key) -> Option<Nanos> {
+        self.map
+            .lock()
+            .states
+            .get(key)
+            .and_then(|entry| entry.state.peek_one())
+    }
+
+    fn reset(&self, key: &Self::Key) {
+        self.map.lock().remove(key);
+    }
+}
+
+impl<K: Hash + Eq + Clone> SnapshottableKeyedStateStore<K> for LruStateStore<K> {
+    fn snapshot(&self) -> Vec<(K, Nanos)> {
+        let map = self.map.lock();
+        // Least recently checked keys first, so that restoring the snapshot keeps their order:
+        map.recency
+            .values()
+            .filter_map(|key| {
+                let tat = map.states[key].state.peek_one()?;
+                Some((key.clone(), tat))
+            })
+            .collect()
+    }
+
+    fn restore(&self, key: K, state: Nanos) {
+        let evicted = {
+            let mut map = self.map.lock();
+            map.remove(&key);
+            map.insert(key, InMemoryState::new(state), self.capacity)
+        };
+        self.report(evicted);
+    }
+}
+
+impl<K: Hash + Eq + Clone> CountableKeyedStateStore<K> for LruStateStore<K> {
+    fn len(&self) -> usize {
+        self.map.lock().states.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.map.lock().states.is_empty()
+    }
+}
+
+impl<K: Hash + Eq + Clone> ShrinkableKeyedStateStore<K> for LruStateStore<K> {
+    fn retain_recent(&self, drop_below: Nanos) {
+        let mut evicted = Vec::new();
+        {
+            let mut guard = self.map.lock();
+            let map = &mut *guard;
+            let recency = &mut map.recency;
+            map.states.retain(|key, entry| {
+                let keep = !entry.state.is_older_than(drop_below);
+                if !keep {
+                    recency.remove(&entry.used);
+                    if self.on_evict.is_some() {
+                        evicted.push(key.clone());
+                    }
+                }
+                keep
+            });
+        }
+        self.report(evicted);
+    }
+
+    fn shrink_to_fit(&self) {
+        self.map.lock().states.shrink_to_fit();
+    }
+
+    fn clear(&self) {
+        let mut map = self.map.lock();
+        map.states.clear();
+        map.recency.clear();
+    }
+}
+
+/// # Keyed rate limiters - LRU-bounded
+impl<K> RateLimiter<K, LruStateStore<K>, clock::DefaultClock>
+where
+    K: Hash + Eq + Clone,
+{
+    /// Constructs a new keyed rate limiter that holds the states of at most `capacity` keys,
+    /// evicting the least recently checked key's state when it needs to make room (see
+    /// [`LruStateStore`]).
+    pub fn lru(quota: Quota, capacity: NonZeroUsize) -> Self {
+        let clock = clock::DefaultClock::default();
+        Self::lru_with_clock(quota, capacity, &clock)
+    }
+}
+
+impl<K, C> RateLimiter<K, LruStateStore<K>, C>
+where
+    K: Hash + Eq + Clone,
+    C: clock::Clock,
+{
+    /// Constructs a new keyed rate limiter with a custom clock that holds the states of at most
+    /// `capacity` keys, evicting the least recently checked key's state when it needs to make
+    /// room (see [`LruStateStore`]).
+    pub fn lru_with_clock(quota: Quota, capacity: NonZeroUsize, clock: &C) -> Self {
+        RateLimiter::new(quota, LruStateStore::new(capacity), clock)
+    }
+}
@@ -0,0 +1,316 @@
+#![cfg(feature = "redis-store")]
+
+use std::prelude::v1::*;
+
+use crate::clock::SystemClock;
+use crate::nanos::Nanos;
+use crate::state::StateStore;
+use crate::{Quota, RateLimiter};
+use redis::Script;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// Converts a state as stored in Redis (where zero means that the key has no state) to [`Nanos`].
+///
+/// States are stored as decimal numbers of nanoseconds, so they can be as wide as [`Nanos`].
+fn state_from(stored: u128) -> Option<Nanos> {
+    if stored == 0 {
+        None
+    } else {
+        Some(Nanos::saturating_from_u128(stored))
+    }
+}
+
+/// Replaces the value at `KEYS[1]` with `ARGV[2]` if it is still `ARGV[1]` (the empty string
+/// meaning that the key does not exist), expiring it after `ARGV[3]` milliseconds (if given).
+///
+/// Returns `{1, new_value}` if the value was replaced, and `{0, current_value}` otherwise.
+const COMPARE_AND_SET: &str = r"
+local current = redis.call('GET', KEYS[1]) or ''
+if current ~= ARGV[1] then
+  return {0, current}
+end
+if ARGV[3] == '' then
+  redis.call('SET', KEYS[1], ARGV[2])
+else
+  redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+end
+return {1, ARGV[2]}
+";
+
+/// An error that a [`RedisStateStore`] encountered while talking to Redis.
+#[derive(Debug)]
+pub enum RedisStoreError {
+    /// No connection could be taken from the connection pool.
+    Pool(r2d2::Error),
+
+    /// Redis returned an error, or the connection to it failed.
+    Redis(redis::RedisError),
+}
+
+impl fmt::Display for RedisStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            RedisStoreError::Pool(e) => write!(f, "could not get a redis connection: {}", e),
+            RedisStoreError::Redis(e) => write!(f, "redis error: {}", e),
+        }
+    }
+}
+
+impl Error for RedisStoreError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RedisStoreError::Pool(e) => Some(e),
+            RedisStoreError::Redis(e) => Some(e),
+        }
+    }
+}
+
+impl From<r2d2::Error> for RedisStoreError {
+    fn from(e: r2d2::Error) -> Self {
+        RedisStoreError::Pool(e)
+    }
+}
+
+impl From<redis::RedisError> for RedisStoreError {
+    fn from(e: redis::RedisError) -> Self {
+        RedisStoreError::Redis(e)
+    }
+}
+
+/// What a [`RedisStateStore`] decides for cells that it can't check because it can't reach Redis.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum RedisFailurePolicy {
+    /// Reject the cell, as if the key's rate limit was reached. This is the default, so that an
+    /// outage of Redis doesn't turn off rate limiting.
+    ///
+    /// The rejection can't know when Redis will be reachable again, so its
+    /// [`NotUntil`][crate::NotUntil] reports the longest possible wait; callers should retry
+    /// after a backoff of their own rather than relying on it.
+    #[default]
+    FailClosed,
+
+    /// Allow the cell through as if the key was fresh, without updating its state. This keeps the
+    /// protected service available while Redis is down, but doesn't limit its use at all.
+    FailOpen,
+}
+
+/// A keyed state store that keeps rate-limiting states in Redis, so that they can be shared by
+/// rate limiters in several processes.
+///
+/// Each key's state is stored under the Redis key made from the store's prefix followed by the
+/// key's [`Display`][fmt::Display] representation. States are updated with a compare-and-set Lua
+/// script, so rate-limiting decisions are consistent across all processes using the same Redis
+/// keys.
+///
+/// ## Clocks
+/// The rate-limiting decisions are made using the *local* clock of the
+/// [`RateLimiter`]; Redis server time is not used. All rate limiters sharing a Redis state store
+/// must measure time from the same starting point, which is what
+/// [`RateLimiter::redis`] does (it uses the [`SystemClock`], measuring from the UNIX epoch).
+/// Their system clocks need to be kept in sync (e.g. via NTP): clock skew between processes
+/// makes a process's decisions as lenient or as strict as its clock is behind or ahead.
+///
+/// ## Errors
+/// Since [`StateStore`] methods can not fail, a `RedisStateStore` that can't reach Redis decides
+/// according to its [failure policy][RedisStateStore::with_failure_policy]: By default, it "fails
+/// closed" and rejects the cell, so that an outage of Redis doesn't turn off rate limiting. The
+/// policy also applies to methods that only look at a key's state, like
+/// [`available_for_key`][RateLimiter::available_for_key], which reports no capacity while the
+/// store fails closed. The error is passed to the handler set with
+/// [`on_error`][RedisStateStore::on_error], so that the outage can be noticed either way.
+pub struct RedisStateStore<K> {
+    pool: r2d2::Pool<redis::Client>,
+    prefix: String,
+    ttl: Option<Duration>,
+    script: Script,
+    on_error: Box<dyn Fn(RedisStoreError) + Send + Sync>,
+    failure_policy: RedisFailurePolicy,
+    keys: PhantomData<fn(&K)>,
+}
+
+impl<K> RedisStateStore<K> {
+    /// Constructs a state store that uses connections from `pool` and stores states under keys
+    /// starting with `prefix`.
+    ///
+    /// The store fails closed, rejecting cells while it can't reach Redis (see
+    /// [`with_failure_policy`][RedisStateStore::with_failure_policy]).
+    pub fn new(pool: r2d2::Pool<redis::Client>, prefix: impl Into<String>) -> Self {
+        RedisStateStore {
+            pool,
+            prefix: prefix.into(),
+            ttl: None,
+            script: Script::new(COMPARE_AND_SET),
+            on_error: Box::new(|_| {}),
+            failure_policy: RedisFailurePolicy::default(),
+            keys: PhantomData,
+        }
+    }
+
+    /// Makes Redis expire each key's state `ttl` after its last update.
+    ///
+    /// Expired states are indistinguishable from fresh ones, so `ttl` should be at least the
+    /// time it takes to replenish the quota's entire burst capacity.
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        RedisStateStore {
+            ttl: Some(ttl),
+            ..self
+        }
+    }
+
+    /// Sets the function that gets called with errors encountered while talking to Redis.
+    pub fn on_error(self, handler: impl Fn(RedisStoreError) + Send + Sync + 'static) -> Self {
+        RedisStateStore {
+            on_error: Box::new(handler),
+            ..self
+        }
+    }
+
+    /// Sets what the store decides for cells while it can't reach Redis.
+    pub fn with_failure_policy(self, failure_policy: RedisFailurePolicy) -> Self {
+        RedisStateStore {
+            failure_policy,
+            ..self
+        }
+    }
+
+    /// Returns the state that the store's failure policy decides cells with while Redis can't
+    /// be reached.
+    fn failure_state(&self) -> Option<Nanos> {
+        match self.failure_policy {
+            RedisFailurePolicy::FailOpen => None,
+            // No cell conforms to a state this far in the future:
+            RedisFailurePolicy::FailClosed => Some(Nanos::MAX),
+        }
+    }
+
+    fn redis_key(&self, key: &K) -> String
+    where
+        K: fmt::Display,
+    {
+        format!("{}{}", self.prefix, key)
+    }
+
+    fn try_measure_and_replace<T, F, E>(
+        &self,
+        key: &str,
+        f: &F,
+    ) -> Result<Result<T, E>, RedisStoreError>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        let mut conn = self.pool.get()?;
+        let mut prev: u128 = redis::cmd("GET")
+            .arg(key)
+            .query::<Option<u128>>(&mut *conn)?
+            .unwrap_or(0);
+        let ttl = self
+            .ttl
+            .map(|ttl| ttl.as_millis().to_string())
+            .unwrap_or_default();
+        loop {
+            let (result, new_data) = match f(state_from(prev)) {
+                Ok(decision) => decision,
+                Err(e) => return Ok(Err(e)),
+            };
+            let new_data = new_data.as_u128().to_string();
+            let expected = if prev == 0 {
+                String::new()
+            } else {
+                prev.to_string()
+            };
+            let (replaced, current): (bool, String) = self
+                .script
+                .key(key)
+                .arg(expected)
+                .arg(new_data)
+                .arg(&ttl)
+                .invoke(&mut *conn)?;
+            if replaced {
+                return Ok(Ok(result));
+            }
+            // Treat states that we can't parse like missing ones:
+            prev = current.parse().unwrap_or(0);
+        }
+    }
+}
+
+impl<K> fmt::Debug for RedisStateStore<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("RedisStateStore")
+            .field("prefix", &self.prefix)
+            .field("ttl", &self.ttl)
+            .field("failure_policy", &self.failure_policy)
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone + fmt::Display> StateStore for RedisStateStore<K> {
+    type Key = K;
+
+    fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        match self.try_measure_and_replace(&self.redis_key(key), &f) {
+            Ok(result) => result,
+            Err(e) => {
+                (self.on_error)(e);
+                f(self.failure_state()).map(|(result, _)| result)
+            }
+        }
+    }
+
+    fn peek(&self, key: &Self::Key) -> Option<Nanos> {
+        let result = self
+            .pool
+            .get()
+            .map_err(RedisStoreError::from)
+            .and_then(|mut conn| {
+                redis::cmd("GET")
+                    .arg(self.redis_key(key))
+                    .query::<Option<u128>>(&mut *conn)
+                    .map_err(RedisStoreError::from)
+            });
+        match result {
+            Ok(state) => state.and_then(state_from),
+            Err(e) => {
+                (self.on_error)(e);
+                self.failure_state()
+            }
+        }
+    }
+
+    fn reset(&self, key: &Self::Key) {
+        let result = self
+            .pool
+            .get()
+            .map_err(RedisStoreError::from)
+            .and_then(|mut conn| {
+                redis::cmd("DEL")
+                    .arg(self.redis_key(key))
+                    .query::<()>(&mut *conn)
+                    .map_err(RedisStoreError::from)
+            });
+        if let Err(e) = result {
+            (self.on_error)(e);
+        }
+    }
+}
+
+/// # Keyed rate limiters - Redis-backed
+impl<K> RateLimiter<K, RedisStateStore<K>, SystemClock>
+where
+    K: Hash + Eq + Clone + fmt::Display,
+{
+    /// Constructs a new keyed rate limiter backed by a [`RedisStateStore`].
+    ///
+    /// The rate limiter uses the [`SystemClock`] and measures rate-limiting states from the UNIX
+    /// epoch, so that all rate limiters constructed this way can share their states.
+    pub fn redis(quota: Quota, state: RedisStateStore<K>) -> Self {
+        RateLimiter::new_with_start(quota, state, &SystemClock, UNIX_EPOCH)
+    }
+}
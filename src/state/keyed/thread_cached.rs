@@ -0,0 +1,220 @@
+use std::prelude::v1::*;
+
+use crate::gcra::NotUntil;
+use crate::middleware::RateLimitingMiddleware;
+use crate::nanos::Nanos;
+use crate::state::keyed::KeyedStateStore;
+use crate::state::StateStore;
+use crate::{clock, RateLimiter, StateInformation};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::num::NonZeroU32;
+use std::time::Duration;
+use thread_local::ThreadLocal;
+
+/// A keyed rate limiter that takes cells from a shared rate limiter in chunks, and lets each
+/// thread hand out the cells of its chunks without consulting the shared rate limiter again.
+///
+/// When a thread checks a key that it holds no cells for, it takes a chunk of cells for that key
+/// from the shared rate limiter, allows the first one through and keeps the rest for its next
+/// checks of the key. If the key doesn't have a whole chunk's worth of capacity left, the thread
+/// checks a single cell against the shared rate limiter instead, so keys with little capacity
+/// are still limited exactly. This makes most checks of keys that the same thread checks often
+/// thread-local, which scales much better than checking a shared state store (even a concurrent
+/// one like [`DashMapStateStore`][crate::state::keyed::DashMapStateStore]) on every call.
+///
+/// # Precision
+///
+/// Cells are counted against the quota when their chunk is taken, not when they are allowed
+/// through. Cells that a thread holds can therefore be allowed through at a time when the shared
+/// rate limiter would reject them, and cells that a thread holds for a key can't be used by
+/// other threads. At any time, each thread holds fewer than `chunk_size` cells per key, so the
+/// cells allowed through for a key over any period exceed what the shared rate limiter would
+/// allow by at most `chunk_size × thread_count`. Cells that threads hold are never returned to
+/// the shared rate limiter.
+///
+/// Cells that a thread holds expire once the shared rate limiter would have replenished their
+/// entire chunk, so that a thread can't use cells it took long ago at a time when the shared
+/// rate limiter has no capacity left. Each thread keeps the cells it holds in a map of keys, and
+/// removes the keys whose cells expired whenever it takes a new chunk (at most once per
+/// expiry period), so the map only grows with the number of keys that the thread took chunks
+/// for recently.
+///
+/// Chunks are taken like with the shared rate limiter's
+/// [`check_key_n`][RateLimiter::check_key_n], so its middleware sees the chunks that are allowed
+/// through rather than the individual cells, and the chunk size should be well below the quota's
+/// burst size. If a key has no chunk's worth of capacity left, the middleware only sees the
+/// decision on the single cell.
+///
+/// Construct one with [`RateLimiter::thread_cached`]. This requires the `thread-cache` feature.
+///
+/// # Example
+/// ```rust
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::hashmap(Quota::per_second(nonzero!(100u32)))
+///     .thread_cached(nonzero!(10u32));
+/// assert_eq!(Ok(()), lim.check_key(&"user"));
+/// // The shared rate limiter counts the entire chunk:
+/// assert_eq!(90, lim.limiter().available_for_key(&"user"));
+/// ```
+pub struct ThreadCachedRateLimiter<K, S, C, MW>
+where
+    K: Send,
+    S: StateStore<Key = K>,
+    C: clock::Clock,
+{
+    limiter: RateLimiter<K, S, C, MW>,
+    chunk_size: NonZeroU32,
+    held: ThreadLocal<RefCell<Held<K, C::Instant>>>,
+}
+
+/// The cells that a thread holds.
+struct Held<K, P> {
+    /// The number of cells held per key, and when they expire; keys that the thread holds no
+    /// cells for have no entry.
+    cells: HashMap<K, (u32, P)>,
+    /// When the keys whose cells expired are next removed from `cells`.
+    next_sweep: P,
+}
+
+/// Forwards everything but negative decisions to the shared rate limiter's middleware, while a
+/// thread tries to take a chunk: If the chunk doesn't fit, the decision on the single cell that
+/// the thread checks instead is the one that counts.
+struct ChunkMiddleware<'a, MW>(&'a MW);
+
+impl<K: ?Sized, P: clock::Reference, MW: RateLimitingMiddleware<K, P>> RateLimitingMiddleware<K, P>
+    for ChunkMiddleware<'_, MW>
+{
+    type PositiveOutcome = MW::PositiveOutcome;
+
+    fn allow_cell(&self, key: &K, state: StateInformation) -> Self::PositiveOutcome {
+        self.0.allow_cell(key, state)
+    }
+
+    fn veto_cell(&self, key: &K, state: &StateInformation) -> Option<Duration> {
+        self.0.veto_cell(key, state)
+    }
+}
+
+/// # Keyed rate limiters - Thread-local caching
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    K: Hash + Eq + Clone + Send,
+    S: KeyedStateStore<K>,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Wraps the rate limiter so that each thread takes cells from it in chunks of `chunk_size`
+    /// cells per key, and checks keys against the cells it holds (see
+    /// [`ThreadCachedRateLimiter`]).
+    pub fn thread_cached(self, chunk_size: NonZeroU32) -> ThreadCachedRateLimiter<K, S, C, MW> {
+        ThreadCachedRateLimiter {
+            limiter: self,
+            chunk_size,
+            held: ThreadLocal::new(),
+        }
+    }
+}
+
+impl<K, S, C, MW> ThreadCachedRateLimiter<K, S, C, MW>
+where
+    K: Hash + Eq + Clone + Send,
+    S: KeyedStateStore<K>,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Allow a single cell through the rate limiter for the given key, using a cell that the
+    /// current thread holds for the key if there is one.
+    ///
+    /// If the thread holds no cells for the key and the shared rate limiter rejects the cell,
+    /// `check_key` returns the shared rate limiter's negative decision.
+    pub fn check_key(&self, key: &K) -> Result<(), NotUntil<'_, C::Instant>> {
+        let now = self.limiter.clock.now();
+        let held = self.held.get_or(|| {
+            RefCell::new(Held {
+                cells: HashMap::new(),
+                next_sweep: now,
+            })
+        });
+        {
+            let mut held = held.borrow_mut();
+            match held.cells.get_mut(key) {
+                Some((_, expires_at)) if *expires_at <= now => {
+                    held.cells.remove(key);
+                }
+                Some((1, _)) => {
+                    held.cells.remove(key);
+                    return Ok(());
+                }
+                Some((cells, _)) => {
+                    *cells -= 1;
+                    return Ok(());
+                }
+                None => {}
+            }
+        }
+        if self.chunk_size.get() > 1 && self.take_chunk(key, now).is_ok() {
+            let expiry = self.expiry();
+            let mut held = held.borrow_mut();
+            if held.next_sweep <= now {
+                held.cells.retain(|_, (_, expires_at)| *expires_at > now);
+                held.next_sweep = now + expiry;
+            }
+            held.cells
+                .insert(key.clone(), (self.chunk_size.get() - 1, now + expiry));
+            return Ok(());
+        }
+        self.limiter.check_key(key).map(|_| ())
+    }
+
+    /// Takes a chunk of cells for the key from the shared rate limiter, without informing its
+    /// middleware if the chunk doesn't fit.
+    fn take_chunk(&self, key: &K, now: C::Instant) -> Result<MW::PositiveOutcome, ()> {
+        self.limiter
+            .gcra
+            .test_n_all_and_update(
+                self.limiter.start,
+                key,
+                self.chunk_size,
+                &self.limiter.state,
+                now,
+                &ChunkMiddleware(&self.limiter.middleware),
+            )
+            .map_err(|_| ())
+    }
+
+    /// Returns how long after taking a chunk the shared rate limiter has replenished it.
+    fn expiry(&self) -> Nanos {
+        let (t, _) = self.limiter.gcra.params();
+        t.times(u64::from(self.chunk_size.get())).ceil()
+    }
+
+    /// Returns the shared rate limiter.
+    pub fn limiter(&self) -> &RateLimiter<K, S, C, MW> {
+        &self.limiter
+    }
+
+    /// Returns the number of cells that threads take from the shared rate limiter at once.
+    pub fn chunk_size(&self) -> NonZeroU32 {
+        self.chunk_size
+    }
+}
+
+impl<K, S, C, MW> fmt::Debug for ThreadCachedRateLimiter<K, S, C, MW>
+where
+    K: Send + fmt::Debug,
+    S: StateStore<Key = K> + fmt::Debug,
+    C: clock::Clock + fmt::Debug,
+    C::Instant: fmt::Debug,
+    MW: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("ThreadCachedRateLimiter")
+            .field("limiter", &self.limiter)
+            .field("chunk_size", &self.chunk_size)
+            .finish()
+    }
+}
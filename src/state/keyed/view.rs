@@ -0,0 +1,117 @@
+use crate::gcra::NotUntil;
+use crate::middleware::RateLimitingMiddleware;
+use crate::state::keyed::KeyedStateStore;
+use crate::state::InsufficientCapacity;
+use crate::{clock, NegativeMultiDecision, RateLimiter};
+use std::hash::Hash;
+use std::num::NonZeroU32;
+
+/// A view of a keyed rate limiter that checks cells under a single key, like a direct rate
+/// limiter does.
+///
+/// The view doesn't have a state of its own: It forwards to the keyed rate limiter's methods
+/// with its key, so all views for the same key (and the keyed rate limiter itself) share that
+/// key's state, while views for different keys are independent.
+///
+/// Construct one with [`RateLimiter::for_key`].
+///
+/// # Example
+/// ```rust
+/// # #[cfg(feature = "std")] fn main() {
+/// # use nonzero_ext::*;
+/// # use governor::{Quota, RateLimiter};
+/// let lim = RateLimiter::keyed(Quota::per_second(nonzero!(1u32)));
+/// let alice = lim.for_key("alice");
+/// assert_eq!(Ok(()), alice.check());
+/// assert_ne!(Ok(()), alice.check());
+/// assert_ne!(Ok(()), lim.check_key(&"alice"));
+/// assert_eq!(Ok(()), lim.for_key("bob").check());
+/// # } #[cfg(not(feature = "std"))] fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct SingleKeyRateLimiter<'a, K, S, C, MW>
+where
+    S: KeyedStateStore<K>,
+    K: Hash,
+    C: clock::Clock,
+{
+    limiter: &'a RateLimiter<K, S, C, MW>,
+    key: K,
+}
+
+/// # Keyed rate limiters - Views for a single key
+impl<K, S, C, MW> RateLimiter<K, S, C, MW>
+where
+    S: KeyedStateStore<K>,
+    K: Hash,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Returns a view of the rate limiter that checks cells under `key`, like a direct rate
+    /// limiter does (see [`SingleKeyRateLimiter`]).
+    pub fn for_key(&self, key: K) -> SingleKeyRateLimiter<'_, K, S, C, MW> {
+        SingleKeyRateLimiter { limiter: self, key }
+    }
+}
+
+impl<'a, K, S, C, MW> SingleKeyRateLimiter<'a, K, S, C, MW>
+where
+    S: KeyedStateStore<K>,
+    K: Hash + Eq + Clone,
+    C: clock::Clock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Allow a single cell through the rate limiter for the view's key (see
+    /// [`check_key`][RateLimiter::check_key]).
+    pub fn check(&self) -> Result<MW::PositiveOutcome, NotUntil<'a, C::Instant>> {
+        self.limiter.check_key(&self.key)
+    }
+
+    /// Allow *only all* `n` cells through the rate limiter for the view's key (see
+    /// [`check_key_n`][RateLimiter::check_key_n]).
+    pub fn check_n(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<MW::PositiveOutcome, NegativeMultiDecision<NotUntil<'a, C::Instant>>> {
+        self.limiter.check_key_n(&self.key, n)
+    }
+
+    /// Returns the number of cells that the rate limiter would allow through for the view's key
+    /// right now (see [`available_for_key`][RateLimiter::available_for_key]).
+    pub fn available(&self) -> u32 {
+        self.limiter.available_for_key(&self.key)
+    }
+
+    /// Returns the key that the view checks cells under.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the keyed rate limiter that the view forwards to.
+    pub fn limiter(&self) -> &'a RateLimiter<K, S, C, MW> {
+        self.limiter
+    }
+}
+
+impl<'a, K, S, C, MW> SingleKeyRateLimiter<'a, K, S, C, MW>
+where
+    K: Hash + Eq + Clone,
+    S: KeyedStateStore<K>,
+    C: clock::AsyncClock,
+    MW: RateLimitingMiddleware<K, C::Instant>,
+{
+    /// Asynchronously resolves as soon as the rate limiter allows a cell through for the view's
+    /// key (see [`until_key_ready`][RateLimiter::until_key_ready]).
+    pub async fn until_ready(&self) -> MW::PositiveOutcome {
+        self.limiter.until_key_ready(&self.key).await
+    }
+
+    /// Asynchronously resolves as soon as the rate limiter allows `n` cells through for the
+    /// view's key (see [`until_key_n_ready`][RateLimiter::until_key_n_ready]).
+    pub async fn until_n_ready(
+        &self,
+        n: NonZeroU32,
+    ) -> Result<MW::PositiveOutcome, InsufficientCapacity> {
+        self.limiter.until_key_n_ready(&self.key, n).await
+    }
+}
@@ -0,0 +1,237 @@
+//! Rate-limiting middleware for [`tower`](https://docs.rs/tower) services.
+//!
+//! A [`RateLimitLayer`] wraps services in a [`RateLimitService`], which checks each request
+//! against a rate limiter before passing it on to the inner service. Requests that exceed the
+//! quota get rejected with an error of the inner service's error type, constructed by a
+//! user-provided function from the [`NotUntil`] outcome.
+//!
+//! # Example
+//! ```rust
+//! # use governor::{tower::RateLimitLayer, Quota, RateLimiter};
+//! # use futures::executor::block_on;
+//! # use nonzero_ext::nonzero;
+//! # use std::sync::Arc;
+//! # use std::time::Duration;
+//! # use tower::{service_fn, Layer, Service};
+//! let limiter = Arc::new(RateLimiter::direct(Quota::per_second(nonzero!(1u32))));
+//! // Reject requests with the time the client should wait before retrying:
+//! let layer = RateLimitLayer::new(limiter, |negative, now| negative.wait_time_from(now));
+//! let mut service = layer.layer(service_fn(|request: u32| async move {
+//!     Ok::<_, Duration>(request * 2)
+//! }));
+//!
+//! assert_eq!(block_on(service.call(21)), Ok(42));
+//! assert!(block_on(service.call(21)).is_err());
+//! ```
+
+use std::prelude::v1::*;
+
+use crate::{
+    clock,
+    state::{NotKeyed, StateStore},
+    NotUntil, RateLimiter,
+};
+use futures::future::{self, Either, Ready};
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A [`Layer`] that wraps services in a [`RateLimitService`].
+///
+/// The layer holds a shared reference to the rate limiter, so all services it produces draw from
+/// the same quota.
+pub struct RateLimitLayer<S, C, X, M>
+where
+    S: StateStore,
+    C: clock::Clock,
+{
+    limiter: Arc<RateLimiter<S::Key, S, C>>,
+    key: X,
+    map_err: M,
+}
+
+impl<S, C, Request, M> RateLimitLayer<S, C, fn(&Request) -> NotKeyed, M>
+where
+    S: StateStore<Key = NotKeyed>,
+    C: clock::Clock,
+{
+    /// Constructs a layer that checks every request against a direct rate limiter.
+    ///
+    /// `map_err` is called with the negative rate-limiting outcome and the time at which the
+    /// decision was made, and constructs the error that the service returns for the request.
+    pub fn new<E>(limiter: Arc<RateLimiter<NotKeyed, S, C>>, map_err: M) -> Self
+    where
+        M: Fn(NotUntil<'_, C::Instant>, C::Instant) -> E,
+    {
+        RateLimitLayer {
+            limiter,
+            key: |_| NotKeyed::NonKey,
+            map_err,
+        }
+    }
+}
+
+impl<S, C, X, M> RateLimitLayer<S, C, X, M>
+where
+    S: StateStore,
+    C: clock::Clock,
+{
+    /// Constructs a layer that checks every request against a keyed rate limiter, under the key
+    /// that `key` extracts from the request.
+    ///
+    /// `map_err` is called with the negative rate-limiting outcome and the time at which the
+    /// decision was made, and constructs the error that the service returns for the request.
+    pub fn keyed<Request, E>(limiter: Arc<RateLimiter<S::Key, S, C>>, key: X, map_err: M) -> Self
+    where
+        X: Fn(&Request) -> S::Key,
+        M: Fn(NotUntil<'_, C::Instant>, C::Instant) -> E,
+    {
+        RateLimitLayer {
+            limiter,
+            key,
+            map_err,
+        }
+    }
+}
+
+impl<S, C, X, M> Clone for RateLimitLayer<S, C, X, M>
+where
+    S: StateStore,
+    C: clock::Clock,
+    X: Clone,
+    M: Clone,
+{
+    fn clone(&self) -> Self {
+        RateLimitLayer {
+            limiter: Arc::clone(&self.limiter),
+            key: self.key.clone(),
+            map_err: self.map_err.clone(),
+        }
+    }
+}
+
+impl<S, C, X, M> fmt::Debug for RateLimitLayer<S, C, X, M>
+where
+    S: StateStore + fmt::Debug,
+    S::Key: fmt::Debug,
+    C: clock::Clock + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("RateLimitLayer")
+            .field("limiter", &self.limiter)
+            .finish()
+    }
+}
+
+impl<Inner, S, C, X, M> Layer<Inner> for RateLimitLayer<S, C, X, M>
+where
+    S: StateStore,
+    C: clock::Clock,
+    X: Clone,
+    M: Clone,
+{
+    type Service = RateLimitService<Inner, S, C, X, M>;
+
+    fn layer(&self, inner: Inner) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+            key: self.key.clone(),
+            map_err: self.map_err.clone(),
+        }
+    }
+}
+
+/// A [`Service`] that checks requests against a rate limiter before passing them on to an inner
+/// service.
+///
+/// Requests that conform to the rate limit are passed on to the inner service; requests that
+/// don't get rejected with the error that the service's error-mapping function constructs.
+///
+/// Services of this type are constructed by a [`RateLimitLayer`].
+pub struct RateLimitService<Inner, S, C, X, M>
+where
+    S: StateStore,
+    C: clock::Clock,
+{
+    inner: Inner,
+    limiter: Arc<RateLimiter<S::Key, S, C>>,
+    key: X,
+    map_err: M,
+}
+
+impl<Inner, S, C, X, M> RateLimitService<Inner, S, C, X, M>
+where
+    S: StateStore,
+    C: clock::Clock,
+{
+    /// Returns a reference to the inner service.
+    pub fn get_ref(&self) -> &Inner {
+        &self.inner
+    }
+
+    /// Consumes the rate-limited service, returning the inner service.
+    pub fn into_inner(self) -> Inner {
+        self.inner
+    }
+}
+
+impl<Inner, S, C, X, M> Clone for RateLimitService<Inner, S, C, X, M>
+where
+    Inner: Clone,
+    S: StateStore,
+    C: clock::Clock,
+    X: Clone,
+    M: Clone,
+{
+    fn clone(&self) -> Self {
+        RateLimitService {
+            inner: self.inner.clone(),
+            limiter: Arc::clone(&self.limiter),
+            key: self.key.clone(),
+            map_err: self.map_err.clone(),
+        }
+    }
+}
+
+impl<Inner, S, C, X, M> fmt::Debug for RateLimitService<Inner, S, C, X, M>
+where
+    Inner: fmt::Debug,
+    S: StateStore + fmt::Debug,
+    S::Key: fmt::Debug,
+    C: clock::Clock + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        f.debug_struct("RateLimitService")
+            .field("inner", &self.inner)
+            .field("limiter", &self.limiter)
+            .finish()
+    }
+}
+
+impl<Request, Inner, S, C, X, M> Service<Request> for RateLimitService<Inner, S, C, X, M>
+where
+    Inner: Service<Request>,
+    S: StateStore,
+    C: clock::Clock,
+    X: Fn(&Request) -> S::Key,
+    M: Fn(NotUntil<'_, C::Instant>, C::Instant) -> Inner::Error,
+{
+    type Response = Inner::Response;
+    type Error = Inner::Error;
+    type Future = Either<Inner::Future, Ready<Result<Inner::Response, Inner::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let key = (self.key)(&request);
+        match self.limiter.check_any_key(&key) {
+            (_, Ok(())) => Either::Left(self.inner.call(request)),
+            (now, Err(negative)) => Either::Right(future::err((self.map_err)(negative, now))),
+        }
+    }
+}
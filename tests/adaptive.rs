@@ -0,0 +1,55 @@
+use governor::{clock::FakeRelativeClock, state::AdaptiveRateLimiter, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+/// Checks cells every millisecond for `ms` milliseconds, returning how many were allowed.
+fn drain(lim: &AdaptiveRateLimiter<FakeRelativeClock>, clock: &FakeRelativeClock, ms: u64) -> u64 {
+    let mut allowed = 0;
+    for _ in 0..ms {
+        while lim.check().is_ok() {
+            allowed += 1;
+        }
+        clock.advance(Duration::from_millis(1));
+    }
+    allowed
+}
+
+#[test]
+fn overloads_decrease_the_rate_and_successes_recover_it() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::adaptive_with_clock(
+        Quota::per_second(nonzero!(10u32)),
+        Quota::per_second(nonzero!(100u32)),
+        &clock,
+    );
+    // Use up the initial burst, then measure the maximum rate:
+    drain(&lim, &clock, 1000);
+    let full = drain(&lim, &clock, 1000);
+    assert_eq!(100, full);
+
+    for _ in 0..3 {
+        lim.report_overload();
+    }
+    assert_eq!(12.5, lim.current_rate());
+    drain(&lim, &clock, 1000);
+    let throttled = drain(&lim, &clock, 1000);
+    assert!(
+        (12..=13).contains(&throttled),
+        "{} cells allowed",
+        throttled
+    );
+
+    for _ in 0..10 {
+        lim.report_overload();
+    }
+    assert_eq!(10.0, lim.current_rate());
+    assert_eq!(Quota::per_second(nonzero!(10u32)), lim.current_quota());
+
+    for _ in 0..100 {
+        lim.report_success();
+    }
+    assert_eq!(100.0, lim.current_rate());
+    drain(&lim, &clock, 1000);
+    let recovered = drain(&lim, &clock, 1000);
+    assert_eq!(full, recovered);
+}
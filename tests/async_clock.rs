@@ -0,0 +1,411 @@
+//! Tests for waiting on rate limiters with clocks that bring their own timer, as they would in
+//! `no_std` builds.
+
+use governor::{
+    clock::{AsyncClock, Clock, FakeRelativeClock},
+    middleware::{NoOpMiddleware, StateInformationMiddleware},
+    state::{keyed::HashMapStateStore, InMemoryState, InsufficientCapacity, TimedOut, UntilReady},
+    Jitter, Quota, RateLimiter, SeededJitterRng,
+};
+use nonzero_ext::nonzero;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::Duration;
+
+/// A clock whose timer only fires when it is polled a second time, advancing the fake time by
+/// the delay's duration.
+#[derive(Clone, Default)]
+struct FakeTimerClock {
+    clock: FakeRelativeClock,
+    delays: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl Clock for FakeTimerClock {
+    type Instant = <FakeRelativeClock as Clock>::Instant;
+
+    fn now(&self) -> Self::Instant {
+        self.clock.now()
+    }
+}
+
+struct FakeDelay {
+    clock: FakeRelativeClock,
+    duration: Duration,
+    armed: bool,
+}
+
+impl Future for FakeDelay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.armed {
+            self.clock.advance(self.duration);
+            Poll::Ready(())
+        } else {
+            self.armed = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncClock for FakeTimerClock {
+    type Delay = FakeDelay;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        self.delays.lock().unwrap().push(duration);
+        FakeDelay {
+            clock: self.clock.clone(),
+            duration,
+            armed: false,
+        }
+    }
+}
+
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    unsafe { Waker::from_raw(clone(std::ptr::null())) }
+}
+
+fn poll<F: Future>(future: Pin<&mut F>) -> Poll<F::Output> {
+    let waker = noop_waker();
+    future.poll(&mut Context::from_waker(&waker))
+}
+
+#[test]
+fn until_ready_waits_on_the_clocks_timer() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    lim.check().unwrap();
+    lim.check().unwrap();
+
+    let mut ready = Box::pin(lim.until_ready());
+    assert_eq!(Poll::Pending, poll(ready.as_mut()));
+    assert_eq!(
+        *clock.delays.lock().unwrap(),
+        vec![Duration::from_millis(500)]
+    );
+    assert_eq!(Poll::Ready(()), poll(ready.as_mut()));
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+}
+
+#[test]
+fn until_n_ready_waits_on_the_clocks_timer() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    lim.check_n(nonzero!(4u32)).unwrap();
+
+    let mut ready = Box::pin(lim.until_n_ready(nonzero!(2u32)));
+    assert!(poll(ready.as_mut()).is_pending());
+    assert!(matches!(poll(ready.as_mut()), Poll::Ready(Ok(()))));
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+    assert_eq!(
+        *clock.delays.lock().unwrap(),
+        vec![Duration::from_millis(500)]
+    );
+}
+
+#[test]
+fn until_n_ready_rejects_batches_beyond_the_burst_size_without_waiting() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    // Even while the rate limiter is exhausted, an impossible batch fails on the first poll:
+    lim.check_n(nonzero!(4u32)).unwrap();
+    let impossible = Err(InsufficientCapacity {
+        requested: 5,
+        max_burst: 4,
+    });
+
+    let mut ready = Box::pin(lim.until_n_ready(nonzero!(5u32)));
+    assert_eq!(Poll::Ready(impossible), poll(ready.as_mut()));
+    static RNG: SeededJitterRng = SeededJitterRng::new(0);
+    let jitter = Jitter::up_to_with_rng(Duration::from_secs(1), &RNG);
+    let mut ready = Box::pin(lim.until_n_ready_with_jitter(nonzero!(5u32), jitter));
+    assert_eq!(Poll::Ready(impossible), poll(ready.as_mut()));
+    assert!(clock.delays.lock().unwrap().is_empty());
+    assert_eq!(Duration::ZERO, clock.now().into());
+}
+
+#[test]
+fn until_key_ready_waits_on_the_clocks_timer() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    lim.check_key(&"foo").unwrap();
+
+    let mut ready = Box::pin(lim.until_key_ready(&"foo"));
+    assert_eq!(Poll::Pending, poll(ready.as_mut()));
+    assert_eq!(Poll::Ready(()), poll(ready.as_mut()));
+    assert_eq!(Duration::from_secs(1), clock.now().into());
+}
+
+#[test]
+fn until_key_n_ready_with_jitter_waits_for_all_cells_and_jitter() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(42);
+    static EXPECTED_RNG: SeededJitterRng = SeededJitterRng::new(42);
+    let jitter = Jitter::new_with_rng(
+        Duration::from_millis(10),
+        Duration::from_millis(100),
+        &EXPECTED_RNG,
+    );
+    let expected_jitter = jitter + Duration::ZERO;
+
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    lim.check_key_n(&"foo", nonzero!(4u32)).unwrap();
+
+    let mut ready =
+        Box::pin(lim.until_key_n_ready_with_jitter(&"foo", nonzero!(3u32), jitter.with_rng(&RNG)));
+    assert!(poll(ready.as_mut()).is_pending());
+    assert!(matches!(poll(ready.as_mut()), Poll::Ready(Ok(()))));
+    assert_eq!(
+        *clock.delays.lock().unwrap(),
+        vec![Duration::from_millis(750) + expected_jitter]
+    );
+    assert_eq!(0, lim.available_for_key(&"foo"));
+    assert_eq!(4, lim.available_for_key(&"bar"));
+}
+
+#[test]
+fn until_key_n_ready_rejects_batches_beyond_the_burst_size() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    let mut ready = Box::pin(lim.until_key_n_ready(&"foo", nonzero!(5u32)));
+    assert_eq!(
+        Poll::Ready(Err(InsufficientCapacity {
+            requested: 5,
+            max_burst: 4
+        })),
+        poll(ready.as_mut())
+    );
+    assert!(clock.delays.lock().unwrap().is_empty());
+}
+
+#[test]
+fn zero_cost_resolves_right_away_on_exhausted_limiters() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    lim.check_n(nonzero!(4u32)).unwrap();
+    let before = lim.state_information();
+    let mut ready = Box::pin(lim.until_cost_ready(0));
+    assert_eq!(Poll::Ready(Ok(None)), poll(ready.as_mut()));
+    assert_eq!(before, lim.state_information());
+
+    let mut ready = Box::pin(lim.until_cost_ready(2));
+    assert!(poll(ready.as_mut()).is_pending());
+    assert_eq!(Poll::Ready(Ok(Some(()))), poll(ready.as_mut()));
+
+    let keyed = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    keyed.check_key_n(&"foo", nonzero!(4u32)).unwrap();
+    let mut ready = Box::pin(keyed.until_key_cost_ready(&"foo", 0));
+    assert_eq!(Poll::Ready(Ok(None)), poll(ready.as_mut()));
+    let mut ready = Box::pin(keyed.until_key_cost_ready(&"foo", 5));
+    assert_eq!(
+        Poll::Ready(Err(InsufficientCapacity {
+            requested: 5,
+            max_burst: 4
+        })),
+        poll(ready.as_mut())
+    );
+    assert_eq!(
+        *clock.delays.lock().unwrap(),
+        vec![Duration::from_millis(500)]
+    );
+}
+
+#[test]
+fn until_ready_with_timeout_gives_up_without_using_capacity() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_minute(nonzero!(5u32)), &clock);
+    lim.check_n(nonzero!(5u32)).unwrap();
+    let before = lim.state_information();
+
+    let mut ready = Box::pin(lim.until_ready_with_timeout(Duration::from_secs(1)));
+    assert_eq!(
+        Poll::Ready(Err(TimedOut(Duration::from_secs(12)))),
+        poll(ready.as_mut())
+    );
+    assert!(clock.delays.lock().unwrap().is_empty());
+    assert_eq!(
+        before.remaining_burst_capacity(),
+        lim.state_information().remaining_burst_capacity()
+    );
+    assert_eq!(lim.check().unwrap_err(), lim.check().unwrap_err());
+
+    clock.clock.advance(Duration::from_secs(12));
+    assert_eq!(lim.available(), 1);
+}
+
+#[test]
+fn until_ready_with_timeout_waits_within_timeout() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    lim.check_n(nonzero!(2u32)).unwrap();
+
+    let mut ready = Box::pin(lim.until_ready_with_timeout(Duration::from_secs(1)));
+    assert!(poll(ready.as_mut()).is_pending());
+    assert_eq!(Poll::Ready(Ok(())), poll(ready.as_mut()));
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+}
+
+#[test]
+fn until_ready_with_jitter_and_timeout_shortens_delay() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    lim.check_n(nonzero!(2u32)).unwrap();
+
+    static RNG: SeededJitterRng = SeededJitterRng::new(0);
+    let jitter = Jitter::up_to_with_rng(Duration::from_secs(10), &RNG);
+    let mut ready =
+        Box::pin(lim.until_ready_with_jitter_and_timeout(jitter, Duration::from_millis(600)));
+    while poll(ready.as_mut()).is_pending() {}
+    assert!(
+        clock
+            .delays
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|delay| *delay <= Duration::from_millis(600)),
+        "delays: {:?}",
+        clock.delays.lock().unwrap()
+    );
+    let elapsed: Duration = clock.now().into();
+    assert!(elapsed <= Duration::from_millis(600));
+}
+
+#[test]
+fn until_key_ready_with_timeout_gives_up_without_using_capacity() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_minute(nonzero!(5u32)), &clock);
+    lim.check_key_n(&"foo", nonzero!(5u32)).unwrap();
+
+    let mut ready = Box::pin(lim.until_key_ready_with_timeout(&"foo", Duration::from_secs(1)));
+    assert_eq!(
+        Poll::Ready(Err(TimedOut(Duration::from_secs(12)))),
+        poll(ready.as_mut())
+    );
+    clock.clock.advance(Duration::from_secs(12));
+    assert_eq!(lim.available_for_key(&"foo"), 1);
+
+    let mut ready = Box::pin(lim.until_key_ready_with_timeout(&"foo", Duration::from_secs(1)));
+    assert_eq!(Poll::Ready(Ok(())), poll(ready.as_mut()));
+}
+
+#[test]
+fn until_keys_ready_waits_for_the_slowest_key() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    lim.check_key(&"fast").unwrap();
+    clock.clock.advance(Duration::from_millis(600));
+    lim.check_key(&"slow").unwrap();
+
+    // "fast" is ready in 400ms, "slow" in 1s:
+    let keys = ["fast", "slow", "idle"];
+    let mut ready = Box::pin(lim.until_keys_ready(&keys));
+    assert_eq!(Poll::Pending, poll(ready.as_mut()));
+    assert_eq!(lim.available_for_key(&"idle"), 1);
+    assert_eq!(Poll::Ready(()), poll(ready.as_mut()));
+    assert_eq!(*clock.delays.lock().unwrap(), vec![Duration::from_secs(1)]);
+    assert_eq!(Duration::from_millis(1600), clock.now().into());
+    for key in keys {
+        assert_ne!(Ok(()), lim.check_key(&key), "{}", key);
+    }
+}
+
+#[test]
+fn builder_assembles_a_customized_rate_limiter() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(7);
+    static EXPECTED_RNG: SeededJitterRng = SeededJitterRng::new(7);
+    let jitter = Jitter::up_to_with_rng(Duration::from_millis(100), &EXPECTED_RNG);
+    let expected_jitter = jitter + Duration::ZERO;
+
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::builder()
+        .default_jitter(jitter.with_rng(&RNG))
+        .middleware(StateInformationMiddleware)
+        .clock(&clock)
+        .store(HashMapStateStore::<&str>::default())
+        .quota(Quota::per_second(nonzero!(2u32)))
+        .build();
+    assert_eq!(
+        Ok(1),
+        lim.check_key(&"foo").map(|s| s.remaining_burst_capacity())
+    );
+    lim.check_key(&"foo").unwrap();
+
+    // Waiting without an explicit jitter uses the default one:
+    let mut ready = Box::pin(lim.until_key_ready(&"foo"));
+    assert!(poll(ready.as_mut()).is_pending());
+    assert!(poll(ready.as_mut()).is_ready());
+    assert_eq!(
+        *clock.delays.lock().unwrap(),
+        vec![Duration::from_millis(500) + expected_jitter]
+    );
+}
+
+#[test]
+fn single_key_view_waits_for_its_key() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let view = lim.for_key("foo");
+    view.check().unwrap();
+
+    let mut ready = Box::pin(view.until_ready());
+    assert_eq!(Poll::Pending, poll(ready.as_mut()));
+    assert_eq!(Poll::Ready(()), poll(ready.as_mut()));
+    assert_eq!(Duration::from_secs(1), clock.now().into());
+    assert_eq!(1, lim.available_for_key(&"bar"));
+}
+
+#[test]
+fn until_ready_futures_can_be_named_and_stored() {
+    let clock = FakeTimerClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    lim.check().unwrap();
+    lim.check().unwrap();
+
+    let mut waiting: Vec<UntilReady<'_, InMemoryState, FakeTimerClock, NoOpMiddleware>> = vec![
+        lim.until_ready(),
+        lim.until_ready_with_jitter(Jitter::default()),
+    ];
+    for ready in waiting.iter_mut() {
+        let mut ready = Pin::new(ready);
+        assert_eq!(Poll::Pending, poll(ready.as_mut()));
+        assert_eq!(Poll::Ready(()), poll(ready.as_mut()));
+    }
+    assert_eq!(
+        *clock.delays.lock().unwrap(),
+        vec![Duration::from_millis(500), Duration::from_millis(500)]
+    );
+    assert_eq!(Duration::from_secs(1), clock.now().into());
+}
+
+#[test]
+fn until_ready_with_jitter_is_reproducible_with_a_seeded_rng() {
+    fn wait_twice(rng: &'static SeededJitterRng) -> Vec<Duration> {
+        let clock = FakeTimerClock::default();
+        let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+        let jitter = Jitter::up_to_with_rng(Duration::from_secs(1), rng);
+        lim.check().unwrap();
+        for _ in 0..2 {
+            let mut ready = Box::pin(lim.until_ready_with_jitter(jitter));
+            assert_eq!(Poll::Pending, poll(ready.as_mut()));
+            assert_eq!(Poll::Ready(()), poll(ready.as_mut()));
+        }
+        let delays = clock.delays.lock().unwrap();
+        delays.clone()
+    }
+    static FIRST: SeededJitterRng = SeededJitterRng::new(1234);
+    static SECOND: SeededJitterRng = SeededJitterRng::new(1234);
+
+    let first = wait_twice(&FIRST);
+    assert_eq!(2, first.len());
+    // The first wait is jittered past the replenishment interval:
+    assert!(first[0] > Duration::from_secs(1), "{:?}", first);
+    assert_eq!(first, wait_twice(&SECOND));
+}
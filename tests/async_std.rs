@@ -0,0 +1,91 @@
+#![cfg(feature = "async-std")]
+//! Tests for waiting on rate limiters with `async-std`'s timer, without any other runtime.
+
+use async_std::task;
+use governor::{
+    clock::{AsyncClock, AsyncStdClock, Clock, FakeRelativeClock},
+    Quota, RateLimiter,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+/// A fake clock that waits with `async-std`'s timer, then advances the fake time by the delay.
+#[derive(Clone, Default)]
+struct SleepingFakeClock(FakeRelativeClock);
+
+impl Clock for SleepingFakeClock {
+    type Instant = <FakeRelativeClock as Clock>::Instant;
+
+    fn now(&self) -> Self::Instant {
+        self.0.now()
+    }
+}
+
+impl AsyncClock for SleepingFakeClock {
+    type Delay = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        let clock = self.0.clone();
+        Box::pin(async move {
+            task::sleep(duration).await;
+            clock.advance(duration);
+        })
+    }
+}
+
+#[test]
+fn until_ready_on_a_fake_clock() {
+    let clock = SleepingFakeClock::default();
+    let lim = RateLimiter::direct_with_clock(
+        Quota::with_period(Duration::from_millis(50)).unwrap(),
+        &clock,
+    );
+    let start = Instant::now();
+    task::block_on(async {
+        lim.until_ready().await;
+        lim.until_ready().await;
+    });
+    let waited: Duration = clock.now().into();
+    assert_eq!(Duration::from_millis(50), waited);
+    assert!(
+        start.elapsed() >= waited,
+        "waited only {:?}",
+        start.elapsed()
+    );
+}
+
+#[test]
+fn until_ready_on_the_default_clock() {
+    let lim = RateLimiter::direct(Quota::with_period(Duration::from_millis(50)).unwrap());
+    let start = Instant::now();
+    task::block_on(async {
+        lim.until_ready().await;
+        lim.until_ready().await;
+    });
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(40),
+        "waited only {:?}",
+        elapsed
+    );
+}
+
+#[test]
+fn until_ready_on_the_async_std_clock() {
+    let lim = RateLimiter::direct_with_clock(
+        Quota::with_period(Duration::from_millis(50)).unwrap(),
+        &AsyncStdClock,
+    );
+    let start = Instant::now();
+    task::block_on(async {
+        lim.until_ready().await;
+        lim.until_ready().await;
+    });
+    let elapsed = start.elapsed();
+    assert!(
+        elapsed >= Duration::from_millis(40),
+        "waited only {:?}",
+        elapsed
+    );
+}
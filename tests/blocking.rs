@@ -0,0 +1,134 @@
+#![cfg(feature = "std")]
+
+use governor::{
+    clock::{BlockingClock, Clock, FakeRelativeClock},
+    state::{keyed::HashMapStateStore, InsufficientCapacity},
+    Jitter, Quota, RateLimiter, SeededJitterRng,
+};
+use nonzero_ext::nonzero;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn until_ready_blocking_sleeps_on_the_clock() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    lim.check_n(nonzero!(2u32)).unwrap();
+
+    lim.until_ready_blocking();
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+    assert!(lim.check().is_err());
+}
+
+#[test]
+fn until_ready_blocking_with_jitter() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    lim.check_n(nonzero!(2u32)).unwrap();
+
+    static RNG: SeededJitterRng = SeededJitterRng::new(0);
+    lim.until_ready_blocking_with_jitter(Jitter::new_with_rng(
+        Duration::from_millis(100),
+        Duration::from_millis(1),
+        &RNG,
+    ));
+    let now: Duration = clock.now().into();
+    assert!(now >= Duration::from_millis(600), "{:?}", now);
+    assert!(now <= Duration::from_millis(601), "{:?}", now);
+}
+
+#[test]
+fn until_n_ready_blocking() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    lim.check_n(nonzero!(4u32)).unwrap();
+
+    assert!(lim.until_n_ready_blocking(nonzero!(2u32)).is_ok());
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+    assert!(matches!(
+        lim.until_n_ready_blocking(nonzero!(5u32)),
+        Err(InsufficientCapacity {
+            requested: 5,
+            max_burst: 4
+        })
+    ));
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+}
+
+#[test]
+fn until_key_ready_blocking() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    lim.check_key(&"foo").unwrap();
+
+    lim.until_key_ready_blocking(&"bar");
+    assert_eq!(Duration::from_secs(0), clock.now().into());
+    lim.until_key_ready_blocking(&"foo");
+    assert_eq!(Duration::from_secs(1), clock.now().into());
+}
+
+#[test]
+fn until_key_ready_blocking_with_borrowed_keys() {
+    let clock = FakeRelativeClock::default();
+    let lim: RateLimiter<String, HashMapStateStore<String>, _> =
+        RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    lim.check_key("foo").unwrap();
+
+    lim.until_key_ready_blocking("foo");
+    assert_eq!(Duration::from_secs(1), clock.now().into());
+    lim.until_key_ready_blocking_with_jitter("foo", Jitter::none());
+    assert_eq!(Duration::from_secs(2), clock.now().into());
+    assert_eq!(1, lim.len());
+}
+
+/// A clock that wakes up halfway through every other sleep.
+#[derive(Clone, Default)]
+struct RestlessClock {
+    clock: FakeRelativeClock,
+    sleeps: Arc<AtomicU32>,
+}
+
+impl Clock for RestlessClock {
+    type Instant = <FakeRelativeClock as Clock>::Instant;
+
+    fn now(&self) -> Self::Instant {
+        self.clock.now()
+    }
+}
+
+impl BlockingClock for RestlessClock {
+    fn sleep(&self, duration: Duration) {
+        if self.sleeps.fetch_add(1, Ordering::Relaxed) & 1 == 0 {
+            self.clock.advance(duration / 2);
+        } else {
+            self.clock.advance(duration);
+        }
+    }
+}
+
+#[test]
+fn early_wakeups_check_again() {
+    let clock = RestlessClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    lim.check().unwrap();
+
+    lim.until_ready_blocking();
+    let now: Duration = clock.now().into();
+    assert_eq!(now, Duration::from_secs(1));
+    assert_eq!(clock.sleeps.load(Ordering::Relaxed), 2);
+    assert!(lim.check().is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn until_ready_blocking_parks_the_thread() {
+    use std::time::Instant;
+
+    let lim = RateLimiter::direct(Quota::per_second(nonzero!(10u32)));
+    lim.check_n(nonzero!(10u32)).unwrap();
+
+    let start = Instant::now();
+    lim.until_ready_blocking();
+    assert!(start.elapsed() >= Duration::from_millis(90));
+}
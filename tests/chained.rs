@@ -0,0 +1,83 @@
+#![cfg(feature = "std")]
+
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    middleware::{MetricsMiddleware, RateLimitDecision},
+    Quota, RateLimiter,
+};
+use nonzero_ext::nonzero;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn counting(decisions: &Arc<AtomicU64>) -> MetricsMiddleware<impl Fn(RateLimitDecision)> {
+    let decisions = decisions.clone();
+    MetricsMiddleware::new(move |_| {
+        decisions.fetch_add(1, Ordering::Relaxed);
+    })
+}
+
+#[test]
+fn primary_rejection_skips_secondary() {
+    let clock = FakeRelativeClock::default();
+    let consulted = Arc::new(AtomicU64::new(0));
+    let primary = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let secondary = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock)
+        .with_middleware(counting(&consulted));
+    let lim = RateLimiter::chained(primary, secondary);
+
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(consulted.load(Ordering::Relaxed), 2);
+    for _ in 0..10 {
+        assert_ne!(Ok(()), lim.check());
+    }
+    assert_eq!(consulted.load(Ordering::Relaxed), 2);
+    assert_eq!(lim.secondary().available(), 3);
+}
+
+#[test]
+fn secondary_rejection_refunds_primary() {
+    let clock = FakeRelativeClock::default();
+    let primary = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    let secondary = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let lim = RateLimiter::chained(primary, secondary);
+
+    assert_eq!(Ok(()), lim.check());
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_secs(1),
+        "the secondary's negative decision is returned"
+    );
+    assert_ne!(Ok(()), lim.check());
+    assert_eq!(lim.primary().available(), 4);
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(Ok(()), lim.check());
+    let (_, secondary) = lim.into_inner();
+    assert_ne!(Ok(()), secondary.check());
+}
+
+#[test]
+fn chained_keyed() {
+    let clock = FakeRelativeClock::default();
+    let consulted = Arc::new(AtomicU64::new(0));
+    let primary = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let secondary = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_middleware(counting(&consulted));
+    let lim = RateLimiter::chained(primary, secondary);
+
+    assert_eq!(Ok(()), lim.check_key(&"foo"));
+    assert_ne!(Ok(()), lim.check_key(&"foo"));
+    assert_eq!(Ok(()), lim.check_key(&"bar"));
+    assert_eq!(consulted.load(Ordering::Relaxed), 2);
+
+    // Once the secondary rejects a key, the primary gets its cell back:
+    let primary = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    let secondary = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let lim = RateLimiter::chained(primary, secondary);
+    assert_eq!(Ok(()), lim.check_key(&"foo"));
+    assert_ne!(Ok(()), lim.check_key(&"foo"));
+    assert_eq!(lim.primary().available_for_key(&"foo"), 4);
+}
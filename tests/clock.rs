@@ -0,0 +1,211 @@
+#![cfg(feature = "std")]
+
+use governor::clock::{
+    Clock, CoarseMonotonicClock, DefaultClock, DynClock, FakeRelativeClock, Reference, TickClock,
+};
+use governor::state::{InMemoryState, NotKeyed};
+use governor::{Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn coarse_clock_advances() {
+    let clock = CoarseMonotonicClock::with_interval(Duration::from_millis(1)).unwrap();
+    let before = clock.now();
+    thread::sleep(Duration::from_millis(20));
+    let after = clock.now();
+    assert!(after > before);
+    let elapsed: Duration = after.duration_since(before).into();
+    assert!(elapsed <= Duration::from_millis(200));
+}
+
+#[test]
+fn coarse_clock_roughly_honors_limits() {
+    let clock = CoarseMonotonicClock::with_interval(Duration::from_millis(1)).unwrap();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(50u32)), &clock);
+
+    let start = Instant::now();
+    let mut allowed = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        if lim.check().is_ok() {
+            allowed += 1;
+        }
+        thread::sleep(Duration::from_micros(100));
+    }
+    // The burst of 50, plus up to 50 that replenish during the second:
+    assert!(allowed >= 50, "{} cells allowed", allowed);
+    assert!(allowed <= 102, "{} cells allowed", allowed);
+}
+
+#[cfg(feature = "quanta")]
+#[test]
+fn quanta_upkeep_clock_roughly_honors_limits() {
+    use governor::clock::QuantaUpkeepClock;
+
+    let clock = QuantaUpkeepClock::from_interval(Duration::from_millis(1)).unwrap();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(50u32)), &clock);
+
+    let start = Instant::now();
+    let mut allowed = 0;
+    while start.elapsed() < Duration::from_secs(1) {
+        if lim.check().is_ok() {
+            allowed += 1;
+        }
+        thread::sleep(Duration::from_micros(100));
+    }
+    // The burst of 50, plus up to 50 that replenish during the second, plus what replenishes
+    // within the upkeep interval:
+    assert!(allowed >= 50, "{} cells allowed", allowed);
+    assert!(allowed <= 102, "{} cells allowed", allowed);
+}
+
+#[cfg(feature = "quanta")]
+#[test]
+fn quanta_upkeep_clock_waits_until_ready() {
+    use governor::clock::QuantaUpkeepClock;
+
+    let clock = QuantaUpkeepClock::from_interval(Duration::from_millis(1)).unwrap();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(20u32)), &clock);
+    while lim.check().is_ok() {}
+    let start = Instant::now();
+    futures::executor::block_on(lim.until_ready());
+    assert!(start.elapsed() <= Duration::from_millis(500));
+}
+
+fn limiter_with_clock(clock: &DynClock) -> RateLimiter<NotKeyed, InMemoryState, DynClock> {
+    RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), clock)
+}
+
+#[test]
+fn dyn_clock_throttles() {
+    let fake = FakeRelativeClock::default();
+    fake.advance(Duration::from_secs(100));
+    let use_fake = true;
+    let clock = if use_fake {
+        DynClock::new(fake.clone())
+    } else {
+        DynClock::new(DefaultClock::default())
+    };
+    assert_eq!(clock.now(), Duration::from_secs(0).into());
+
+    let lim = limiter_with_clock(&clock);
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(Ok(()), lim.check());
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(500)
+    );
+    fake.advance(Duration::from_millis(500));
+    assert_eq!(Ok(()), lim.check());
+    assert!(lim.check().is_err());
+}
+
+#[test]
+fn dyn_clock_over_real_clock() {
+    let clock = DynClock::new(DefaultClock::default());
+    let lim = limiter_with_clock(&clock);
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(Ok(()), lim.check());
+    assert!(lim.check().is_err());
+
+    let before = clock.now();
+    thread::sleep(Duration::from_millis(10));
+    assert!(clock.now() > before);
+}
+
+#[test]
+fn tick_clock_replenishes_one_cell_per_tick() {
+    let quota = Quota::per_second(nonzero!(4u32));
+    let clock = TickClock::new(quota);
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(Ok(()), lim.check_n(nonzero!(4u32)));
+    assert_eq!(0, lim.available());
+
+    clock.advance(2);
+    assert_eq!(2, clock.ticks());
+    assert_eq!(Duration::from_millis(500), clock.now().into());
+    assert_eq!(2, lim.available());
+    assert_eq!(Ok(()), lim.check_n(nonzero!(2u32)));
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn tick_clock_does_not_accumulate_rounding_errors() {
+    // A third of a second isn't a whole number of nanoseconds:
+    let quota = Quota::per_second(nonzero!(3u32)).allow_burst(nonzero!(1u32));
+    let clock = TickClock::new(quota);
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    for _ in 0..3_000 {
+        assert_eq!(Ok(()), lim.check());
+        assert_ne!(Ok(()), lim.check());
+        clock.advance(1);
+    }
+    assert_eq!(Duration::from_secs(1000), clock.now().into());
+}
+
+/// A clock that can be set to any time, including times in the past.
+#[derive(Clone, Default)]
+struct SettableClock {
+    elapsed: Arc<Mutex<Duration>>,
+}
+
+impl SettableClock {
+    fn set(&self, elapsed: Duration) {
+        *self.elapsed.lock().unwrap() = elapsed;
+    }
+}
+
+impl Clock for SettableClock {
+    type Instant = <FakeRelativeClock as Clock>::Instant;
+
+    fn now(&self) -> Self::Instant {
+        FakeRelativeClock::default().now() + *self.elapsed.lock().unwrap()
+    }
+}
+
+#[test]
+fn clock_regressions_do_not_over_admit() {
+    let clock = SettableClock::default();
+    let quota = Quota::per_second(nonzero!(10u32));
+    let gcra = RateLimiter::direct_with_clock(quota, &clock);
+    let window = RateLimiter::direct_sliding_window_with_clock(quota, &clock);
+    let admitted = |check: &dyn Fn() -> bool| (0..20).filter(|_| check()).count();
+    let ms = Duration::from_millis(1);
+
+    clock.set(ms * 900);
+    assert_eq!(admitted(&|| gcra.check().is_ok()), 10);
+    assert_eq!(admitted(&|| window.check().is_ok()), 10);
+    clock.set(ms * 1000);
+    assert_eq!(admitted(&|| gcra.check().is_ok()), 1);
+    assert_eq!(admitted(&|| window.check().is_ok()), 0);
+    assert_eq!(gcra.clock_regressions(), 0);
+
+    // The clock goes back by 50ms: Measured at that time, the sliding window's earlier burst
+    // would mostly lie outside the window, but time stands still instead.
+    clock.set(ms * 950);
+    assert_eq!(admitted(&|| gcra.check().is_ok()), 0);
+    assert_eq!(admitted(&|| window.check().is_ok()), 0);
+    assert_eq!(gcra.clock_regressions(), 20);
+    assert_eq!(window.clock_regressions(), 20);
+
+    // Once the clock catches up, the rate limiters replenish from where they left off:
+    clock.set(ms * 1100);
+    assert_eq!(admitted(&|| gcra.check().is_ok()), 1);
+    assert_eq!(admitted(&|| window.check().is_ok()), 1);
+    assert_eq!(gcra.clock_regressions(), 20);
+}
+
+#[test]
+fn small_clock_regressions_are_tolerated() {
+    let clock = SettableClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+    clock.set(Duration::from_secs(1));
+    assert_eq!(Ok(()), lim.check());
+    // Readings taken concurrently can arrive slightly out of order:
+    clock.set(Duration::from_secs(1) - Duration::from_micros(100));
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(lim.clock_regressions(), 0);
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "std")]
+
+use futures::task::noop_waker_ref;
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    state::{ConcurrencyRateLimiter, ConcurrencyRejection},
+    Quota,
+};
+use nonzero_ext::nonzero;
+use std::future::Future;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+fn poll<F: Future + ?Sized>(future: std::pin::Pin<&mut F>) -> Poll<F::Output> {
+    future.poll(&mut Context::from_waker(noop_waker_ref()))
+}
+
+#[test]
+fn held_guards_saturate_the_concurrency_limit() {
+    let clock = FakeRelativeClock::default();
+    let lim = ConcurrencyRateLimiter::with_clock(
+        Quota::per_second(nonzero!(10u32)),
+        nonzero!(2u32),
+        &clock,
+    );
+    let first = lim.check().unwrap();
+    let second = lim.check().unwrap();
+    assert_eq!(lim.in_flight(), 2);
+
+    // The quota still has capacity, but no concurrency slot is free:
+    assert_eq!(lim.available(), 8);
+    assert!(matches!(lim.check(), Err(ConcurrencyRejection::Saturated)));
+    assert_eq!(lim.available(), 8);
+
+    drop(first);
+    assert_eq!(lim.in_flight(), 1);
+    let third = lim.check().unwrap();
+    assert_eq!(lim.available(), 7);
+    drop((second, third));
+    assert_eq!(lim.in_flight(), 0);
+}
+
+#[test]
+fn rate_limited_checks_release_their_slot() {
+    let clock = FakeRelativeClock::default();
+    let lim = ConcurrencyRateLimiter::with_clock(
+        Quota::per_second(nonzero!(2u32)),
+        nonzero!(5u32),
+        &clock,
+    );
+    let _guards = [lim.check().unwrap(), lim.check().unwrap()];
+    match lim.check() {
+        Err(ConcurrencyRejection::RateLimited(negative)) => {
+            assert_eq!(
+                negative.wait_time_from(clock.now()),
+                Duration::from_millis(500)
+            )
+        }
+        other => panic!("expected a rate-limited check, got {:?}", other.map(|_| ())),
+    }
+    assert_eq!(lim.in_flight(), 2);
+}
+
+#[test]
+fn until_ready_waits_for_a_concurrency_slot() {
+    let lim = ConcurrencyRateLimiter::new(Quota::per_second(nonzero!(100u32)), nonzero!(1u32));
+    let guard = lim.check().unwrap();
+
+    let mut ready = Box::pin(lim.until_ready());
+    assert!(poll(ready.as_mut()).is_pending());
+    assert!(poll(ready.as_mut()).is_pending());
+    assert_eq!(lim.available(), 99);
+
+    drop(guard);
+    match poll(ready.as_mut()) {
+        Poll::Ready(guard) => {
+            assert_eq!(lim.in_flight(), 1);
+            drop(guard);
+        }
+        Poll::Pending => panic!("the released slot should be taken"),
+    }
+    assert_eq!(lim.in_flight(), 0);
+}
@@ -104,19 +104,98 @@ fn all_capacity_check_rejects_excess() {
     let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
 
     assert_eq!(
-        Err(NegativeMultiDecision::InsufficientCapacity(5)),
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 15,
+            max_burst: 5,
+        }),
         lb.check_n(nonzero!(15u32))
     );
     assert_eq!(
-        Err(NegativeMultiDecision::InsufficientCapacity(5)),
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 6,
+            max_burst: 5,
+        }),
         lb.check_n(nonzero!(6u32))
     );
     assert_eq!(
-        Err(NegativeMultiDecision::InsufficientCapacity(5)),
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 7,
+            max_burst: 5,
+        }),
         lb.check_n(nonzero!(7u32))
     );
 }
 
+#[test]
+fn check_n_distinguishes_insufficient_capacity_from_retry_later() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    // A full bucket's worth is allowed through:
+    assert_eq!(Ok(()), lb.check_n(nonzero!(5u32)));
+
+    // One more than the bucket can hold can never be allowed through:
+    assert_eq!(
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 6,
+            max_burst: 5,
+        }),
+        lb.check_n(nonzero!(6u32))
+    );
+
+    // Cells that fit into the bucket, but not into what has replenished so far, can be retried
+    // later:
+    clock.advance(Duration::from_millis(400));
+    match lb.check_n(nonzero!(3u32)) {
+        Err(NegativeMultiDecision::BatchNonConforming(3, negative)) => {
+            let wait = negative.wait_time_from(clock.now());
+            assert_eq!(Duration::from_millis(200), wait);
+            clock.advance(wait);
+            assert_eq!(Ok(()), lb.check_n(nonzero!(3u32)));
+        }
+        other => panic!("expected to be retried later, got {:?}", other),
+    }
+}
+
+#[test]
+fn paces_streamed_bytes() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::bytes_per_second(nonzero!(5_000u32)), &clock);
+
+    // Stream 10 KB in 1 KB chunks, waiting whenever the limiter says so:
+    let mut sent_at = vec![];
+    for _ in 0..10 {
+        loop {
+            match lb.check_cost(1_000) {
+                Ok(Some(())) => break,
+                Err(NegativeMultiDecision::BatchNonConforming(1_000, negative)) => {
+                    clock.advance(negative.wait_time_from(clock.now()));
+                }
+                other => panic!("unexpected decision {:?}", other),
+            }
+        }
+        let elapsed: Duration = clock.now().into();
+        sent_at.push(elapsed.as_millis());
+        // Zero-byte writes never use up (or wait for) any of the quota:
+        assert_eq!(Ok(None), lb.check_cost(0));
+    }
+
+    // The first 5 KB go out in a burst, the rest at 5 KB/s:
+    assert_eq!(vec![0, 0, 0, 0, 0, 200, 400, 600, 800, 1000], sent_at);
+}
+
+#[test]
+fn zero_cost_checks_leave_exhausted_limiters_alone() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    assert_eq!(Ok(Some(())), lb.check_cost(2));
+
+    let exhausted = lb.state_information();
+    assert_eq!(Ok(None), lb.check_cost(0));
+    assert_eq!(exhausted, lb.state_information());
+    assert!(lb.check_cost(1).is_err());
+}
+
 #[test]
 fn correct_wait_time() {
     let clock = FakeRelativeClock::default();
@@ -163,3 +242,676 @@ fn actual_threadsafety() {
     clock.advance(ms * 998);
     assert_eq!(Ok(()), lim.check());
 }
+
+#[test]
+fn set_quota_increase_takes_effect() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let ms = Duration::from_millis(1);
+
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+
+    // A larger burst is available immediately:
+    lim.set_quota(Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(10u32)));
+    for _ in 0..3 {
+        assert_eq!(Ok(()), lim.check());
+    }
+    assert_ne!(Ok(()), lim.check());
+
+    // ...and cells replenish at the new rate:
+    clock.advance(ms * 200);
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn set_quota_decrease_takes_effect() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+    let ms = Duration::from_millis(1);
+
+    assert_eq!(Ok(()), lim.check());
+
+    // The cell allowed through earlier is not charged again:
+    lim.set_quota(Quota::per_second(nonzero!(1u32)));
+    assert_eq!(Ok(()), lim.check());
+    let negative = lim.check().unwrap_err();
+    assert_eq!(negative.wait_time_from(clock.now()), ms * 200);
+
+    // ...but the new rate applies to cells allowed through from now on:
+    clock.advance(ms * 200);
+    assert_eq!(Ok(()), lim.check());
+    let negative = lim.check().unwrap_err();
+    assert_eq!(negative.wait_time_from(clock.now()), ms * 1000);
+}
+
+#[test]
+fn peek_does_not_consume() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lim.peek());
+    }
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lim.peek());
+        assert_eq!(Ok(()), lim.check());
+    }
+    assert_eq!(lim.peek(), lim.check());
+    assert_ne!(Ok(()), lim.peek());
+}
+
+#[test]
+fn refund_restores_capacity() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lim.check());
+    }
+    assert_ne!(Ok(()), lim.check());
+
+    lim.refund(nonzero!(2u32));
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn refund_never_exceeds_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    // refunding on a fresh rate limiter does nothing:
+    lim.refund(nonzero!(3u32));
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lim.check());
+    }
+    assert_ne!(Ok(()), lim.check());
+
+    // refunding more than was used restores only the full burst:
+    lim.refund(nonzero!(10u32));
+    assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn concurrent_refunds_never_exceed_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+
+    crossbeam::scope(|scope| {
+        for _i in 0..20 {
+            scope.spawn(|_| {
+                for _ in 0..100 {
+                    if lim.check().is_ok() {
+                        lim.refund(nonzero!(1u32));
+                    }
+                    lim.refund(nonzero!(1u32));
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    assert_eq!(Ok(()), lim.check_n(nonzero!(10u32)));
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn reset_restores_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+    assert_ne!(Ok(()), lim.check());
+
+    lim.reset();
+    assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn is_cold_until_the_first_cell() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    assert!(lim.is_cold());
+    // Inspecting the rate limiter doesn't warm it up:
+    assert_eq!(5, lim.available());
+    assert_eq!(Ok(()), lim.peek());
+    assert!(lim.is_cold());
+
+    assert_eq!(Ok(()), lim.check());
+    assert!(!lim.is_cold());
+    // Once replenished, the rate limiter is idle, but not cold:
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(5, lim.available());
+    assert!(!lim.is_cold());
+
+    lim.reset();
+    assert!(lim.is_cold());
+}
+
+#[test]
+fn wait_time_from_rounded_rounds_up_to_the_granularity() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_minute(nonzero!(1u32)), &clock);
+    let second = Duration::from_secs(1);
+
+    assert_eq!(Ok(()), lim.check());
+    clock.advance(Duration::from_millis(58_800));
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(1200)
+    );
+    assert_eq!(
+        negative.wait_time_from_rounded(clock.now(), second),
+        second * 2
+    );
+    assert_eq!(
+        negative.wait_time_from_rounded(clock.now(), Duration::from_millis(400)),
+        Duration::from_millis(1200)
+    );
+    assert_eq!(
+        negative.wait_time_from_rounded(clock.now(), Duration::ZERO),
+        Duration::from_millis(1200)
+    );
+
+    clock.advance(Duration::from_millis(200));
+    let negative = lim.check().unwrap_err();
+    assert_eq!(negative.wait_time_from_rounded(clock.now(), second), second);
+}
+
+#[test]
+fn retry_after_secs_rounds_up() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_minute(nonzero!(1u32)), &clock);
+    let ms = Duration::from_millis(1);
+
+    assert_eq!(Ok(()), lim.check());
+    let negative = lim.check().unwrap_err();
+    assert_eq!(negative.retry_after_secs(clock.now()), 60);
+
+    clock.advance(ms * 59_500);
+    let negative = lim.check().unwrap_err();
+    assert_eq!(negative.wait_time_from(clock.now()), ms * 500);
+    assert_eq!(negative.retry_after_secs(clock.now()), 1);
+
+    clock.advance(ms * 500);
+    assert_eq!(negative.retry_after_secs(clock.now()), 0);
+}
+
+#[cfg(feature = "httpdate")]
+#[test]
+fn retry_after_http_date_is_not_early() {
+    use std::time::SystemTime;
+
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_minute(nonzero!(1u32)), &clock);
+
+    assert_eq!(Ok(()), lim.check());
+    let negative = lim.check().unwrap_err();
+    let earliest = SystemTime::now() + negative.wait_time_from(clock.now());
+    let date = httpdate::parse_http_date(&negative.retry_after_http_date(clock.now())).unwrap();
+    assert!(date >= earliest);
+    assert!(date < earliest + Duration::from_secs(2));
+}
+
+#[test]
+fn not_until_reports_quota() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(2u32));
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+
+    assert_eq!(Ok(()), lim.check_n(nonzero!(2u32)));
+    let negative = lim.check().unwrap_err();
+    assert_eq!(negative.quota(), quota);
+    assert_eq!(
+        negative.earliest_possible(),
+        clock.now() + Duration::from_millis(200)
+    );
+}
+
+#[test]
+fn available_cells() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+    assert_eq!(lim.available(), 10);
+
+    assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+    assert_eq!(lim.available(), 5);
+    assert_eq!(lim.available(), 5);
+    assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+    assert_eq!(lim.available(), 0);
+
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(lim.available(), 2);
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(lim.available(), 10);
+}
+
+#[test]
+fn borrowing_refuses_cells_until_repaid() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+
+    let borrowed = lim.check_n_allow_borrow(nonzero!(100u32)).unwrap();
+    assert_eq!(borrowed.borrowed_cells(), 90);
+    assert_eq!(borrowed.wait_time(), Duration::from_millis(9100));
+    assert!(lim.check().is_err());
+    assert!(lim.check_n_allow_borrow(nonzero!(1u32)).is_err());
+
+    clock.advance(Duration::from_millis(9000));
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(100)
+    );
+    clock.advance(Duration::from_millis(100));
+    assert_eq!(Ok(()), lim.check());
+}
+
+#[test]
+fn borrowing_within_capacity() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+
+    let borrowed = lim.check_n_allow_borrow(nonzero!(4u32)).unwrap();
+    assert_eq!(borrowed.borrowed_cells(), 0);
+    assert_eq!(borrowed.wait_time(), Duration::from_secs(0));
+    assert_eq!(lim.available(), 6);
+}
+
+#[test]
+fn borrowing_keeps_average_rate() {
+    let quota = Quota::per_second(nonzero!(10u32));
+    let admitted = |borrow: bool| {
+        let clock = FakeRelativeClock::default();
+        let lim = RateLimiter::direct_with_clock(quota, &clock);
+        let mut admitted = 0;
+        if borrow {
+            admitted += lim
+                .check_n_allow_borrow(nonzero!(100u32))
+                .map_or(0, |_| 100);
+        }
+        for _ in 0..2000 {
+            if lim.check().is_ok() {
+                admitted += 1;
+            }
+            clock.advance(Duration::from_millis(10));
+        }
+        admitted
+    };
+    let borrowing = admitted(true);
+    let steady = admitted(false);
+    assert!(
+        (borrowing as i64 - steady as i64).abs() <= 1,
+        "borrowing admitted {} cells, steady admitted {}",
+        borrowing,
+        steady
+    );
+}
+
+#[test]
+fn full_at_after_exhausting_burst() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(5u32));
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(lim.full_at(clock.now()), Duration::from_secs(0));
+
+    assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+    assert_eq!(
+        lim.full_at(clock.now()),
+        quota.replenish_interval() * quota.burst_size().get()
+    );
+    clock.advance(Duration::from_millis(300));
+    assert_eq!(lim.full_at(clock.now()), Duration::from_millis(700));
+    assert_eq!(
+        lim.full_at(clock.now() + Duration::from_millis(100)),
+        Duration::from_millis(600)
+    );
+
+    clock.advance(Duration::from_millis(699));
+    assert_eq!(lim.available(), 4);
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(lim.full_at(clock.now()), Duration::from_secs(0));
+    assert_eq!(lim.available(), 5);
+}
+
+/// Counts the cells that a rate limiter allows through in `duration` after its burst capacity is
+/// used up, checking as many cells as it allows every `step` (which must be shorter than it takes
+/// to replenish the burst capacity).
+fn throughput(quota: Quota, duration: Duration, step: Duration) -> u64 {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(quota, &clock);
+    while lb.check().is_ok() {}
+
+    let mut allowed = 0;
+    let mut elapsed = Duration::ZERO;
+    while elapsed < duration {
+        clock.advance(step);
+        elapsed += step;
+        while lb.check().is_ok() {
+            allowed += 1;
+        }
+    }
+    allowed
+}
+
+#[test]
+fn high_rate_throughput_is_accurate() {
+    let quota = Quota::per_second(nonzero!(5_000_000u32)).allow_burst(nonzero!(100u32));
+    let allowed = throughput(quota, Duration::from_millis(10), Duration::from_micros(1));
+    let nominal = 50_000;
+    assert!(
+        allowed.abs_diff(nominal) <= nominal / 100,
+        "allowed {} cells, expected {}",
+        allowed,
+        nominal
+    );
+}
+
+#[test]
+fn fractional_interval_throughput_is_accurate() {
+    // One cell per 3.33ns, which rounds down to 3ns, or 333,333,333 cells per second:
+    let quota = Quota::per_second(nonzero!(300_000_000u32)).allow_burst(nonzero!(100u32));
+    let allowed = throughput(quota, Duration::from_millis(1), Duration::from_nanos(100));
+    let nominal = 300_000;
+    assert!(
+        allowed.abs_diff(nominal) <= nominal / 100,
+        "allowed {} cells, expected {}",
+        allowed,
+        nominal
+    );
+
+    let quota = Quota::per_second(nonzero!(3_000_000u32)).allow_burst(nonzero!(100u32));
+    let allowed = throughput(quota, Duration::from_millis(10), Duration::from_micros(5));
+    assert!(allowed.abs_diff(30_000) <= 1, "allowed {} cells", allowed);
+}
+
+#[test]
+fn enforces_burst_after_centuries_of_idling() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    clock.advance(Duration::from_nanos(u64::MAX) - Duration::from_secs(60 * 60));
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lb.check());
+    }
+    assert_ne!(Ok(()), lb.check());
+    clock.advance(Duration::from_millis(200));
+    assert_eq!(Ok(()), lb.check());
+    assert_ne!(Ok(()), lb.check());
+}
+
+// With the `wide-nanos` feature, time ends much later (see tests/wide_nanos.rs):
+#[cfg(not(feature = "wide-nanos"))]
+#[test]
+fn never_allows_unlimited_cells_at_the_end_of_time() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    clock.advance(Duration::from_nanos(u64::MAX - 1_000));
+    let allowed = (0..100).filter(|_| lb.check().is_ok()).count();
+    assert!(allowed <= 5, "allowed {} cells", allowed);
+
+    // The burst capacity of this quota is longer than the clock can represent:
+    let clock = FakeRelativeClock::default();
+    let huge = Quota::per_hour(nonzero!(1u32)).allow_burst(nonzero!(u32::MAX));
+    let lb = RateLimiter::direct_with_clock(huge, &clock);
+    clock.advance(Duration::from_nanos(u64::MAX - 1_000));
+    assert!(lb.check_n(nonzero!(u32::MAX)).is_err());
+    let allowed = (0..100).filter(|_| lb.check().is_ok()).count();
+    assert!(allowed <= 5, "allowed {} cells", allowed);
+}
+
+#[test]
+fn daily_quota_over_many_days() {
+    let clock = FakeRelativeClock::default();
+    let day = Duration::from_secs(60 * 60 * 24);
+    let quota = Quota::with_period(day).unwrap().allow_burst(nonzero!(3u32));
+    let lb = RateLimiter::direct_with_clock(quota, &clock);
+    for _ in 0..3 {
+        assert_eq!(Ok(()), lb.check());
+    }
+
+    // Ten years of one cell per day:
+    for _ in 0..10 * 365 {
+        assert_eq!(day, lb.check().unwrap_err().wait_time_from(clock.now()));
+        clock.advance(day);
+        assert_eq!(Ok(()), lb.check(), "Now: {:?}", clock.now());
+    }
+    assert_ne!(Ok(()), lb.check());
+
+    clock.advance(day * 3);
+    assert_eq!(3, lb.state_information().remaining_burst_capacity());
+    for _ in 0..3 {
+        assert_eq!(Ok(()), lb.check());
+    }
+    assert_eq!(day, lb.check().unwrap_err().wait_time_from(clock.now()));
+
+    clock.advance(day * 1000);
+    assert_eq!(3, lb.state_information().remaining_burst_capacity());
+    assert_eq!(Duration::ZERO, lb.full_at(clock.now()));
+}
+
+#[test]
+fn check_at_explicit_times() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let start = clock.now();
+    let ms = Duration::from_millis(1);
+
+    assert_eq!(Ok(()), lb.check_at(start));
+    assert_eq!(Ok(()), lb.check_at(start + ms));
+    assert_eq!(
+        lb.check_at(start + ms * 2)
+            .unwrap_err()
+            .wait_time_from(start + ms * 2),
+        ms * 498
+    );
+    // Every 500ms, one more cell is allowed through:
+    for i in 1..100 {
+        let now = start + ms * (500 * i);
+        assert_eq!(Ok(()), lb.check_at(now), "at {:?}", now);
+        assert_ne!(Ok(()), lb.check_at(now + ms), "at {:?}", now + ms);
+    }
+    // The clock was never advanced:
+    assert_eq!(start, clock.now());
+}
+
+#[test]
+fn check_n_at_explicit_times() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    let start = clock.now();
+    let ms = Duration::from_millis(1);
+
+    assert_eq!(Ok(()), lb.check_n_at(nonzero!(4u32), start));
+    for i in 1..50 {
+        let now = start + ms * (500 * i);
+        assert!(lb.check_n_at(nonzero!(3u32), now).is_err(), "at {:?}", now);
+        assert_eq!(Ok(()), lb.check_n_at(nonzero!(2u32), now), "at {:?}", now);
+    }
+    assert_eq!(
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 5,
+            max_burst: 4,
+        }),
+        lb.check_n_at(nonzero!(5u32), start)
+    );
+}
+
+#[test]
+fn only_failures_use_up_capacity() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    let outcomes = [true, false, true, true, false, true, true, false, true];
+    for succeeded in outcomes {
+        assert_eq!(Ok(()), lim.try_begin());
+        if !succeeded {
+            lim.charge_failure();
+        }
+    }
+    assert_eq!(1, lim.available());
+    lim.charge_failure();
+    assert!(lim.try_begin().is_err());
+
+    // Failures of attempts that began concurrently don't deepen the throttle:
+    lim.charge_failure();
+    lim.charge_failure();
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(Ok(()), lim.try_begin());
+    assert_eq!(1, lim.available());
+}
+
+#[test]
+fn relaxed_ordering_never_over_admits() {
+    use crossbeam;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let quota = Quota::per_second(nonzero!(100u32));
+    let clock = FakeRelativeClock::default();
+    let relaxed = RateLimiter::direct_relaxed_with_clock(quota, &clock);
+    let strict = RateLimiter::direct_with_clock(quota, &clock);
+    for _round in 0..10 {
+        let allowed = AtomicU32::new(0);
+        crossbeam::scope(|scope| {
+            for _thread in 0..8 {
+                scope.spawn(|_| {
+                    for _ in 0..1000 {
+                        if relaxed.check().is_ok() {
+                            allowed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+        let mut expected = 0;
+        while strict.check().is_ok() {
+            expected += 1;
+        }
+        assert_eq!(expected, allowed.load(Ordering::Relaxed));
+        clock.advance(Duration::from_millis(250));
+    }
+}
+
+#[test]
+fn schedule_paces_shaped_cells() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)).shaping(), &clock);
+    for i in 0..10 {
+        assert_eq!(Duration::from_millis(500) * i, lim.schedule());
+    }
+    assert!(lim.check().is_err());
+
+    // Once the scheduled cells have gone through, the next one is scheduled right away:
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(Duration::ZERO, lim.schedule());
+    assert_eq!(Duration::from_millis(500), lim.schedule());
+}
+
+#[test]
+fn earliest_possible_is_wait_time_after_now() {
+    use governor::clock::Reference;
+
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    lim.check_n(nonzero!(4u32)).unwrap();
+    clock.advance(Duration::from_millis(100));
+    let negative = lim.check().unwrap_err();
+    let now = clock.now();
+    let wait: Duration = negative.earliest_possible().duration_since(now).into();
+    assert_eq!(Duration::from_millis(150), wait);
+    assert_eq!(wait, negative.wait_time_from(now));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn earliest_possible_is_a_std_instant() {
+    use governor::clock::MonotonicClock;
+
+    let clock = MonotonicClock;
+    let lim = RateLimiter::direct_with_clock(Quota::per_minute(nonzero!(1u32)), &clock);
+    lim.check().unwrap();
+    let negative = lim.check().unwrap_err();
+    let now = std::time::Instant::now();
+    let deadline: std::time::Instant = negative.earliest_possible();
+    assert_eq!(deadline - now, negative.wait_time_from(now));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn export_and_restore_state() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_minute(nonzero!(10u32));
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(Ok(()), lim.check_n(nonzero!(7u32)));
+
+    // Restoring into a rate limiter with a different clock resumes where we left off:
+    let new_clock = FakeRelativeClock::default();
+    new_clock.advance(Duration::from_secs(3600));
+    let restored = RateLimiter::from_snapshot(quota, &new_clock, lim.export_state());
+    assert_eq!(3, restored.available());
+    assert!(restored.check_n(nonzero!(4u32)).is_err());
+
+    // An exhausted rate limiter stays exhausted:
+    assert_eq!(Ok(()), lim.check_n(nonzero!(3u32)));
+    let restored = RateLimiter::from_snapshot(quota, &new_clock, lim.export_state());
+    assert_eq!(0, restored.available());
+    new_clock.advance(Duration::from_secs(6));
+    assert_eq!(1, restored.available());
+
+    // A snapshot of a fresh rate limiter restores to a fresh one:
+    let fresh = RateLimiter::direct_with_clock(quota, &clock);
+    let restored = RateLimiter::from_snapshot(quota, &new_clock, fresh.export_state());
+    assert_eq!(10, restored.available());
+}
+
+#[test]
+fn check_batch_reports_each_cell_in_order() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::with_period(Duration::from_millis(250))
+        .unwrap()
+        .allow_burst(nonzero!(3u32));
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    let outcomes = lim.check_batch(5);
+    assert_eq!(5, outcomes.len());
+    assert!(outcomes[..3].iter().all(|outcome| outcome.is_ok()));
+
+    let waits: Vec<Duration> = outcomes[3..]
+        .iter()
+        .map(|outcome| outcome.as_ref().unwrap_err().wait_time_from(clock.now()))
+        .collect();
+    let interval = Duration::from_millis(250);
+    assert_eq!(waits, vec![interval, interval * 2]);
+    // The batch used up exactly the capacity of the cells that were allowed through:
+    assert_eq!(0, lim.available());
+    clock.advance(interval);
+    assert_eq!(Ok(()), lim.check());
+    assert!(lim.check_batch(0).is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn check_deadline_returns_now_plus_the_wait_time() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    assert_eq!(Ok(()), lim.check_deadline());
+    assert_eq!(Ok(()), lim.check_deadline());
+    clock.advance(Duration::from_millis(100));
+    let before = std::time::Instant::now();
+    let deadline = lim.check_deadline().unwrap_err();
+    let after = std::time::Instant::now();
+    let wait = Duration::from_millis(400);
+    assert!(deadline >= before + wait, "{:?}", deadline - before);
+    assert!(deadline <= after + wait, "{:?}", after + wait - deadline);
+
+    // The rejected cell isn't counted:
+    clock.advance(wait);
+    assert_eq!(Ok(()), lim.check_deadline());
+}
@@ -1,7 +1,8 @@
 #![cfg(feature = "std")]
 
 use futures::executor::block_on;
-use governor::{Quota, RateLimiter};
+use governor::state::{keyed::DefaultKeyedStateStore, InsufficientCapacity};
+use governor::{Jitter, Quota, RateLimiter, SeededJitterRng};
 use more_asserts::*;
 use nonzero_ext::*;
 use std::sync::Arc;
@@ -53,6 +54,27 @@ fn pauses_keyed() {
     assert_ge!(i.elapsed(), Duration::from_millis(100));
 }
 
+#[test]
+fn pauses_keyed_with_default_jitter() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(3);
+    static EXPECTED_RNG: SeededJitterRng = SeededJitterRng::new(3);
+    let jitter = Jitter::new(Duration::from_millis(50), Duration::from_millis(50));
+    let i = Instant::now();
+    let expected = jitter.with_rng(&EXPECTED_RNG) + Duration::from_millis(100);
+    let lim =
+        RateLimiter::keyed_with_jitter(Quota::per_second(nonzero!(10u32)), jitter.with_rng(&RNG));
+
+    // exhaust the limiter:
+    loop {
+        if lim.check_key(&1u32).is_err() {
+            break;
+        }
+    }
+
+    block_on(lim.until_key_ready(&1u32));
+    assert_ge!(i.elapsed(), expected);
+}
+
 #[test]
 fn proceeds() {
     let i = Instant::now();
@@ -80,6 +102,23 @@ fn proceeds_keyed() {
     assert_le!(i.elapsed(), Duration::from_millis(100));
 }
 
+#[test]
+fn proceeds_keyed_with_borrowed_keys() {
+    let lim: RateLimiter<String, DefaultKeyedStateStore<String>, _> =
+        RateLimiter::keyed(Quota::per_second(nonzero!(1u32)));
+
+    assert_eq!(Ok(Some(())), block_on(lim.until_key_cost_ready("a", 1)));
+    assert_eq!(
+        Ok(()),
+        block_on(lim.until_key_ready_with_timeout("b", Duration::ZERO))
+    );
+    assert!(block_on(lim.until_key_ready_with_timeout("b", Duration::from_millis(10))).is_err());
+    let jitter = Jitter::up_to(Duration::from_millis(1));
+    let waited = lim.until_key_ready_with_jitter_and_timeout("a", jitter, Duration::ZERO);
+    assert!(block_on(waited).is_err());
+    assert_eq!(2, lim.len());
+}
+
 #[test]
 fn multiple() {
     let i = Instant::now();
@@ -136,5 +175,52 @@ fn multiple_keyed() {
 fn errors_on_exceeded_capacity() {
     let lim = RateLimiter::direct(Quota::per_second(nonzero!(10u32)));
 
-    block_on(lim.until_n_ready(nonzero!(11u32))).unwrap_err();
+    assert_eq!(
+        Err(InsufficientCapacity {
+            requested: 11,
+            max_burst: 10
+        }),
+        block_on(lim.until_n_ready(nonzero!(11u32)))
+    );
+}
+
+#[test]
+fn fair_waiters_acquire_cells_in_order() {
+    use futures::executor::LocalPool;
+    use futures::task::LocalSpawnExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let lim = Rc::new(RateLimiter::direct(Quota::per_second(nonzero!(20u32))));
+    while lim.check().is_ok() {}
+
+    let started = Rc::new(RefCell::new(vec![]));
+    let acquired = Rc::new(RefCell::new(vec![]));
+    let mut pool = LocalPool::new();
+    for task in 0..5 {
+        let (lim, started, acquired) = (lim.clone(), started.clone(), acquired.clone());
+        pool.spawner()
+            .spawn_local(async move {
+                started.borrow_mut().push(task);
+                lim.until_ready_fair().await;
+                acquired.borrow_mut().push(task);
+            })
+            .unwrap();
+    }
+    let i = Instant::now();
+    pool.run();
+    assert_eq!(*started.borrow(), *acquired.borrow());
+    // The five cells are spaced 50ms apart, starting within 50ms of exhausting the limiter:
+    assert_ge!(i.elapsed(), Duration::from_millis(200));
+}
+
+#[test]
+fn dropped_fair_waiters_refund_their_cell() {
+    use futures::FutureExt;
+
+    let lim = RateLimiter::direct(Quota::per_second(nonzero!(10u32)));
+    while lim.check().is_ok() {}
+    let earliest = lim.check().unwrap_err().earliest_possible();
+    assert!(lim.until_ready_fair().now_or_never().is_none());
+    assert_eq!(earliest, lim.check().unwrap_err().earliest_possible());
 }
@@ -0,0 +1,81 @@
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    state::{Admission, GraceRateLimiter},
+    Quota,
+};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+#[test]
+fn penalties_grow_until_the_grace_cells_run_out() {
+    let clock = FakeRelativeClock::default();
+    let lim = GraceRateLimiter::with_clock(Quota::per_second(nonzero!(4u32)), 3, &clock).unwrap();
+    assert_eq!(lim.grace(), 3);
+    assert_eq!(lim.available(), 7);
+
+    for _ in 0..4 {
+        assert_eq!(Ok(Admission::Allowed), lim.check());
+    }
+    let penalties: Vec<_> = (0..3)
+        .map(|_| match lim.check() {
+            Ok(Admission::Throttled { penalty }) => penalty,
+            other => panic!("expected a throttled cell, got {:?}", other),
+        })
+        .collect();
+    assert_eq!(
+        penalties,
+        [250, 500, 1000].map(Duration::from_millis).to_vec()
+    );
+
+    // Past the grace cells, the rate limiter rejects cells until one replenishes:
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(250)
+    );
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(
+        Ok(Admission::Throttled {
+            penalty: Duration::from_secs(1)
+        }),
+        lim.check()
+    );
+}
+
+#[test]
+fn grace_cells_replenish_at_the_quotas_rate() {
+    let clock = FakeRelativeClock::default();
+    let lim = GraceRateLimiter::with_clock(Quota::per_second(nonzero!(2u32)), 2, &clock).unwrap();
+
+    let mut allowed = 0;
+    for _ in 0..1000 {
+        if lim.check().is_ok() {
+            allowed += 1;
+        }
+        clock.advance(Duration::from_millis(10));
+    }
+    // Ten seconds at 2 cells per second, plus the burst and grace cells:
+    assert_eq!(allowed, 2 + 2 + 19);
+    assert_eq!(
+        Ok(Admission::Throttled {
+            penalty: Duration::from_secs(1)
+        }),
+        lim.check()
+    );
+}
+
+#[test]
+fn grace_cells_must_fit_the_burst_size() {
+    assert!(GraceRateLimiter::with_clock(
+        Quota::per_second(nonzero!(4u32)),
+        u32::MAX,
+        &FakeRelativeClock::default(),
+    )
+    .is_none());
+    assert!(GraceRateLimiter::with_clock(
+        Quota::per_second(nonzero!(4u32)),
+        u32::MAX - 4,
+        &FakeRelativeClock::default(),
+    )
+    .is_some());
+}
@@ -0,0 +1,47 @@
+#![cfg(feature = "http")]
+
+use governor::{
+    clock::FakeRelativeClock,
+    http::{insert_rate_limit_headers, RATELIMIT_LIMIT, RATELIMIT_REMAINING, RATELIMIT_RESET},
+    middleware::StateInformationMiddleware,
+    Quota, RateLimiter,
+};
+use http::{HeaderMap, HeaderValue};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+#[test]
+fn sets_rate_limit_headers() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_minute(nonzero!(60u32)), &clock)
+        .with_middleware(StateInformationMiddleware);
+    let mut headers = HeaderMap::new();
+    headers.insert(RATELIMIT_REMAINING, HeaderValue::from_static("stale"));
+
+    lim.check().unwrap();
+    lim.check().unwrap();
+    let state = lim.check().unwrap();
+    insert_rate_limit_headers(&state, &mut headers);
+    assert_eq!(headers.len(), 3);
+    assert_eq!(headers[RATELIMIT_LIMIT], "60");
+    assert_eq!(headers[RATELIMIT_REMAINING], "57");
+    assert_eq!(headers[RATELIMIT_RESET], "3");
+
+    // Partial seconds until the quota is fully replenished round up:
+    clock.advance(Duration::from_millis(2500));
+    let state = lim.check().unwrap();
+    insert_rate_limit_headers(&state, &mut headers);
+    assert_eq!(headers[RATELIMIT_REMAINING], "58");
+    assert_eq!(headers[RATELIMIT_RESET], "2");
+}
+
+#[test]
+fn reset_is_zero_when_full() {
+    let lim = RateLimiter::direct(Quota::per_second(nonzero!(5u32)));
+    let state = lim.state_information();
+    let mut headers = HeaderMap::new();
+    insert_rate_limit_headers(&state, &mut headers);
+    assert_eq!(headers[RATELIMIT_LIMIT], "5");
+    assert_eq!(headers[RATELIMIT_REMAINING], "5");
+    assert_eq!(headers[RATELIMIT_RESET], "0");
+}
@@ -0,0 +1,267 @@
+use governor::distribution::JitterDistribution;
+use governor::{DecorrelatedJitter, Jitter, JitterRng, SeededJitterRng};
+use rand::RngCore;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// A source of randomness that counts how often it is used.
+#[derive(Debug)]
+struct CountingRng(AtomicU64);
+
+impl JitterRng for CountingRng {
+    fn next_u64(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+#[test]
+fn none_adds_nothing_without_randomness() {
+    static RNG: CountingRng = CountingRng(AtomicU64::new(0));
+    let jitter = Jitter::none().with_rng(&RNG);
+    for d in [
+        Duration::ZERO,
+        Duration::new(24, 123_456_789),
+        Duration::MAX,
+    ] {
+        assert_eq!(d, jitter + d);
+        assert_eq!(d, Jitter::none() + d);
+    }
+    assert_eq!(0, RNG.0.load(Ordering::Relaxed));
+    assert_eq!(Duration::ZERO, Jitter::none().max());
+}
+
+#[test]
+fn seeded_jitter_is_reproducible() {
+    static RNG_A: SeededJitterRng = SeededJitterRng::new(1234);
+    static RNG_B: SeededJitterRng = SeededJitterRng::new(1234);
+    let a = Jitter::new_with_rng(Duration::from_millis(10), Duration::from_millis(50), &RNG_A);
+    let b = Jitter::new_with_rng(Duration::from_millis(10), Duration::from_millis(50), &RNG_B);
+
+    let zero = Duration::new(0, 0);
+    let seq_a: Vec<Duration> = (0..100).map(|_| a + zero).collect();
+    let seq_b: Vec<Duration> = (0..100).map(|_| b + zero).collect();
+    assert_eq!(seq_a, seq_b);
+}
+
+#[test]
+fn seeded_jitter_differs_by_seed() {
+    static RNG_A: SeededJitterRng = SeededJitterRng::new(1);
+    static RNG_B: SeededJitterRng = SeededJitterRng::new(2);
+    let a = Jitter::up_to_with_rng(Duration::from_secs(1), &RNG_A);
+    let b = Jitter::up_to_with_rng(Duration::from_secs(1), &RNG_B);
+
+    let zero = Duration::new(0, 0);
+    let seq_a: Vec<Duration> = (0..10).map(|_| a + zero).collect();
+    let seq_b: Vec<Duration> = (0..10).map(|_| b + zero).collect();
+    assert_ne!(seq_a, seq_b);
+}
+
+#[test]
+fn seeded_jitter_stays_in_interval() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(99);
+    let jitter = Jitter::new_with_rng(Duration::from_secs(1), Duration::from_secs(1), &RNG);
+    let reference = Duration::from_secs(24);
+    for _ in 0..1000 {
+        let result = jitter + reference;
+        assert!(result >= reference + Duration::from_secs(1));
+        assert!(result < reference + Duration::from_secs(2));
+    }
+}
+
+/// Picks whole milliseconds from the source of randomness, so the values are predictable.
+#[derive(Debug)]
+struct Millis;
+
+impl JitterDistribution for Millis {
+    fn sample(&self, rng: &mut dyn RngCore, min: Duration, interval: Duration) -> Duration {
+        min + Duration::from_millis(rng.next_u64() % interval.as_millis() as u64)
+    }
+}
+
+#[test]
+fn supplied_source_drives_jitter_without_the_thread_rng() {
+    // Constructing jitter with a source works without `std`, as on bare-metal targets:
+    static COUNTER: CountingRng = CountingRng(AtomicU64::new(3));
+    let jitter = Jitter::new_with_rng(Duration::from_secs(1), Duration::from_secs(1), &COUNTER)
+        .with_distribution(&Millis);
+    assert_eq!(jitter.min(), Duration::from_secs(1));
+    assert_eq!(jitter.max(), Duration::from_secs(2));
+
+    let zero = Duration::new(0, 0);
+    let offsets: Vec<Duration> = (0..3).map(|_| jitter + zero).collect();
+    assert_eq!(
+        offsets,
+        [1003, 1004, 1005].map(Duration::from_millis).to_vec()
+    );
+    assert_eq!(6, COUNTER.0.load(Ordering::Relaxed));
+
+    static DECORRELATED_COUNTER: CountingRng = CountingRng(AtomicU64::new(0));
+    let decorrelated = DecorrelatedJitter::new_with_rng(
+        Duration::from_millis(10),
+        Duration::from_secs(1),
+        &DECORRELATED_COUNTER,
+    );
+    for _ in 0..5 {
+        let value = &decorrelated + zero;
+        assert!(value >= Duration::from_millis(10), "{:?}", value);
+        assert!(value <= Duration::from_secs(1), "{:?}", value);
+    }
+    assert!(DECORRELATED_COUNTER.0.load(Ordering::Relaxed) > 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn bounds_up_to() {
+    let jitter = Jitter::up_to(Duration::from_millis(300));
+    assert_eq!(jitter.min(), Duration::new(0, 0));
+    assert_eq!(jitter.max(), Duration::from_millis(300));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn bounds_new() {
+    let jitter = Jitter::new(Duration::from_secs(1), Duration::from_millis(500));
+    assert_eq!(jitter.min(), Duration::from_secs(1));
+    assert_eq!(jitter.max(), Duration::from_millis(1500));
+
+    let reference = Duration::from_secs(3);
+    for _ in 0..100 {
+        let result = jitter + reference;
+        assert!(result >= reference + jitter.min());
+        assert!(result <= reference + jitter.max());
+    }
+}
+
+#[test]
+fn decorrelated_grows_and_stays_capped() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(7);
+    let base = Duration::from_millis(10);
+    let cap = Duration::from_secs(1);
+    let jitter = DecorrelatedJitter::new_with_rng(base, cap, &RNG);
+
+    let zero = Duration::new(0, 0);
+    let mut previous = base;
+    let mut largest = zero;
+    for _ in 0..50 {
+        let next = &jitter + zero;
+        assert!(next >= base, "{:?} < {:?}", next, base);
+        assert!(next <= cap, "{:?} > {:?}", next, cap);
+        assert!(next <= previous * 3, "{:?} > 3 * {:?}", next, previous);
+        previous = next;
+        largest = largest.max(next);
+    }
+    // With a cap 100x the base, 50 draws should have grown well past the base:
+    assert!(largest > cap / 2, "largest: {:?}", largest);
+}
+
+#[test]
+fn decorrelated_swaps_a_base_longer_than_the_cap() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(11);
+    let short = Duration::from_millis(10);
+    let long = Duration::from_secs(1);
+    let jitter = DecorrelatedJitter::new_with_rng(long, short, &RNG);
+
+    let zero = Duration::new(0, 0);
+    let mut values = Vec::new();
+    for _ in 0..50 {
+        let next = &jitter + zero;
+        assert!(next >= short, "{:?} < {:?}", next, short);
+        assert!(next <= long, "{:?} > {:?}", next, long);
+        values.push(next);
+    }
+    // The values grow from the shorter duration instead of sticking to either bound:
+    assert!(values.iter().any(|v| *v < long / 2), "{:?}", values);
+    assert!(values.iter().any(|v| *v > short * 2), "{:?}", values);
+}
+
+#[test]
+fn decorrelated_next_jitter_is_exact() {
+    static RNG_A: SeededJitterRng = SeededJitterRng::new(3);
+    static RNG_B: SeededJitterRng = SeededJitterRng::new(3);
+    let base = Duration::from_millis(1);
+    let cap = Duration::from_millis(100);
+    let a = DecorrelatedJitter::new_with_rng(base, cap, &RNG_A);
+    let b = DecorrelatedJitter::new_with_rng(base, cap, &RNG_B);
+
+    let zero = Duration::new(0, 0);
+    for _ in 0..20 {
+        let jitter = a.next_jitter();
+        assert_eq!(jitter.min(), jitter.max());
+        assert_eq!(jitter + zero, jitter.min());
+        assert_eq!(jitter.min(), &b + zero);
+    }
+}
+
+#[test]
+fn explicit_uniform_matches_default() {
+    use governor::distribution::Uniform;
+
+    static RNG_A: SeededJitterRng = SeededJitterRng::new(11);
+    static RNG_B: SeededJitterRng = SeededJitterRng::new(11);
+    let interval = Duration::from_secs(2);
+    let default = Jitter::up_to_with_rng(interval, &RNG_A);
+    let uniform = Jitter::up_to_with_rng(interval, &RNG_B).with_distribution(&Uniform);
+
+    let zero = Duration::new(0, 0);
+    for _ in 0..50 {
+        assert_eq!(default + zero, uniform + zero);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn exponential_favors_short_values() {
+    use governor::distribution::Exponential;
+
+    static RNG: SeededJitterRng = SeededJitterRng::new(5);
+    static EXPONENTIAL: Exponential = Exponential::new(4.0);
+    let min = Duration::from_millis(100);
+    let interval = Duration::from_secs(1);
+    let jitter = Jitter::new(min, interval)
+        .with_distribution(&EXPONENTIAL)
+        .with_rng(&RNG);
+
+    let zero = Duration::new(0, 0);
+    let mut short = 0;
+    for _ in 0..1000 {
+        let value = jitter + zero;
+        assert!(value >= jitter.min());
+        assert!(value < jitter.max());
+        if value < min + interval / 2 {
+            short += 1;
+        }
+    }
+    assert!(
+        short > 800,
+        "only {} of 1000 values in the first half",
+        short
+    );
+}
+
+#[test]
+fn decorrelated_saturates_with_huge_caps() {
+    static RNG: SeededJitterRng = SeededJitterRng::new(5);
+    let base = Duration::from_secs(1);
+    let jitter = DecorrelatedJitter::new_with_rng(base, Duration::MAX, &RNG);
+
+    // Tripling the previous value eventually exceeds the longest representable duration:
+    let zero = Duration::new(0, 0);
+    let longest = Duration::from_nanos(u64::MAX);
+    let mut largest = zero;
+    for _ in 0..200 {
+        let next = &jitter + zero;
+        assert!(next >= base, "{:?} < {:?}", next, base);
+        assert!(next <= longest, "{:?} > {:?}", next, longest);
+        largest = largest.max(next);
+    }
+    assert!(largest > longest / 3, "largest: {:?}", largest);
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "positive rate")]
+fn exponential_rejects_non_positive_rates_on_construction() {
+    use governor::distribution::Exponential;
+
+    let _ = Exponential::new(0.0);
+}
@@ -6,7 +6,10 @@ use governor::{
     Quota, RateLimiter,
 };
 use nonzero_ext::nonzero;
-use std::hash::Hash;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
 const KEYS: &[u32] = &[1u32, 2u32];
@@ -121,3 +124,250 @@ fn actual_threadsafety() {
         assert_eq!(Ok(()), lim.check_key(key));
     }
 }
+
+#[test]
+fn peek_does_not_consume() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::dashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    for key in KEYS {
+        for _ in 0..2 {
+            assert_eq!(Ok(()), lim.peek_key(key));
+        }
+        for _ in 0..2 {
+            assert_eq!(Ok(()), lim.check_key(key));
+        }
+        assert_ne!(Ok(()), lim.peek_key(key));
+        assert_ne!(Ok(()), lim.check_key(key));
+    }
+
+    // peeking doesn't create entries for keys:
+    assert_eq!(Ok(()), lim.peek_key(&3));
+    assert_eq!(retained_keys(&[1, 2, 3], lim), KEYS);
+}
+
+#[test]
+fn reset_key_restores_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::dashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    for key in KEYS {
+        assert_eq!(Ok(()), lim.check_key_n(key, nonzero!(2u32)));
+        assert_ne!(Ok(()), lim.check_key(key));
+    }
+
+    lim.reset_key(&1);
+    assert_eq!(Ok(()), lim.check_key_n(&1, nonzero!(2u32)));
+    assert_ne!(Ok(()), lim.check_key(&1));
+    // other keys are unaffected:
+    assert_ne!(Ok(()), lim.check_key(&2));
+
+    // resetting an untracked key does nothing:
+    lim.reset_key(&3);
+    assert_eq!(retained_keys(&[1, 2, 3], lim), KEYS);
+}
+
+#[test]
+fn snapshot_and_restore() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(2u32));
+    let ms = Duration::from_millis(1);
+    let lim = RateLimiter::dashmap_with_clock(quota, &clock);
+
+    assert_eq!(Ok(()), lim.check_key(&3));
+    clock.advance(ms * 1000);
+    assert_eq!(Ok(()), lim.check_key_n(&1, nonzero!(2u32)));
+    assert_eq!(Ok(()), lim.check_key(&2));
+    clock.advance(ms * 200);
+    let snapshot = lim.snapshot();
+    // key 3 has replenished completely:
+    let mut keys: Vec<u32> = snapshot.iter().map(|(k, _)| *k).collect();
+    keys.sort();
+    assert_eq!(keys, KEYS);
+
+    // restoring into a new rate limiter with a different clock resumes where we left off:
+    let new_clock = FakeRelativeClock::default();
+    new_clock.advance(ms * 10_000);
+    let restored = RateLimiter::dashmap_with_clock(quota, &new_clock);
+    restored.restore(snapshot);
+    assert_ne!(Ok(()), restored.check_key(&1));
+    assert_eq!(Ok(()), restored.check_key(&2));
+    assert_ne!(Ok(()), restored.check_key(&2));
+    assert_eq!(Ok(()), restored.check_key_n(&3, nonzero!(2u32)));
+
+    new_clock.advance(ms * 300);
+    assert_eq!(Ok(()), restored.check_key(&1));
+}
+
+#[test]
+fn retain_recent_shrinks() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::dashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let ms = Duration::from_millis(1);
+    assert!(lim.is_empty());
+
+    for key in 0..10u32 {
+        lim.check_key(&key).unwrap();
+    }
+    assert_eq!(lim.len(), 10);
+
+    // keys that are still being limited are retained:
+    clock.advance(ms * 500);
+    lim.check_key(&0).unwrap_err();
+    lim.retain_recent();
+    assert_eq!(lim.len(), 10);
+
+    clock.advance(ms * 1600);
+    lim.retain_recent();
+    assert!(lim.is_empty());
+}
+
+#[test]
+fn iter_returns_tracked_keys() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::dashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    for (key, cells) in &[(1u32, 1u32), (2, 5), (3, 2)] {
+        for _ in 0..*cells {
+            lim.check_key(key).unwrap();
+        }
+    }
+
+    let mut states: Vec<(u32, u32)> = lim
+        .iter()
+        .map(|(key, info)| (key, info.remaining_burst_capacity()))
+        .collect();
+    states.sort();
+    assert_eq!(states, vec![(1, 4), (2, 0), (3, 3)]);
+}
+
+#[test]
+fn preallocate_retain_and_shrink() {
+    let clock = FakeRelativeClock::default();
+    let lb =
+        RateLimiter::dashmap_with_capacity_and_clock(Quota::per_second(nonzero!(2u32)), 64, &clock);
+    assert!(lb.is_empty());
+
+    // Fill the map past its preallocated capacity, using up even keys' whole burst:
+    for key in 0..200u32 {
+        assert_eq!(Ok(()), lb.check_key(&key));
+        if key & 1 == 0 {
+            assert_eq!(Ok(()), lb.check_key(&key));
+        }
+    }
+    assert_eq!(200, lb.len());
+
+    // Keep only the keys that have no capacity left:
+    lb.retain_keys(|key, state| {
+        assert_eq!(key & 1 == 0, state.remaining_burst_capacity() == 0);
+        state.remaining_burst_capacity() == 0
+    });
+    assert_eq!(100, lb.len());
+    lb.shrink_to_fit();
+    assert_eq!(100, lb.len());
+
+    // Kept keys are still limited, removed keys start fresh:
+    assert_ne!(Ok(()), lb.check_key(&0));
+    assert_eq!(Ok(()), lb.check_key(&1));
+    assert_eq!(Ok(()), lb.check_key(&1));
+    assert_eq!(101, lb.len());
+}
+
+#[test]
+fn preallocation_does_not_change_decisions() {
+    const THREADS: u32 = 8;
+    const KEYS_PER_THREAD: u32 = 500;
+
+    let quota = Quota::per_second(nonzero!(2u32));
+    let clock = FakeRelativeClock::default();
+    // Room for no keys, fewer keys than there are shards, and all the keys:
+    for &capacity in &[0, 3, (THREADS * KEYS_PER_THREAD) as usize] {
+        let lb = Arc::new(RateLimiter::dashmap_with_capacity_and_clock(
+            quota, capacity, &clock,
+        ));
+        let children: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let lb = lb.clone();
+                std::thread::spawn(move || {
+                    let keys = thread * KEYS_PER_THREAD..(thread + 1) * KEYS_PER_THREAD;
+                    keys.map(|key| (0..3).filter(|_| lb.check_key(&key).is_ok()).count())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for child in children {
+            // Each key allows exactly its burst through:
+            assert!(child.join().unwrap().iter().all(|&allowed| allowed == 2));
+        }
+        assert_eq!((THREADS * KEYS_PER_THREAD) as usize, lb.len());
+    }
+}
+
+/// A keyed SipHash hasher that counts how many hashers it built.
+#[derive(Clone, Default)]
+struct CountingSipHash {
+    inner: RandomState,
+    built: Arc<AtomicUsize>,
+}
+
+impl BuildHasher for CountingSipHash {
+    type Hasher = <RandomState as BuildHasher>::Hasher;
+
+    fn build_hasher(&self) -> Self::Hasher {
+        self.built.fetch_add(1, Ordering::Relaxed);
+        self.inner.build_hasher()
+    }
+}
+
+#[test]
+fn custom_hasher() {
+    let clock = FakeRelativeClock::default();
+    let hasher = CountingSipHash::default();
+    let lb = RateLimiter::dashmap_with_hasher_and_clock(
+        Quota::per_second(nonzero!(2u32)),
+        hasher.clone(),
+        &clock,
+    );
+    for key in ["alice", "bob"] {
+        assert_eq!(Ok(()), lb.check_key(&key));
+        assert_eq!(Ok(()), lb.check_key(&key));
+        assert_ne!(Ok(()), lb.check_key(&key));
+    }
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(Ok(()), lb.check_key(&"alice"));
+    assert_ne!(Ok(()), lb.check_key(&"alice"));
+    assert_eq!(2, lb.len());
+    assert!(hasher.built.load(Ordering::Relaxed) >= 8);
+}
+
+#[test]
+fn reset_all_under_concurrent_checks() {
+    let clock = FakeRelativeClock::default();
+    let lim = Arc::new(RateLimiter::dashmap_with_clock(
+        Quota::per_second(nonzero!(5u32)),
+        &clock,
+    ));
+
+    let checkers: Vec<_> = (0..4u32)
+        .map(|thread| {
+            let lim = Arc::clone(&lim);
+            std::thread::spawn(move || {
+                for i in 0..1000u32 {
+                    let _ = lim.check_key(&(thread * 1000 + i % 20));
+                }
+            })
+        })
+        .collect();
+    for _ in 0..10 {
+        lim.reset_all();
+    }
+    for checker in checkers {
+        checker.join().unwrap();
+    }
+
+    lim.reset_all();
+    assert!(lim.is_empty());
+    for key in 0..20u32 {
+        assert_eq!(Ok(()), lim.check_key_n(&key, nonzero!(5u32)));
+    }
+}
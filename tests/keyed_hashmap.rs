@@ -1,4 +1,4 @@
-use governor::state::keyed::HashMapStateStore;
+use governor::state::keyed::{CompositeKey, HashMapStateStore};
 use governor::{
     clock::{Clock, FakeRelativeClock},
     Quota, RateLimiter,
@@ -115,3 +115,543 @@ fn actual_threadsafety() {
         assert_eq!(Ok(()), lim.check_key(key));
     }
 }
+
+#[test]
+fn peek_does_not_consume() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    for key in KEYS {
+        for _ in 0..2 {
+            assert_eq!(Ok(()), lim.peek_key(key));
+        }
+        for _ in 0..2 {
+            assert_eq!(Ok(()), lim.check_key(key));
+        }
+        assert_ne!(Ok(()), lim.peek_key(key));
+        assert_ne!(Ok(()), lim.check_key(key));
+    }
+
+    // peeking doesn't create entries for keys:
+    assert_eq!(Ok(()), lim.peek_key(&3));
+    assert_eq!(retained_keys(lim), KEYS);
+}
+
+#[test]
+fn reset_key_restores_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    for key in KEYS {
+        assert_eq!(Ok(()), lim.check_key_n(key, nonzero!(2u32)));
+        assert_ne!(Ok(()), lim.check_key(key));
+    }
+
+    lim.reset_key(&1);
+    assert_eq!(Ok(()), lim.check_key_n(&1, nonzero!(2u32)));
+    assert_ne!(Ok(()), lim.check_key(&1));
+    // other keys are unaffected:
+    assert_ne!(Ok(()), lim.check_key(&2));
+
+    // resetting an untracked key does nothing:
+    lim.reset_key(&3);
+    assert_eq!(retained_keys(lim), KEYS);
+}
+
+#[test]
+fn zero_cost_checks_leave_keys_alone() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    assert_eq!(Ok(Some(())), lim.check_key_cost(&1, 2));
+    let exhausted = lim.key_state_information(&1);
+    assert_eq!(Ok(None), lim.check_key_cost(&1, 0));
+    assert_eq!(exhausted, lim.key_state_information(&1));
+    assert!(lim.check_key_cost(&1, 1).is_err());
+
+    // Zero-cost checks don't create states for keys:
+    assert_eq!(Ok(None), lim.check_key_cost(&2, 0));
+    assert_eq!(lim.len(), 1);
+}
+
+#[test]
+fn reset_all_restores_every_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    for key in 0..10u32 {
+        assert_eq!(Ok(()), lim.check_key_n(&key, nonzero!(4u32)));
+    }
+    assert_eq!(lim.len(), 10);
+
+    lim.reset_all();
+    assert!(lim.is_empty());
+    for key in 0..10u32 {
+        assert_eq!(Ok(()), lim.check_key_n(&key, nonzero!(5u32)));
+        assert_ne!(Ok(()), lim.check_key(&key));
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn snapshot_and_restore() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(2u32));
+    let ms = Duration::from_millis(1);
+    let lim = RateLimiter::hashmap_with_clock(quota, &clock);
+
+    assert_eq!(Ok(()), lim.check_key(&3));
+    clock.advance(ms * 1000);
+    assert_eq!(Ok(()), lim.check_key_n(&1, nonzero!(2u32)));
+    assert_eq!(Ok(()), lim.check_key(&2));
+    clock.advance(ms * 200);
+    let snapshot = lim.snapshot();
+    // key 3 has replenished completely:
+    let mut keys: Vec<u32> = snapshot.iter().map(|(k, _)| *k).collect();
+    keys.sort();
+    assert_eq!(keys, KEYS);
+
+    // restoring into a new rate limiter with a different clock resumes where we left off:
+    let new_clock = FakeRelativeClock::default();
+    new_clock.advance(ms * 10_000);
+    let restored = RateLimiter::hashmap_with_clock(quota, &new_clock);
+    restored.restore(snapshot);
+    assert_ne!(Ok(()), restored.check_key(&1));
+    assert_eq!(Ok(()), restored.check_key(&2));
+    assert_ne!(Ok(()), restored.check_key(&2));
+    assert_eq!(Ok(()), restored.check_key_n(&3, nonzero!(2u32)));
+
+    new_clock.advance(ms * 300);
+    assert_eq!(Ok(()), restored.check_key(&1));
+}
+
+#[test]
+fn retain_recent_shrinks() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let ms = Duration::from_millis(1);
+    assert!(lim.is_empty());
+
+    for key in 0..10u32 {
+        lim.check_key(&key).unwrap();
+    }
+    assert_eq!(lim.len(), 10);
+
+    // keys that are still being limited are retained:
+    clock.advance(ms * 500);
+    lim.check_key(&0).unwrap_err();
+    lim.retain_recent();
+    assert_eq!(lim.len(), 10);
+
+    clock.advance(ms * 1600);
+    lim.retain_recent();
+    assert!(lim.is_empty());
+}
+
+#[test]
+fn iter_returns_tracked_keys() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+
+    for (key, cells) in &[(1u32, 1u32), (2, 5), (3, 2)] {
+        for _ in 0..*cells {
+            lim.check_key(key).unwrap();
+        }
+    }
+
+    let mut states: Vec<(u32, u32)> = lim
+        .iter()
+        .map(|(key, info)| (key, info.remaining_burst_capacity()))
+        .collect();
+    states.sort();
+    assert_eq!(states, vec![(1, 4), (2, 0), (3, 3)]);
+}
+
+#[test]
+fn throttled_count_counts_exhausted_keys() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(3u32)), &clock);
+    assert_eq!(lim.throttled_count(), 0);
+
+    for key in 0..4u32 {
+        assert_eq!(Ok(()), lim.check_key_n(&key, nonzero!(3u32)));
+    }
+    for key in 4..10u32 {
+        assert_eq!(Ok(()), lim.check_key_n(&key, nonzero!(2u32)));
+    }
+    assert_eq!(lim.len(), 10);
+    assert_eq!(lim.throttled_count(), 4);
+
+    // Once a cell replenishes, the exhausted keys aren't throttled anymore:
+    clock.advance(Duration::from_millis(333));
+    assert_eq!(lim.throttled_count(), 4);
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(lim.throttled_count(), 0);
+}
+
+#[test]
+fn premium_key_gets_higher_rate() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let premium = Quota::per_second(nonzero!(10u32));
+    let ms = Duration::from_millis(100);
+
+    let mut allowed = (0, 0);
+    for _ in 0..30 {
+        if lim.check_key(&"free").is_ok() {
+            allowed.0 += 1;
+        }
+        if lim.check_key_with_quota(&"premium", premium).is_ok() {
+            allowed.1 += 1;
+        }
+        clock.advance(ms);
+    }
+    // Over three seconds, the free key gets its burst plus 2 cells per second, and the premium
+    // key gets one cell every 100ms:
+    assert_eq!(allowed, (2 + 5, 30));
+}
+
+#[test]
+fn switching_quotas_keeps_state() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let premium = Quota::per_second(nonzero!(10u32));
+
+    for _ in 0..10 {
+        assert_eq!(Ok(()), lim.check_key_with_quota(&"foo", premium));
+    }
+    // The premium burst used up the key's capacity under the default quota, too:
+    assert_ne!(Ok(()), lim.check_key(&"foo"));
+    let negative = lim.check_key_with_quota(&"foo", premium).unwrap_err();
+    assert_eq!(negative.quota(), premium);
+}
+
+#[test]
+fn available_for_key() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(4u32)), &clock);
+    assert_eq!(Ok(()), lim.check_key_n(&1u32, nonzero!(2u32)));
+    assert_eq!(lim.available_for_key(&1u32), 2);
+    assert_eq!(lim.available_for_key(&2u32), 4);
+    assert_eq!(lim.len(), 1);
+}
+
+#[test]
+fn check_keys_all_or_nothing() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    assert_eq!(Ok(()), lim.check_key_n(&"team", nonzero!(2u32)));
+    assert_eq!(Ok(()), lim.check_key_n(&"org", nonzero!(5u32)));
+
+    let negative = lim.check_keys(&["user", "team", "org"]).unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(200)
+    );
+    assert_eq!(lim.available_for_key(&"user"), 5);
+    assert_eq!(lim.available_for_key(&"team"), 3);
+    assert_eq!(lim.available_for_key(&"org"), 0);
+    assert_eq!(lim.len(), 2);
+
+    clock.advance(Duration::from_millis(200));
+    assert_eq!(Ok(()), lim.check_keys(&["user", "team", "org"]));
+    assert_eq!(lim.available_for_key(&"user"), 4);
+    assert_eq!(lim.available_for_key(&"team"), 3);
+    assert_eq!(lim.available_for_key(&"org"), 0);
+}
+
+#[test]
+fn check_keys_returns_longest_wait() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    assert_eq!(Ok(()), lim.check_key_n(&1u32, nonzero!(5u32)));
+    clock.advance(Duration::from_millis(100));
+    assert_eq!(Ok(()), lim.check_key_n(&3u32, nonzero!(5u32)));
+
+    let negative = lim.check_keys(&[1u32, 3]).unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(200)
+    );
+}
+
+#[test]
+fn check_keys_charges_repeated_keys() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    assert_eq!(Ok(()), lim.check_key_n(&1u32, nonzero!(4u32)));
+
+    assert!(lim.check_keys(&[2u32, 1, 1]).is_err());
+    assert_eq!(lim.available_for_key(&1u32), 1);
+    assert_eq!(lim.available_for_key(&2u32), 5);
+    assert_eq!(Ok(()), lim.check_keys(&[2u32, 1]));
+    assert_eq!(Ok(()), lim.check_keys(&[]));
+}
+
+#[test]
+fn check_keys_threadsafety() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_hour(nonzero!(10u32)), &clock);
+    let both = AtomicU32::new(0);
+    let single = AtomicU32::new(0);
+
+    crossbeam::scope(|scope| {
+        for thread in 0..12 {
+            let (lim, both, single) = (&lim, &both, &single);
+            scope.spawn(move |_| {
+                for _ in 0..5 {
+                    if thread % 3 == 0 {
+                        if lim.check_key(&"b").is_ok() {
+                            single.fetch_add(1, Ordering::Relaxed);
+                        }
+                    } else if lim.check_keys(&["a", "b"]).is_ok() {
+                        both.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let (both, single) = (both.into_inner(), single.into_inner());
+    assert_eq!(both + single, 10);
+    assert_eq!(10 - lim.available_for_key(&"a"), both);
+    assert_eq!(lim.available_for_key(&"b"), 0);
+}
+
+#[test]
+fn full_at_for_key() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(4u32));
+    let lim = RateLimiter::hashmap_with_clock(quota, &clock);
+    assert_eq!(Ok(()), lim.check_key_n(&1u32, nonzero!(4u32)));
+    assert_eq!(Ok(()), lim.check_key(&2u32));
+
+    assert_eq!(
+        lim.full_at_for_key(&1u32, clock.now()),
+        quota.burst_size_replenished_in()
+    );
+    assert_eq!(
+        lim.full_at_for_key(&2u32, clock.now()),
+        Duration::from_millis(250)
+    );
+    assert_eq!(
+        lim.full_at_for_key(&3u32, clock.now()),
+        Duration::from_secs(0)
+    );
+}
+
+#[test]
+fn daily_quota_over_many_days() {
+    let clock = FakeRelativeClock::default();
+    let day = Duration::from_secs(60 * 60 * 24);
+    let lb = RateLimiter::hashmap_with_clock(Quota::with_period(day).unwrap(), &clock);
+    for n in 0..5 * 365 {
+        assert_eq!(Ok(()), lb.check_key(&"daily"), "day {}", n);
+        assert_eq!(
+            day,
+            lb.check_key(&"daily")
+                .unwrap_err()
+                .wait_time_from(clock.now())
+        );
+        if n % 7 == 0 {
+            assert_eq!(Ok(()), lb.check_key(&"weekly"), "day {}", n);
+        }
+        clock.advance(day);
+    }
+    assert_eq!(lb.len(), 2);
+}
+
+#[test]
+fn tuple_keys_are_limited_independently() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    assert_eq!(Ok(()), lim.check_key(&(1, "a")));
+    assert_ne!(Ok(()), lim.check_key(&(1, "a")));
+    assert_eq!(Ok(()), lim.check_key(&(1, "b")));
+    assert_eq!(Ok(()), lim.check_key(&(2, "a")));
+}
+
+#[test]
+fn composite_keys_are_limited_independently() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    assert_eq!(Ok(()), lim.check_key(&CompositeKey::from((1, "a"))));
+    assert_ne!(
+        Ok(()),
+        lim.check_key(&CompositeKey::new().with(1).with("a"))
+    );
+    assert_eq!(Ok(()), lim.check_key(&CompositeKey::from((1, "b"))));
+    assert_eq!(Ok(()), lim.check_key(&CompositeKey::from((1, "a", "b"))));
+
+    // Separators within components can't make different keys look the same:
+    let joined = CompositeKey::from(("a:b", "c"));
+    let split = CompositeKey::from(("a", "b:c"));
+    assert_ne!(joined, split);
+    assert_ne!(joined.to_string(), split.to_string());
+    assert_eq!(r"a\:b:c", joined.to_string());
+}
+
+#[test]
+fn only_failures_use_up_key_capacity() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lim.try_begin_key(&1u32));
+    }
+    lim.charge_failure_key(&1u32);
+    lim.charge_failure_key(&1u32);
+    assert!(lim.try_begin_key(&1u32).is_err());
+    assert_eq!(2, lim.available_for_key(&2u32));
+}
+
+#[test]
+fn extracts_keys_from_requests() {
+    use governor::state::keyed::{KeyExtractor, RequestError};
+
+    struct Request {
+        user: Option<u32>,
+    }
+
+    struct UserExtractor;
+
+    impl KeyExtractor<Request> for UserExtractor {
+        type Key = u32;
+        type Error = &'static str;
+
+        fn extract(&self, req: &Request) -> Result<u32, &'static str> {
+            req.user.ok_or("anonymous request")
+        }
+    }
+
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_key_extractor(UserExtractor);
+
+    let alice = Request { user: Some(1) };
+    let bob = Request { user: Some(2) };
+    assert_eq!(Ok(()), lim.check(&alice));
+    assert_eq!(Ok(()), lim.check(&alice));
+    assert!(matches!(
+        lim.check(&alice),
+        Err(RequestError::RateLimited(_))
+    ));
+    // Other keys have their own state:
+    assert_eq!(Ok(()), lim.check(&bob));
+    assert_eq!(1, lim.limiter().available_for_key(&2));
+
+    // Failing to extract a key doesn't make a decision:
+    assert_eq!(
+        Err(RequestError::KeyExtraction("anonymous request")),
+        lim.check(&Request { user: None })
+    );
+    assert_eq!(1, lim.limiter().available_for_key(&2));
+
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(Ok(()), lim.check(&alice));
+}
+
+#[test]
+fn single_key_views_share_their_keys_state() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(3u32)), &clock);
+    let first = lim.for_key(1u32);
+    let second = lim.for_key(1u32);
+    let other = lim.for_key(2u32);
+    assert_eq!(first.key(), &1);
+
+    assert_eq!(Ok(()), first.check_n(nonzero!(2u32)));
+    assert_eq!(1, second.available());
+    assert_eq!(Ok(()), second.check());
+    assert_ne!(Ok(()), first.check());
+    assert_ne!(Ok(()), lim.check_key(&1));
+
+    // Other keys' views are independent:
+    assert_eq!(3, other.available());
+    assert_eq!(Ok(()), other.check_n(nonzero!(3u32)));
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(3, first.available());
+}
+
+#[test]
+fn state_age_counts_from_the_last_cell() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    assert_eq!(None, lim.state_age_for_key(&1u32));
+
+    // The first cell advances the state by one replenishment interval more:
+    lim.check_key(&1u32).unwrap();
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(
+        Some(Duration::from_millis(4500)),
+        lim.state_age_for_key(&1u32)
+    );
+    assert_eq!(None, lim.state_age_for_key(&2u32));
+
+    lim.check_key(&1u32).unwrap();
+    assert_eq!(Some(Duration::ZERO), lim.state_age_for_key(&1u32));
+    clock.advance(Duration::from_secs(5));
+    assert_eq!(Some(Duration::from_secs(5)), lim.state_age_for_key(&1u32));
+
+    // A burst advances the state past the last cell, until it could have replenished:
+    lim.check_key_n(&1u32, nonzero!(2u32)).unwrap();
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(
+        Some(Duration::from_millis(500)),
+        lim.state_age_for_key(&1u32)
+    );
+
+    lim.reset_key(&1u32);
+    assert_eq!(None, lim.state_age_for_key(&1u32));
+}
+
+#[test]
+fn borrowed_keys_share_the_owned_keys_state() {
+    let clock = FakeRelativeClock::default();
+    let lim: RateLimiter<String, HashMapStateStore<String>, _> =
+        RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    assert_eq!(Ok(()), lim.check_key("alice"));
+    assert_eq!(Ok(()), lim.check_key(&"alice".to_string()));
+    assert_ne!(Ok(()), lim.check_key("alice"));
+    assert_eq!(1, lim.len());
+
+    assert_eq!(Ok(()), lim.check_key_n("bob", nonzero!(2u32)));
+    assert_ne!(Ok(()), lim.check_key(&"bob".to_string()));
+    assert_eq!(2, lim.len());
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(Ok(()), lim.check_key("alice"));
+
+    let quota = Quota::per_second(nonzero!(1u32));
+    assert_eq!(Ok(()), lim.check_key_with_quota("carol", quota));
+    assert_ne!(
+        Ok(()),
+        lim.check_key_with_quota(&"carol".to_string(), quota)
+    );
+    assert_eq!(Ok(None), lim.check_key_cost("dave", 0));
+    assert_eq!(Ok(Some(())), lim.check_key_cost("dave", 2));
+    assert_ne!(Ok(Some(())), lim.check_key_cost(&"dave".to_string(), 1));
+    assert_eq!(4, lim.len());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn check_key_deadline_returns_now_plus_the_keys_wait_time() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+
+    assert_eq!(Ok(()), lim.check_key_deadline(&1u32));
+    clock.advance(Duration::from_millis(250));
+    let before = std::time::Instant::now();
+    let deadline = lim.check_key_deadline(&1u32).unwrap_err();
+    let after = std::time::Instant::now();
+    let wait = Duration::from_millis(750);
+    assert!(deadline >= before + wait, "{:?}", deadline - before);
+    assert!(deadline <= after + wait, "{:?}", after + wait - deadline);
+
+    assert_eq!(Ok(()), lim.check_key_deadline(&2u32));
+}
@@ -0,0 +1,171 @@
+use governor::{clock::FakeRelativeClock, state::keyed::LruStateStore, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn capacity(n: usize) -> NonZeroUsize {
+    NonZeroUsize::new(n).unwrap()
+}
+
+#[test]
+fn evicts_least_recently_checked_key() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::lru_with_clock(Quota::per_hour(nonzero!(1u32)), capacity(3), &clock);
+
+    for key in 0..3u32 {
+        assert_eq!(Ok(()), lim.check_key(&key));
+    }
+    assert_eq!(lim.len(), 3);
+
+    // Key 4 evicts key 0, the oldest one:
+    assert_eq!(Ok(()), lim.check_key(&4));
+    assert_eq!(lim.len(), 3);
+    for key in &[1u32, 2, 4] {
+        assert!(lim.check_key(key).is_err(), "key {} was evicted", key);
+    }
+    // Key 0 starts fresh, evicting key 1:
+    assert_eq!(Ok(()), lim.check_key(&0));
+    assert_eq!(Ok(()), lim.check_key(&1));
+    assert_eq!(lim.len(), 3);
+}
+
+#[test]
+fn checking_a_key_keeps_it() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::lru_with_clock(Quota::per_hour(nonzero!(1u32)), capacity(2), &clock);
+
+    assert_eq!(Ok(()), lim.check_key(&"old"));
+    assert_eq!(Ok(()), lim.check_key(&"other"));
+    // A rejected check counts as a check, too:
+    assert!(lim.check_key(&"old").is_err());
+    assert_eq!(Ok(()), lim.check_key(&"new"));
+
+    assert!(lim.check_key(&"old").is_err());
+    assert!(lim.check_key(&"new").is_err());
+    assert_eq!(Ok(()), lim.check_key(&"other"));
+}
+
+#[test]
+fn peeking_does_not_keep_a_key() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::lru_with_clock(Quota::per_hour(nonzero!(1u32)), capacity(2), &clock);
+
+    assert_eq!(Ok(()), lim.check_key(&1u32));
+    assert_eq!(Ok(()), lim.check_key(&2u32));
+    assert_eq!(lim.available_for_key(&1), 0);
+    assert_eq!(Ok(()), lim.check_key(&3u32));
+    assert_eq!(lim.available_for_key(&1), 1);
+    assert_eq!(lim.available_for_key(&2), 0);
+}
+
+#[test]
+fn reset_and_retain_recent() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::lru_with_clock(Quota::per_second(nonzero!(1u32)), capacity(3), &clock);
+
+    for key in 0..3u32 {
+        assert_eq!(Ok(()), lim.check_key(&key));
+    }
+    lim.reset_key(&1);
+    assert_eq!(lim.len(), 2);
+    // There's room for one more key without evicting any:
+    assert_eq!(Ok(()), lim.check_key(&3));
+    assert!(lim.check_key(&0).is_err());
+
+    clock.advance(Duration::from_secs(2));
+    lim.retain_recent();
+    assert!(lim.is_empty());
+    for key in 0..3u32 {
+        assert_eq!(Ok(()), lim.check_key(&key));
+    }
+    assert_eq!(lim.len(), 3);
+}
+
+#[test]
+fn reports_evicted_keys_to_the_hook() {
+    let clock = FakeRelativeClock::default();
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&evicted);
+    let store = LruStateStore::new(capacity(2)).on_evict(move |key: &u32| {
+        recorded.lock().unwrap().push(*key);
+    });
+    let lim = RateLimiter::new(Quota::per_second(nonzero!(1u32)), store, &clock);
+
+    for key in 0..4u32 {
+        assert_eq!(Ok(()), lim.check_key(&key));
+    }
+    assert_eq!(*evicted.lock().unwrap(), vec![0, 1]);
+
+    // Explicitly removed keys aren't reported, but keys that `retain_recent` removes are:
+    lim.reset_key(&2);
+    clock.advance(Duration::from_secs(2));
+    lim.retain_recent();
+    assert!(lim.is_empty());
+    assert_eq!(*evicted.lock().unwrap(), vec![0, 1, 3]);
+}
+
+#[test]
+fn reset_all_forgets_every_key() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::lru_with_clock(Quota::per_second(nonzero!(1u32)), capacity(3), &clock);
+
+    for key in 0..3u32 {
+        assert_eq!(Ok(()), lim.check_key(&key));
+    }
+    lim.reset_all();
+    assert!(lim.is_empty());
+    // The store is empty, so checking new keys doesn't evict any of them:
+    for key in 3..6u32 {
+        assert_eq!(Ok(()), lim.check_key(&key));
+    }
+    assert_eq!(lim.len(), 3);
+    for key in 3..6u32 {
+        assert!(lim.check_key(&key).is_err());
+    }
+}
+
+#[test]
+fn into_state_store_capacity() {
+    let clock = FakeRelativeClock::default();
+    let lim: RateLimiter<u32, LruStateStore<u32>, _> =
+        RateLimiter::lru_with_clock(Quota::per_second(nonzero!(1u32)), capacity(7), &clock);
+    assert_eq!(lim.into_state_store().capacity(), capacity(7));
+}
+
+#[test]
+fn evicts_under_concurrent_checks() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::lru_with_clock(Quota::per_hour(nonzero!(1u32)), capacity(64), &clock);
+
+    crossbeam::scope(|scope| {
+        for thread in 0..8u32 {
+            let lim = &lim;
+            scope.spawn(move |_| {
+                for key in 0..100u32 {
+                    let _ = lim.check_key(&(thread * 1000 + key));
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(lim.len(), 64);
+    assert_eq!(lim.iter().count(), 64);
+}
+
+#[test]
+fn rejected_new_keys_do_not_evict_other_keys() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_hour(nonzero!(2u32));
+    let lim = RateLimiter::lru_with_clock(quota, capacity(2), &clock);
+    assert_eq!(Ok(()), lim.check_key(&1u32));
+    assert_eq!(Ok(()), lim.check_key(&2u32));
+
+    // Refunds to keys that have no state are rejected, so they don't make room for their keys:
+    for key in 3..10u32 {
+        lim.refund_key(&key, nonzero!(1u32));
+    }
+    assert_eq!(2, lim.len());
+    assert_eq!(1, lim.available_for_key(&1));
+    assert_eq!(1, lim.available_for_key(&2));
+}
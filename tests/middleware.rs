@@ -0,0 +1,357 @@
+#![cfg(feature = "std")]
+
+use governor::{
+    clock::{self, Clock, FakeRelativeClock},
+    middleware::{
+        AdmittedRateMiddleware, MetricsMiddleware, ObserverMiddleware, RateLimitDecision,
+        RateLimitingMiddleware, StateInformationMiddleware, WaitTimeMiddleware,
+    },
+    NegativeMultiDecision, NotUntil, Quota, RateLimiter, StateInformation,
+};
+use nonzero_ext::nonzero;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Default)]
+struct Counters {
+    allowed: AtomicU64,
+    throttled: AtomicU64,
+}
+
+fn counting(counters: &Arc<Counters>) -> MetricsMiddleware<impl Fn(RateLimitDecision)> {
+    let counters = counters.clone();
+    MetricsMiddleware::new(move |decision| {
+        let counter = match decision {
+            RateLimitDecision::Allowed => &counters.allowed,
+            RateLimitDecision::Throttled => &counters.throttled,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    })
+}
+
+#[test]
+fn metrics_count_allowed_and_throttled() {
+    let counters = Arc::new(Counters::default());
+    let lim =
+        RateLimiter::direct_with_middleware(Quota::per_hour(nonzero!(3u32)), counting(&counters));
+    let results: Vec<_> = (0..10).map(|_| lim.check().is_ok()).collect();
+
+    assert_eq!(results.iter().filter(|ok| **ok).count(), 3);
+    assert_eq!(counters.allowed.load(Ordering::Relaxed), 3);
+    assert_eq!(counters.throttled.load(Ordering::Relaxed), 7);
+}
+
+#[test]
+fn metrics_count_batches_once() {
+    let clock = FakeRelativeClock::default();
+    let counters = Arc::new(Counters::default());
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock)
+        .with_middleware(counting(&counters));
+
+    assert_eq!(Ok(()), lim.check_n(nonzero!(4u32)));
+    assert!(lim.check_n(nonzero!(4u32)).is_err());
+    assert_eq!(
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 6,
+            max_burst: 5,
+        }),
+        lim.check_n(nonzero!(6u32))
+    );
+
+    assert_eq!(counters.allowed.load(Ordering::Relaxed), 1);
+    assert_eq!(counters.throttled.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn metrics_count_keyed() {
+    let clock = FakeRelativeClock::default();
+    let counters = Arc::new(Counters::default());
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(1u32)), &clock)
+        .with_middleware(counting(&counters));
+
+    assert!(lim.check_key(&1u32).is_ok());
+    assert!(lim.check_key(&1u32).is_err());
+    assert!(lim.check_key(&2u32).is_ok());
+    clock.advance(Duration::from_secs(1));
+    assert!(lim.check_key(&1u32).is_ok());
+
+    assert_eq!(counters.allowed.load(Ordering::Relaxed), 3);
+    assert_eq!(counters.throttled.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn observer_sees_keyed_decisions_in_order() {
+    let clock = FakeRelativeClock::default();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let observed = seen.clone();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_middleware(ObserverMiddleware::new(move |decision| {
+            observed
+                .lock()
+                .unwrap()
+                .push((*decision.key(), decision.wait_time()));
+        }));
+
+    assert!(lim.check_key(&1u32).is_ok());
+    assert!(lim.check_key(&1u32).is_ok());
+    assert!(lim.check_key(&1u32).is_err());
+    assert!(lim.check_key(&2u32).is_ok());
+    clock.advance(Duration::from_millis(200));
+    assert!(lim.check_key(&1u32).is_err());
+    clock.advance(Duration::from_millis(300));
+    assert!(lim.check_key(&1u32).is_ok());
+    assert!(lim.check_key_n(&1u32, nonzero!(2u32)).is_err());
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            (1, None),
+            (1, None),
+            (1, Some(Duration::from_millis(500))),
+            (2, None),
+            (1, Some(Duration::from_millis(300))),
+            (1, None),
+            (1, Some(Duration::from_secs(1))),
+        ]
+    );
+}
+
+#[test]
+fn observer_sees_direct_decisions() {
+    let clock = FakeRelativeClock::default();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let observed = seen.clone();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock)
+        .with_middleware(ObserverMiddleware::new(move |decision| {
+            observed.lock().unwrap().push(decision.wait_time());
+        }));
+
+    assert!(lim.check().is_ok());
+    assert!(lim.check().is_err());
+    clock.advance(Duration::from_millis(250));
+    assert!(lim.check().is_err());
+    clock.advance(Duration::from_millis(750));
+    assert!(lim.check().is_ok());
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![
+            None,
+            Some(Duration::from_secs(1)),
+            Some(Duration::from_millis(750)),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn wait_times_match_reported_waits() {
+    let clock = FakeRelativeClock::default();
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let record = recorded.clone();
+    let middleware = || {
+        let record = record.clone();
+        WaitTimeMiddleware::new(move |wait| record.lock().unwrap().push(wait))
+    };
+    let direct = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock)
+        .with_middleware(middleware());
+    let keyed = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_middleware(middleware());
+
+    let mut reported = vec![];
+    for _ in 0..10 {
+        match direct.check() {
+            Ok(()) => {}
+            Err(negative) => reported.push(negative.wait_time_from(clock.now())),
+        }
+        match keyed.check_key(&"key") {
+            Ok(()) => {}
+            Err(negative) => reported.push(negative.wait_time_from(clock.now())),
+        }
+        match direct.check_n(nonzero!(3u32)) {
+            Err(NegativeMultiDecision::BatchNonConforming(_, negative)) => {
+                reported.push(negative.wait_time_from(clock.now()))
+            }
+            Err(e) => panic!("unexpected {:?}", e),
+            Ok(()) => {}
+        }
+        clock.advance(Duration::from_millis(70));
+    }
+
+    assert!(
+        reported.len() > 10,
+        "only throttled {} times",
+        reported.len()
+    );
+    assert!(reported.iter().any(|wait| *wait > Duration::ZERO));
+    assert_eq!(*recorded.lock().unwrap(), reported);
+}
+
+#[test]
+fn state_information_middleware() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(3u32)), &clock)
+        .with_middleware(StateInformationMiddleware);
+
+    assert_eq!(
+        Ok(2),
+        lim.check().map(|state| state.remaining_burst_capacity())
+    );
+    assert_eq!(
+        Ok(0),
+        lim.check_n(nonzero!(2u32))
+            .map(|state| state.remaining_burst_capacity())
+    );
+    assert!(lim.check().is_err());
+}
+
+#[test]
+fn remaining_ratio_direct() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(4u32)), &clock)
+        .with_middleware(StateInformationMiddleware);
+    assert_eq!(1.0, lim.state_information().remaining_ratio());
+
+    assert_eq!(Ok(0.75), lim.check().map(|state| state.remaining_ratio()));
+    assert_eq!(
+        Ok(0.0),
+        lim.check_n(nonzero!(3u32))
+            .map(|state| state.remaining_ratio())
+    );
+    assert_eq!(0.0, lim.state_information().remaining_ratio());
+}
+
+#[test]
+fn remaining_ratio_keyed() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(4u32)), &clock)
+        .with_middleware(StateInformationMiddleware);
+    assert_eq!(1.0, lim.key_state_information(&1u32).remaining_ratio());
+
+    assert_eq!(
+        Ok(0.75),
+        lim.check_key(&1u32).map(|state| state.remaining_ratio())
+    );
+    assert_eq!(
+        Ok(0.0),
+        lim.check_key_n(&1u32, nonzero!(3u32))
+            .map(|state| state.remaining_ratio())
+    );
+    assert_eq!(0.0, lim.key_state_information(&1u32).remaining_ratio());
+    assert_eq!(1.0, lim.key_state_information(&2u32).remaining_ratio());
+}
+
+#[test]
+fn admitted_rate_converges_to_the_achieved_rate() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(10u32)), &clock)
+        .with_middleware(AdmittedRateMiddleware::new(Duration::from_secs(5)));
+    assert_eq!(0.0, lim.admitted_rate());
+
+    // Admit a steady stream at half the quota's rate for ten windows:
+    for _ in 0..250 {
+        assert_eq!(Ok(()), lim.check());
+        clock.advance(Duration::from_millis(200));
+    }
+    let rate = lim.admitted_rate();
+    assert!((rate - 5.0).abs() < 0.25, "measured {}", rate);
+
+    // Throttled cells don't count:
+    while lim.check().is_ok() {}
+    assert!(
+        lim.admitted_rate() < 9.0,
+        "measured {}",
+        lim.admitted_rate()
+    );
+
+    // Without traffic, the rate decays:
+    clock.advance(Duration::from_secs(25));
+    assert!(
+        lim.admitted_rate() < 0.1,
+        "measured {}",
+        lim.admitted_rate()
+    );
+}
+
+#[test]
+fn admitted_rate_counts_all_cells_of_a_batch() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(100u32)), &clock)
+        .with_middleware(AdmittedRateMiddleware::new(Duration::from_secs(5)));
+    for _ in 0..250 {
+        assert_eq!(Ok(()), lim.check_n(nonzero!(5u32)));
+        clock.advance(Duration::from_millis(200));
+    }
+    let rate = lim.admitted_rate();
+    assert!((rate - 25.0).abs() < 1.25, "measured {}", rate);
+}
+
+#[test]
+fn admitted_rate_under_concurrent_checks() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1000u32)), &clock)
+        .with_middleware(AdmittedRateMiddleware::new(Duration::from_secs(1)));
+    crossbeam::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|_| {
+                for _ in 0..100 {
+                    assert_eq!(Ok(()), lim.check());
+                }
+            });
+        }
+    })
+    .unwrap();
+    // All 400 cells were allowed through at the same time, adding 400 / window:
+    let rate = lim.admitted_rate();
+    assert!((rate - 400.0).abs() < 1e-6, "measured {}", rate);
+}
+
+struct KillSwitch {
+    on: Arc<AtomicBool>,
+    vetoed: Arc<AtomicU64>,
+}
+
+impl<K, P: clock::Reference> RateLimitingMiddleware<K, P> for KillSwitch {
+    type PositiveOutcome = ();
+
+    fn allow_cell(&self, _key: &K, _state: StateInformation) {}
+
+    fn disallow_cell(&self, _key: &K, _not_until: &NotUntil<'_, P>) {
+        self.vetoed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn veto_cell(&self, _key: &K, _state: &StateInformation) -> Option<Duration> {
+        if self.on.load(Ordering::Relaxed) {
+            Some(Duration::from_secs(5))
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn veto_rejects_allowed_cells_while_the_kill_switch_is_on() {
+    let clock = FakeRelativeClock::default();
+    let on = Arc::new(AtomicBool::new(false));
+    let rejected = Arc::new(AtomicU64::new(0));
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_middleware(KillSwitch {
+            on: Arc::clone(&on),
+            vetoed: Arc::clone(&rejected),
+        });
+    assert_eq!(Ok(()), lim.check());
+
+    on.store(true, Ordering::Relaxed);
+    let negative = lim.check().unwrap_err();
+    assert_eq!(Duration::from_secs(5), negative.wait_time_from(clock.now()));
+    assert!(lim.check_n(nonzero!(1u32)).is_err());
+    assert_eq!(2, rejected.load(Ordering::Relaxed));
+    // Vetoed cells don't use up any capacity:
+    assert_eq!(1, lim.available());
+
+    on.store(false, Ordering::Relaxed);
+    assert_eq!(Ok(()), lim.check());
+    assert!(lim.check().is_err());
+    assert_eq!(3, rejected.load(Ordering::Relaxed));
+}
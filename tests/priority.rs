@@ -0,0 +1,78 @@
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    state::PriorityRateLimiter,
+    Quota,
+};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+#[test]
+fn high_priority_gets_the_reserve_after_low_priority_saturates() {
+    let clock = FakeRelativeClock::default();
+    let lim = PriorityRateLimiter::with_clock(Quota::per_second(nonzero!(10u32)), [3], &clock);
+    assert_eq!(lim.levels(), 2);
+
+    let allowed = (0..20)
+        .filter(|_| lim.check_with_priority(0).is_ok())
+        .count();
+    assert_eq!(allowed, 7);
+    assert_eq!(lim.available(), 3);
+
+    let allowed = (0..20)
+        .filter(|_| lim.check_with_priority(1).is_ok())
+        .count();
+    assert_eq!(allowed, 3);
+    assert_ne!(Ok(()), lim.check_with_priority(1));
+}
+
+#[test]
+fn levels_share_the_quota() {
+    let clock = FakeRelativeClock::default();
+    let lim = PriorityRateLimiter::with_clock(Quota::per_second(nonzero!(10u32)), [6, 2], &clock);
+    assert_eq!(lim.levels(), 3);
+
+    // Saturate every level for ten seconds; the total never exceeds the quota:
+    let mut allowed = [0; 3];
+    for _ in 0..1000 {
+        for (level, allowed) in allowed.iter_mut().enumerate().rev() {
+            if lim.check_with_priority(level).is_ok() {
+                *allowed += 1;
+            }
+        }
+        clock.advance(Duration::from_millis(10));
+    }
+    assert_eq!(allowed.iter().sum::<u32>(), 10 + 99);
+    // The lower levels only get cells until they reach their reserves; after that, the highest
+    // level (checked first) takes every cell that replenishes:
+    assert_eq!(allowed, [1, 3, 105]);
+}
+
+#[test]
+fn rejections_report_when_the_level_can_go_again() {
+    let clock = FakeRelativeClock::default();
+    let lim = PriorityRateLimiter::with_clock(Quota::per_second(nonzero!(4u32)), [2], &clock);
+    assert_eq!(Ok(()), lim.check_with_priority(0));
+    assert_eq!(Ok(()), lim.check_with_priority(1));
+    assert_eq!(Ok(()), lim.check_with_priority(1));
+
+    // Level 0 needs three cells to be available, i.e. two more to replenish:
+    let negative = lim.check_with_priority(0).unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(500)
+    );
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(Ok(()), lim.check_with_priority(0));
+    // Levels beyond the highest one count as the highest:
+    assert_eq!(Ok(()), lim.check_with_priority(5));
+}
+
+#[test]
+#[should_panic]
+fn reserves_must_be_below_the_burst_size() {
+    PriorityRateLimiter::with_clock(
+        Quota::per_second(nonzero!(4u32)),
+        [4],
+        &FakeRelativeClock::default(),
+    );
+}
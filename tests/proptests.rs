@@ -34,7 +34,8 @@ fn accurate_not_until() {
     proptest!(test_config(), |(capacity: Count, additional: Count, wait_time_parts: Count)| {
         let clock = FakeRelativeClock::default();
         let lb = RateLimiter::direct_with_clock(Quota::per_second(capacity.0), &clock);
-        let step = Duration::from_secs(1) / capacity.0.get();
+        // The replenishment interval, rounded up to whole nanoseconds:
+        let step = Duration::from_nanos(1_000_000_000u64.div_ceil(u64::from(capacity.0.get())));
 
         // use up the burst capacity:
         for _ in 0..capacity.0.get() {
@@ -70,3 +71,86 @@ fn accurate_not_until() {
         }
     });
 }
+
+#[test]
+fn explicit_times_never_exceed_rate() {
+    let offsets = prop::collection::vec(0..10_000_000_000u64, 1..500);
+    proptest!(test_config(), |(capacity: Count, mut offsets in offsets)| {
+        offsets.sort_unstable();
+        let clock = FakeRelativeClock::default();
+        let start = clock.now();
+        let lb = RateLimiter::direct_with_clock(Quota::per_second(capacity.0), &clock);
+        let interval = Duration::from_secs(1).as_nanos() as u64 / u64::from(capacity.0.get());
+
+        let mut allowed = 0u64;
+        for offset in offsets {
+            if lb.check_at(start + Duration::from_nanos(offset)).is_ok() {
+                allowed += 1;
+            }
+            // The burst capacity, plus whatever was replenished since the start:
+            let limit = u64::from(capacity.0.get()) + offset / interval + 1;
+            prop_assert!(allowed <= limit,
+                         "allowed {} cells by {}ns, limit {}",
+                         allowed, offset, limit);
+        }
+    });
+}
+
+#[cfg(feature = "proptest")]
+#[test]
+fn generated_check_steps_never_exceed_quota() {
+    use governor::proptest::{check_steps, max_admitted, quotas};
+
+    let strategy = quotas().prop_flat_map(|quota| (Just(quota), check_steps(quota, 1..200)));
+    proptest!(test_config(), |((quota, steps) in strategy)| {
+        let clock = FakeRelativeClock::default();
+        let lb = RateLimiter::direct_with_clock(quota, &clock);
+
+        let mut allowed = 0u128;
+        let mut elapsed = Duration::from_secs(0);
+        for step in &steps {
+            clock.advance(step.advance);
+            elapsed += step.advance;
+            if lb.check_n(step.n).is_ok() {
+                allowed += u128::from(step.n.get());
+            }
+            let limit = max_admitted(quota, elapsed);
+            prop_assert!(
+                allowed <= limit,
+                "allowed {} cells after {:?} under {:?}, limit {}",
+                allowed,
+                elapsed,
+                quota,
+                limit
+            );
+        }
+    });
+}
+
+/// Pins the cases that proptest found (see `proptests.regressions`): Quotas that replenish cells
+/// faster than once a nanosecond still admit their burst at the start, and no more.
+#[cfg(feature = "proptest")]
+#[test]
+fn sub_nanosecond_quotas_admit_their_burst() {
+    use governor::proptest::max_admitted;
+
+    for &(per_second, burst) in &[(1_045_801_025u32, 61u32), (1_024_147_620, 32)] {
+        let quota = Quota::per_second(NonZeroU32::new(per_second).unwrap())
+            .allow_burst(NonZeroU32::new(burst).unwrap());
+        assert_eq!(
+            u128::from(burst),
+            max_admitted(quota, Duration::from_secs(0))
+        );
+
+        let clock = FakeRelativeClock::default();
+        let lb = RateLimiter::direct_with_clock(quota, &clock);
+        for _ in 0..burst {
+            assert_eq!(Ok(()), lb.check());
+        }
+        assert!(
+            lb.check().is_err(),
+            "{:?} allowed more than its burst",
+            quota
+        );
+    }
+}
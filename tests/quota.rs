@@ -0,0 +1,572 @@
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    GcraParams, ParseQuotaError, Quota, RateLimiter,
+};
+use nonzero_ext::nonzero;
+use std::num::NonZeroU32;
+use std::time::Duration;
+
+#[test]
+fn from_hz_below_one_hz() {
+    let quota = Quota::from_hz(0.5).unwrap();
+    assert_eq!(quota.replenish_interval(), Duration::from_secs(2));
+    assert_eq!(quota.burst_size().get(), 1);
+
+    let quota = Quota::from_hz(1.0 / 3.0).unwrap();
+    assert_eq!(
+        quota.replenish_interval(),
+        Duration::from_nanos(3_000_000_000)
+    );
+    assert_eq!(quota.burst_size().get(), 1);
+}
+
+#[test]
+fn from_hz_fractional() {
+    let quota = Quota::from_hz(2.5).unwrap();
+    assert_eq!(quota.replenish_interval(), Duration::from_millis(400));
+    assert_eq!(quota.burst_size().get(), 2);
+}
+
+#[test]
+fn from_hz_above_one_mhz() {
+    let quota = Quota::from_hz(2_500_000.0).unwrap();
+    assert_eq!(quota.replenish_interval(), Duration::from_nanos(400));
+    assert_eq!(quota.burst_size().get(), 2_500_000);
+
+    let quota = Quota::from_hz(3_000_000.0).unwrap();
+    assert_eq!(quota.replenish_interval(), Duration::from_nanos(333));
+    assert_eq!(quota.burst_size().get(), 3_000_000);
+}
+
+#[test]
+fn from_hz_rejects_invalid() {
+    assert_eq!(Quota::from_hz(0.0), None);
+    assert_eq!(Quota::from_hz(-1.0), None);
+    assert_eq!(Quota::from_hz(f64::NAN), None);
+    assert_eq!(Quota::from_hz(f64::INFINITY), None);
+    assert_eq!(Quota::from_hz(1e10), None);
+    assert_eq!(Quota::from_hz(1e-20), None);
+}
+
+#[test]
+fn per_minute_introspection() {
+    let quota = Quota::per_minute(NonZeroU32::new(60).unwrap());
+    assert_eq!(quota.replenish_interval(), Duration::from_secs(1));
+    assert_eq!(quota.burst_size().get(), 60);
+    assert_eq!(quota.burst_size_replenished_in(), Duration::from_secs(60));
+}
+
+#[test]
+fn introspection_matches_limiter() {
+    let quota = Quota::per_second(nonzero!(4u32)).allow_burst(nonzero!(3u32));
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+
+    for _ in 0..quota.burst_size().get() {
+        assert_eq!(Ok(()), lim.check());
+    }
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        quota.replenish_interval()
+    );
+}
+
+#[test]
+fn min_picks_stricter_parameters() {
+    let fast_small = Quota::per_second(nonzero!(10u32)).allow_burst(nonzero!(2u32));
+    let slow_large = Quota::per_second(nonzero!(2u32)).allow_burst(nonzero!(10u32));
+    let combined = fast_small.min(slow_large);
+    assert_eq!(combined, slow_large.min(fast_small));
+    assert_eq!(combined.replenish_interval(), Duration::from_millis(500));
+    assert_eq!(combined.burst_size().get(), 2);
+}
+
+#[test]
+fn min_never_admits_more_than_both() {
+    let per_user = Quota::per_second(nonzero!(10u32));
+    let global = Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(20u32));
+    let combined = per_user.min(global);
+
+    let clock = FakeRelativeClock::default();
+    let user_lim = RateLimiter::direct_with_clock(per_user, &clock);
+    let global_lim = RateLimiter::direct_with_clock(global, &clock);
+    let combined_lim = RateLimiter::direct_with_clock(combined, &clock);
+
+    let mut admitted_separately = 0;
+    let mut admitted_combined = 0;
+    // A request every 50ms for 10 seconds:
+    for _ in 0..200 {
+        if user_lim.check().is_ok() && global_lim.check().is_ok() {
+            admitted_separately += 1;
+        }
+        if combined_lim.check().is_ok() {
+            admitted_combined += 1;
+        }
+        clock.advance(Duration::from_millis(50));
+    }
+    assert!(
+        admitted_combined <= admitted_separately,
+        "{} > {}",
+        admitted_combined,
+        admitted_separately
+    );
+    // Both approaches enforce the global rate of 5/s in the long run; they only differ in the
+    // initial burst (of 10 and 20 cells, respectively).
+    assert!(admitted_combined >= 50, "admitted {}", admitted_combined);
+    assert!(
+        admitted_combined <= 50 + 10,
+        "admitted {}",
+        admitted_combined
+    );
+    assert!(
+        admitted_separately <= 50 + 20,
+        "admitted {}",
+        admitted_separately
+    );
+}
+
+#[test]
+fn start_empty_throttles_first_check() {
+    let quota = Quota::per_second(nonzero!(5u32)).start_empty();
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        quota.replenish_interval()
+    );
+    assert_eq!(lim.available(), 0);
+    clock.advance(quota.replenish_interval());
+    assert_eq!(Ok(()), lim.check());
+    assert!(lim.check().is_err());
+
+    clock.advance(quota.burst_size_replenished_in());
+    assert_eq!(lim.available(), 5);
+}
+
+#[test]
+fn start_empty_check_n_waits_for_batch() {
+    let quota = Quota::per_second(nonzero!(5u32)).start_empty();
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+
+    assert!(lim.check_n(nonzero!(3u32)).is_err());
+    clock.advance(Duration::from_millis(400));
+    assert!(lim.check_n(nonzero!(3u32)).is_err());
+    clock.advance(Duration::from_millis(200));
+    assert_eq!(Ok(()), lim.check_n(nonzero!(3u32)));
+}
+
+#[test]
+fn start_empty_applies_to_new_keys_and_resets() {
+    let quota = Quota::per_second(nonzero!(5u32)).start_empty();
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(quota, &clock);
+
+    clock.advance(Duration::from_secs(10));
+    assert!(lim.check_key(&"new").is_err());
+    clock.advance(Duration::from_millis(200));
+    assert_eq!(Ok(()), lim.check_key(&"new"));
+
+    let direct = RateLimiter::direct_with_clock(quota, &clock);
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(direct.available(), 0);
+    assert!(direct.check().is_err());
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(direct.available(), 5);
+    direct.reset();
+    assert_eq!(direct.available(), 0);
+}
+
+#[test]
+fn min_keeps_start_empty() {
+    let empty = Quota::per_second(nonzero!(5u32)).start_empty();
+    let full = Quota::per_second(nonzero!(10u32));
+    assert_eq!(empty.min(full), full.min(empty));
+    assert_eq!(
+        full.min(empty),
+        Quota::per_second(nonzero!(5u32)).start_empty()
+    );
+    assert_ne!(full.min(full), full.start_empty());
+}
+
+#[test]
+fn fractional_intervals() {
+    let quota = Quota::per_second(nonzero!(3u32));
+    assert_eq!(
+        quota.replenish_interval(),
+        Duration::from_nanos(333_333_333)
+    );
+    assert_eq!(quota.burst_size_replenished_in(), Duration::from_secs(1));
+
+    let quota = Quota::per_second(nonzero!(300_000_000u32));
+    assert_eq!(quota.replenish_interval(), Duration::from_nanos(3));
+    assert_ne!(quota, Quota::with_period(Duration::from_nanos(3)).unwrap());
+    assert_eq!(
+        quota
+            .allow_burst(nonzero!(3u32))
+            .burst_size_replenished_in(),
+        Duration::from_nanos(10)
+    );
+}
+
+#[test]
+fn gcra_params_round_trip() {
+    let quotas = [
+        Quota::per_second(nonzero!(1u32)),
+        Quota::per_second(nonzero!(5u32)).strict_interval(),
+        Quota::per_minute(nonzero!(7u32)).allow_burst(nonzero!(3u32)),
+        Quota::from_hz(300_000_000.0).unwrap(),
+        Quota::with_period(Duration::from_nanos(1))
+            .unwrap()
+            .allow_burst(nonzero!(1_000u32)),
+    ];
+    for quota in quotas {
+        let params = quota.gcra_params();
+        let restored = Quota::from_gcra_params(params).unwrap();
+        assert_eq!(quota, restored, "{:?}", params);
+        assert_eq!(params, restored.gcra_params());
+    }
+}
+
+#[test]
+fn gcra_params_limit_identically() {
+    let quota = Quota::per_second(nonzero!(3u32)).allow_burst(nonzero!(2u32));
+    let clock = FakeRelativeClock::default();
+    let original = RateLimiter::direct_with_clock(quota, &clock);
+    let restored = RateLimiter::direct_with_clock(
+        Quota::from_gcra_params(quota.gcra_params()).unwrap(),
+        &clock,
+    );
+    for _ in 0..50 {
+        for _ in 0..3 {
+            assert_eq!(original.check().is_ok(), restored.check().is_ok());
+        }
+        clock.advance(Duration::from_millis(70));
+    }
+}
+
+/// Makes the decisions that the [`GcraParams`] docs describe, for checks of single cells.
+struct DocumentedGcra {
+    params: GcraParams,
+    tat: Option<u128>,
+}
+
+impl DocumentedGcra {
+    fn check(&mut self, t0: u128) -> bool {
+        let t = self.params.emission_interval.as_nanos();
+        let tau = self.params.delay_variation_tolerance.as_nanos();
+        let tat = self.tat.unwrap_or(t0 + t);
+        if t0 + tau < tat {
+            return false;
+        }
+        let base = if self.params.strict {
+            tat.max(t0 + t)
+        } else {
+            tat.max(t0)
+        };
+        self.tat = Some(base + t);
+        true
+    }
+}
+
+#[test]
+fn gcra_params_describe_the_decisions() {
+    let quotas = [
+        Quota::per_second(nonzero!(2u32)),
+        Quota::per_minute(nonzero!(7u32)).allow_burst(nonzero!(3u32)),
+        Quota::per_second(nonzero!(5u32)).strict_interval(),
+        Quota::per_second(nonzero!(5u32))
+            .strict_interval()
+            .allow_burst(nonzero!(3u32)),
+        Quota::per_second(nonzero!(10u32)).with_tolerance(Duration::from_secs(2)),
+    ];
+    for quota in quotas {
+        let clock = FakeRelativeClock::default();
+        let lim = RateLimiter::direct_with_clock(quota, &clock);
+        let mut documented = DocumentedGcra {
+            params: quota.gcra_params(),
+            tat: None,
+        };
+        let mut allowed = 0;
+        let mut t0 = Duration::ZERO;
+        // Check a few cells at a time, at irregular intervals:
+        for step in 0..300u64 {
+            for _ in 0..step % 4 {
+                let expected = documented.check(t0.as_nanos());
+                assert_eq!(expected, lim.check().is_ok(), "{:?} at {:?}", quota, t0);
+                allowed += usize::from(expected);
+            }
+            let advance = Duration::from_millis((step * 37) % 401);
+            clock.advance(advance);
+            t0 += advance;
+        }
+        assert!(allowed > 0);
+    }
+}
+
+#[test]
+fn from_gcra_params_rejects_impossible_params() {
+    let params = Quota::per_second(nonzero!(2u32)).gcra_params();
+    let too_short = GcraParams {
+        emission_interval: Duration::ZERO,
+        emission_interval_frac: 1,
+        ..params
+    };
+    assert_eq!(None, Quota::from_gcra_params(too_short));
+    let no_burst = GcraParams {
+        delay_variation_tolerance: Duration::from_millis(499),
+        ..params
+    };
+    assert_eq!(None, Quota::from_gcra_params(no_burst));
+}
+
+#[test]
+fn one_per_admits_exactly_one_cell_per_period() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::one_per(Duration::from_secs(90)).unwrap();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+
+    for _ in 0..5 {
+        assert_eq!(Ok(()), lim.check());
+        assert_ne!(Ok(()), lim.check());
+        clock.advance(Duration::from_secs(90) - Duration::from_nanos(1));
+        assert_ne!(Ok(()), lim.check());
+        clock.advance(Duration::from_nanos(1));
+    }
+    assert_eq!(None, Quota::one_per(Duration::ZERO));
+}
+
+#[test]
+fn strict_interval_rejects_back_to_back_cells_after_idling() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(1u32)).strict_interval();
+    assert_eq!(1, quota.burst_size().get());
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(Ok(()), lim.check());
+
+    clock.advance(Duration::from_secs(10));
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+    clock.advance(Duration::from_secs(1) - Duration::from_nanos(1));
+    assert_ne!(Ok(()), lim.check());
+    clock.advance(Duration::from_nanos(1));
+    assert_eq!(Ok(()), lim.check());
+}
+
+#[test]
+fn tolerance_absorbs_clustered_arrivals_at_the_same_rate() {
+    let quota = Quota::per_second(nonzero!(10u32)).allow_burst(nonzero!(2u32));
+    let tolerant = quota.with_tolerance(Duration::from_millis(500));
+    assert_eq!(tolerant.burst_size(), quota.burst_size());
+
+    // Cells arrive at the quota's rate on average, but in clusters of five every 500ms:
+    let admit_clusters = |quota: Quota| {
+        let clock = FakeRelativeClock::default();
+        let lim = RateLimiter::direct_with_clock(quota, &clock);
+        let mut admitted = 0;
+        for _ in 0..20 {
+            admitted += (0..5).filter(|_| lim.check().is_ok()).count();
+            clock.advance(Duration::from_millis(500));
+        }
+        admitted
+    };
+    assert!(admit_clusters(quota) < 100);
+    assert_eq!(admit_clusters(tolerant), 100);
+
+    // Saturated, both allow cells through at the same rate from then on:
+    let saturated_rate = |quota: Quota| {
+        let clock = FakeRelativeClock::default();
+        let lim = RateLimiter::direct_with_clock(quota, &clock);
+        while lim.check().is_ok() {}
+        let mut admitted = 0;
+        for _ in 0..1000 {
+            clock.advance(Duration::from_millis(10));
+            admitted += (0..5).filter(|_| lim.check().is_ok()).count();
+        }
+        admitted
+    };
+    assert_eq!(saturated_rate(quota), 100);
+    assert_eq!(saturated_rate(tolerant), 100);
+    assert_eq!(
+        quota.with_tolerance(Duration::ZERO).gcra_params(),
+        quota.gcra_params()
+    );
+}
+
+#[test]
+fn tolerances_allowing_more_than_u32_max_cells_saturate() {
+    // Allows 2^34 - 4 cells through at once, more than fit into a u32:
+    let quota = Quota::per_second(nonzero!(1u32))
+        .with_tolerance(Duration::from_secs(4 * u64::from(u32::MAX)));
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(lim.state_information().remaining_burst_capacity(), u32::MAX);
+
+    for _ in 0..4 {
+        assert!(lim.check_n(NonZeroU32::MAX).is_ok());
+    }
+    let nu = lim.check().unwrap_err();
+    assert_eq!(nu.quota().burst_size(), NonZeroU32::MAX);
+    assert_eq!(lim.state_information().remaining_burst_capacity(), 0);
+}
+
+#[test]
+fn scaling_multiplies_the_rate() {
+    let base = Quota::per_second(nonzero!(10u32));
+    let doubled = base.scale(2.0).unwrap();
+    assert_eq!(doubled, Quota::per_second(nonzero!(20u32)));
+
+    // Saturate both for ten seconds:
+    let admitted = |quota: Quota| {
+        let clock = FakeRelativeClock::default();
+        let lim = RateLimiter::direct_with_clock(quota, &clock);
+        let mut admitted = 0;
+        for _ in 0..1000 {
+            admitted += (0..10).filter(|_| lim.check().is_ok()).count();
+            clock.advance(Duration::from_millis(10));
+        }
+        admitted
+    };
+    assert_eq!(admitted(base), 10 + 99);
+    assert_eq!(admitted(doubled), 20 + 199);
+
+    // Burst sizes are rounded, but stay at least one cell:
+    let base = Quota::per_minute(nonzero!(5u32)).start_empty();
+    let scaled = base.scale(0.3).unwrap();
+    assert_eq!(scaled.burst_size().get(), 2);
+    assert_eq!(scaled.replenish_interval(), Duration::from_secs(40));
+    assert_eq!(scaled, scaled.start_empty());
+    assert_eq!(base.scale(0.01).unwrap().burst_size().get(), 1);
+}
+
+#[test]
+fn scaling_rejects_invalid_factors() {
+    let base = Quota::per_second(nonzero!(10u32));
+    for factor in &[0.0, -1.0, f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        assert_eq!(base.scale(*factor), None, "{}", factor);
+    }
+    // The burst size would overflow:
+    assert_eq!(base.scale(1e9), None);
+    // The interval would be too long:
+    assert_eq!(Quota::per_hour(nonzero!(1u32)).scale(1e-12), None);
+}
+
+#[test]
+fn parses_quotas_from_strings() {
+    let parsed: Quota = "100/s".parse().unwrap();
+    assert_eq!(parsed, Quota::per_second(nonzero!(100u32)));
+    assert_eq!(parsed.replenish_interval(), Duration::from_millis(10));
+    assert_eq!(parsed.burst_size().get(), 100);
+
+    let parsed: Quota = " 60 / minute ".parse().unwrap();
+    assert_eq!(parsed, Quota::per_minute(nonzero!(60u32)));
+
+    let parsed: Quota = "1000/hour;burst=50".parse().unwrap();
+    assert_eq!(parsed.replenish_interval(), Duration::from_millis(3600));
+    assert_eq!(parsed.burst_size().get(), 50);
+
+    let parsed: Quota = "2/d; burst = 1".parse().unwrap();
+    assert_eq!(
+        parsed.replenish_interval(),
+        Duration::from_secs(12 * 60 * 60)
+    );
+    assert_eq!(parsed.burst_size().get(), 1);
+
+    let parsed: Quota = "5/ms".parse().unwrap();
+    assert_eq!(parsed.replenish_interval(), Duration::from_micros(200));
+}
+
+#[test]
+fn parsed_bursts_are_lowered_like_allow_burst() {
+    let parsed: Quota = "1/h;burst=4294967295".parse().unwrap();
+    let quota = Quota::per_hour(nonzero!(1u32)).allow_burst(nonzero!(u32::MAX));
+    assert_eq!(parsed, quota);
+    #[cfg(not(feature = "wide-nanos"))]
+    assert!(parsed.burst_size() < nonzero!(u32::MAX));
+}
+
+#[test]
+fn rejects_malformed_quota_strings() {
+    assert_eq!(
+        Err(ParseQuotaError::InvalidCount("0".to_string())),
+        "0/s".parse::<Quota>()
+    );
+    assert_eq!(
+        Err(ParseQuotaError::UnknownUnit("fortnight".to_string())),
+        "100/fortnight".parse::<Quota>()
+    );
+    assert_eq!(
+        Err(ParseQuotaError::UnknownUnit("s garbage".to_string())),
+        "100/s garbage".parse::<Quota>()
+    );
+    assert_eq!(Err(ParseQuotaError::Malformed), "100".parse::<Quota>());
+    assert_eq!(
+        Err(ParseQuotaError::InvalidCount("-1".to_string())),
+        "-1/s".parse::<Quota>()
+    );
+    assert_eq!(
+        Err(ParseQuotaError::InvalidCount("5000000000".to_string())),
+        "5000000000/s".parse::<Quota>()
+    );
+    assert_eq!(
+        Err(ParseQuotaError::InvalidCount("0".to_string())),
+        "10/s;burst=0".parse::<Quota>()
+    );
+    assert_eq!(
+        Err(ParseQuotaError::UnknownOption("jitter=5".to_string())),
+        "10/s;jitter=5".parse::<Quota>()
+    );
+    assert_eq!(
+        "unknown unit of time `fortnight`, expected one of ms, s, min, h or d",
+        "100/fortnight".parse::<Quota>().unwrap_err().to_string()
+    );
+}
+
+#[test]
+fn bursts_with_long_periods_are_admitted_once_then_throttled() {
+    let hour = Duration::from_secs(60 * 60);
+    let quota = Quota::with_period(hour)
+        .unwrap()
+        .allow_burst(nonzero!(10_000u32));
+    assert_eq!(10_000, quota.burst_size().get());
+    assert_eq!(hour * 10_000, quota.burst_size_replenished_in());
+
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    let admitted = (0..20_000).filter(|_| lim.check().is_ok()).count();
+    assert_eq!(10_000, admitted);
+    assert_eq!(hour, lim.check().unwrap_err().wait_time_from(clock.now()));
+
+    // After that, one cell per hour:
+    clock.advance(hour);
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+    clock.advance(hour * 10);
+    assert_eq!(10, (0..20).filter(|_| lim.check().is_ok()).count());
+}
+
+// With the `wide-nanos` feature, windows can be much longer (see tests/wide_nanos.rs):
+#[cfg(not(feature = "wide-nanos"))]
+#[test]
+fn bursts_that_do_not_fit_into_the_longest_window_are_lowered() {
+    let year = Duration::from_secs(365 * 24 * 60 * 60);
+    let quota = Quota::with_period(year)
+        .unwrap()
+        .allow_burst(nonzero!(1_000u32));
+    assert_eq!(583, quota.burst_size().get());
+    assert!(quota.burst_size_replenished_in() <= Duration::from_nanos(u64::MAX));
+
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(Ok(()), lim.check_n(nonzero!(583u32)));
+    assert_ne!(Ok(()), lim.check());
+
+    // Burst sizes that fit are kept:
+    let quota = Quota::with_period(year)
+        .unwrap()
+        .allow_burst(nonzero!(500u32));
+    assert_eq!(500, quota.burst_size().get());
+}
@@ -0,0 +1,74 @@
+use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+/// Checks cells every millisecond for `ms` milliseconds, returning how many were allowed.
+fn drain(
+    lim: &governor::state::RampingRateLimiter<FakeRelativeClock>,
+    clock: &FakeRelativeClock,
+    ms: u64,
+) -> u64 {
+    let mut allowed = 0;
+    for _ in 0..ms {
+        while lim.check().is_ok() {
+            allowed += 1;
+        }
+        clock.advance(Duration::from_millis(1));
+    }
+    allowed
+}
+
+#[test]
+fn admits_the_interpolated_rate_midway() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::ramp_with_clock(
+        Quota::per_second(nonzero!(10u32)),
+        Quota::per_second(nonzero!(100u32)),
+        Duration::from_secs(10),
+        &clock,
+    );
+    drain(&lim, &clock, 4500);
+    // Between 4.5s and 5.5s, the rate moves from 50.5 to 59.5 cells per second:
+    let midway = drain(&lim, &clock, 1000);
+    assert!((52..=58).contains(&midway), "allowed {}", midway);
+}
+
+#[test]
+fn stays_at_end_quota_after_the_ramp() {
+    let clock = FakeRelativeClock::default();
+    let end = Quota::per_second(nonzero!(100u32));
+    let lim = RateLimiter::ramp_with_clock(
+        Quota::per_second(nonzero!(10u32)),
+        end,
+        Duration::from_secs(10),
+        &clock,
+    );
+    assert_eq!(Quota::per_second(nonzero!(10u32)), lim.current_quota());
+    drain(&lim, &clock, 11_000);
+    assert_eq!(end, lim.current_quota());
+    assert_eq!(100, drain(&lim, &clock, 1000));
+}
+
+#[test]
+fn steps_keep_the_end_quota_settings() {
+    let clock = FakeRelativeClock::default();
+    let end = Quota::per_second(nonzero!(1u32))
+        .strict_interval()
+        .allow_burst(nonzero!(100u32))
+        .start_empty()
+        .with_tolerance(Duration::from_secs(30));
+    let lim = RateLimiter::ramp_with_clock(
+        Quota::per_second(nonzero!(10u32)),
+        end,
+        Duration::from_secs(10),
+        &clock,
+    );
+    clock.advance(Duration::from_secs(5));
+    // The rate limiter has no state yet, so it starts empty under the step's quota:
+    assert!(lim.check().is_err());
+    let midway = lim.current_quota();
+    assert_ne!(end, midway);
+    let params = midway.gcra_params();
+    assert!(params.strict);
+    assert_eq!(Duration::from_secs(30), params.delay_variation_tolerance);
+}
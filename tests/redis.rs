@@ -0,0 +1,252 @@
+#![cfg(feature = "redis-store")]
+
+//! Tests for the Redis state store, against a minimal in-process stand-in for a Redis server
+//! that understands just the commands the store uses.
+//!
+//! The tests named `real_redis_*` run the store's Lua script on an actual Redis server instead.
+//! They are ignored by default; to run them, start a server and run
+//! `cargo test --features redis-store --test redis -- --ignored`, with `GOVERNOR_REDIS_URL` set
+//! to the server's URL if it isn't `redis://127.0.0.1/`.
+
+use governor::{
+    clock::FakeRelativeClock,
+    state::keyed::{RedisFailurePolicy, RedisStateStore},
+    Quota, RateLimiter,
+};
+use nonzero_ext::nonzero;
+use std::collections::HashMap;
+use std::env;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type Data = Arc<Mutex<HashMap<String, String>>>;
+
+fn read_command(reader: &mut impl BufRead) -> Option<Vec<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let count: usize = line.trim_end().strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+    for _ in 0..count {
+        line.clear();
+        reader.read_line(&mut line).ok()?;
+        let len: usize = line.trim_end().strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(len);
+        args.push(String::from_utf8(arg).ok()?);
+    }
+    Some(args)
+}
+
+fn bulk(value: &str) -> String {
+    format!("${}\r\n{}\r\n", value.len(), value)
+}
+
+fn respond(data: &Data, args: &[String]) -> String {
+    let mut data = data.lock().unwrap();
+    match args[0].to_uppercase().as_str() {
+        "PING" => "+PONG\r\n".to_string(),
+        "GET" => data
+            .get(&args[1])
+            .map(|v| bulk(v))
+            .unwrap_or_else(|| "$-1\r\n".to_string()),
+        "DEL" => format!(":{}\r\n", data.remove(&args[1]).map_or(0, |_| 1)),
+        "SCRIPT" => bulk("loaded"),
+        // EVALSHA <sha> 1 <key> <expected> <new> <ttl>: the store's compare-and-set script.
+        "EVALSHA" => {
+            let (key, expected, new) = (&args[3], &args[4], &args[5]);
+            let current = data.get(key).cloned().unwrap_or_default();
+            if &current == expected {
+                data.insert(key.clone(), new.clone());
+                format!("*2\r\n:1\r\n{}", bulk(new))
+            } else {
+                format!("*2\r\n:0\r\n{}", bulk(&current))
+            }
+        }
+        _ => "-ERR unknown command\r\n".to_string(),
+    }
+}
+
+/// Starts a fake Redis server and returns its URL.
+fn fake_redis() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let data: Data = Default::default();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = stream.unwrap();
+            let data = data.clone();
+            thread::spawn(move || serve(stream, data));
+        }
+    });
+    format!("redis://{}/", addr)
+}
+
+fn serve(stream: TcpStream, data: Data) {
+    let mut writer = stream.try_clone().unwrap();
+    let mut reader = BufReader::new(stream);
+    while let Some(args) = read_command(&mut reader) {
+        if writer.write_all(respond(&data, &args).as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Returns the URL of a port that nothing listens on.
+fn unreachable_redis() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    format!("redis://{}/", listener.local_addr().unwrap())
+}
+
+/// Returns the URL of the Redis server that the `real_redis_*` tests run against.
+fn real_redis() -> String {
+    env::var("GOVERNOR_REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string())
+}
+
+fn pool(url: &str) -> r2d2::Pool<redis::Client> {
+    let client = redis::Client::open(url).unwrap();
+    r2d2::Pool::builder()
+        .max_size(2)
+        .connection_timeout(Duration::from_millis(500))
+        .build_unchecked(client)
+}
+
+fn store(url: &str) -> RedisStateStore<u32> {
+    store_with_prefix(url, "governor-test:")
+}
+
+/// Returns a store that keeps its states under Redis keys starting with `prefix`.
+fn store_with_prefix(url: &str, prefix: &str) -> RedisStateStore<u32> {
+    RedisStateStore::new(pool(url), prefix)
+}
+
+/// Returns a prefix for the Redis keys of a test that are unique to this run of the test.
+fn unique_prefix(test: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    format!("governor-test:{}:{}:{}:", test, std::process::id(), nanos)
+}
+
+fn shares_limits(url: &str, prefix: &str) {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(2u32));
+    let one = RateLimiter::new(quota, store_with_prefix(url, prefix), &clock);
+    let other = RateLimiter::new(quota, store_with_prefix(url, prefix), &clock);
+
+    assert_eq!(Ok(()), one.check_key(&1));
+    assert_eq!(Ok(()), other.check_key(&1));
+    assert_ne!(Ok(()), one.check_key(&1));
+    assert_ne!(Ok(()), other.check_key(&1));
+
+    // other keys are unaffected:
+    assert_eq!(Ok(()), other.check_key(&2));
+
+    clock.advance(Duration::from_millis(500));
+    assert_eq!(Ok(()), other.check_key(&1));
+    assert_ne!(Ok(()), one.check_key(&1));
+
+    one.reset_key(&1);
+    assert_eq!(Ok(()), other.check_key_n(&1, nonzero!(2u32)));
+}
+
+#[test]
+fn instances_share_limits() {
+    shares_limits(&fake_redis(), "governor-test:");
+}
+
+#[test]
+#[ignore = "needs a Redis server, see the module docs"]
+fn real_redis_instances_share_limits() {
+    shares_limits(&real_redis(), &unique_prefix("share"));
+}
+
+#[test]
+#[ignore = "needs a Redis server, see the module docs"]
+fn real_redis_concurrent_checks_allow_exactly_the_burst() {
+    let prefix = unique_prefix("concurrent");
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_hour(nonzero!(100u32));
+    let allowed = Arc::new(AtomicUsize::new(0));
+    let children: Vec<_> = (0..8)
+        .map(|_| {
+            let lim = RateLimiter::new(quota, store_with_prefix(&real_redis(), &prefix), &clock);
+            let allowed = allowed.clone();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    if lim.check_key(&1).is_ok() {
+                        allowed.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+        })
+        .collect();
+    for child in children {
+        child.join().unwrap();
+    }
+    // The compare-and-set script lets no two instances use up the same cell:
+    assert_eq!(100, allowed.load(Ordering::SeqCst));
+}
+
+#[test]
+#[ignore = "needs a Redis server, see the module docs"]
+fn real_redis_states_expire_after_their_ttl() {
+    let prefix = unique_prefix("ttl");
+    let url = real_redis();
+    let clock = FakeRelativeClock::default();
+    let store = store_with_prefix(&url, &prefix).with_ttl(Duration::from_millis(200));
+    let lim = RateLimiter::new(Quota::per_hour(nonzero!(1u32)), store, &clock);
+
+    assert_eq!(Ok(()), lim.check_key(&1));
+    assert_ne!(Ok(()), lim.check_key(&1));
+    let mut conn = pool(&url).get().unwrap();
+    let ttl: i64 = redis::cmd("PTTL")
+        .arg(format!("{}1", prefix))
+        .query(&mut *conn)
+        .unwrap();
+    assert!(ttl > 0 && ttl <= 200, "{}", ttl);
+
+    thread::sleep(Duration::from_millis(400));
+    assert_eq!(Ok(()), lim.check_key(&1));
+}
+
+#[test]
+fn errors_fail_closed() {
+    let errors = Arc::new(AtomicUsize::new(0));
+    let counter = errors.clone();
+    let store = store(&unreachable_redis()).on_error(move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::new(Quota::per_second(nonzero!(1u32)), store, &clock);
+
+    assert_ne!(Ok(()), lim.check_key(&1));
+    assert_ne!(Ok(()), lim.check_key(&2));
+    assert_eq!(0, lim.available_for_key(&3));
+    assert_eq!(errors.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn errors_fail_open_if_configured() {
+    let errors = Arc::new(AtomicUsize::new(0));
+    let counter = errors.clone();
+    let store = store(&unreachable_redis())
+        .with_failure_policy(RedisFailurePolicy::FailOpen)
+        .on_error(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::new(Quota::per_second(nonzero!(1u32)), store, &clock);
+
+    assert_eq!(Ok(()), lim.check_key(&1));
+    assert_eq!(Ok(()), lim.check_key(&1));
+    assert_eq!(1, lim.available_for_key(&1));
+    assert_eq!(errors.load(Ordering::SeqCst), 3);
+}
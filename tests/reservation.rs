@@ -0,0 +1,56 @@
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    middleware::StateInformationMiddleware,
+    Quota, RateLimiter,
+};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+#[test]
+fn redeemed_reservations_stay_counted() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_middleware(StateInformationMiddleware);
+    let reservation = lim.reserve().unwrap();
+    assert_eq!(clock.now(), reservation.valid_from());
+    assert_eq!(1, lim.available());
+
+    clock.advance(Duration::from_millis(100));
+    let state = reservation.redeem();
+    assert_eq!(1, state.remaining_burst_capacity());
+    assert_eq!(1, lim.available());
+}
+
+#[test]
+fn dropped_reservations_are_refunded() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let first = lim.reserve().unwrap();
+    let second = lim.reserve().unwrap();
+    assert_eq!(0, lim.available());
+
+    drop(first);
+    assert_eq!(1, lim.available());
+    second.cancel();
+    assert_eq!(2, lim.available());
+}
+
+#[test]
+fn reservations_count_against_the_burst() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(3u32)), &clock);
+    let reservations: Vec<_> = (0..3).map(|_| lim.reserve().unwrap()).collect();
+    assert!(lim.reserve().is_err());
+    assert!(lim.check().is_err());
+
+    // Once a cell replenishes, it can be reserved (or checked) again:
+    clock.advance(Duration::from_millis(334));
+    let fourth = lim.reserve().unwrap();
+    assert!(lim.check().is_err());
+
+    for reservation in reservations {
+        reservation.redeem();
+    }
+    fourth.redeem();
+    assert!(lim.check().is_err());
+}
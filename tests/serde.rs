@@ -0,0 +1,156 @@
+#![cfg(feature = "serde")]
+
+use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+#[test]
+fn quota_roundtrip() {
+    let quota = Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(20u32));
+    let json = serde_json::to_string(&quota).unwrap();
+    let restored: Quota = serde_json::from_str(&json).unwrap();
+    assert_eq!(quota, restored);
+    assert_eq!(restored.burst_size(), nonzero!(20u32));
+    assert_eq!(restored.replenish_interval(), Duration::from_millis(200));
+}
+
+#[test]
+fn fractional_interval_roundtrip() {
+    let quota = Quota::per_second(nonzero!(3_000_000u32));
+    let json = serde_json::to_string(&quota).unwrap();
+    assert!(json.contains("replenish_interval_frac"), "{}", json);
+    let restored: Quota = serde_json::from_str(&json).unwrap();
+    assert_eq!(quota, restored);
+
+    let json = serde_json::to_string(&Quota::per_second(nonzero!(5u32))).unwrap();
+    assert!(!json.contains("replenish_interval_frac"), "{}", json);
+}
+
+#[test]
+fn sub_nanosecond_interval_roundtrip() {
+    let quota = Quota::per_second(nonzero!(2_000_000_000u32));
+    assert_eq!(quota.replenish_interval(), Duration::ZERO);
+    let json = serde_json::to_string(&quota).unwrap();
+    let restored: Quota = serde_json::from_str(&json).unwrap();
+    assert_eq!(quota, restored);
+    assert_eq!(Ok(restored), "2000000000/s".parse());
+}
+
+#[test]
+fn roundtripped_quota_limits_identically() {
+    let quota = Quota::per_second(nonzero!(5u32)).allow_burst(nonzero!(10u32));
+    let restored: Quota = serde_json::from_str(&serde_json::to_string(&quota).unwrap()).unwrap();
+
+    let clock = FakeRelativeClock::default();
+    let original = RateLimiter::direct_with_clock(quota, &clock);
+    let restored = RateLimiter::direct_with_clock(restored, &clock);
+    for _ in 0..30 {
+        assert_eq!(original.check().is_ok(), restored.check().is_ok());
+        clock.advance(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn start_empty_roundtrip() {
+    let quota = Quota::per_second(nonzero!(5u32)).start_empty();
+    let json = serde_json::to_string(&quota).unwrap();
+    assert!(json.contains(r#""start_empty":true"#), "{}", json);
+    let restored: Quota = serde_json::from_str(&json).unwrap();
+    assert_eq!(quota, restored);
+
+    let json = serde_json::to_string(&Quota::per_second(nonzero!(5u32))).unwrap();
+    assert!(!json.contains("start_empty"), "{}", json);
+    let json = r#"{"max_burst":5,"replenish_interval":{"secs":0,"nanos":200000000}}"#;
+    let restored: Quota = serde_json::from_str(json).unwrap();
+    assert_eq!(Quota::per_second(nonzero!(5u32)), restored);
+}
+
+#[test]
+fn strict_interval_roundtrip() {
+    let quota = Quota::per_second(nonzero!(5u32)).strict_interval();
+    let json = serde_json::to_string(&quota).unwrap();
+    assert!(json.contains(r#""strict":true"#), "{}", json);
+    let restored: Quota = serde_json::from_str(&json).unwrap();
+    assert_eq!(quota, restored);
+
+    let json = serde_json::to_string(&Quota::per_second(nonzero!(5u32))).unwrap();
+    assert!(!json.contains("strict"), "{}", json);
+}
+
+#[test]
+fn rejects_zero_burst() {
+    let json = r#"{"max_burst":0,"replenish_interval":{"secs":1,"nanos":0}}"#;
+    assert!(serde_json::from_str::<Quota>(json).is_err());
+}
+
+#[test]
+fn lowers_bursts_that_do_not_fit_into_the_longest_window() {
+    // 2^32 - 1 cells of an hour each take ~490,000 years to replenish:
+    let json = r#"{"max_burst":4294967295,"replenish_interval":{"secs":3600,"nanos":0}}"#;
+    let restored: Quota = serde_json::from_str(json).unwrap();
+    let quota = Quota::with_period(Duration::from_secs(3600))
+        .unwrap()
+        .allow_burst(nonzero!(u32::MAX));
+    assert_eq!(quota, restored);
+    #[cfg(not(feature = "wide-nanos"))]
+    assert!(restored.burst_size() < nonzero!(u32::MAX));
+}
+
+#[test]
+fn rejects_zero_interval() {
+    let json = r#"{"max_burst":10,"replenish_interval":{"secs":0,"nanos":0}}"#;
+    let err = serde_json::from_str::<Quota>(json).unwrap_err();
+    assert!(err.to_string().contains("replenish_interval"), "{}", err);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn state_snapshot_round_trip() {
+    use governor::state::keyed::StateSnapshot;
+
+    let lim = RateLimiter::keyed(Quota::per_minute(nonzero!(1u32)));
+    lim.check_key(&"foo").unwrap();
+    let snapshot = lim.snapshot();
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let restored: Vec<(&str, StateSnapshot)> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, snapshot);
+
+    let new_lim = RateLimiter::keyed(Quota::per_minute(nonzero!(1u32)));
+    new_lim.restore(restored);
+    assert_ne!(Ok(()), new_lim.check_key(&"foo"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn direct_state_snapshot_round_trip() {
+    use governor::state::keyed::StateSnapshot;
+
+    let quota = Quota::per_minute(nonzero!(2u32));
+    let lim = RateLimiter::direct(quota);
+    lim.check().unwrap();
+    let snapshot = lim.export_state();
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let restored: StateSnapshot = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, snapshot);
+
+    let new_lim = RateLimiter::from_snapshot(quota, &FakeRelativeClock::default(), restored);
+    assert_eq!(1, new_lim.available());
+}
+
+#[test]
+fn not_until_serializes_wait_time_and_quota() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(2u32));
+    let lim = RateLimiter::direct_with_clock(quota, &clock);
+    lim.check().unwrap();
+    lim.check().unwrap();
+    let negative = lim.check().unwrap_err();
+
+    let json: serde_json::Value = serde_json::to_value(&negative).unwrap();
+    let wait_time: Duration = serde_json::from_value(json["wait_time"].clone()).unwrap();
+    assert!(wait_time > Duration::from_secs(0), "{}", json);
+    assert_eq!(wait_time, Duration::from_millis(500));
+    assert_eq!(json["retry_after_secs"], 1);
+    let violated: Quota = serde_json::from_value(json["quota"].clone()).unwrap();
+    assert_eq!(violated, quota);
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "std")]
+
+use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+#[test]
+fn single_thread_uses_all_shards() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::direct_sharded_with_clock(
+        Quota::per_second(nonzero!(8u32)),
+        nonzero!(4u32),
+        &clock,
+    );
+
+    for _ in 0..8 {
+        assert_eq!(Ok(()), lim.check());
+    }
+    assert_ne!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.peek());
+
+    lim.reset();
+    assert_eq!(Ok(()), lim.peek());
+    assert_eq!(Ok(()), lim.check());
+}
+
+#[test]
+fn aggregate_never_exceeds_quota_by_more_than_shards() {
+    let clock = FakeRelativeClock::default();
+    let shards = 4;
+    let lim = RateLimiter::direct_sharded_with_clock(
+        Quota::per_second(nonzero!(10u32)),
+        nonzero!(4u32),
+        &clock,
+    );
+    let allowed = AtomicU32::new(0);
+
+    for second in 1..=3 {
+        crossbeam::scope(|scope| {
+            for _i in 0..16 {
+                scope.spawn(|_| {
+                    for _ in 0..10 {
+                        if lim.check().is_ok() {
+                            allowed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+        clock.advance(Duration::from_secs(1));
+
+        let allowed = allowed.load(Ordering::Relaxed);
+        assert!(allowed <= 10 * second + shards, "{} cells allowed", allowed);
+        assert!(allowed >= 10, "{} cells allowed", allowed);
+    }
+}
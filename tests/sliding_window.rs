@@ -0,0 +1,122 @@
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    middleware::StateInformationMiddleware,
+    NegativeMultiDecision, Quota, RateLimiter,
+};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+fn admitted(mut check: impl FnMut() -> bool) -> usize {
+    (0..20).filter(|_| check()).count()
+}
+
+#[test]
+fn burst_at_window_boundary_compared_to_gcra() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(10u32));
+    let gcra = RateLimiter::direct_with_clock(quota, &clock);
+    let window = RateLimiter::direct_sliding_window_with_clock(quota, &clock);
+    let ms = Duration::from_millis(1);
+
+    // A burst late in the first window:
+    clock.advance(ms * 900);
+    assert_eq!(admitted(|| gcra.check().is_ok()), 10);
+    assert_eq!(admitted(|| window.check().is_ok()), 10);
+
+    // Right at the window boundary, GCRA has replenished a cell; the sliding window still
+    // counts the entire burst:
+    clock.advance(ms * 100);
+    assert_eq!(admitted(|| gcra.check().is_ok()), 1);
+    assert_eq!(admitted(|| window.check().is_ok()), 0);
+
+    clock.advance(ms * 100);
+    assert_eq!(admitted(|| gcra.check().is_ok()), 1);
+    assert_eq!(admitted(|| window.check().is_ok()), 1);
+
+    // Half of the burst's window has slid out of view:
+    clock.advance(ms * 400);
+    assert_eq!(admitted(|| gcra.check().is_ok()), 4);
+    assert_eq!(admitted(|| window.check().is_ok()), 4);
+
+    clock.advance(ms * 500);
+    assert_eq!(admitted(|| gcra.check().is_ok()), 5);
+    assert_eq!(admitted(|| window.check().is_ok()), 5);
+}
+
+#[test]
+fn reports_wait_until_a_cell_fits() {
+    let clock = FakeRelativeClock::default();
+    let lim =
+        RateLimiter::direct_sliding_window_with_clock(Quota::per_second(nonzero!(10u32)), &clock);
+    clock.advance(Duration::from_millis(900));
+    assert_eq!(Ok(()), lim.check_n(nonzero!(10u32)));
+
+    let negative = lim.check().unwrap_err();
+    assert_eq!(
+        negative.wait_time_from(clock.now()),
+        Duration::from_millis(200)
+    );
+    clock.advance(Duration::from_millis(199));
+    assert_ne!(Ok(()), lim.check());
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(Ok(()), lim.check());
+}
+
+#[test]
+fn batches() {
+    let clock = FakeRelativeClock::default();
+    let lim =
+        RateLimiter::direct_sliding_window_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    assert_eq!(
+        Err(NegativeMultiDecision::InsufficientCapacity {
+            requested: 6,
+            max_burst: 5,
+        }),
+        lim.check_n(nonzero!(6u32))
+    );
+    assert_eq!(Ok(()), lim.check_n(nonzero!(3u32)));
+    assert!(matches!(
+        lim.check_n(nonzero!(3u32)),
+        Err(NegativeMultiDecision::BatchNonConforming(3, _))
+    ));
+    assert_eq!(Ok(()), lim.check_n(nonzero!(2u32)));
+}
+
+#[test]
+fn idle_windows_are_forgotten() {
+    let clock = FakeRelativeClock::default();
+    let lim =
+        RateLimiter::direct_sliding_window_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    assert_eq!(admitted(|| lim.check().is_ok()), 5);
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(admitted(|| lim.check().is_ok()), 5);
+}
+
+#[test]
+fn state_information() {
+    let clock = FakeRelativeClock::default();
+    let lim =
+        RateLimiter::direct_sliding_window_with_clock(Quota::per_second(nonzero!(4u32)), &clock)
+            .with_middleware(StateInformationMiddleware);
+    assert_eq!(
+        Ok(3),
+        lim.check().map(|state| state.remaining_burst_capacity())
+    );
+    assert_eq!(
+        Ok(0),
+        lim.check_n(nonzero!(3u32))
+            .map(|state| state.remaining_burst_capacity())
+    );
+}
+
+#[test]
+fn start_empty() {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::per_second(nonzero!(5u32)).start_empty();
+    let lim = RateLimiter::direct_sliding_window_with_clock(quota, &clock);
+    assert!(lim.check().is_err());
+    clock.advance(quota.replenish_interval());
+    assert_eq!(admitted(|| lim.check().is_ok()), 1);
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(admitted(|| lim.check().is_ok()), 5);
+}
@@ -2,8 +2,14 @@
 
 use futures::executor::block_on;
 use futures::{stream, StreamExt};
-use governor::{prelude::*, Quota, RateLimiter};
+use governor::{
+    clock::{AsyncClock, Clock, FakeRelativeClock},
+    prelude::*,
+    Quota, RateLimiter,
+};
+use more_asserts::*;
 use nonzero_ext::*;
+use std::future::{ready, Ready};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -26,3 +32,73 @@ fn stream() {
     assert!(i.elapsed() > Duration::from_millis(200));
     assert!(i.elapsed() <= Duration::from_millis(300));
 }
+
+/// A clock that doesn't wait, but advances a fake clock instead.
+#[derive(Clone, Default)]
+struct SkippingClock(FakeRelativeClock);
+
+impl Clock for SkippingClock {
+    type Instant = <FakeRelativeClock as Clock>::Instant;
+
+    fn now(&self) -> Self::Instant {
+        self.0.now()
+    }
+}
+
+impl AsyncClock for SkippingClock {
+    type Delay = Ready<()>;
+
+    fn delay(&self, duration: Duration) -> Self::Delay {
+        self.0.advance(duration);
+        ready(())
+    }
+}
+
+#[test]
+fn stream_paces_items_on_fake_clock() {
+    let clock = SkippingClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+    let items: Vec<_> = block_on(stream::iter(0..).ratelimit_stream(&lim).take(10).collect());
+    assert_eq!(items, (0..10).collect::<Vec<_>>());
+
+    // Two items make it through right away, the other eight come in 500ms apart:
+    let elapsed: Duration = clock.now().into();
+    assert_ge!(elapsed, Duration::from_secs(4));
+    assert_lt!(elapsed, Duration::from_millis(4500));
+}
+
+#[test]
+fn stream_ending_returns_cell() {
+    let clock = SkippingClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+    let mut stream = stream::iter(0..3).ratelimit_stream(&lim);
+    assert_eq!(block_on(stream.next()), Some(0));
+    assert_eq!(block_on(stream.next()), Some(1));
+    assert_eq!(block_on(stream.next()), Some(2));
+
+    // The stream ends after a wait for the next cell; that cell is not used up:
+    assert_eq!(block_on(stream.next()), None);
+    let ended_at = clock.now();
+    assert_eq!(block_on(stream.next()), None);
+    assert_eq!(clock.now(), ended_at);
+    assert_eq!(Ok(()), lim.check());
+}
+
+#[test]
+fn stream_dropped_while_reading_returns_cell() {
+    let clock = SkippingClock::default();
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(1u32)), &clock);
+
+    // A cell is allowed through for the next item, but the underlying stream never produces it:
+    let mut stream = stream::pending::<()>().ratelimit_stream(&lim);
+    assert!(block_on(async { futures::poll!(stream.next()) }).is_pending());
+    assert!(lim.check().is_err());
+    drop(stream);
+    assert_eq!(Ok(()), lim.check());
+
+    clock.0.advance(Duration::from_secs(1));
+    let mut stream = stream::pending::<()>().ratelimit_stream(&lim);
+    assert!(block_on(async { futures::poll!(stream.next()) }).is_pending());
+    let _inner = stream.into_inner();
+    assert_eq!(Ok(()), lim.check());
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "thread-cache")]
+
+use governor::{
+    clock::{FakeRelativeClock, Reference},
+    middleware::RateLimitingMiddleware,
+    NotUntil, Quota, RateLimiter, StateInformation,
+};
+use nonzero_ext::nonzero;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+const THREADS: u32 = 4;
+const CHUNK: u32 = 5;
+
+#[test]
+fn aggregate_admissions_stay_within_bound() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(50u32)), &clock)
+        .thread_cached(nonzero!(5u32));
+    let strict = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(50u32)), &clock);
+    let allowed = AtomicU32::new(0);
+    let mut strictly_allowed = 0;
+    for _round in 0..10 {
+        crossbeam::scope(|scope| {
+            for _thread in 0..THREADS {
+                scope.spawn(|_| {
+                    for _ in 0..200 {
+                        if lim.check_key(&"key").is_ok() {
+                            allowed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+        while strict.check_key(&"key").is_ok() {
+            strictly_allowed += 1;
+        }
+        let allowed = allowed.load(Ordering::Relaxed);
+        assert!(
+            allowed <= strictly_allowed + CHUNK * THREADS,
+            "{} allowed, strictly {}",
+            allowed,
+            strictly_allowed
+        );
+        assert!(allowed + CHUNK * THREADS >= strictly_allowed);
+        clock.advance(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn keys_with_little_capacity_are_checked_exactly() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(3u32)), &clock)
+        .thread_cached(nonzero!(5u32));
+    for _ in 0..3 {
+        assert_eq!(Ok(()), lim.check_key(&1u32));
+    }
+    assert!(lim.check_key(&1u32).is_err());
+    assert_eq!(Ok(()), lim.check_key(&2u32));
+}
+
+#[derive(Default)]
+struct Decisions {
+    allowed: AtomicU32,
+    disallowed: AtomicU32,
+}
+
+impl<K, P: Reference> RateLimitingMiddleware<K, P> for &Decisions {
+    type PositiveOutcome = ();
+
+    fn allow_cell(&self, _key: &K, _state: StateInformation) {
+        self.allowed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn disallow_cell(&self, _key: &K, _not_until: &NotUntil<'_, P>) {
+        self.disallowed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn middleware_only_sees_the_final_decision() {
+    let clock = FakeRelativeClock::default();
+    let decisions = Decisions::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(10u32)), &clock)
+        .with_middleware(&decisions)
+        .thread_cached(nonzero!(5u32));
+    assert_eq!(Ok(()), lim.limiter().check_key_n(&1u32, nonzero!(7u32)));
+
+    // The key has no chunk's worth of capacity left, so the cells are checked one by one:
+    for _ in 0..3 {
+        assert_eq!(Ok(()), lim.check_key(&1u32));
+    }
+    assert!(lim.check_key(&1u32).is_err());
+    assert_eq!(4, decisions.allowed.load(Ordering::Relaxed));
+    assert_eq!(1, decisions.disallowed.load(Ordering::Relaxed));
+}
+
+#[test]
+fn held_cells_expire() {
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(10u32)), &clock)
+        .thread_cached(nonzero!(5u32));
+    assert_eq!(Ok(()), lim.check_key(&1u32));
+    assert_eq!(Ok(()), lim.check_key(&1u32));
+
+    // Once the shared rate limiter has replenished the chunk, the thread can't use the cells it
+    // still holds:
+    clock.advance(Duration::from_secs(1));
+    while lim.limiter().check_key(&1u32).is_ok() {}
+    assert!(lim.check_key(&1u32).is_err());
+}
@@ -0,0 +1,84 @@
+use governor::{
+    clock::{Clock, FakeRelativeClock},
+    state::TieredRateLimiter,
+    Quota,
+};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+#[test]
+fn allows_only_what_all_tiers_allow() {
+    let clock = FakeRelativeClock::default();
+    let lim = TieredRateLimiter::with_clock(
+        vec![
+            Quota::per_second(nonzero!(3u32)),
+            Quota::per_minute(nonzero!(5u32)),
+        ],
+        &clock,
+    );
+    assert_eq!(lim.tiers(), 2);
+
+    let allowed = (0..10).filter(|_| lim.check().is_ok()).count();
+    assert_eq!(allowed, 3);
+
+    clock.advance(Duration::from_secs(1));
+    let allowed = (0..10).filter(|_| lim.check().is_ok()).count();
+    assert_eq!(allowed, 2);
+
+    clock.advance(Duration::from_secs(1));
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn rejected_cells_do_not_use_up_earlier_tiers() {
+    let clock = FakeRelativeClock::default();
+    let lim = TieredRateLimiter::with_clock(
+        vec![
+            Quota::per_minute(nonzero!(2u32)),
+            Quota::per_second(nonzero!(1u32)),
+        ],
+        &clock,
+    );
+
+    assert_eq!(Ok(()), lim.check());
+    // The per-minute tier allows these, but the per-second tier doesn't:
+    for _ in 0..5 {
+        assert_ne!(Ok(()), lim.check());
+    }
+
+    // The per-minute tier still has a cell left:
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(Ok(()), lim.check());
+    clock.advance(Duration::from_secs(1));
+    assert_ne!(Ok(()), lim.check());
+}
+
+#[test]
+fn reports_longest_wait() {
+    let clock = FakeRelativeClock::default();
+    let quotas = vec![
+        Quota::per_second(nonzero!(1u32)),
+        Quota::per_minute(nonzero!(1u32)),
+    ];
+    let lim = TieredRateLimiter::with_clock(quotas.clone(), &clock);
+    let reversed = TieredRateLimiter::with_clock(quotas.into_iter().rev(), &clock);
+
+    for lim in &[lim, reversed] {
+        assert_eq!(Ok(()), lim.check());
+        let negative = lim.check().unwrap_err();
+        assert_eq!(
+            negative.wait_time_from(clock.now()),
+            Duration::from_secs(60)
+        );
+        assert_eq!(negative.quota(), Quota::per_minute(nonzero!(1u32)));
+    }
+}
+
+#[test]
+fn no_tiers_allow_everything() {
+    let clock = FakeRelativeClock::default();
+    let lim = TieredRateLimiter::with_clock(vec![], &clock);
+    for _ in 0..100 {
+        assert_eq!(Ok(()), lim.check());
+    }
+}
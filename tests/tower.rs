@@ -0,0 +1,79 @@
+#![cfg(feature = "tower")]
+
+use futures::executor::block_on;
+use governor::{clock::FakeRelativeClock, tower::RateLimitLayer, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::{service_fn, Layer, Service, ServiceExt};
+
+#[derive(Debug, PartialEq)]
+enum Error {
+    RateLimited(Duration),
+}
+
+impl From<Infallible> for Error {
+    fn from(e: Infallible) -> Self {
+        match e {}
+    }
+}
+
+fn call<S: Service<u32>>(service: &mut S, request: u32) -> Result<S::Response, S::Error> {
+    block_on(async { service.ready().await?.call(request).await })
+}
+
+fn echo(request: u32) -> futures::future::Ready<Result<u32, Error>> {
+    futures::future::ok(request)
+}
+
+#[test]
+fn direct_service_rejects_excess_requests() {
+    let clock = FakeRelativeClock::default();
+    let limiter = Arc::new(RateLimiter::direct_with_clock(
+        Quota::per_second(nonzero!(2u32)),
+        &clock,
+    ));
+    let layer = RateLimitLayer::new(limiter, |negative, now| {
+        Error::RateLimited(negative.wait_time_from(now))
+    });
+    let mut service = layer.layer(service_fn(echo));
+
+    for request in 0..2 {
+        let response = call(&mut service, request);
+        assert_eq!(response, Ok(request));
+    }
+    let response = call(&mut service, 2);
+    assert_eq!(
+        response,
+        Err(Error::RateLimited(Duration::from_millis(500)))
+    );
+
+    clock.advance(Duration::from_millis(500));
+    let response = call(&mut service, 3);
+    assert_eq!(response, Ok(3));
+}
+
+#[test]
+fn keyed_service_limits_per_key() {
+    let clock = FakeRelativeClock::default();
+    let limiter = Arc::new(RateLimiter::hashmap_with_clock(
+        Quota::per_second(nonzero!(1u32)),
+        &clock,
+    ));
+    let layer = RateLimitLayer::keyed(
+        limiter,
+        |request: &u32| request % 2,
+        |negative, now| Error::RateLimited(negative.wait_time_from(now)),
+    );
+    let mut service = layer.layer(service_fn(echo));
+
+    assert_eq!(call(&mut service, 0), Ok(0));
+    assert_eq!(call(&mut service, 1), Ok(1));
+    assert_eq!(
+        call(&mut service, 2),
+        Err(Error::RateLimited(Duration::from_secs(1)))
+    );
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(call(&mut service, 2), Ok(2));
+}
@@ -0,0 +1,83 @@
+#![cfg(feature = "tracing")]
+
+use governor::{clock::FakeRelativeClock, middleware::TracingMiddleware, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// An event's level and fields, formatted with `Debug`.
+type RecordedEvent = (Level, HashMap<String, String>);
+
+/// A subscriber that records every event.
+#[derive(Clone, Default)]
+struct Recorder {
+    events: Arc<Mutex<Vec<RecordedEvent>>>,
+}
+
+struct Fields<'a>(&'a mut HashMap<String, String>);
+
+impl Visit for Fields<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = HashMap::new();
+        event.record(&mut Fields(&mut fields));
+        self.events
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), fields));
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn throttled_checks_emit_the_wait_time() {
+    let recorder = Recorder::default();
+    let clock = FakeRelativeClock::default();
+    let lim = RateLimiter::hashmap_with_clock(Quota::per_second(nonzero!(2u32)), &clock)
+        .with_middleware(TracingMiddleware);
+
+    tracing::subscriber::with_default(recorder.clone(), || {
+        assert_eq!(Ok(()), lim.check_key(&"alice"));
+        assert_eq!(Ok(()), lim.check_key(&"alice"));
+        assert!(lim.check_key(&"alice").is_err());
+    });
+
+    let events = recorder.events.lock().unwrap();
+    assert_eq!(events.len(), 3);
+    let (level, allowed) = &events[0];
+    assert_eq!(*level, Level::TRACE);
+    assert_eq!(allowed["key"], "\"alice\"");
+    assert_eq!(allowed["allowed"], "true");
+    assert_eq!(allowed["remaining"], "1");
+
+    let (level, throttled) = &events[2];
+    assert_eq!(*level, Level::DEBUG);
+    assert_eq!(throttled["key"], "\"alice\"");
+    assert_eq!(throttled["allowed"], "false");
+    assert_eq!(throttled["wait_time"], "500ms");
+}
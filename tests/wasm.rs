@@ -0,0 +1,31 @@
+#![cfg(feature = "wasm")]
+
+use governor::{clock::WasmClock, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// The simulated time, in microseconds.
+static NOW_MICROS: AtomicU64 = AtomicU64::new(0);
+
+fn simulated_now() -> f64 {
+    NOW_MICROS.load(Ordering::SeqCst) as f64 / 1000.0
+}
+
+#[test]
+fn throttles_over_simulated_time() {
+    let clock = WasmClock::with_time_source(simulated_now);
+    NOW_MICROS.store(12_345_678, Ordering::SeqCst);
+    let lim = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(2u32)), &clock);
+
+    assert_eq!(Ok(()), lim.check());
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+
+    // a fraction of a millisecond is not enough:
+    NOW_MICROS.fetch_add(499_900, Ordering::SeqCst);
+    assert_ne!(Ok(()), lim.check());
+
+    NOW_MICROS.fetch_add(100, Ordering::SeqCst);
+    assert_eq!(Ok(()), lim.check());
+    assert_ne!(Ok(()), lim.check());
+}
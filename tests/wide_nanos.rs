@@ -0,0 +1,70 @@
+#![cfg(feature = "wide-nanos")]
+
+use governor::{clock::FakeRelativeClock, Quota, RateLimiter};
+use nonzero_ext::nonzero;
+use std::time::Duration;
+
+const YEAR: Duration = Duration::from_secs(60 * 60 * 24 * 365);
+
+/// Returns which of a day's worth of checks, made every few minutes after the clock has been
+/// running for `years`, a rate limiter for three cells per day allows through.
+fn daily_checks(years: u32, keyed: bool) -> Vec<bool> {
+    let clock = FakeRelativeClock::default();
+    let quota = Quota::with_period(Duration::from_secs(60 * 60 * 8))
+        .unwrap()
+        .allow_burst(nonzero!(3u32));
+    let direct = RateLimiter::direct_with_clock(quota, &clock);
+    let keyed_lb = RateLimiter::hashmap_with_clock(quota, &clock);
+    let check = || {
+        if keyed {
+            keyed_lb.check_key(&1u32).is_ok()
+        } else {
+            direct.check().is_ok()
+        }
+    };
+    assert!(check());
+    clock.advance(YEAR * years);
+    (0..24 * 12)
+        .map(|_| {
+            let allowed = check();
+            clock.advance(Duration::from_secs(5 * 60));
+            allowed
+        })
+        .collect()
+}
+
+#[test]
+fn behaves_the_same_past_584_years() {
+    for &keyed in &[false, true] {
+        let decisions = daily_checks(100, keyed);
+        assert_eq!(6, decisions.iter().filter(|allowed| **allowed).count());
+        assert_eq!(decisions, daily_checks(1_000, keyed));
+        assert_eq!(decisions, daily_checks(1_000_000, keyed));
+    }
+}
+
+#[test]
+fn burst_windows_longer_than_584_years() {
+    let clock = FakeRelativeClock::default();
+    // One cell per hour, for a burst window of ~490,000 years:
+    let quota = Quota::per_hour(nonzero!(1u32)).allow_burst(nonzero!(u32::MAX));
+    assert_eq!(nonzero!(u32::MAX), quota.burst_size());
+    let lb = RateLimiter::direct_with_clock(quota, &clock);
+    assert_eq!(Ok(()), lb.check_n(nonzero!(u32::MAX)));
+    assert_ne!(Ok(()), lb.check());
+    clock.advance(Duration::from_secs(60 * 60));
+    assert_eq!(Ok(()), lb.check());
+}
+
+#[test]
+fn never_allows_unlimited_cells_at_the_end_of_time() {
+    let clock = FakeRelativeClock::default();
+    let lb = RateLimiter::direct_with_clock(Quota::per_second(nonzero!(5u32)), &clock);
+    // 2^96 nanoseconds, minus 1,000:
+    for _ in 0..4 {
+        clock.advance(Duration::MAX);
+    }
+    clock.advance(Duration::new(5_441_186_219_426_131_129, 543_949_339));
+    let allowed = (0..100).filter(|_| lb.check().is_ok()).count();
+    assert!(allowed <= 5, "allowed {} cells", allowed);
+}